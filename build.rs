@@ -0,0 +1,38 @@
+use std::process::Command;
+
+/// Exposes the toolchain and the commit the binary was built from to `hephaestus_build_info`
+fn main() {
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_owned());
+    let rustc_version = command_output(&rustc, &["--version"])
+        .and_then(|version| version.split_whitespace().nth(1).map(str::to_owned));
+
+    // Allows the packaging scripts to provide the commit, e.g. when building from a tarball
+    println!("cargo:rerun-if-env-changed=HEPHAESTUS_GIT_SHA");
+    let git_sha = std::env::var("HEPHAESTUS_GIT_SHA")
+        .ok()
+        .or_else(|| command_output("git", &["rev-parse", "--short=12", "HEAD"]));
+
+    if std::path::Path::new(".git/HEAD").exists() {
+        println!("cargo:rerun-if-changed=.git/HEAD");
+        println!("cargo:rerun-if-changed=.git/refs/heads");
+    }
+
+    println!(
+        "cargo:rustc-env=HEPHAESTUS_RUSTC_VERSION={}",
+        rustc_version.as_deref().unwrap_or("unknown")
+    );
+    println!(
+        "cargo:rustc-env=HEPHAESTUS_GIT_SHA={}",
+        git_sha.as_deref().unwrap_or("unknown")
+    );
+}
+
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    let stdout = String::from_utf8(output.stdout).ok()?;
+    Some(stdout.trim().to_owned()).filter(|s| !s.is_empty())
+}
@@ -1,38 +1,73 @@
 use hephaestus::bootstrap::init_collectors;
 use hephaestus::config::{
-    Configuration, get_config_base_path, print_config, should_print_config_and_exit,
+    Configuration, get_config_base_path, get_validate_config_path, print_config,
+    should_print_config_and_exit, validate_config_file,
 };
+#[cfg(unix)]
+use hephaestus::logging::reload_on_signal;
 use hephaestus::logging::setup_logging;
+#[cfg(unix)]
+use hephaestus::server::reload::reload_on_sighup;
 use hephaestus::server::start_server;
-use hephaestus::server::state::{AppState, Inner};
-use std::ops::Sub;
-use std::sync::Arc;
-use std::time::Duration;
-use tokio::sync::Mutex;
-use tokio::time::Instant;
+use hephaestus::server::state::AppState;
+use hephaestus::{self_check, statsd};
 
 #[tokio::main(flavor = "current_thread")]
 async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let configuration = Configuration::load(get_config_base_path(std::env::args())?)?;
+    if let Some(path) = get_validate_config_path(std::env::args())? {
+        match validate_config_file(&path) {
+            Ok(()) => {
+                println!("Configuration [{}] is valid", path);
+                return Ok(());
+            }
+            Err(e) => {
+                eprintln!("Configuration [{}] is invalid: {:#}", path, e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let config_path = get_config_base_path(std::env::args())?;
+    let configuration = Configuration::load(&config_path)?;
     if should_print_config_and_exit(std::env::args()) {
         print_config(&configuration)?;
         return Ok(());
     }
 
-    let _guard = setup_logging(&configuration.log)?;
+    let guard = setup_logging(&configuration.log)?;
     tracing::info!("Starting Hephaestus");
 
+    #[cfg(unix)]
+    tokio::spawn(reload_on_signal(config_path.clone(), guard.reloader()));
+
+    // The reloads and --validate-config validate it too, but the server must not
+    // start with, e.g., half-configured authentication either
+    configuration.validate()?;
+
     let registry = prometheus::Registry::new();
-    let collectors = init_collectors(&configuration, &registry)?;
-
-    let state = AppState {
-        inner: Arc::new(Inner {
-            configuration,
-            registry,
-            collectors,
-            last_collection: Mutex::new(Instant::now().sub(Duration::from_hours(1))),
-        }),
-    };
+    let collectors = init_collectors(&configuration, &registry).await?;
+
+    let state = AppState::new(configuration, registry, collectors);
+
+    #[cfg(unix)]
+    tokio::spawn(reload_on_sighup(
+        config_path,
+        state.clone(),
+        guard.reloader(),
+    ));
+
+    if state.configuration.self_check.enabled {
+        tokio::spawn(self_check::run(state.clone()));
+    }
+
+    if state.configuration.statsd.enabled {
+        let state = state.clone();
+        tokio::spawn(async move {
+            if let Err(error) = statsd::run(state).await {
+                tracing::error!(?error, "The StatsD exporter failed");
+            }
+        });
+    }
 
     start_server(state).await?;
     tracing::info!("Bye!");
@@ -1,62 +1,700 @@
 use crate::config::Configuration;
-use crate::datasource::TokioReader;
-use crate::domain::{Collector, Metric};
+use crate::datasource::{Roots, TokioCommandRunner, TokioReader};
+use crate::domain::{Metric, RegisteredCollector};
+use crate::metrics::MetricOptions;
+use crate::metrics::no_operation::NoOpCollector;
+use crate::server::telemetry;
 use crate::{datasource, metrics};
-use prometheus::Registry;
+use prometheus::core::{Collector, Desc};
+use prometheus::proto::MetricFamily;
+use prometheus::{IntCounter, Opts, Registry};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 
-pub fn init_collectors(
+/// Collectors reading from procfs and sysfs are cheap, so they are executed first
+const PRIORITY_LOCAL: i32 = 100;
+/// Collectors querying a daemon over a socket
+const PRIORITY_NETWORK: i32 = 50;
+/// Collectors spawning external processes are the most expensive ones
+const PRIORITY_SUBPROCESS: i32 = 0;
+
+pub async fn init_collectors(
     config: &Configuration,
     registry: &Registry,
-) -> anyhow::Result<Vec<Box<dyn Collector>>> {
+) -> anyhow::Result<Vec<RegisteredCollector>> {
+    let collector = &config.collector;
     let mut collectors = vec![];
 
-    let data_source = datasource::memory_usage::MemoryUsage::new(TokioReader::new());
+    telemetry::register(registry)?;
+
+    let options = MetricOptions {
+        namespace: config.metrics.namespace.clone(),
+        static_labels: config.metrics.static_labels.clone(),
+        temperature_unit: config.metrics.temperature_unit,
+        zero_if_absent: collector.zero_if_absent.iter().cloned().collect(),
+    };
+
+    let roots = Roots::new(&config.datasource.proc_root, &config.datasource.sys_root);
+
+    let data_source = datasource::memory_usage::MemoryUsage::new(TokioReader::new(), roots.clone());
     let mem_usage =
         metrics::memory_usage::MemoryUsage::new(config.collector.memory_usage.clone(), data_source);
-    collectors.push(mem_usage.register(registry)?);
+    let priority = collector.memory_usage.priority.unwrap_or(PRIORITY_LOCAL);
+    let max_series = collector.memory_usage.max_series;
+    collectors.push(register_collector(mem_usage, priority, max_series, &options, registry).await?);
+
+    let data_source = datasource::swap::ProcSwaps::new(TokioReader::new(), roots.clone());
+    let swap = metrics::swap::Swap::new(config.collector.swap.clone(), data_source);
+    let priority = collector.swap.priority.unwrap_or(PRIORITY_LOCAL);
+    let max_series = collector.swap.max_series;
+    collectors.push(register_collector(swap, priority, max_series, &options, registry).await?);
+
+    let data_source = datasource::zram::SysfsZram::new(TokioReader::new(), roots.clone());
+    let zram = metrics::zram::Zram::new(config.collector.zram.clone(), data_source);
+    let priority = collector.zram.priority.unwrap_or(PRIORITY_LOCAL);
+    let max_series = collector.zram.max_series;
+    collectors.push(register_collector(zram, priority, max_series, &options, registry).await?);
 
-    let data_source = datasource::cpu_frequency::CpuFrequency::new(TokioReader::new());
+    let data_source = datasource::numa::SysfsNuma::new(TokioReader::new(), roots.clone());
+    let numa = metrics::numa::Numa::new(config.collector.numa.clone(), data_source);
+    let priority = collector.numa.priority.unwrap_or(PRIORITY_LOCAL);
+    let max_series = collector.numa.max_series;
+    collectors.push(register_collector(numa, priority, max_series, &options, registry).await?);
+
+    let data_source = datasource::vmstat::ProcVmStat::new(
+        TokioReader::new(),
+        roots.clone(),
+        collector.vmstat.keys(),
+    );
+    let vmstat = metrics::vmstat::VmStat::new(config.collector.vmstat.clone(), data_source);
+    let priority = collector.vmstat.priority.unwrap_or(PRIORITY_LOCAL);
+    let max_series = collector.vmstat.max_series;
+    collectors.push(register_collector(vmstat, priority, max_series, &options, registry).await?);
+
+    let data_source =
+        datasource::cpu_frequency::CpuFrequency::new(TokioReader::new(), roots.clone());
     let cpu_freq = metrics::cpu_frequency::CpuFrequency::new(
         config.collector.cpu_frequency.clone(),
         data_source,
     );
-    collectors.push(cpu_freq.register(registry)?);
+    let priority = collector.cpu_frequency.priority.unwrap_or(PRIORITY_LOCAL);
+    let max_series = collector.cpu_frequency.max_series;
+    collectors.push(register_collector(cpu_freq, priority, max_series, &options, registry).await?);
+
+    let data_source =
+        datasource::cpu_throttle::SysfsCpuThrottle::new(TokioReader::new(), roots.clone());
+    let cpu_throttle =
+        metrics::cpu_throttle::CpuThrottle::new(config.collector.cpu_throttle.clone(), data_source);
+    let priority = collector.cpu_throttle.priority.unwrap_or(PRIORITY_LOCAL);
+    let max_series = collector.cpu_throttle.max_series;
+    collectors
+        .push(register_collector(cpu_throttle, priority, max_series, &options, registry).await?);
+
+    let data_source = datasource::platform::SysfsPlatform::new(TokioReader::new(), roots.clone());
+    let platform = metrics::platform::Platform::new(config.collector.platform.clone(), data_source);
+    let priority = collector.platform.priority.unwrap_or(PRIORITY_LOCAL);
+    let max_series = collector.platform.max_series;
+    collectors.push(register_collector(platform, priority, max_series, &options, registry).await?);
+
+    let data_source = datasource::os_info::ProcOsInfo::new(
+        TokioReader::new(),
+        roots.clone(),
+        config.collector.os_info.os_release_paths.clone(),
+    );
+    let os_info = metrics::os_info::OsInfo::new(config.collector.os_info.clone(), data_source);
+    let priority = collector.os_info.priority.unwrap_or(PRIORITY_LOCAL);
+    let max_series = collector.os_info.max_series;
+    collectors.push(register_collector(os_info, priority, max_series, &options, registry).await?);
+
+    let data_source =
+        datasource::processes_top::ProcProcesses::new(TokioReader::new(), roots.clone())
+            .with_min_sample_interval(Duration::from_millis(
+                collector.processes_top.min_sample_interval_ms,
+            ))
+            .with_reuse_previous(collector.processes_top.reuse_previous_sample);
+    let processes_top = metrics::processes_top::ProcessesTop::new(
+        config.collector.processes_top.clone(),
+        data_source,
+    );
+    let priority = collector.processes_top.priority.unwrap_or(PRIORITY_LOCAL);
+    let max_series = collector.processes_top.max_series;
+    collectors
+        .push(register_collector(processes_top, priority, max_series, &options, registry).await?);
 
-    let data_source = datasource::cpu_usage::CpuUsage::new(TokioReader::new());
+    let data_source = datasource::cpu_usage::CpuUsage::new(TokioReader::new(), roots.clone())
+        .with_min_sample_interval(Duration::from_millis(
+            collector.cpu_usage.min_sample_interval_ms,
+        ))
+        .with_reuse_previous(collector.cpu_usage.reuse_previous_sample);
     let cpu_usage =
         metrics::cpu_usage::CpuUsage::new(config.collector.cpu_usage.clone(), data_source);
-    collectors.push(cpu_usage.register(registry)?);
+    let priority = collector.cpu_usage.priority.unwrap_or(PRIORITY_LOCAL);
+    let max_series = collector.cpu_usage.max_series;
+    collectors.push(register_collector(cpu_usage, priority, max_series, &options, registry).await?);
+
+    let data_source = datasource::rapl::PowercapRapl::new(TokioReader::new(), roots.clone());
+    let rapl = metrics::rapl::Rapl::new(config.collector.rapl.clone(), data_source);
+    let priority = collector.rapl.priority.unwrap_or(PRIORITY_LOCAL);
+    let max_series = collector.rapl.max_series;
+    collectors.push(register_collector(rapl, priority, max_series, &options, registry).await?);
 
-    let data_source = datasource::network_io::NetworkIo::new(TokioReader::new());
+    let data_source = datasource::network_io::NetworkIo::new(TokioReader::new(), roots.clone());
     let net_io =
         metrics::network_io::NetworkIo::new(config.collector.network_io.clone(), data_source);
-    collectors.push(net_io.register(registry)?);
+    let priority = collector.network_io.priority.unwrap_or(PRIORITY_LOCAL);
+    let max_series = collector.network_io.max_series;
+    collectors.push(register_collector(net_io, priority, max_series, &options, registry).await?);
+
+    let data_source = datasource::conntrack::ProcConntrack::new(TokioReader::new(), roots.clone());
+    let conntrack =
+        metrics::conntrack::Conntrack::new(config.collector.conntrack.clone(), data_source);
+    let priority = collector.conntrack.priority.unwrap_or(PRIORITY_LOCAL);
+    let max_series = collector.conntrack.max_series;
+    collectors.push(register_collector(conntrack, priority, max_series, &options, registry).await?);
 
-    let data_source = datasource::disk_io::DiskIo::new(TokioReader::new());
+    let data_source = datasource::wifi::ProcWireless::new(TokioReader::new(), roots.clone());
+    let wifi = metrics::wifi::Wifi::new(config.collector.wifi.clone(), data_source);
+    let priority = collector.wifi.priority.unwrap_or(PRIORITY_LOCAL);
+    let max_series = collector.wifi.max_series;
+    collectors.push(register_collector(wifi, priority, max_series, &options, registry).await?);
+
+    let data_source = datasource::disk_io::DiskIo::new(
+        TokioReader::new(),
+        roots.clone(),
+        collector.disk_io.sector_size,
+        collector.disk_io.use_stable_names,
+    );
     let disk_io = metrics::disk_io::DiskIo::new(config.collector.disk_io.clone(), data_source);
-    collectors.push(disk_io.register(registry)?);
+    let priority = collector.disk_io.priority.unwrap_or(PRIORITY_LOCAL);
+    let max_series = collector.disk_io.max_series;
+    collectors.push(register_collector(disk_io, priority, max_series, &options, registry).await?);
 
-    let data_source = datasource::disk_smart::SmartCtl::new();
+    let mut data_source = datasource::disk_smart::SmartCtl::new(TokioCommandRunner::new());
+    if collector.disk_temp.use_stable_names {
+        data_source = data_source.with_stable_names(TokioReader::new());
+    }
     let disk_temp =
         metrics::disk_smart::Smart::new(config.collector.disk_temp.clone(), data_source);
-    collectors.push(disk_temp.register(registry)?);
+    let priority = collector.disk_temp.priority.unwrap_or(PRIORITY_SUBPROCESS);
+    let max_series = collector.disk_temp.max_series;
+    collectors.push(register_collector(disk_temp, priority, max_series, &options, registry).await?);
 
     let data_source = datasource::nut::Nut::new(config.datasource.nut.clone())?;
     let ups = metrics::ups::Ups::new(config.collector.ups.clone(), data_source);
-    collectors.push(ups.register(registry)?);
+    let priority = collector.ups.priority.unwrap_or(PRIORITY_NETWORK);
+    let max_series = collector.ups.max_series;
+    collectors.push(register_collector(ups, priority, max_series, &options, registry).await?);
 
-    let data_source = datasource::zfs_arc::KstatZfs::new(TokioReader::new());
+    let data_source = datasource::zfs_arc::KstatZfs::new(TokioReader::new(), roots.clone());
     let zfs_arc = metrics::zfs_arc::ZfsArc::new(config.collector.zfs_arc.clone(), data_source);
-    collectors.push(zfs_arc.register(registry)?);
+    let priority = collector.zfs_arc.priority.unwrap_or(PRIORITY_LOCAL);
+    let max_series = collector.zfs_arc.max_series;
+    collectors.push(register_collector(zfs_arc, priority, max_series, &options, registry).await?);
 
-    let data_source = datasource::zfs_dataset::KstatZfsDatasetIo::new(TokioReader::new());
+    let data_source =
+        datasource::zfs_dataset::KstatZfsDatasetIo::new(TokioReader::new(), roots.clone());
     let zfs_dataset =
         metrics::zfs_dataset::ZfsDatasetIo::new(config.collector.zfs_dataset.clone(), data_source);
-    collectors.push(zfs_dataset.register(registry)?);
+    let priority = collector.zfs_dataset.priority.unwrap_or(PRIORITY_LOCAL);
+    let max_series = collector.zfs_dataset.max_series;
+    collectors
+        .push(register_collector(zfs_dataset, priority, max_series, &options, registry).await?);
+
+    let data_source = datasource::zpool::ZpoolCli::new(TokioCommandRunner::new());
+    let zpool = metrics::zpool::Zpool::new(config.collector.zpool.clone(), data_source);
+    let priority = collector.zpool.priority.unwrap_or(PRIORITY_SUBPROCESS);
+    let max_series = collector.zpool.max_series;
+    collectors.push(register_collector(zpool, priority, max_series, &options, registry).await?);
+
+    let data_source = datasource::timesync::Chrony::new(TokioCommandRunner::new());
+    let timesync = metrics::timesync::Timesync::new(config.collector.timesync.clone(), data_source);
+    let priority = collector.timesync.priority.unwrap_or(PRIORITY_SUBPROCESS);
+    let max_series = collector.timesync.max_series;
+    collectors.push(register_collector(timesync, priority, max_series, &options, registry).await?);
+
+    let data_source = datasource::btrfs::BtrfsCli::new(
+        TokioReader::new(),
+        roots.clone(),
+        TokioCommandRunner::new(),
+    );
+    let btrfs = metrics::btrfs::Btrfs::new(config.collector.btrfs.clone(), data_source);
+    let priority = collector.btrfs.priority.unwrap_or(PRIORITY_SUBPROCESS);
+    let max_series = collector.btrfs.max_series;
+    collectors.push(register_collector(btrfs, priority, max_series, &options, registry).await?);
 
-    let data_source = datasource::docker::DockerClient::new();
+    let data_source = datasource::kmsg::DevKmsg::new(&collector.kmsg.levels);
+    let kmsg = metrics::kmsg::Kmsg::new(config.collector.kmsg.clone(), data_source);
+    let priority = collector.kmsg.priority.unwrap_or(PRIORITY_LOCAL);
+    let max_series = collector.kmsg.max_series;
+    collectors.push(register_collector(kmsg, priority, max_series, &options, registry).await?);
+
+    let data_source = datasource::users::WhoUsers::new(TokioCommandRunner::new());
+    let users = metrics::users::Users::new(config.collector.users.clone(), data_source);
+    let priority = collector.users.priority.unwrap_or(PRIORITY_SUBPROCESS);
+    let max_series = collector.users.max_series;
+    collectors.push(register_collector(users, priority, max_series, &options, registry).await?);
+
+    let data_source = datasource::nfs::MountStatsNfs::new(TokioReader::new(), roots.clone());
+    let nfs = metrics::nfs::Nfs::new(config.collector.nfs.clone(), data_source);
+    let priority = collector.nfs.priority.unwrap_or(PRIORITY_LOCAL);
+    let max_series = collector.nfs.max_series;
+    collectors.push(register_collector(nfs, priority, max_series, &options, registry).await?);
+
+    let data_source = datasource::filesystem::ProcMounts::new(TokioReader::new(), roots.clone());
+    let filesystem =
+        metrics::filesystem::Filesystem::new(config.collector.filesystem.clone(), data_source);
+    let priority = collector.filesystem.priority.unwrap_or(PRIORITY_LOCAL);
+    let max_series = collector.filesystem.max_series;
+    collectors
+        .push(register_collector(filesystem, priority, max_series, &options, registry).await?);
+
+    let data_source = datasource::slabinfo::ProcSlabInfo::new(TokioReader::new(), roots.clone());
+    let slabinfo = metrics::slabinfo::SlabInfo::new(config.collector.slabinfo.clone(), data_source);
+    let priority = collector.slabinfo.priority.unwrap_or(PRIORITY_LOCAL);
+    let max_series = collector.slabinfo.max_series;
+    collectors.push(register_collector(slabinfo, priority, max_series, &options, registry).await?);
+
+    // An invalid host or TLS setup fails the startup, unless the collector is disabled anyway
+    let data_source = match collector.docker.enabled {
+        true => datasource::docker::DockerClient::new(config.datasource.docker.clone())?,
+        false => datasource::docker::DockerClient::unconnected(config.datasource.docker.clone()),
+    };
     let docker = metrics::docker::Docker::new(config.collector.docker.clone(), data_source);
-    collectors.push(docker.register(registry)?);
+    let priority = collector.docker.priority.unwrap_or(PRIORITY_NETWORK);
+    let max_series = collector.docker.max_series;
+    collectors.push(register_collector(docker, priority, max_series, &options, registry).await?);
+
+    let data_source = datasource::cgroup::SysfsCgroup::new(TokioReader::new(), roots.clone());
+    let cgroup = metrics::cgroup::Cgroup::new(config.collector.cgroup.clone(), data_source);
+    let priority = collector.cgroup.priority.unwrap_or(PRIORITY_LOCAL);
+    let max_series = collector.cgroup.max_series;
+    collectors.push(register_collector(cgroup, priority, max_series, &options, registry).await?);
+
+    let data_source = datasource::tokio_runtime::TokioRuntime::new();
+    let tokio_runtime = metrics::tokio_runtime::TokioRuntime::new(
+        config.collector.tokio_runtime.clone(),
+        data_source,
+    );
+    let priority = collector.tokio_runtime.priority.unwrap_or(PRIORITY_LOCAL);
+    let max_series = collector.tokio_runtime.max_series;
+    collectors
+        .push(register_collector(tokio_runtime, priority, max_series, &options, registry).await?);
+
+    // The sort is stable, so collectors with equal priority keep their registration order
+    collectors.sort_by_key(|c| std::cmp::Reverse(c.priority));
 
     Ok(collectors)
 }
+
+/// Registers the metric's series in a registry of its own, which is in turn
+/// exposed through the shared `registry`. This allows gathering the metrics
+/// of a single collector, as well as the metrics of all collectors at once.
+///
+/// When `max_series` is set, the collector reports only as many entities as fit in that
+/// many series in total, as a safety valve against runaway cardinality.
+pub async fn register_collector(
+    metric: impl Metric,
+    priority: i32,
+    max_series: Option<usize>,
+    options: &MetricOptions,
+    registry: &Registry,
+) -> anyhow::Result<RegisteredCollector> {
+    let mut scoped = Registry::new();
+    let collector = match metric.supported().await {
+        true => metric.register(&scoped, options)?,
+        false => {
+            tracing::info!(
+                collector = metric.name(),
+                "The collector is not supported on this machine"
+            );
+            Box::new(NoOpCollector::new(metric.name()))
+        }
+    };
+
+    if let Some(max_series) = max_series {
+        let limited = Registry::new();
+        let limiter = SeriesLimiter::new(collector.name(), scoped, max_series)?;
+        limited.register(Box::new(limiter))?;
+        scoped = limited;
+    }
+
+    let bridge = RegistryBridge::new(collector.name(), scoped.clone())?;
+    registry.register(Box::new(bridge))?;
+
+    Ok(RegisteredCollector {
+        collector,
+        registry: scoped,
+        priority,
+    })
+}
+
+struct RegistryBridge {
+    // The registry only uses the descriptor to uniquely identify the
+    // bridge. The actual descriptors are validated by the inner registry.
+    desc: Desc,
+    registry: Registry,
+}
+
+impl RegistryBridge {
+    fn new(name: &str, registry: Registry) -> anyhow::Result<Self> {
+        let desc = Desc::new(
+            format!("hephaestus_collector_{}", name),
+            format!("Metrics gathered by the {} collector", name),
+            vec![],
+            HashMap::new(),
+        )?;
+
+        Ok(Self { desc, registry })
+    }
+}
+
+impl Collector for RegistryBridge {
+    fn desc(&self) -> Vec<&Desc> {
+        vec![&self.desc]
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        self.registry.gather()
+    }
+}
+
+struct SeriesLimiter {
+    desc: Desc,
+    name: &'static str,
+    registry: Registry,
+    max_series: usize,
+    truncated: IntCounter,
+    warned: AtomicBool,
+}
+
+impl SeriesLimiter {
+    fn new(name: &'static str, registry: Registry, max_series: usize) -> anyhow::Result<Self> {
+        let desc = Desc::new(
+            format!("hephaestus_series_limiter_{}", name),
+            format!("Series limiter of the {} collector", name),
+            vec![],
+            HashMap::new(),
+        )?;
+
+        let truncated = IntCounter::with_opts(
+            Opts::new(
+                "hephaestus_collector_truncated_total",
+                "Number of entities dropped because the collector exceeded its max_series limit",
+            )
+            .const_label("collector", name),
+        )?;
+
+        Ok(Self {
+            desc,
+            name,
+            registry,
+            max_series,
+            truncated,
+            warned: AtomicBool::new(false),
+        })
+    }
+}
+
+impl Collector for SeriesLimiter {
+    fn desc(&self) -> Vec<&Desc> {
+        vec![&self.desc]
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        let mut families = self.registry.gather();
+
+        let total = families
+            .iter()
+            .map(|mf| mf.get_metric().len())
+            .sum::<usize>();
+        if total > self.max_series {
+            // An entity (disk, container, dataset, etc) is identified by the labels of its
+            // series. Keeping the entities in label order makes the same ones survive across
+            // the collections, and keeps all metrics of an entity or none of them.
+            let mut entities = BTreeMap::<Vec<(String, String)>, usize>::new();
+            for metric in families.iter().flat_map(|mf| mf.get_metric()) {
+                *entities.entry(entity_key(metric)).or_default() += 1;
+            }
+
+            let mut remaining = self.max_series;
+            let kept = entities
+                .iter()
+                .map_while(|(key, series)| {
+                    remaining = remaining.checked_sub(*series)?;
+                    Some(key.clone())
+                })
+                .collect::<HashSet<_>>();
+
+            for mf in &mut families {
+                mf.mut_metric()
+                    .retain(|metric| kept.contains(&entity_key(metric)));
+            }
+            families.retain(|mf| !mf.get_metric().is_empty());
+
+            let dropped = entities.len() - kept.len();
+            self.truncated.inc_by(dropped as u64);
+            if !self.warned.swap(true, Ordering::Relaxed) {
+                tracing::warn!(
+                    collector = self.name,
+                    total,
+                    max_series = self.max_series,
+                    dropped,
+                    "The collector exceeded its max_series limit"
+                );
+            }
+        }
+
+        families.extend(self.truncated.collect());
+        families
+    }
+}
+
+/// The const labels are the same on every series, so they do not affect the grouping
+fn entity_key(metric: &prometheus::proto::Metric) -> Vec<(String, String)> {
+    metric
+        .get_label()
+        .iter()
+        .map(|label| (label.name().to_owned(), label.value().to_owned()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain;
+    use prometheus::IntGaugeVec;
+
+    struct ManySeries {
+        series: usize,
+        families: usize,
+    }
+
+    impl Metric for ManySeries {
+        fn name(&self) -> &'static str {
+            "many_series"
+        }
+
+        fn register(
+            self,
+            registry: &Registry,
+            _options: &MetricOptions,
+        ) -> anyhow::Result<Box<dyn domain::Collector>> {
+            for family in 0..self.families {
+                let name = format!("test_many_series_{}", family);
+                let gauge = IntGaugeVec::new(Opts::new(name, "help"), &["device"])?;
+                for idx in 0..self.series {
+                    gauge.with_label_values(&[format!("dev{:02}", idx)]).set(1);
+                }
+                registry.register(Box::new(gauge))?;
+            }
+
+            Ok(Box::new(crate::metrics::no_operation::NoOpCollector::new(
+                "many_series",
+            )))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_max_series_truncates_series() {
+        let registry = Registry::new();
+        register_collector(
+            ManySeries {
+                series: 20,
+                families: 1,
+            },
+            0,
+            Some(5),
+            &MetricOptions::default(),
+            &registry,
+        )
+        .await
+        .unwrap();
+
+        let families = registry.gather();
+        let series = families
+            .iter()
+            .find(|mf| mf.name() == "test_many_series_0")
+            .unwrap();
+        let labels = series
+            .get_metric()
+            .iter()
+            .map(|m| m.get_label()[0].value().to_owned())
+            .collect::<Vec<_>>();
+        assert_eq!(labels, vec!["dev00", "dev01", "dev02", "dev03", "dev04"]);
+
+        let truncated = families
+            .iter()
+            .find(|mf| mf.name() == "hephaestus_collector_truncated_total")
+            .expect("the truncated entities counter is exposed");
+        let counter = &truncated.get_metric()[0];
+        assert_eq!(counter.get_label()[0].value(), "many_series");
+        assert_eq!(counter.get_counter().value(), 15.0);
+    }
+
+    #[tokio::test]
+    async fn test_max_series_spans_families() {
+        let registry = Registry::new();
+        let registered = register_collector(
+            ManySeries {
+                series: 3,
+                families: 2,
+            },
+            0,
+            Some(5),
+            &MetricOptions::default(),
+            &registry,
+        )
+        .await
+        .unwrap();
+
+        let families = registered.registry.gather();
+        let devices = families
+            .iter()
+            .filter(|mf| mf.name().starts_with("test_many_series"))
+            .map(|mf| {
+                mf.get_metric()
+                    .iter()
+                    .map(|m| m.get_label()[0].value().to_owned())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            devices,
+            vec![vec!["dev00", "dev01"], vec!["dev00", "dev01"]]
+        );
+
+        let truncated = families
+            .iter()
+            .find(|mf| mf.name() == "hephaestus_collector_truncated_total")
+            .unwrap();
+        assert_eq!(truncated.get_metric()[0].get_counter().value(), 1.0);
+    }
+
+    #[tokio::test]
+    async fn test_max_series_not_exceeded() {
+        let registry = Registry::new();
+        let registered = register_collector(
+            ManySeries {
+                series: 3,
+                families: 1,
+            },
+            0,
+            Some(5),
+            &MetricOptions::default(),
+            &registry,
+        )
+        .await
+        .unwrap();
+
+        let families = registered.registry.gather();
+        assert_eq!(families.len(), 2);
+        assert_eq!(families[1].get_metric().len(), 3);
+    }
+
+    struct Unsupported;
+
+    impl Metric for Unsupported {
+        fn name(&self) -> &'static str {
+            "unsupported"
+        }
+
+        async fn supported(&self) -> bool {
+            false
+        }
+
+        fn register(
+            self,
+            _registry: &Registry,
+            _options: &MetricOptions,
+        ) -> anyhow::Result<Box<dyn domain::Collector>> {
+            panic!("unsupported metrics are not registered");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_metric() {
+        let registry = Registry::new();
+        let registered =
+            register_collector(Unsupported, 0, None, &MetricOptions::default(), &registry)
+                .await
+                .unwrap();
+
+        assert_eq!(registered.collector.name(), "unsupported");
+        assert!(registered.collector.collect().await.is_ok());
+        assert!(registered.registry.gather().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_init_zfs_collectors() {
+        let proc_root =
+            std::env::temp_dir().join(format!("hephaestus-zfs-proc-{}", std::process::id()));
+        let kstat = proc_root.join("spl/kstat/zfs");
+        std::fs::create_dir_all(&kstat).unwrap();
+        std::fs::write(
+            kstat.join("arcstats"),
+            "13 1 0x01 2 2\nname type data\nhits 4 100\nmisses 4 10\nsize 4 4096\n",
+        )
+        .unwrap();
+        std::fs::create_dir_all(kstat.join("tank")).unwrap();
+        std::fs::write(
+            kstat.join("tank/objset-0x36"),
+            "250 1 0x01 1 1\nname type data\ndataset_name 7 tank/data\nreads 4 12\nwrites 4 3\n",
+        )
+        .unwrap();
+
+        let mut config = Configuration::default();
+        config.datasource.proc_root = proc_root.to_string_lossy().into_owned();
+        config.collector.zfs_arc.enabled = true;
+        config.collector.zfs_dataset.enabled = true;
+
+        let registry = Registry::new();
+        let collectors = init_collectors(&config, &registry).await.unwrap();
+
+        for registered in &collectors {
+            if registered.collector.name().starts_with("zfs_") {
+                registered.collector.collect().await.unwrap();
+            }
+        }
+
+        // A disabled or unsupported collector would be a no-op with the same name
+        let families = registry.gather();
+        assert!(families.iter().any(|mf| mf.name() == "zfs_arc_hits_total"));
+        assert!(
+            families
+                .iter()
+                .any(|mf| mf.name() == "zfs_dataset_reads_total")
+        );
+
+        std::fs::remove_dir_all(&proc_root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_self_metrics() {
+        let registry = Registry::new();
+        init_collectors(&Configuration::default(), &registry)
+            .await
+            .unwrap();
+
+        let families = registry.gather();
+        let up = families
+            .iter()
+            .find(|mf| mf.name() == "hephaestus_up")
+            .expect("the up gauge is registered");
+        assert_eq!(up.get_metric()[0].get_gauge().value(), 1.0);
+
+        let build_info = families
+            .iter()
+            .find(|mf| mf.name() == "hephaestus_build_info")
+            .expect("the build info is registered");
+        let labels = build_info.get_metric()[0]
+            .get_label()
+            .iter()
+            .map(|l| (l.name(), l.value()))
+            .collect::<HashMap<_, _>>();
+        assert_eq!(labels["version"], env!("CARGO_PKG_VERSION"));
+        assert!(!labels["rustc"].is_empty());
+        assert!(!labels["git_sha"].is_empty());
+        assert_eq!(build_info.get_metric()[0].get_gauge().value(), 1.0);
+    }
+}
@@ -1,12 +1,21 @@
-use crate::datasource::nut;
+use crate::datasource::{docker as docker_ds, nut};
 use crate::metrics::{
-    cpu_frequency, cpu_usage, disk_io, disk_smart, docker, memory_usage, network_io, ups, zfs_arc,
-    zfs_dataset,
+    TemperatureUnit, btrfs, cgroup, conntrack, cpu_frequency, cpu_throttle, cpu_usage, disk_io,
+    disk_smart, docker, filesystem, kmsg, memory_usage, network_io, nfs, numa, os_info, platform,
+    processes_top, rapl, slabinfo, swap, timesync, tokio_runtime, ups, users, vmstat, wifi,
+    zfs_arc, zfs_dataset, zpool, zram,
 };
+use crate::self_check;
+use crate::server::auth::Credentials;
+use crate::statsd;
 use config::Config;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::SocketAddr;
 use std::path::Path;
+use std::str::FromStr;
 use std::time::Duration;
+use tracing_subscriber::filter::{Directive, LevelFilter};
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Configuration {
@@ -14,6 +23,9 @@ pub struct Configuration {
     pub http: Http,
     pub collector: Collectors,
     pub datasource: DataSources,
+    pub self_check: self_check::Config,
+    pub statsd: statsd::Config,
+    pub metrics: Metrics,
 }
 
 impl Configuration {
@@ -37,6 +49,161 @@ impl Configuration {
 
         Ok(cfg.try_deserialize()?)
     }
+
+    /// Checks the settings which would otherwise only fail (or be silently
+    /// ignored) at runtime, without touching the system or the network
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let mut errors = vec![];
+
+        if LevelFilter::from_str(&self.log.level).is_err() {
+            errors.push(format!("log.level: invalid log level [{}]", self.log.level));
+        }
+
+        for directive in &self.log.directives {
+            if directive.parse::<Directive>().is_err() {
+                errors.push(format!("log.directives: invalid directive [{}]", directive));
+            }
+        }
+
+        if self.log.enable_log_file && self.log.log_file_directory.is_none() {
+            errors.push("log.log_file_directory: required when log files are enabled".to_owned());
+        }
+
+        if self.http.address.trim().is_empty() {
+            errors.push("http.address: must not be empty".to_owned());
+        }
+
+        if self.http.timeout == 0 {
+            errors.push("http.timeout: must be greater than zero".to_owned());
+        }
+
+        if self.http.collection_timeout == Some(0) {
+            errors.push("http.collection_timeout: must be greater than zero".to_owned());
+        }
+
+        // The router panics on invalid or conflicting paths, so they are rejected up front
+        let metrics_path = self.http.metrics_path.trim_end_matches('/');
+        let health_path = self.http.health_path.trim_end_matches('/');
+        for (name, path) in [("metrics_path", metrics_path), ("health_path", health_path)] {
+            if !path.starts_with('/') || path.contains("//") || path.contains(['{', '}', '*']) {
+                errors.push(format!(
+                    "http.{}: must start with '/', must not be the root path and must not \
+                     contain empty segments or wildcards [{}]",
+                    name, path
+                ));
+            }
+        }
+
+        let influx_path = format!("{}/influx", metrics_path);
+        if self.http.enable_health && (health_path == metrics_path || health_path == influx_path) {
+            errors.push("http.health_path: must differ from the metrics paths".to_owned());
+        }
+
+        if let Err(e) = Credentials::new(self.http.auth.as_ref()) {
+            errors.push(e.to_string());
+        }
+
+        for level in &self.collector.kmsg.levels {
+            if !kmsg::LEVELS.contains(&level.as_str()) {
+                errors.push(format!("collector.kmsg.levels: invalid level [{}]", level));
+            }
+        }
+
+        if let Some(namespace) = &self.metrics.namespace
+            && !is_valid_namespace(namespace)
+        {
+            errors.push(format!(
+                "metrics.namespace: invalid metric name prefix [{}]",
+                namespace
+            ));
+        }
+
+        for name in self.metrics.static_labels.keys() {
+            if !is_valid_label_name(name) {
+                errors.push(format!(
+                    "metrics.static_labels: invalid label name [{}]",
+                    name
+                ));
+            }
+        }
+
+        for (name, root) in [
+            ("proc_root", &self.datasource.proc_root),
+            ("sys_root", &self.datasource.sys_root),
+        ] {
+            if !root.starts_with('/') {
+                errors.push(format!("datasource.{}: must be an absolute path", name));
+            }
+        }
+
+        if self.collector.collector_interval_secs == Some(0) {
+            errors.push("collector.collector_interval_secs: must be greater than zero".to_owned());
+        }
+
+        if self.collector.collector_timeout_ms == Some(0) {
+            errors.push("collector.collector_timeout_ms: must be greater than zero".to_owned());
+        }
+
+        if self.self_check.enabled && self.self_check.interval == 0 {
+            errors.push("self_check.interval: must be greater than zero".to_owned());
+        }
+
+        if self.statsd.enabled {
+            if self.statsd.interval_secs == 0 {
+                errors.push("statsd.interval_secs: must be greater than zero".to_owned());
+            }
+
+            if self.statsd.address.parse::<SocketAddr>().is_err() {
+                errors.push(format!(
+                    "statsd.address: invalid socket address [{}]",
+                    self.statsd.address
+                ));
+            }
+        }
+
+        let docker = &self.datasource.docker;
+        if let Some(host) = &docker.host {
+            match docker_ds::DockerHost::parse(host) {
+                Err(error) => errors.push(format!("datasource.docker.host: {}", error)),
+                Ok(docker_ds::DockerHost::Unix(_)) if docker.tls_cert_dir.is_some() => {
+                    errors
+                        .push("datasource.docker.tls_cert_dir: requires a tcp:// host".to_owned());
+                }
+                Ok(_) => {}
+            }
+        }
+
+        let nut = &self.datasource.nut;
+        if format!("{}:{}", nut.address, nut.port)
+            .parse::<SocketAddr>()
+            .is_err()
+        {
+            errors.push(format!(
+                "datasource.nut.address: invalid IP address [{}]",
+                nut.address
+            ));
+        }
+
+        if let Some(ca_file) = nut
+            .tls_ca_file
+            .as_ref()
+            .filter(|path| !Path::new(path).is_file())
+        {
+            errors.push(format!(
+                "datasource.nut.tls_ca_file: no such file [{}]",
+                ca_file
+            ));
+        }
+
+        if !errors.is_empty() {
+            return Err(anyhow::anyhow!(
+                "Invalid configuration:\n  {}",
+                errors.join("\n  ")
+            ));
+        }
+
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -67,6 +234,20 @@ pub struct Http {
     pub port: u16,
     pub address: String,
     pub timeout: u64,
+    /// Maximum time in milliseconds to wait for the collectors. The ones that have
+    /// not completed in time are skipped and their previous measurements are served.
+    pub collection_timeout: Option<u64>,
+    /// Scrapes within that many seconds of the last collection are served the cached
+    /// measurements, instead of running the collectors again
+    pub scrape_cache_secs: u64,
+    /// Credentials required to access the metrics endpoints
+    pub auth: Option<Auth>,
+    pub metrics_path: String,
+    pub health_path: String,
+    pub enable_health: bool,
+    /// Serves the last measurements of the collectors as JSON on `/debug/stats`.
+    /// Disabled by default, because they include device serial numbers.
+    pub enable_debug: bool,
 }
 
 impl Default for Http {
@@ -75,27 +256,100 @@ impl Default for Http {
             port: 9123,
             address: "0.0.0.0".to_owned(),
             timeout: Duration::from_secs(10).as_millis() as u64,
+            collection_timeout: None,
+            scrape_cache_secs: 1,
+            auth: None,
+            metrics_path: "/metrics".to_owned(),
+            health_path: "/health".to_owned(),
+            enable_health: true,
+            enable_debug: false,
         }
     }
 }
 
+/// Either a bearer token, or a username and password for basic authentication
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Auth {
+    pub bearer_token: Option<String>,
+    pub basic_username: Option<String>,
+    pub basic_password: Option<String>,
+}
+
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct Collectors {
     pub cpu_usage: cpu_usage::Config,
     pub cpu_frequency: cpu_frequency::Config,
+    pub cpu_throttle: cpu_throttle::Config,
+    pub platform: platform::Config,
+    pub os_info: os_info::Config,
+    pub processes_top: processes_top::Config,
     pub memory_usage: memory_usage::Config,
+    pub swap: swap::Config,
+    pub zram: zram::Config,
+    pub numa: numa::Config,
+    pub vmstat: vmstat::Config,
     pub network_io: network_io::Config,
+    pub conntrack: conntrack::Config,
+    pub wifi: wifi::Config,
     pub disk_io: disk_io::Config,
     pub disk_temp: disk_smart::Config,
     pub ups: ups::Config,
     pub zfs_arc: zfs_arc::Config,
     pub zfs_dataset: zfs_dataset::Config,
+    pub zpool: zpool::Config,
+    pub timesync: timesync::Config,
+    pub btrfs: btrfs::Config,
+    pub nfs: nfs::Config,
+    pub filesystem: filesystem::Config,
+    pub slabinfo: slabinfo::Config,
+    pub rapl: rapl::Config,
     pub docker: docker::Config,
+    pub cgroup: cgroup::Config,
+    pub kmsg: kmsg::Config,
+    pub users: users::Config,
+    pub tokio_runtime: tokio_runtime::Config,
+    /// Names of the metrics which are emitted as zero, instead of being
+    /// omitted, when the collected value is not available
+    pub zero_if_absent: Vec<String>,
+    /// When set, the collectors run in the background every that many seconds and
+    /// the scrapes are served the latest measurements, instead of triggering them
+    pub collector_interval_secs: Option<u64>,
+    /// Maximum time in milliseconds a single collector may run. A collector which
+    /// exceeds it is abandoned and its previous measurements are served.
+    pub collector_timeout_ms: Option<u64>,
 }
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Metrics {
+    /// Prepended to the names of all collector metrics, e.g. `node` turns
+    /// `system_cpu_usage_ratio` into `node_system_cpu_usage_ratio`
+    pub namespace: Option<String>,
+    /// Constant labels attached to every series, e.g. `host = "fileserver"`
+    pub static_labels: HashMap<String, String>,
+    /// The unit of the temperature metrics, either `celsius` or `fahrenheit`.
+    /// The unit is part of the metric names, e.g. `..._temperature_fahrenheit`
+    pub temperature_unit: TemperatureUnit,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DataSources {
+    /// Where procfs is mounted, e.g. `/host/proc` when running in a container
+    pub proc_root: String,
+    /// Where sysfs is mounted, e.g. `/host/sys` when running in a container
+    pub sys_root: String,
     pub nut: nut::Config,
+    pub docker: docker_ds::Config,
+}
+
+impl Default for DataSources {
+    fn default() -> Self {
+        Self {
+            proc_root: "/proc".to_owned(),
+            sys_root: "/sys".to_owned(),
+            nut: nut::Config::default(),
+            docker: docker_ds::Config::default(),
+        }
+    }
 }
 
 pub fn get_config_base_path<I, S>(args: I) -> anyhow::Result<String>
@@ -152,6 +406,69 @@ where
         .any(|arg| arg.as_ref() == "--print-config")
 }
 
+/// Returns the path of the configuration file passed via `--validate-config`, if any
+pub fn get_validate_config_path<I, S>(args: I) -> anyhow::Result<Option<String>>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    const ARG_VALIDATE: &str = "--validate-config";
+    const ARG_VALIDATE_EQ: &str = "--validate-config=";
+
+    let mut iter = args.into_iter();
+    while let Some(arg) = iter.next() {
+        let arg = arg.as_ref();
+
+        let path = if arg == ARG_VALIDATE {
+            iter.next().map(|v| v.as_ref().trim().to_owned())
+        } else if let Some(path) = arg.strip_prefix(ARG_VALIDATE_EQ) {
+            Some(path.trim().to_owned())
+        } else {
+            continue;
+        };
+
+        return match path.filter(|p| !p.is_empty() && !p.starts_with('-')) {
+            Some(path) => Ok(Some(path)),
+            None => Err(anyhow::anyhow!(
+                "Expected configuration file path after {}",
+                ARG_VALIDATE
+            )),
+        };
+    }
+
+    Ok(None)
+}
+
+/// Loads and validates a single configuration file, which unlike
+/// the regular configuration is required to exist
+pub fn validate_config_file(path: impl AsRef<Path>) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    if !path.is_file() {
+        return Err(anyhow::anyhow!("No such file: {}", path.display()));
+    }
+
+    Configuration::load(path)?.validate()
+}
+
+/// The namespace must itself be a valid metric name
+fn is_valid_namespace(namespace: &str) -> bool {
+    let mut chars = namespace.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == ':')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == ':')
+}
+
+/// Label names starting with `__` are reserved for internal use by Prometheus
+fn is_valid_label_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    !name.starts_with("__")
+        && chars
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
 pub fn print_config(config: &Configuration) -> anyhow::Result<()> {
     println!("{}", toml::to_string(config)?);
     Ok(())
@@ -159,7 +476,131 @@ pub fn print_config(config: &Configuration) -> anyhow::Result<()> {
 
 #[cfg(test)]
 mod tests {
-    use crate::config::{get_config_base_path, should_print_config_and_exit};
+    use crate::config::{
+        Configuration, get_config_base_path, get_validate_config_path,
+        should_print_config_and_exit, validate_config_file,
+    };
+    use std::path::PathBuf;
+
+    fn write_config(name: &str, content: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("hephaestus-{}-{}.toml", name, std::process::id()));
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_validate_config_file() {
+        let path = write_config(
+            "valid",
+            r#"
+[log]
+level = "DEBUG"
+directives = ["hyper=warn"]
+
+[http]
+address = "127.0.0.1"
+port = 9100
+"#,
+        );
+
+        let result = validate_config_file(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(result.is_ok(), "{:?}", result);
+    }
+
+    #[test]
+    fn test_validate_invalid_config_file() {
+        let path = write_config(
+            "invalid",
+            r#"
+[log]
+level = "LOUD"
+
+[http]
+timeout = 0
+
+[http.auth]
+basic_username = "prometheus"
+
+[datasource.nut]
+address = "ups.local"
+tls_ca_file = "/nonexistent/ca.pem"
+
+[metrics]
+namespace = "0node"
+static_labels = { "host-name" = "fileserver" }
+"#,
+        );
+
+        let result = validate_config_file(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let error = result.unwrap_err().to_string();
+        assert!(error.contains("log.level"), "{}", error);
+        assert!(error.contains("http.timeout"), "{}", error);
+        assert!(error.contains("http.auth"), "{}", error);
+        assert!(error.contains("datasource.nut.address"), "{}", error);
+        assert!(error.contains("datasource.nut.tls_ca_file"), "{}", error);
+        assert!(error.contains("metrics.namespace"), "{}", error);
+        assert!(error.contains("metrics.static_labels"), "{}", error);
+    }
+
+    #[test]
+    fn test_validate_http_paths() {
+        for (metrics_path, health_path) in [
+            ("", "/health"),
+            ("metrics", "/health"),
+            ("/", "/health"),
+            ("//", "/health"),
+            ("/metrics/{collector}", "/health"),
+            ("/metrics", "/metrics/"),
+            ("/metrics", "/metrics/influx"),
+        ] {
+            let mut config = Configuration::default();
+            config.http.metrics_path = metrics_path.to_owned();
+            config.http.health_path = health_path.to_owned();
+            config.http.enable_health = true;
+
+            let error = config.validate().unwrap_err().to_string();
+            assert!(error.contains("http."), "{}", error);
+        }
+
+        let mut config = Configuration::default();
+        config.http.metrics_path = "/node/metrics/".to_owned();
+        config.http.enable_health = true;
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_validate_missing_config_file() {
+        assert!(validate_config_file("/nonexistent/hephaestus.toml").is_err());
+    }
+
+    #[test]
+    fn test_get_validate_config_path() {
+        let cases = [
+            (vec!["app"], Some(None)),
+            (
+                vec!["app", "--validate-config", "cfg.toml"],
+                Some(Some("cfg.toml")),
+            ),
+            (
+                vec!["app", "--validate-config=cfg.toml"],
+                Some(Some("cfg.toml")),
+            ),
+            (vec!["app", "--validate-config"], None),
+            (vec!["app", "--validate-config", "--print-config"], None),
+            (vec!["app", "--validate-config="], None),
+        ];
+
+        for (args, expected) in cases {
+            let result = get_validate_config_path(&args).ok();
+            let expected = expected.map(|p| p.map(str::to_owned));
+            assert_eq!(result, expected, "args={:?}", args);
+        }
+    }
 
     #[test]
     fn test_should_print_config_and_exit_cases() {
@@ -0,0 +1,396 @@
+use crate::datasource::{CommandRunner, Reader, Roots};
+use crate::domain::Probe;
+use crate::metrics::btrfs::{AllocationStats, BtrfsStats, DataSource, DeviceStats};
+use std::collections::HashSet;
+use tokio::time::Instant;
+
+const PATH_MOUNTS: &str = "mounts";
+const FS_TYPE_BTRFS: &str = "btrfs";
+const BTRFS: &str = "btrfs";
+
+pub struct BtrfsCli<R, C> {
+    reader: R,
+    roots: Roots,
+    runner: C,
+}
+
+impl<R, C> BtrfsCli<R, C>
+where
+    R: Reader,
+    C: CommandRunner,
+{
+    pub fn new(reader: R, roots: Roots, runner: C) -> Self {
+        Self {
+            reader,
+            roots,
+            runner,
+        }
+    }
+
+    async fn run(&self, args: &[&str]) -> anyhow::Result<String> {
+        let output = self.runner.run(BTRFS, args).await?;
+        if !output.status.success() {
+            return Err(anyhow::anyhow!(
+                "btrfs {} failed with status {:?}: {}",
+                args.join(" "),
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+impl<R, C> Probe for BtrfsCli<R, C>
+where
+    R: Reader,
+    C: CommandRunner,
+{
+    async fn supported(&self) -> bool {
+        // The statistics are queried with the btrfs tool, which may not be installed
+        self.run(&["--version"]).await.is_ok()
+    }
+}
+
+impl<R, C> DataSource for BtrfsCli<R, C>
+where
+    R: Reader,
+    C: CommandRunner,
+{
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn btrfs_stats(&self) -> anyhow::Result<BtrfsStats> {
+        let mounts = self
+            .reader
+            .read_to_string(self.roots.proc(PATH_MOUNTS))
+            .await?;
+
+        let mut devices = vec![];
+        let mut allocations = vec![];
+        for mount in parse_btrfs_mounts(&mounts) {
+            match self.run(&["device", "stats", &mount]).await {
+                Ok(content) => devices.extend(parse_device_stats(&mount, &content)),
+                Err(e) => tracing::error!(error=?e, mount, "Failed to read btrfs device stats"),
+            }
+
+            match self.run(&["filesystem", "usage", "-b", &mount]).await {
+                Ok(content) => allocations.extend(parse_filesystem_usage(&mount, &content)),
+                Err(e) => tracing::error!(error=?e, mount, "Failed to read btrfs filesystem usage"),
+            }
+        }
+
+        Ok(BtrfsStats {
+            timestamp: Instant::now(),
+            devices,
+            allocations,
+        })
+    }
+}
+
+/// Returns the mount points of the btrfs filesystems listed in `/proc/mounts`.
+///
+/// A filesystem (or its subvolumes) may be mounted at several places, but the statistics
+/// are per filesystem, so only the first mount point of each source device is returned.
+fn parse_btrfs_mounts(content: &str) -> Vec<String> {
+    let mut seen = HashSet::new();
+    let mut mounts = vec![];
+
+    for line in content.lines() {
+        let mut parts = line.split_whitespace();
+        let (Some(source), Some(target), Some(fs_type)) =
+            (parts.next(), parts.next(), parts.next())
+        else {
+            continue;
+        };
+
+        if fs_type != FS_TYPE_BTRFS || !seen.insert(source) {
+            continue;
+        }
+
+        mounts.push(unescape_mount_path(target));
+    }
+
+    mounts
+}
+
+/// The kernel escapes spaces, tabs, newlines and backslashes in `/proc/mounts` as octal sequences
+fn unescape_mount_path(path: &str) -> String {
+    let bytes = path.as_bytes();
+    let mut result = Vec::with_capacity(bytes.len());
+
+    let mut idx = 0;
+    while idx < bytes.len() {
+        if bytes[idx] == b'\\'
+            && let Some(value) = bytes
+                .get(idx + 1..idx + 4)
+                .and_then(|octal| std::str::from_utf8(octal).ok())
+                .and_then(|octal| u8::from_str_radix(octal, 8).ok())
+        {
+            result.push(value);
+            idx += 4;
+            continue;
+        }
+
+        result.push(bytes[idx]);
+        idx += 1;
+    }
+
+    String::from_utf8_lossy(&result).into_owned()
+}
+
+/// Parses the output of `btrfs device stats <mount>`, which consists of lines such as
+/// `[/dev/sda].write_io_errs    0`. The devices are returned in the order of appearance.
+fn parse_device_stats(mount: &str, content: &str) -> Vec<DeviceStats> {
+    let mut devices: Vec<DeviceStats> = vec![];
+
+    for line in content.lines() {
+        let Some((key, value)) = line.split_once(char::is_whitespace) else {
+            continue;
+        };
+
+        let Some((device, counter)) = key.strip_prefix('[').and_then(|key| key.rsplit_once("]."))
+        else {
+            continue;
+        };
+
+        let Ok(value) = value.trim().parse::<u64>() else {
+            tracing::debug!("Skipping unexpected btrfs device stats line: {}", line);
+            continue;
+        };
+
+        let idx = match devices.iter().position(|d| d.device == device) {
+            Some(idx) => idx,
+            None => {
+                devices.push(DeviceStats {
+                    mount: mount.to_owned(),
+                    device: device.to_owned(),
+                    ..Default::default()
+                });
+                devices.len() - 1
+            }
+        };
+
+        let stats = &mut devices[idx];
+        match counter {
+            "write_io_errs" => stats.write_errors = value,
+            "read_io_errs" => stats.read_errors = value,
+            "flush_io_errs" => stats.flush_errors = value,
+            "corruption_errs" => stats.corruption_errors = value,
+            "generation_errs" => stats.generation_errors = value,
+            _ => {}
+        }
+    }
+
+    devices
+}
+
+/// Parses the per block group type headers from the output of `btrfs filesystem usage -b`,
+/// such as `Metadata,DUP: Size:1073741824, Used:131072 (0.01%)`.
+fn parse_filesystem_usage(mount: &str, content: &str) -> Vec<AllocationStats> {
+    let mut allocations = vec![];
+
+    for line in content.lines() {
+        let Some((header, values)) = line.split_once(':') else {
+            continue;
+        };
+
+        let Some((kind, profile)) = header.split_once(',') else {
+            continue;
+        };
+
+        let kind = kind.trim().to_lowercase();
+        if !matches!(kind.as_str(), "data" | "metadata" | "system") {
+            continue;
+        }
+
+        let (mut size, mut used) = (None, None);
+        for value in values.split(',') {
+            let value = value.trim();
+            if let Some(v) = value.strip_prefix("Size:") {
+                size = v.split_whitespace().next().and_then(|v| v.parse().ok());
+            } else if let Some(v) = value.strip_prefix("Used:") {
+                used = v.split_whitespace().next().and_then(|v| v.parse().ok());
+            }
+        }
+
+        let (Some(size), Some(used)) = (size, used) else {
+            tracing::debug!("Skipping unexpected btrfs filesystem usage line: {}", line);
+            continue;
+        };
+
+        allocations.push(AllocationStats {
+            mount: mount.to_owned(),
+            kind,
+            profile: profile.trim().to_lowercase(),
+            size,
+            used,
+        });
+    }
+
+    allocations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datasource::tests::{HardcodedCommandRunner, HardcodedReader};
+
+    const MOUNTS: &str = r#"sysfs /sys sysfs rw,nosuid,nodev,noexec,relatime 0 0
+proc /proc proc rw,nosuid,nodev,noexec,relatime 0 0
+/dev/nvme0n1p2 / btrfs rw,relatime,ssd,space_cache=v2,subvolid=256,subvol=/@ 0 0
+/dev/nvme0n1p2 /home btrfs rw,relatime,ssd,space_cache=v2,subvolid=257,subvol=/@home 0 0
+/dev/nvme0n1p1 /boot/efi vfat rw,relatime,fmask=0077,dmask=0077 0 0
+/dev/sda /mnt/media\040library btrfs rw,relatime,space_cache=v2,subvolid=5,subvol=/ 0 0
+"#;
+
+    const DEVICE_STATS: &str = r#"[/dev/sda].write_io_errs    0
+[/dev/sda].read_io_errs     3
+[/dev/sda].flush_io_errs    0
+[/dev/sda].corruption_errs  12
+[/dev/sda].generation_errs  0
+[/dev/sdb].write_io_errs    1
+[/dev/sdb].read_io_errs     0
+[/dev/sdb].flush_io_errs    2
+[/dev/sdb].corruption_errs  0
+[/dev/sdb].generation_errs  4
+"#;
+
+    const FILESYSTEM_USAGE: &str = r#"Overall:
+    Device size:                      4000787030016
+    Device allocated:                 1086626725888
+    Device unallocated:               2914160304128
+    Device missing:                               0
+    Used:                              997921251328
+    Free (estimated):                 1501366362112      (min: 1501366362112)
+    Free (statfs, df):                1501366358016
+    Data ratio:                                2.00
+    Metadata ratio:                            2.00
+    Global reserve:                       536870912      (used: 0)
+    Multiple profiles:                           no
+
+Data,RAID1: Size:541165879296, Used:497632755712 (91.96%)
+   /dev/sda     541165879296
+   /dev/sdb     541165879296
+
+Metadata,RAID1: Size:2147483648, Used:1327611904 (61.82%)
+   /dev/sda       2147483648
+   /dev/sdb       2147483648
+
+System,RAID1: Size:33554432, Used:98304 (0.29%)
+   /dev/sda         33554432
+   /dev/sdb         33554432
+
+Unallocated:
+   /dev/sda     1457080152064
+   /dev/sdb     1457080152064
+"#;
+
+    #[test]
+    fn test_parse_btrfs_mounts() {
+        let mounts = parse_btrfs_mounts(MOUNTS);
+        assert_eq!(mounts, vec!["/", "/mnt/media library"]);
+    }
+
+    #[test]
+    fn test_parse_device_stats() {
+        let devices = parse_device_stats("/mnt/data", DEVICE_STATS);
+        assert_eq!(devices.len(), 2);
+
+        assert_eq!(
+            devices[0],
+            DeviceStats {
+                mount: "/mnt/data".to_owned(),
+                device: "/dev/sda".to_owned(),
+                write_errors: 0,
+                read_errors: 3,
+                flush_errors: 0,
+                corruption_errors: 12,
+                generation_errors: 0,
+            }
+        );
+
+        assert_eq!(
+            devices[1],
+            DeviceStats {
+                mount: "/mnt/data".to_owned(),
+                device: "/dev/sdb".to_owned(),
+                write_errors: 1,
+                read_errors: 0,
+                flush_errors: 2,
+                corruption_errors: 0,
+                generation_errors: 4,
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_filesystem_usage() {
+        let allocations = parse_filesystem_usage("/mnt/data", FILESYSTEM_USAGE);
+        assert_eq!(allocations.len(), 3);
+
+        assert_eq!(allocations[0].mount, "/mnt/data");
+        assert_eq!(allocations[0].kind, "data");
+        assert_eq!(allocations[0].profile, "raid1");
+        assert_eq!(allocations[0].size, 541165879296);
+        assert_eq!(allocations[0].used, 497632755712);
+
+        assert_eq!(allocations[1].kind, "metadata");
+        assert_eq!(allocations[1].size, 2147483648);
+        assert_eq!(allocations[1].used, 1327611904);
+
+        assert_eq!(allocations[2].kind, "system");
+        assert_eq!(allocations[2].size, 33554432);
+        assert_eq!(allocations[2].used, 98304);
+    }
+
+    #[tokio::test]
+    async fn test_btrfs_datasource() {
+        let mut reader = HardcodedReader::new();
+        reader.add_response(
+            Roots::default().proc(PATH_MOUNTS),
+            "/dev/sda /mnt/data btrfs rw,relatime 0 0\n",
+        );
+
+        let mut runner = HardcodedCommandRunner::new();
+        runner.add_response("btrfs device stats /mnt/data", 0, DEVICE_STATS);
+        runner.add_response("btrfs filesystem usage -b /mnt/data", 0, FILESYSTEM_USAGE);
+
+        let ds = BtrfsCli::new(reader, Roots::default(), runner);
+        let stats = ds.btrfs_stats().await.unwrap();
+        assert_eq!(stats.devices.len(), 2);
+        assert_eq!(stats.allocations.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_btrfs_datasource_command_failure() {
+        let mut reader = HardcodedReader::new();
+        reader.add_response(
+            Roots::default().proc(PATH_MOUNTS),
+            "/dev/sda /mnt/data btrfs rw,relatime 0 0\n",
+        );
+
+        let mut runner = HardcodedCommandRunner::new();
+        runner.add_response("btrfs device stats /mnt/data", 1, "");
+        runner.add_response("btrfs filesystem usage -b /mnt/data", 0, FILESYSTEM_USAGE);
+
+        let ds = BtrfsCli::new(reader, Roots::default(), runner);
+        let stats = ds.btrfs_stats().await.unwrap();
+        assert!(stats.devices.is_empty());
+        assert_eq!(stats.allocations.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_without_btrfs_tools() {
+        let ds = BtrfsCli::new(
+            HardcodedReader::new(),
+            Roots::default(),
+            HardcodedCommandRunner::new(),
+        );
+        assert!(!ds.supported().await);
+
+        let mut runner = HardcodedCommandRunner::new();
+        runner.add_response("btrfs --version", 0, "btrfs-progs v6.6.3\n");
+        let ds = BtrfsCli::new(HardcodedReader::new(), Roots::default(), runner);
+        assert!(ds.supported().await);
+    }
+}
@@ -0,0 +1,210 @@
+use crate::datasource::{Reader, Roots};
+use crate::domain::Probe;
+use crate::metrics::cgroup::{CgroupStats, CgroupsStats, DataSource};
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use tokio::time::Instant;
+
+const PATH_CGROUP: &str = "fs/cgroup";
+// Present only at the root of the unified (v2) hierarchy
+const PATH_CONTROLLERS: &str = "fs/cgroup/cgroup.controllers";
+
+const MICROS_PER_SECOND: f64 = 1_000_000.0;
+
+pub struct SysfsCgroup<R> {
+    reader: R,
+    roots: Roots,
+}
+
+impl<R> SysfsCgroup<R>
+where
+    R: Reader,
+{
+    pub fn new(reader: R, roots: Roots) -> Self {
+        Self { reader, roots }
+    }
+
+    /// Returns `None` when the file does not exist, i.e. the controller is not enabled
+    async fn read(&self, cgroup: &str, file: &str) -> anyhow::Result<Option<String>> {
+        let path = format!("{}/{}/{}", self.roots.sys(PATH_CGROUP), cgroup, file);
+
+        match self.reader.read_to_string(&path).await {
+            Ok(content) => Ok(Some(content)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(anyhow::anyhow!("Failed to read {}: {}", path, e)),
+        }
+    }
+
+    async fn cgroup(&self, cgroup: &str) -> anyhow::Result<CgroupStats> {
+        let memory_used = self
+            .read(cgroup, "memory.current")
+            .await?
+            .and_then(|v| v.trim().parse().ok());
+
+        // The limit is "max" when the memory is not limited
+        let memory_limit = self
+            .read(cgroup, "memory.max")
+            .await?
+            .and_then(|v| v.trim().parse().ok());
+
+        let cpu_usage_seconds = self.read(cgroup, "cpu.stat").await?.and_then(|v| {
+            let usec = parse_flat_keyed(&v).get("usage_usec").copied()?;
+            Some(usec as f64 / MICROS_PER_SECOND)
+        });
+
+        let io = self
+            .read(cgroup, "io.stat")
+            .await?
+            .map(|v| parse_io_stat(&v));
+
+        Ok(CgroupStats {
+            cgroup: cgroup.to_owned(),
+            memory_used,
+            memory_limit,
+            cpu_usage_seconds,
+            io_read_bytes: io.map(|(read, _)| read),
+            io_write_bytes: io.map(|(_, write)| write),
+        })
+    }
+}
+
+impl<R> Probe for SysfsCgroup<R>
+where
+    R: Reader,
+{
+    async fn supported(&self) -> bool {
+        self.reader
+            .read_to_string(self.roots.sys(PATH_CONTROLLERS))
+            .await
+            .is_ok()
+    }
+}
+
+impl<R> DataSource for SysfsCgroup<R>
+where
+    R: Reader,
+{
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn cgroup_stats(&self, cgroups: &[String]) -> anyhow::Result<CgroupsStats> {
+        let mut stats = Vec::with_capacity(cgroups.len());
+
+        for cgroup in cgroups {
+            let cgroup = cgroup.trim_matches('/');
+            let path = format!("{}/{}", self.roots.sys(PATH_CGROUP), cgroup);
+
+            // The services may be stopped, which removes their cgroup
+            if self.reader.read_dir(&path).await.is_err() {
+                tracing::debug!("Skipping missing cgroup: {}", path);
+                continue;
+            }
+
+            stats.push(self.cgroup(cgroup).await?);
+        }
+
+        Ok(CgroupsStats {
+            timestamp: Instant::now(),
+            cgroups: stats,
+        })
+    }
+}
+
+/// Parses the flat keyed files, such as `cpu.stat`, with one `key value` per line
+fn parse_flat_keyed(content: &str) -> HashMap<&str, u64> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let (key, value) = line.split_once(' ')?;
+            Some((key, value.trim().parse().ok()?))
+        })
+        .collect()
+}
+
+/// Sums the read and written bytes across all devices in `io.stat`:
+///
+/// ```text
+/// 8:16 rbytes=1459200 wbytes=314773504 rios=192 wios=353 dbytes=0 dios=0
+/// ```
+fn parse_io_stat(content: &str) -> (u64, u64) {
+    let mut read = 0u64;
+    let mut write = 0u64;
+
+    for (key, value) in content
+        .lines()
+        .flat_map(|line| line.split_whitespace().skip(1))
+        .filter_map(|kv| kv.split_once('='))
+    {
+        let Ok(value) = value.parse::<u64>() else {
+            continue;
+        };
+
+        match key {
+            "rbytes" => read = read.wrapping_add(value),
+            "wbytes" => write = write.wrapping_add(value),
+            _ => {}
+        }
+    }
+
+    (read, write)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datasource::tests::HardcodedReader;
+
+    const CPU_STAT: &str = "usage_usec 2500000
+user_usec 2000000
+system_usec 500000
+nr_periods 0
+nr_throttled 0
+throttled_usec 0
+";
+
+    const IO_STAT: &str = "8:16 rbytes=1459200 wbytes=314773504 rios=192 wios=353 dbytes=0 dios=0
+259:0 rbytes=1000 wbytes=2000 rios=1 wios=2 dbytes=0 dios=0
+";
+
+    #[test]
+    fn test_parse_flat_keyed() {
+        let stats = parse_flat_keyed(CPU_STAT);
+
+        assert_eq!(stats.get("usage_usec"), Some(&2500000));
+        assert_eq!(stats.get("system_usec"), Some(&500000));
+        assert_eq!(stats.get("missing"), None);
+    }
+
+    #[test]
+    fn test_parse_io_stat() {
+        assert_eq!(parse_io_stat(IO_STAT), (1460200, 314775504));
+        assert_eq!(parse_io_stat(""), (0, 0));
+    }
+
+    #[tokio::test]
+    async fn test_cgroup_stats() -> anyhow::Result<()> {
+        let root = Roots::default().sys(PATH_CGROUP);
+        let mut reader = HardcodedReader::new();
+        reader.add_dir(format!("{root}/system.slice"), &["memory.current"]);
+        reader.add_response(format!("{root}/system.slice/memory.current"), "4096\n");
+        reader.add_response(format!("{root}/system.slice/memory.max"), "max\n");
+        reader.add_response(format!("{root}/system.slice/cpu.stat"), CPU_STAT);
+        reader.add_response(format!("{root}/system.slice/io.stat"), IO_STAT);
+
+        let data_source = SysfsCgroup::new(reader, Roots::default());
+        let cgroups = ["/system.slice".to_owned(), "gone.slice".to_owned()];
+        let stats = data_source.cgroup_stats(&cgroups).await?;
+
+        assert_eq!(
+            stats.cgroups,
+            [CgroupStats {
+                cgroup: "system.slice".into(),
+                memory_used: Some(4096),
+                memory_limit: None,
+                cpu_usage_seconds: Some(2.5),
+                io_read_bytes: Some(1460200),
+                io_write_bytes: Some(314775504),
+            }]
+        );
+
+        Ok(())
+    }
+}
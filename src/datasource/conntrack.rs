@@ -0,0 +1,166 @@
+use crate::datasource::{Reader, Roots};
+use crate::domain::Probe;
+use crate::metrics::conntrack::{ConntrackStats, DataSource};
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use tokio::time::Instant;
+
+const PATH_COUNT: &str = "sys/net/netfilter/nf_conntrack_count";
+const PATH_MAX: &str = "sys/net/netfilter/nf_conntrack_max";
+const PATH_STAT: &str = "net/stat/nf_conntrack";
+
+pub struct ProcConntrack<R> {
+    reader: R,
+    roots: Roots,
+}
+
+impl<R> ProcConntrack<R>
+where
+    R: Reader,
+{
+    pub fn new(reader: R, roots: Roots) -> Self {
+        Self { reader, roots }
+    }
+
+    /// Returns `None` if the file does not exist
+    async fn read_optional(&self, path: &str) -> anyhow::Result<Option<String>> {
+        match self.reader.read_to_string(path).await {
+            Ok(content) => Ok(Some(content)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(anyhow::anyhow!("Failed to read {}: {}", path, e)),
+        }
+    }
+}
+
+impl<R> Probe for ProcConntrack<R>
+where
+    R: Reader,
+{
+    async fn supported(&self) -> bool {
+        // The sysctls are present only while the nf_conntrack module is loaded
+        self.reader
+            .read_to_string(self.roots.proc(PATH_COUNT))
+            .await
+            .is_ok()
+    }
+}
+
+impl<R> DataSource for ProcConntrack<R>
+where
+    R: Reader,
+{
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn conntrack_stats(&self) -> anyhow::Result<ConntrackStats> {
+        let entries = self
+            .reader
+            .read_to_string(self.roots.proc(PATH_COUNT))
+            .await?
+            .trim()
+            .parse()?;
+
+        let max_entries = self
+            .read_optional(&self.roots.proc(PATH_MAX))
+            .await?
+            .and_then(|v| v.trim().parse().ok());
+
+        let counters = self
+            .read_optional(&self.roots.proc(PATH_STAT))
+            .await?
+            .map(|v| sum_per_cpu_stats(&v))
+            .unwrap_or_default();
+
+        Ok(ConntrackStats {
+            timestamp: Instant::now(),
+            entries,
+            max_entries,
+            inserted: counters.get("insert").copied(),
+            dropped: counters.get("drop").copied(),
+            invalid: counters.get("invalid").copied(),
+        })
+    }
+}
+
+/// Sums the per-CPU counters of `/proc/net/stat/nf_conntrack`. The first line
+/// names the columns, which differ between kernel versions, followed by one
+/// line of hexadecimal values per CPU:
+///
+/// ```text
+/// entries  clashres found new invalid ignore delete chainlength insert insert_failed drop early_drop ...
+/// 000001f4  00000000 00000000 00000000 00000021 00000000 00000000 00000000 00000000 00000000 00000003 00000000 ...
+/// ```
+///
+/// The `entries` column is the global count repeated on every line, so it is not summed.
+fn sum_per_cpu_stats(content: &str) -> HashMap<String, u64> {
+    let mut lines = content.lines();
+    let Some(header) = lines.next() else {
+        return HashMap::new();
+    };
+
+    let columns = header.split_whitespace().collect::<Vec<_>>();
+    let mut sums = HashMap::new();
+
+    for line in lines {
+        for (column, value) in columns.iter().zip(line.split_whitespace()) {
+            if *column == "entries" {
+                continue;
+            }
+
+            let Ok(value) = u64::from_str_radix(value, 16) else {
+                tracing::debug!("Skipping invalid nf_conntrack {} value: {}", column, value);
+                continue;
+            };
+
+            let sum = sums.entry(column.to_string()).or_insert(0u64);
+            *sum = sum.wrapping_add(value);
+        }
+    }
+
+    sums
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datasource::tests::HardcodedReader;
+
+    const NF_CONNTRACK_STAT: &str = "entries  clashres found new invalid ignore delete chainlength insert insert_failed drop early_drop icmp_error  expect_new expect_create expect_delete search_restart
+000001f4  00000000 00000000 00000000 00000021 00000000 00000000 00000000 00000010 00000000 00000003 00000000 00000000  00000000 00000000 00000000 00000002
+000001f4  00000000 00000000 00000000 0000000f 00000000 00000000 00000000 000000f0 00000000 00000001 00000000 00000000  00000000 00000000 00000000 00000000
+";
+
+    #[test]
+    fn test_sum_per_cpu_stats() {
+        let sums = sum_per_cpu_stats(NF_CONNTRACK_STAT);
+
+        assert_eq!(sums["invalid"], 0x21 + 0x0f);
+        assert_eq!(sums["insert"], 0x10 + 0xf0);
+        assert_eq!(sums["drop"], 4);
+        assert_eq!(sums["search_restart"], 2);
+        assert!(!sums.contains_key("entries"));
+    }
+
+    #[test]
+    fn test_sum_per_cpu_stats_without_cpus() {
+        assert!(sum_per_cpu_stats("").is_empty());
+        assert!(sum_per_cpu_stats("entries insert\n").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_conntrack_stats() -> anyhow::Result<()> {
+        let roots = Roots::default();
+        let mut reader = HardcodedReader::new();
+        reader.add_response(roots.proc(PATH_COUNT), "500\n");
+        reader.add_response(roots.proc(PATH_MAX), "2000\n");
+        reader.add_response(roots.proc(PATH_STAT), NF_CONNTRACK_STAT);
+
+        let stats = ProcConntrack::new(reader, roots).conntrack_stats().await?;
+
+        assert_eq!(stats.entries, 500);
+        assert_eq!(stats.usage(), Some(0.25));
+        assert_eq!(stats.inserted, Some(0x100));
+        assert_eq!(stats.dropped, Some(4));
+        assert_eq!(stats.invalid, Some(0x30));
+
+        Ok(())
+    }
+}
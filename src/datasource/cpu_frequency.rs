@@ -1,16 +1,43 @@
-use crate::datasource::Reader;
-use crate::metrics::cpu_frequency::{CpuFreqStats, DataSource};
+use crate::datasource::{Reader, Roots};
+use crate::metrics::cpu_frequency::{CoreFreqStats, CpuFreqStats, DataSource};
+
+const PATH_CPU: &str = "devices/system/cpu";
 
 pub struct CpuFrequency<R> {
     reader: R,
+    roots: Roots,
 }
 
 impl<R> CpuFrequency<R>
 where
     R: Reader,
 {
-    pub fn new(reader: R) -> Self {
-        Self { reader }
+    pub fn new(reader: R, roots: Roots) -> Self {
+        Self { reader, roots }
+    }
+
+    async fn read_optional(&self, core: usize, file: &str) -> Option<String> {
+        let path = format!("{}/cpu{}/cpufreq/{}", self.roots.sys(PATH_CPU), core, file);
+
+        self.reader
+            .read_to_string(&path)
+            .await
+            .inspect_err(|e| {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    tracing::warn!("Failed to read {}: {}", path, e);
+                }
+            })
+            .ok()
+            .map(|content| content.trim().to_owned())
+    }
+
+    async fn read_optional_freq(&self, core: usize, file: &str) -> Option<u64> {
+        let content = self.read_optional(core, file).await?;
+        content
+            .parse::<u64>()
+            .inspect_err(|_| tracing::error!("Failed to parse {} for core {}", file, core))
+            .ok()
+            .map(|freq| freq * 1000)
     }
 }
 
@@ -20,26 +47,42 @@ where
 {
     #[tracing::instrument(level = "debug", skip_all)]
     async fn cpu_freq(&self) -> anyhow::Result<CpuFreqStats> {
-        let mut core_freq = Vec::new();
+        let mut cores = self
+            .reader
+            .read_dir(self.roots.sys(PATH_CPU))
+            .await?
+            .into_iter()
+            .filter_map(|name| name.strip_prefix("cpu")?.parse::<usize>().ok())
+            .collect::<Vec<_>>();
+        cores.sort_unstable();
 
-        for core in 0..256 {
+        let mut core_freq = Vec::new();
+        for core in cores {
             let path = format!(
-                "/sys/devices/system/cpu/cpu{}/cpufreq/scaling_cur_freq",
+                "{}/cpu{}/cpufreq/scaling_cur_freq",
+                self.roots.sys(PATH_CPU),
                 core
             );
 
             match self.reader.read_to_string(&path).await {
                 Ok(content) => {
-                    let freq = content.trim().parse::<u64>().unwrap_or_else(|_| {
-                        tracing::error!("Failed to parse teh CPU frequency for core {}", core);
+                    let current = content.trim().parse::<u64>().unwrap_or_else(|_| {
+                        tracing::error!("Failed to parse the CPU frequency for core {}", core);
                         0
                     }) * 1000;
-                    core_freq.push(freq);
+
+                    core_freq.push(CoreFreqStats {
+                        core,
+                        current,
+                        min: self.read_optional_freq(core, "scaling_min_freq").await,
+                        max: self.read_optional_freq(core, "scaling_max_freq").await,
+                        governor: self.read_optional(core, "scaling_governor").await,
+                    });
                 }
 
                 Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
-                    // There are no more cores to process
-                    break;
+                    // Offline cores do not have a cpufreq directory
+                    tracing::debug!("Skipping CPU {} without frequency scaling", core);
                 }
 
                 Err(e) => return Err(anyhow::anyhow!("Failed to read CPU {}: {}", core, e)),
@@ -56,32 +99,84 @@ where
 
 #[cfg(test)]
 mod tests {
-    use crate::datasource::cpu_frequency::CpuFrequency;
+    use crate::datasource::Roots;
+    use crate::datasource::cpu_frequency::{CpuFrequency, PATH_CPU};
     use crate::datasource::tests::HardcodedReader;
     use crate::metrics::cpu_frequency::DataSource;
 
     #[tokio::test]
     async fn test_cpu_frequency() {
         let mut reader = HardcodedReader::new();
-        reader.add_response(cpu_freq_path(0), format!("{}", 1100980));
-        reader.add_response(cpu_freq_path(1), format!("{}", 883485));
-        reader.add_response(cpu_freq_path(2), format!("{}", 4203950));
-        reader.add_response(cpu_freq_path(3), format!("{}", 5100362));
+        reader.add_dir(
+            Roots::default().sys(PATH_CPU),
+            &[
+                "cpu0", "cpu1", "cpu2", "cpu3", "cpufreq", "cpuidle", "online",
+            ],
+        );
+        reader.add_response(cpu_freq_path(0, "scaling_cur_freq"), format!("{}", 1100980));
+        reader.add_response(cpu_freq_path(1, "scaling_cur_freq"), format!("{}", 883485));
+        reader.add_response(cpu_freq_path(2, "scaling_cur_freq"), format!("{}", 4203950));
+        reader.add_response(cpu_freq_path(3, "scaling_cur_freq"), format!("{}", 5100362));
 
-        let ds = CpuFrequency::new(reader);
+        let ds = CpuFrequency::new(reader, Roots::default());
         let stats = ds.cpu_freq().await.unwrap();
 
         assert_eq!(4, stats.cores.len());
-        assert_eq!(1000 * 1100980, stats.cores[0]);
-        assert_eq!(1000 * 883485, stats.cores[1]);
-        assert_eq!(1000 * 4203950, stats.cores[2]);
-        assert_eq!(1000 * 5100362, stats.cores[3]);
+        assert_eq!(1000 * 1100980, stats.cores[0].current);
+        assert_eq!(1000 * 883485, stats.cores[1].current);
+        assert_eq!(1000 * 4203950, stats.cores[2].current);
+        assert_eq!(1000 * 5100362, stats.cores[3].current);
+
+        assert_eq!(None, stats.cores[0].min);
+        assert_eq!(None, stats.cores[0].max);
+        assert_eq!(None, stats.cores[0].governor);
+    }
+
+    #[tokio::test]
+    async fn test_cpu_frequency_limits_and_governor() {
+        let mut reader = HardcodedReader::new();
+        reader.add_dir(Roots::default().sys(PATH_CPU), &["cpu0", "cpu1"]);
+        for (core, governor) in [(0, "powersave"), (1, "performance")] {
+            reader.add_response(cpu_freq_path(core, "scaling_cur_freq"), "1600000\n");
+            reader.add_response(cpu_freq_path(core, "scaling_min_freq"), "800000\n");
+            reader.add_response(cpu_freq_path(core, "scaling_max_freq"), "4700000\n");
+            reader.add_response(
+                cpu_freq_path(core, "scaling_governor"),
+                format!("{}\n", governor),
+            );
+        }
+
+        let ds = CpuFrequency::new(reader, Roots::default());
+        let stats = ds.cpu_freq().await.unwrap();
+
+        assert_eq!(2, stats.cores.len());
+        assert_eq!(1000 * 1600000, stats.cores[0].current);
+        assert_eq!(Some(1000 * 800000), stats.cores[0].min);
+        assert_eq!(Some(1000 * 4700000), stats.cores[0].max);
+        assert_eq!(Some("powersave"), stats.cores[0].governor.as_deref());
+        assert_eq!(Some("performance"), stats.cores[1].governor.as_deref());
+    }
+
+    #[tokio::test]
+    async fn test_cpu_frequency_offline_cores() {
+        let mut reader = HardcodedReader::new();
+        // cpu2 is offline, and the directory entries are not sorted numerically
+        reader.add_dir(
+            Roots::default().sys(PATH_CPU),
+            &["cpu10", "cpu3", "cpu1", "cpu0", "cpu2", "possible"],
+        );
+        for core in [0, 1, 3, 10] {
+            reader.add_response(cpu_freq_path(core, "scaling_cur_freq"), "1600000\n");
+        }
+
+        let ds = CpuFrequency::new(reader, Roots::default());
+        let stats = ds.cpu_freq().await.unwrap();
+
+        let cores = stats.cores.iter().map(|c| c.core).collect::<Vec<_>>();
+        assert_eq!(vec![0, 1, 3, 10], cores);
     }
 
-    fn cpu_freq_path(cpu: usize) -> String {
-        format!(
-            "/sys/devices/system/cpu/cpu{}/cpufreq/scaling_cur_freq",
-            cpu
-        )
+    fn cpu_freq_path(cpu: usize, file: &str) -> String {
+        format!("/sys/devices/system/cpu/cpu{}/cpufreq/{}", cpu, file)
     }
 }
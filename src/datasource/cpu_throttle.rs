@@ -0,0 +1,204 @@
+use crate::datasource::{Reader, Roots};
+use crate::metrics::cpu_throttle::{
+    CoreThrottleStats, CpuThrottleStats, DataSource, PackageThrottleStats,
+};
+use std::io::ErrorKind;
+use tokio::time::Instant;
+
+const PATH_CPU: &str = "devices/system/cpu";
+
+pub struct SysfsCpuThrottle<R> {
+    reader: R,
+    roots: Roots,
+}
+
+impl<R> SysfsCpuThrottle<R>
+where
+    R: Reader,
+{
+    pub fn new(reader: R, roots: Roots) -> Self {
+        Self { reader, roots }
+    }
+
+    /// Returns `None` if the file does not exist, which is the case on CPUs
+    /// and kernels that do not support thermal throttle reporting
+    async fn read_value(&self, core: usize, file: &str) -> anyhow::Result<Option<u64>> {
+        let path = format!("{}/cpu{}/{}", self.roots.sys(PATH_CPU), core, file);
+
+        match self.reader.read_to_string(&path).await {
+            Ok(content) => Ok(Some(content.trim().parse()?)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(anyhow::anyhow!("Failed to read {}: {}", path, e)),
+        }
+    }
+}
+
+impl<R> DataSource for SysfsCpuThrottle<R>
+where
+    R: Reader,
+{
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn throttle_stats(&self) -> anyhow::Result<CpuThrottleStats> {
+        let mut cores = vec![];
+        let mut packages: Vec<PackageThrottleStats> = vec![];
+
+        let mut all_cores = self
+            .reader
+            .read_dir(self.roots.sys(PATH_CPU))
+            .await?
+            .into_iter()
+            .filter_map(|name| name.strip_prefix("cpu")?.parse::<usize>().ok())
+            .collect::<Vec<_>>();
+        all_cores.sort_unstable();
+
+        for core in all_cores {
+            let Some(package) = self
+                .read_value(core, "topology/physical_package_id")
+                .await?
+            else {
+                // The offline cores have no topology
+                continue;
+            };
+
+            let core_throttle = self
+                .read_value(core, "thermal_throttle/core_throttle_count")
+                .await?;
+
+            if let Some(throttle_count) = core_throttle {
+                cores.push(CoreThrottleStats {
+                    core,
+                    throttle_count,
+                });
+            }
+
+            // All cores in a package report the same package counter
+            if packages.iter().any(|p| p.package == package) {
+                continue;
+            }
+
+            let package_throttle = self
+                .read_value(core, "thermal_throttle/package_throttle_count")
+                .await?;
+
+            if let Some(throttle_count) = package_throttle {
+                packages.push(PackageThrottleStats {
+                    package,
+                    throttle_count,
+                });
+            }
+        }
+
+        Ok(CpuThrottleStats {
+            timestamp: Instant::now(),
+            cores,
+            packages,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datasource::tests::HardcodedReader;
+
+    fn path(core: usize, file: &str) -> String {
+        format!("{}/cpu{}/{}", Roots::default().sys(PATH_CPU), core, file)
+    }
+
+    #[tokio::test]
+    async fn test_cpu_throttle() {
+        let mut reader = HardcodedReader::new();
+        reader.add_dir(
+            Roots::default().sys(PATH_CPU),
+            &[
+                "cpu0", "cpu1", "cpu2", "cpu3", "cpufreq", "cpuidle", "online",
+            ],
+        );
+        for (core, package, core_count, package_count) in
+            [(0, 0, 12, 40), (1, 0, 0, 40), (2, 1, 3, 7), (3, 1, 5, 7)]
+        {
+            reader.add_response(
+                path(core, "topology/physical_package_id"),
+                format!("{package}\n"),
+            );
+            reader.add_response(
+                path(core, "thermal_throttle/core_throttle_count"),
+                format!("{core_count}\n"),
+            );
+            reader.add_response(
+                path(core, "thermal_throttle/package_throttle_count"),
+                format!("{package_count}\n"),
+            );
+        }
+
+        let ds = SysfsCpuThrottle::new(reader, Roots::default());
+        let stats = ds.throttle_stats().await.unwrap();
+
+        assert_eq!(
+            stats.cores,
+            vec![
+                CoreThrottleStats {
+                    core: 0,
+                    throttle_count: 12
+                },
+                CoreThrottleStats {
+                    core: 1,
+                    throttle_count: 0
+                },
+                CoreThrottleStats {
+                    core: 2,
+                    throttle_count: 3
+                },
+                CoreThrottleStats {
+                    core: 3,
+                    throttle_count: 5
+                },
+            ]
+        );
+
+        assert_eq!(
+            stats.packages,
+            vec![
+                PackageThrottleStats {
+                    package: 0,
+                    throttle_count: 40
+                },
+                PackageThrottleStats {
+                    package: 1,
+                    throttle_count: 7
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cpu_throttle_offline_core() {
+        let mut reader = HardcodedReader::new();
+        // cpu1 is offline, and the directory entries are not sorted numerically
+        reader.add_dir(Roots::default().sys(PATH_CPU), &["cpu2", "cpu0", "cpu1"]);
+        for core in [0, 2] {
+            reader.add_response(path(core, "topology/physical_package_id"), "0\n");
+            reader.add_response(path(core, "thermal_throttle/core_throttle_count"), "4\n");
+        }
+
+        let ds = SysfsCpuThrottle::new(reader, Roots::default());
+        let stats = ds.throttle_stats().await.unwrap();
+
+        let cores = stats.cores.iter().map(|c| c.core).collect::<Vec<_>>();
+        assert_eq!(cores, vec![0, 2]);
+    }
+
+    #[tokio::test]
+    async fn test_cpu_throttle_not_supported() {
+        let mut reader = HardcodedReader::new();
+        reader.add_dir(Roots::default().sys(PATH_CPU), &["cpu0", "cpu1"]);
+        reader.add_response(path(0, "topology/physical_package_id"), "0\n");
+        reader.add_response(path(1, "topology/physical_package_id"), "0\n");
+
+        let ds = SysfsCpuThrottle::new(reader, Roots::default());
+        let stats = ds.throttle_stats().await.unwrap();
+
+        assert!(stats.cores.is_empty());
+        assert!(stats.packages.is_empty());
+    }
+}
@@ -1,10 +1,10 @@
-use crate::datasource::Reader;
+use crate::datasource::{Reader, Roots};
 use crate::metrics::cpu_usage::{CoreStats, CoreUsageStats, CpuUsageStats, DataSource};
 use std::sync::Mutex;
 use tokio::time::{Duration, Instant};
 
-const PATH_PROC_STAT: &str = "/proc/stat";
-const MIN_TIME_BETWEEN_MEASUREMENTS: Duration = Duration::from_millis(250);
+const PATH_PROC_STAT: &str = "stat";
+const DEFAULT_MIN_SAMPLE_INTERVAL: Duration = Duration::from_millis(250);
 
 const CPU_USER: usize = 0;
 const CPU_NICE: usize = 1;
@@ -22,22 +22,72 @@ const CPU_STATS_COUNT: usize = 10;
 // the kernel includes Guest and Guest_Nice inside User and Nice counters.
 const CPU_TOTAL_COLUMNS: usize = 8;
 
+// [user, nice, system, idle, iowait, irq, softirq, steal, guest, guest_nice]
+type CpuTimes = [u64; CPU_STATS_COUNT];
+
+#[derive(Debug, Clone, Default)]
+struct ProcStat {
+    // The aggregated "cpu" line
+    total: CpuTimes,
+    // The per-core "cpuN" lines, sorted by the core number
+    cores: Vec<(usize, CpuTimes)>,
+    // The total from the "softirq" line
+    softirqs: Option<u64>,
+}
+
+#[derive(Debug, Clone)]
+struct Sample {
+    timestamp: Instant,
+    stat: ProcStat,
+    usage: CpuUsageStats,
+}
+
 pub struct CpuUsage<R> {
     reader: R,
-    // [user, nice, system, idle, iowait, irq, softirq, steal, guest, guest_nice]
-    measurement: Mutex<Option<(Instant, Vec<[u64; 10]>)>>,
+    roots: Roots,
+    min_sample_interval: Duration,
+    reuse_previous: bool,
+    measurement: Mutex<Option<Sample>>,
 }
 
 impl<R> CpuUsage<R>
 where
     R: Reader,
 {
-    pub fn new(reader: R) -> Self {
+    pub fn new(reader: R, roots: Roots) -> Self {
         Self {
             reader,
+            roots,
+            min_sample_interval: DEFAULT_MIN_SAMPLE_INTERVAL,
+            reuse_previous: false,
             measurement: Mutex::new(None),
         }
     }
+
+    /// The minimum time between the two `/proc/stat` snapshots the usage is computed from
+    pub fn with_min_sample_interval(mut self, interval: Duration) -> Self {
+        self.min_sample_interval = interval;
+        self
+    }
+
+    /// Never sleep while measuring. A measurement taken sooner than the minimum sample
+    /// interval returns the previous usage, and the very first one returns the average
+    /// usage since boot.
+    pub fn with_reuse_previous(mut self, reuse_previous: bool) -> Self {
+        self.reuse_previous = reuse_previous;
+        self
+    }
+
+    async fn wait_for_interval(&self, since: Instant) {
+        if self.reuse_previous {
+            return;
+        }
+
+        let elapsed = since.elapsed();
+        if elapsed < self.min_sample_interval {
+            tokio::time::sleep(self.min_sample_interval.saturating_sub(elapsed)).await;
+        }
+    }
 }
 
 impl<R> DataSource for CpuUsage<R>
@@ -46,53 +96,67 @@ where
 {
     #[tracing::instrument(level = "debug", skip_all)]
     async fn cpu_usage(&self) -> anyhow::Result<CpuUsageStats> {
-        let Ok(mut previous) = self.measurement.lock().map(|x| x.clone()) else {
+        let Ok(previous) = self.measurement.lock().map(|x| x.clone()) else {
             return Err(anyhow::anyhow!(
                 "Failed to retrieve previous CPU usage measurement snapshot due to a poisoned lock"
             ));
         };
 
-        let (timestamp, previous) = match previous.take() {
-            Some(previous) => previous,
+        let previous = match previous {
+            Some(sample)
+                if self.reuse_previous && sample.timestamp.elapsed() < self.min_sample_interval =>
+            {
+                return Ok(sample.usage);
+            }
+            Some(sample) => {
+                self.wait_for_interval(sample.timestamp).await;
+                Some(sample.stat)
+            }
+            None if self.reuse_previous => None,
             None => {
-                let measurement = make_measurement(&self.reader).await?;
-                (Instant::now(), measurement)
+                let measurement = make_measurement(&self.reader, &self.roots).await?;
+                self.wait_for_interval(Instant::now()).await;
+                Some(measurement)
             }
         };
 
-        let time_since_measurement = timestamp.elapsed();
-        if time_since_measurement < MIN_TIME_BETWEEN_MEASUREMENTS {
-            let to_sleep = MIN_TIME_BETWEEN_MEASUREMENTS.saturating_sub(time_since_measurement);
-            tokio::time::sleep(to_sleep).await;
-        }
-
-        let current = make_measurement(&self.reader).await?;
+        let current = make_measurement(&self.reader, &self.roots).await?;
         let now = Instant::now();
-        if previous.len() != current.len() {
-            return Err(anyhow::anyhow!(
-                "Failed to perform CPU usage measurement because of changed core count: previous={}; current={}",
-                previous.len(),
-                current.len()
-            ));
-        }
-
-        let (total_usage, total_breakdown) = calculate_usage(&current[0], &previous[0]);
-        let cores = previous
-            .iter()
-            .zip(current.iter())
-            .skip(1) // the first element is the "total" CPU usage across all cores
-            .map(|(prev, curr)| calculate_usage(curr, prev))
-            .enumerate()
-            .map(|(core, (total_usage, breakdown))| CoreUsageStats {
-                core,
+        let previous = previous.unwrap_or_else(|| since_boot(&current));
+        let (total_usage, total_breakdown) = calculate_usage(&current.total, &previous.total);
+
+        // Only the cores present in both snapshots have a usage. The ones that went
+        // offline are dropped, while the ones that came online are reported starting
+        // with the next measurement, once this snapshot becomes their baseline.
+        let mut cores = Vec::with_capacity(current.cores.len());
+        for (core, curr) in &current.cores {
+            let Ok(idx) = previous.cores.binary_search_by_key(core, |(core, _)| *core) else {
+                tracing::debug!("Skipping CPU core {} without a previous measurement", core);
+                continue;
+            };
+
+            let (total_usage, breakdown) = calculate_usage(curr, &previous.cores[idx].1);
+            cores.push(CoreUsageStats {
+                core: *core,
                 total_usage,
                 breakdown,
-            })
-            .collect::<Vec<_>>();
+            });
+        }
+
+        let usage = CpuUsageStats {
+            total_usage,
+            total_breakdown,
+            cores,
+            softirqs: current.softirqs,
+        };
 
         match self.measurement.lock() {
             Ok(mut guard) => {
-                *guard = Some((now, current));
+                *guard = Some(Sample {
+                    timestamp: now,
+                    stat: current,
+                    usage: usage.clone(),
+                });
             }
             Err(_) => {
                 return Err(anyhow::anyhow!(
@@ -101,40 +165,111 @@ where
             }
         }
 
-        Ok(CpuUsageStats {
-            total_usage,
-            total_breakdown,
-            cores,
-        })
+        Ok(usage)
     }
 }
 
-async fn make_measurement<R: Reader>(reader: &R) -> anyhow::Result<Vec<[u64; 10]>> {
-    let content = reader.read_to_string(PATH_PROC_STAT).await?;
+/// The counters start from zero at boot, so an all-zero snapshot with the same
+/// cores yields the average usage since then
+fn since_boot(current: &ProcStat) -> ProcStat {
+    ProcStat {
+        total: [0; CPU_STATS_COUNT],
+        cores: current
+            .cores
+            .iter()
+            .map(|(core, _)| (*core, [0; CPU_STATS_COUNT]))
+            .collect(),
+        softirqs: None,
+    }
+}
+
+async fn make_measurement<R: Reader>(reader: &R, roots: &Roots) -> anyhow::Result<ProcStat> {
+    let content = reader.read_to_string(roots.proc(PATH_PROC_STAT)).await?;
     Ok(parse_proc_stat(&content))
 }
 
-fn parse_proc_stat(content: &str) -> Vec<[u64; 10]> {
-    content
-        .lines()
-        .filter(|l| l.starts_with("cpu"))
-        .map(|line| {
-            let mut vals = [0u64; 10];
-            for (idx, part) in line.split_whitespace().skip(1).take(10).enumerate() {
-                vals[idx] = part.parse::<u64>().unwrap_or(0);
-            }
+fn parse_proc_stat(content: &str) -> ProcStat {
+    let mut stat = ProcStat::default();
+
+    for line in content.lines() {
+        // The first value is the total, followed by the per softirq type counters
+        if let Some(softirqs) = line.strip_prefix("softirq ") {
+            stat.softirqs = softirqs
+                .split_whitespace()
+                .next()
+                .and_then(|total| total.parse().ok());
+            continue;
+        }
+
+        if !line.starts_with("cpu") {
+            continue;
+        }
+
+        let mut parts = line.split_whitespace();
+        let Some(label) = parts.next() else {
+            continue;
+        };
+
+        let mut vals = [0u64; CPU_STATS_COUNT];
+        for (idx, part) in parts.take(CPU_STATS_COUNT).enumerate() {
+            vals[idx] = part.parse::<u64>().unwrap_or(0);
+        }
 
-            vals
-        })
-        .collect()
+        // The "cpu" line is the total across all cores, while the per-core
+        // lines carry the actual CPU number in their label (i.e. "cpu7")
+        match label.strip_prefix("cpu").filter(|core| !core.is_empty()) {
+            None => stat.total = vals,
+            Some(core) => match core.parse::<usize>() {
+                Ok(core) => stat.cores.push((core, vals)),
+                Err(_) => tracing::debug!("Skipping unexpected /proc/stat line: {}", line),
+            },
+        }
+    }
+
+    // The kernel lists the cores in order, but we should not rely on it
+    stat.cores.sort_unstable_by_key(|(core, _)| *core);
+    stat
 }
 
-fn calculate_usage(curr: &[u64; 10], prev: &[u64; 10]) -> (f64, CoreStats) {
+/// Calculates the CPU usage between two `/proc/stat` snapshots.
+///
+/// The kernel documents that the `iowait` counter is not reliable and can
+/// decrease between two reads, because an idle CPU waiting for I/O may later
+/// be accounted as plain idle (and vice versa). Taking the saturating
+/// difference of each column on its own would then report a zero `iowait`
+/// delta while keeping the full `idle` delta, which overstates the elapsed
+/// time and understates the CPU usage. Both columns represent time in which
+/// the CPU was not busy, so when either of them decreases, their combined
+/// delta is attributed to the column that increased and the other one is
+/// clamped to zero.
+fn calculate_usage(curr: &CpuTimes, prev: &CpuTimes) -> (f64, CoreStats) {
     let mut deltas = [0u64; CPU_STATS_COUNT];
     for i in 0..CPU_STATS_COUNT {
         deltas[i] = curr[i].saturating_sub(prev[i]);
     }
 
+    let iowait_decreased = curr[CPU_IOWAIT] < prev[CPU_IOWAIT];
+    let idle_decreased = curr[CPU_IDLE] < prev[CPU_IDLE];
+    if iowait_decreased || idle_decreased {
+        let prev_not_busy = prev[CPU_IDLE].saturating_add(prev[CPU_IOWAIT]);
+        let curr_not_busy = curr[CPU_IDLE].saturating_add(curr[CPU_IOWAIT]);
+        let not_busy = curr_not_busy.saturating_sub(prev_not_busy);
+
+        tracing::trace!(
+            iowait_decreased,
+            idle_decreased,
+            "Inconsistent idle/iowait counters. Merging their deltas."
+        );
+
+        if iowait_decreased {
+            deltas[CPU_IDLE] = not_busy;
+            deltas[CPU_IOWAIT] = 0;
+        } else {
+            deltas[CPU_IDLE] = 0;
+            deltas[CPU_IOWAIT] = not_busy;
+        }
+    }
+
     // The kernel includes Guest and Guest_Nice inside User and Nice counters.
     // To calculate the actual total elapsed time, we sum columns 0 through 7.
     let total_delta: u64 = deltas.iter().take(CPU_TOTAL_COLUMNS).sum();
@@ -175,26 +310,49 @@ cpu0 600 100 150 200 250 300 350 400 450 500
 cpu1 500 100 150 200 250 300 350 400 450 500
 intr 123456 789
 ctxt 987654
+softirq 7766554 12 2345678 345 456789 23456 0 3456 1234567 0 3703211
 "#;
 
         let result = parse_proc_stat(content);
-        assert_eq!(result.len(), 3);
+        assert_eq!(result.cores.len(), 2);
+        assert_eq!(result.softirqs, Some(7766554));
 
         // Values: user nice system idle iowait irq softirq steal guest guest_nice
         assert_eq!(
-            result[0],
+            result.total,
             [1100, 200, 300, 400, 500, 600, 700, 800, 900, 1000]
         );
         assert_eq!(
-            result[1],
-            [600, 100, 150, 200, 250, 300, 350, 400, 450, 500]
+            result.cores[0],
+            (0, [600, 100, 150, 200, 250, 300, 350, 400, 450, 500])
         );
         assert_eq!(
-            result[2],
-            [500, 100, 150, 200, 250, 300, 350, 400, 450, 500]
+            result.cores[1],
+            (1, [500, 100, 150, 200, 250, 300, 350, 400, 450, 500])
         );
     }
 
+    #[test]
+    fn test_parse_proc_stat_out_of_order_cores() {
+        let content = r#"cpu  40 0 0 0 0 0 0 0 0 0
+cpu7 7 0 0 0 0 0 0 0 0 0
+cpu2 2 0 0 0 0 0 0 0 0 0
+cpu10 10 0 0 0 0 0 0 0 0 0
+cpu0 0 0 0 0 0 0 0 0 0 0
+"#;
+
+        let result = parse_proc_stat(content);
+        assert_eq!(result.total[0], 40);
+
+        let cores = result.cores.iter().map(|(c, _)| *c).collect::<Vec<_>>();
+        assert_eq!(cores, vec![0, 2, 7, 10]);
+
+        // The values must follow their own core, not the position in the file
+        for (core, times) in &result.cores {
+            assert_eq!(*core as u64, times[0]);
+        }
+    }
+
     #[test]
     fn test_parse_proc_stat_with_varying_whitespace() {
         // Test that the parser handles the double-space after 'cpu'
@@ -205,9 +363,9 @@ cpu0 20 20 20 20 20 20 20 20 20 20
 "#;
 
         let result = parse_proc_stat(content);
-        assert_eq!(result.len(), 2);
-        assert_eq!(result[0][0], 10);
-        assert_eq!(result[1][0], 20);
+        assert_eq!(result.cores.len(), 1);
+        assert_eq!(result.total[0], 10);
+        assert_eq!(result.cores[0].1[0], 20);
     }
 
     #[test]
@@ -268,6 +426,39 @@ cpu0 20 20 20 20 20 20 20 20 20 20
         assert!((usage - 0.1).abs() < f64::EPSILON);
     }
 
+    #[test]
+    fn test_calculate_usage_decreasing_iowait() {
+        let prev = [0, 0, 0, 100, 50, 0, 0, 0, 0, 0];
+
+        // iowait went backwards by 10, while idle grew by 70
+        let curr = [20, 0, 0, 170, 40, 0, 0, 0, 0, 0];
+
+        let (usage, stats) = calculate_usage(&curr, &prev);
+
+        // Combined idle+iowait delta: (170 + 40) - (100 + 50) = 60
+        // Total delta: 20 (user) + 60 (idle) = 80
+        assert!((usage - 0.25).abs() < f64::EPSILON);
+        assert!((stats.user - 0.25).abs() < f64::EPSILON);
+        assert!((stats.idle - 0.75).abs() < f64::EPSILON);
+        assert_eq!(stats.iowait, 0.0);
+    }
+
+    #[test]
+    fn test_calculate_usage_decreasing_idle() {
+        let prev = [0, 0, 0, 100, 50, 0, 0, 0, 0, 0];
+
+        // idle went backwards by 10, while iowait grew by 40
+        let curr = [70, 0, 0, 90, 90, 0, 0, 0, 0, 0];
+
+        let (usage, stats) = calculate_usage(&curr, &prev);
+
+        // Combined idle+iowait delta: (90 + 90) - (100 + 50) = 30
+        // Total delta: 70 (user) + 30 (iowait) = 100
+        assert!((usage - 0.7).abs() < f64::EPSILON);
+        assert!((stats.iowait - 0.3).abs() < f64::EPSILON);
+        assert_eq!(stats.idle, 0.0);
+    }
+
     #[test]
     fn test_calculate_usage_saturating_sub() {
         // Ensure that if counters roll over or are weirdly smaller, we don't panic
@@ -294,11 +485,11 @@ cpu0 50 0 80 170 0 0 0 0 0 0
 "#;
 
         let mut reader = HardcodedReader::new();
-        reader.add_response(PATH_PROC_STAT, snapshot_a);
-        reader.add_response(PATH_PROC_STAT, snapshot_b);
-        reader.add_response(PATH_PROC_STAT, snapshot_c);
+        reader.add_response(Roots::default().proc(PATH_PROC_STAT), snapshot_a);
+        reader.add_response(Roots::default().proc(PATH_PROC_STAT), snapshot_b);
+        reader.add_response(Roots::default().proc(PATH_PROC_STAT), snapshot_c);
 
-        let datasource = CpuUsage::new(reader);
+        let datasource = CpuUsage::new(reader, Roots::default());
 
         // Pause time to make the 250ms sleep instant
         tokio::time::pause();
@@ -321,6 +512,75 @@ cpu0 50 0 80 170 0 0 0 0 0 0
         assert!((second_stats.total_breakdown.user - 0.0).abs() < f64::EPSILON);
     }
 
+    #[tokio::test]
+    async fn test_cpu_usage_core_labels_follow_proc_stat() {
+        // cpu3 is listed before cpu1 and cpu2 is missing (i.e. offline)
+        let snapshot_a = r#"cpu  0 0 0 200 0 0 0 0 0 0
+cpu3 0 0 0 100 0 0 0 0 0 0
+cpu1 0 0 0 100 0 0 0 0 0 0
+"#;
+        // cpu1 becomes fully busy, while cpu3 stays idle
+        let snapshot_b = r#"cpu  100 0 0 300 0 0 0 0 0 0
+cpu3 0 0 0 200 0 0 0 0 0 0
+cpu1 100 0 0 100 0 0 0 0 0 0
+"#;
+
+        let mut reader = HardcodedReader::new();
+        reader.add_response(Roots::default().proc(PATH_PROC_STAT), snapshot_a);
+        reader.add_response(Roots::default().proc(PATH_PROC_STAT), snapshot_b);
+
+        let datasource = CpuUsage::new(reader, Roots::default());
+        tokio::time::pause();
+
+        let stats = datasource.cpu_usage().await.unwrap();
+        assert_eq!(stats.cores.len(), 2);
+
+        assert_eq!(stats.cores[0].core, 1);
+        assert!((stats.cores[0].total_usage - 1.0).abs() < f64::EPSILON);
+
+        assert_eq!(stats.cores[1].core, 3);
+        assert!((stats.cores[1].total_usage - 0.0).abs() < f64::EPSILON);
+    }
+
+    #[tokio::test]
+    async fn test_cpu_usage_hotplugged_cores() {
+        let snapshot_a = r#"cpu  0 0 0 300 0 0 0 0 0 0
+cpu0 0 0 0 100 0 0 0 0 0 0
+cpu1 0 0 0 100 0 0 0 0 0 0
+cpu2 0 0 0 100 0 0 0 0 0 0
+"#;
+        // cpu1 goes offline
+        let snapshot_b = r#"cpu  100 0 0 400 0 0 0 0 0 0
+cpu0 100 0 0 100 0 0 0 0 0 0
+cpu2 0 0 0 200 0 0 0 0 0 0
+"#;
+        // cpu1 is back online, but has no baseline yet
+        let snapshot_c = r#"cpu  200 0 0 500 0 0 0 0 0 0
+cpu0 200 0 0 100 0 0 0 0 0 0
+cpu1 0 0 0 100 0 0 0 0 0 0
+cpu2 0 0 0 300 0 0 0 0 0 0
+"#;
+
+        let mut reader = HardcodedReader::new();
+        reader.add_response(Roots::default().proc(PATH_PROC_STAT), snapshot_a);
+        reader.add_response(Roots::default().proc(PATH_PROC_STAT), snapshot_b);
+        reader.add_response(Roots::default().proc(PATH_PROC_STAT), snapshot_c);
+
+        let datasource = CpuUsage::new(reader, Roots::default());
+        tokio::time::pause();
+
+        let stats = datasource.cpu_usage().await.unwrap();
+        let cores = stats.cores.iter().map(|c| c.core).collect::<Vec<_>>();
+        assert_eq!(cores, vec![0, 2]);
+        assert!((stats.cores[0].total_usage - 1.0).abs() < f64::EPSILON);
+        assert!((stats.cores[1].total_usage - 0.0).abs() < f64::EPSILON);
+
+        tokio::time::advance(Duration::from_millis(300)).await;
+        let stats = datasource.cpu_usage().await.unwrap();
+        let cores = stats.cores.iter().map(|c| c.core).collect::<Vec<_>>();
+        assert_eq!(cores, vec![0, 2]);
+    }
+
     #[tokio::test]
     async fn test_cpu_usage_all_metrics_mapping() {
         // Snapshot A: All counters at 100
@@ -333,10 +593,10 @@ cpu0 100 100 100 100 100 100 100 100 100 100"#;
 cpu0 110 120 130 140 150 160 170 180 190 200"#;
 
         let mut reader = HardcodedReader::new();
-        reader.add_response(PATH_PROC_STAT, snapshot_a);
-        reader.add_response(PATH_PROC_STAT, snapshot_b);
+        reader.add_response(Roots::default().proc(PATH_PROC_STAT), snapshot_a);
+        reader.add_response(Roots::default().proc(PATH_PROC_STAT), snapshot_b);
 
-        let datasource = CpuUsage::new(reader);
+        let datasource = CpuUsage::new(reader, Roots::default());
         tokio::time::pause();
 
         let stats = datasource.cpu_usage().await.unwrap();
@@ -407,4 +667,63 @@ cpu0 110 120 130 140 150 160 170 180 190 200"#;
             "Total Usage calculation fail"
         );
     }
+
+    #[tokio::test]
+    async fn test_cpu_usage_reuse_previous() {
+        // 25% usage since boot
+        let snapshot_a = r#"cpu  25 0 0 75 0 0 0 0 0 0
+cpu0 25 0 0 75 0 0 0 0 0 0
+"#;
+        // Added 100 jiffies, 90 user, 10 idle -> 90% usage
+        let snapshot_b = r#"cpu  115 0 0 85 0 0 0 0 0 0
+cpu0 115 0 0 85 0 0 0 0 0 0
+"#;
+
+        let mut reader = HardcodedReader::new();
+        reader.add_response(Roots::default().proc(PATH_PROC_STAT), snapshot_a);
+        reader.add_response(Roots::default().proc(PATH_PROC_STAT), snapshot_b);
+
+        let datasource = CpuUsage::new(reader, Roots::default())
+            .with_min_sample_interval(Duration::from_secs(1))
+            .with_reuse_previous(true);
+        tokio::time::pause();
+
+        // Nothing to compare against, so the usage is averaged since boot
+        let start = Instant::now();
+        let first_stats = datasource.cpu_usage().await.unwrap();
+        assert_eq!(start, Instant::now(), "must not sleep");
+        assert!((first_stats.total_usage - 0.25).abs() < f64::EPSILON);
+        assert_eq!(first_stats.cores.len(), 1);
+
+        // Too soon for a new sample, so /proc/stat is not read again
+        let reused = datasource.cpu_usage().await.unwrap();
+        assert!((reused.total_usage - 0.25).abs() < f64::EPSILON);
+
+        tokio::time::advance(Duration::from_secs(1)).await;
+        let second_stats = datasource.cpu_usage().await.unwrap();
+        assert!((second_stats.total_usage - 0.9).abs() < f64::EPSILON);
+    }
+
+    #[tokio::test]
+    async fn test_cpu_usage_min_sample_interval() {
+        let snapshot_a = r#"cpu  0 0 0 100 0 0 0 0 0 0
+cpu0 0 0 0 100 0 0 0 0 0 0
+"#;
+        let snapshot_b = r#"cpu  50 0 0 150 0 0 0 0 0 0
+cpu0 50 0 0 150 0 0 0 0 0 0
+"#;
+
+        let mut reader = HardcodedReader::new();
+        reader.add_response(Roots::default().proc(PATH_PROC_STAT), snapshot_a);
+        reader.add_response(Roots::default().proc(PATH_PROC_STAT), snapshot_b);
+
+        let datasource = CpuUsage::new(reader, Roots::default())
+            .with_min_sample_interval(Duration::from_secs(2));
+        tokio::time::pause();
+
+        let start = Instant::now();
+        let stats = datasource.cpu_usage().await.unwrap();
+        assert!(start.elapsed() >= Duration::from_secs(2));
+        assert!((stats.total_usage - 0.5).abs() < f64::EPSILON);
+    }
 }
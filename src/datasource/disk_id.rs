@@ -0,0 +1,144 @@
+use crate::datasource::Reader;
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+
+/// Maintained by udev. It is the same inside a container, as long as `/dev` is shared.
+pub const PATH_DISK_BY_ID: &str = "/dev/disk/by-id";
+
+/// Builds the map from the kernel names of the disks, such as `sda`, to their names
+/// in `/dev/disk/by-id`, which do not change across reboots. It is empty when the
+/// directory is not available, so the callers simply omit the stable names.
+pub async fn stable_names<R: Reader>(reader: &R) -> HashMap<String, String> {
+    let entries = match reader.read_dir(PATH_DISK_BY_ID).await {
+        Ok(entries) => entries,
+        Err(e) => {
+            tracing::debug!(error=?e, "The stable disk names are not available");
+            return HashMap::new();
+        }
+    };
+
+    let mut links = Vec::with_capacity(entries.len());
+    for id in entries {
+        let path = format!("{}/{}", PATH_DISK_BY_ID, id);
+        match reader.read_link(&path).await {
+            // The links are relative, e.g. `../../sda`
+            Ok(target) => match target.file_name() {
+                Some(kernel_name) => links.push((kernel_name.to_string_lossy().into_owned(), id)),
+                None => tracing::debug!("Unexpected target of {}: {:?}", path, target),
+            },
+            Err(e) => tracing::trace!(error=?e, "Failed to resolve {}", path),
+        }
+    }
+
+    build_stable_names(links)
+}
+
+/// Looks up the stable name of a device, which may be given as a path. smartctl
+/// reports the NVMe controllers, e.g. `nvme0`, while udev links their namespaces.
+pub fn stable_name<'a>(names: &'a HashMap<String, String>, device: &str) -> Option<&'a str> {
+    let device = device.strip_prefix("/dev/").unwrap_or(device);
+
+    let controller = device
+        .strip_prefix("nvme")
+        .is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()));
+
+    names
+        .get(device)
+        .or_else(|| names.get(&format!("{}n1", device)).filter(|_| controller))
+        .map(String::as_str)
+}
+
+/// Several links point to the same disk, so one of them is picked deterministically
+fn build_stable_names(
+    links: impl IntoIterator<Item = (String, String)>,
+) -> HashMap<String, String> {
+    let mut names = HashMap::new();
+
+    for (kernel_name, id) in links {
+        match names.entry(kernel_name) {
+            Entry::Vacant(e) => {
+                e.insert(id);
+            }
+            Entry::Occupied(mut e) => {
+                if preference(&id) < preference(e.get()) {
+                    e.insert(id);
+                }
+            }
+        }
+    }
+
+    names
+}
+
+/// Prefers the names containing the model and the serial number over the opaque WWN
+/// and EUI ones, and then the shortest one, as the `_1` suffixed NVMe names are aliases
+fn preference(id: &str) -> (bool, usize, &str) {
+    let opaque = ["wwn-", "nvme-eui.", "nvme-nvme."]
+        .iter()
+        .any(|prefix| id.starts_with(prefix));
+
+    (opaque, id.len(), id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datasource::tests::HardcodedReader;
+
+    #[tokio::test]
+    async fn test_stable_names() {
+        let links = [
+            ("ata-ST4000VN008-2DR166_ZGY5ABCD", "../../sda"),
+            ("ata-ST4000VN008-2DR166_ZGY5ABCD-part1", "../../sda1"),
+            ("wwn-0x5000c500c1234567", "../../sda"),
+            ("wwn-0x5000c500c7654321", "../../sdb"),
+            ("nvme-eui.002538b111b12345", "../../nvme0n1"),
+            (
+                "nvme-Samsung_SSD_980_PRO_2TB_S6B0NL0T123456_1",
+                "../../nvme0n1",
+            ),
+            (
+                "nvme-Samsung_SSD_980_PRO_2TB_S6B0NL0T123456",
+                "../../nvme0n1",
+            ),
+        ];
+
+        let mut reader = HardcodedReader::new();
+        reader.add_dir(PATH_DISK_BY_ID, &links.map(|(id, _)| id));
+        for (id, target) in links {
+            reader.add_link(format!("{}/{}", PATH_DISK_BY_ID, id), target);
+        }
+
+        let names = stable_names(&reader).await;
+
+        assert_eq!(names.len(), 4);
+        assert_eq!(names["sda"], "ata-ST4000VN008-2DR166_ZGY5ABCD");
+        assert_eq!(names["sda1"], "ata-ST4000VN008-2DR166_ZGY5ABCD-part1");
+        // Only the WWN is available for this disk
+        assert_eq!(names["sdb"], "wwn-0x5000c500c7654321");
+        assert_eq!(
+            names["nvme0n1"],
+            "nvme-Samsung_SSD_980_PRO_2TB_S6B0NL0T123456"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stable_names_without_udev() {
+        assert!(stable_names(&HardcodedReader::new()).await.is_empty());
+    }
+
+    #[test]
+    fn test_stable_name() {
+        let names = build_stable_names([
+            ("sda".to_owned(), "ata-disk".to_owned()),
+            ("nvme0n1".to_owned(), "nvme-disk".to_owned()),
+        ]);
+
+        assert_eq!(stable_name(&names, "sda"), Some("ata-disk"));
+        assert_eq!(stable_name(&names, "/dev/sda"), Some("ata-disk"));
+        assert_eq!(stable_name(&names, "/dev/nvme0"), Some("nvme-disk"));
+        assert_eq!(stable_name(&names, "nvme0n1"), Some("nvme-disk"));
+        assert_eq!(stable_name(&names, "sdb"), None);
+        assert_eq!(stable_name(&names, "nvme1"), None);
+    }
+}
@@ -1,20 +1,49 @@
-use crate::datasource::Reader;
+use crate::datasource::{Reader, Roots, disk_id};
 use crate::metrics::disk_io::{DataSource, DeviceIoStats, DiskIoStats};
+use std::collections::HashMap;
 use tokio::time::Instant;
 
-const PATH_DISK_STATS: &str = "/proc/diskstats";
+const PATH_DISK_STATS: &str = "diskstats";
+const PATH_SYS_BLOCK: &str = "class/block";
+
+/// The kernel reports the sectors in `/proc/diskstats` in 512-byte units, regardless
+/// of the logical or physical sector size of the device, i.e. even for 4Kn drives
 const KERNEL_SECTOR_SIZE: u64 = 512;
 
 pub struct DiskIo<R> {
     reader: R,
+    roots: Roots,
+    sector_size: bool,
+    stable_names: bool,
 }
 
 impl<R> DiskIo<R>
 where
     R: Reader,
 {
-    pub fn new(reader: R) -> Self {
-        Self { reader }
+    pub fn new(reader: R, roots: Roots, sector_size: bool, stable_names: bool) -> Self {
+        Self {
+            reader,
+            roots,
+            sector_size,
+            stable_names,
+        }
+    }
+
+    /// Reads the hardware sector size of the device. It is not available for partitions.
+    async fn sector_size(&self, device: &str) -> Option<u64> {
+        let path = self.roots.sys(&format!(
+            "{}/{}/queue/hw_sector_size",
+            PATH_SYS_BLOCK, device
+        ));
+
+        match self.reader.read_to_string(&path).await {
+            Ok(content) => content.trim().parse().ok(),
+            Err(e) => {
+                tracing::trace!(error=?e, "The sector size of [{}] is not available", device);
+                None
+            }
+        }
     }
 }
 
@@ -24,9 +53,18 @@ where
 {
     #[tracing::instrument(level = "debug", skip_all)]
     async fn disk_io(&self) -> anyhow::Result<DiskIoStats> {
-        let content = self.reader.read_to_string(PATH_DISK_STATS).await?;
+        let content = self
+            .reader
+            .read_to_string(self.roots.proc(PATH_DISK_STATS))
+            .await?;
         let timestamp = Instant::now();
 
+        // The links are re-read on each measurement, as the disks may be hot-plugged
+        let stable_names = match self.stable_names {
+            true => disk_id::stable_names(&self.reader).await,
+            false => HashMap::new(),
+        };
+
         let mut disks = Vec::new();
         for line in content.lines() {
             let mut parts = line.split_whitespace();
@@ -41,14 +79,6 @@ where
                 continue;
             };
 
-            if device.starts_with("loop")
-                || device.starts_with("zram")
-                || device.starts_with("md1p")
-            {
-                tracing::debug!("Skipping device: {}", device);
-                continue;
-            }
-
             // Column 3: Reads Completed
             let read_ops = parts.next().and_then(|v| v.parse().ok()).unwrap_or(0);
 
@@ -78,12 +108,32 @@ where
                 .unwrap_or(0);
             let bytes_written = sectors_written * KERNEL_SECTOR_SIZE;
 
+            // Columns 10-13: Write time, I/Os in progress, I/O time, Weighted I/O time (skip)
+            // Columns 14-17: Discards completed, Discards merged, Sectors discarded, Discard time
+            // Columns 18-19: Flushes completed, Flush time
+            // The discard columns are available since Linux 4.18, and the flush ones since 5.5
+            let extended = parts.collect::<Vec<_>>();
+            let column = |idx: usize| extended.get(idx - 10).and_then(|v| v.parse::<u64>().ok());
+
+            let sector_size = match self.sector_size {
+                true => self.sector_size(device).await,
+                false => None,
+            };
+
             disks.push(DeviceIoStats {
                 device_name: device.to_string(),
+                id: stable_names.get(device).cloned(),
                 bytes_read,
                 bytes_written,
                 read_ops,
                 write_ops,
+                discard_ops: column(14),
+                discard_bytes: column(16).map(|sectors| sectors * KERNEL_SECTOR_SIZE),
+                flush_ops: column(18),
+                flush_time_ms: column(19),
+                io_time_ms: column(12),
+                sector_size,
+                utilization: None,
             });
         }
 
@@ -93,8 +143,9 @@ where
 
 #[cfg(test)]
 mod tests {
-    use crate::datasource::disk_io::{DiskIo, PATH_DISK_STATS};
+    use crate::datasource::disk_io::{DiskIo, PATH_DISK_STATS, PATH_SYS_BLOCK};
     use crate::datasource::tests::HardcodedReader;
+    use crate::datasource::{Roots, disk_id};
     use crate::metrics::disk_io::DataSource;
 
     const DISK_STATS: &str = r#"   7       0 loop0 133549 0 8587416 51112 0 0 0 0 0 13992709 51112 0 0 0 0 0 0
@@ -108,37 +159,148 @@ mod tests {
    8       1 sda1 90130 15156 6940427 172803 1609 314 69328 1989 0 103673 174792 0 0 0 0 0 0
    9       1 md1p1 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0"#;
 
+    #[tokio::test]
+    async fn test_disk_io_custom_proc_root() {
+        let mut reader = HardcodedReader::new();
+        reader.add_response("/host/proc/diskstats", DISK_STATS);
+
+        let ds = DiskIo::new(reader, Roots::new("/host/proc/", "/host/sys"), false, false);
+        let stats = ds.disk_io().await.unwrap();
+        assert_eq!(10, stats.disks.len());
+    }
+
     #[tokio::test]
     async fn test_disk_io_datasource() {
         let mut reader = HardcodedReader::new();
-        reader.add_response(PATH_DISK_STATS, DISK_STATS);
+        reader.add_response(Roots::default().proc(PATH_DISK_STATS), DISK_STATS);
+
+        let ds = DiskIo::new(reader, Roots::default(), false, false);
+        let stats = ds.disk_io().await.unwrap();
+        assert_eq!(10, stats.disks.len());
+
+        // The devices are filtered by the collector
+        assert_eq!("loop0", stats.disks[0].device_name);
+        assert_eq!("zram0", stats.disks[4].device_name);
+        assert_eq!("md1p1", stats.disks[9].device_name);
+
+        assert_eq!("nvme0n1", stats.disks[5].device_name);
+        assert_eq!(34485575680, stats.disks[5].bytes_written);
+        assert_eq!(284154700800, stats.disks[5].bytes_read);
+        assert_eq!(1793083, stats.disks[5].write_ops);
+        assert_eq!(2745204, stats.disks[5].read_ops);
+
+        assert_eq!("nvme0n1p1", stats.disks[6].device_name);
+        assert_eq!(34485575680, stats.disks[6].bytes_written);
+        assert_eq!(284153189376, stats.disks[6].bytes_read);
+        assert_eq!(1793083, stats.disks[6].write_ops);
+        assert_eq!(2745099, stats.disks[6].read_ops);
+
+        assert_eq!("sda", stats.disks[7].device_name);
+        assert_eq!(35495936, stats.disks[7].bytes_written);
+        assert_eq!(3554174464, stats.disks[7].bytes_read);
+        assert_eq!(1609, stats.disks[7].write_ops);
+        assert_eq!(90175, stats.disks[7].read_ops);
+
+        assert_eq!("sda1", stats.disks[8].device_name);
+        assert_eq!(35495936, stats.disks[8].bytes_written);
+        assert_eq!(3553498624, stats.disks[8].bytes_read);
+        assert_eq!(1609, stats.disks[8].write_ops);
+        assert_eq!(90130, stats.disks[8].read_ops);
+
+        assert_eq!(Some(59324), stats.disks[5].discard_ops);
+        assert_eq!(Some(7646410160 * 512), stats.disks[5].discard_bytes);
+        assert_eq!(Some(158575), stats.disks[5].flush_ops);
+        assert_eq!(Some(41872), stats.disks[5].flush_time_ms);
+        assert_eq!(Some(584873), stats.disks[5].io_time_ms);
+
+        assert_eq!(Some(0), stats.disks[7].discard_ops);
+        assert_eq!(Some(0), stats.disks[7].discard_bytes);
+        assert_eq!(Some(1), stats.disks[7].flush_ops);
+        assert_eq!(Some(0), stats.disks[7].flush_time_ms);
+    }
+
+    #[tokio::test]
+    async fn test_disk_io_datasource_legacy_format() {
+        const LEGACY_DISK_STATS: &str = r#"   8       0 sda 90175 15156 6941747 172836 1609 314 69328 1989 0 102770 174825
+   8      16 sdb 4821 120 391704 6012 77 12 1208 95 0 5210 6107 312 0 1048576 44"#;
+
+        let mut reader = HardcodedReader::new();
+        reader.add_response(Roots::default().proc(PATH_DISK_STATS), LEGACY_DISK_STATS);
+
+        let ds = DiskIo::new(reader, Roots::default(), false, false);
+        let stats = ds.disk_io().await.unwrap();
+        assert_eq!(2, stats.disks.len());
+
+        assert_eq!("sda", stats.disks[0].device_name);
+        assert_eq!(3554174464, stats.disks[0].bytes_read);
+        assert_eq!(1609, stats.disks[0].write_ops);
+        assert_eq!(None, stats.disks[0].discard_ops);
+        assert_eq!(None, stats.disks[0].discard_bytes);
+        assert_eq!(None, stats.disks[0].flush_ops);
+        assert_eq!(None, stats.disks[0].flush_time_ms);
+        assert_eq!(Some(102770), stats.disks[0].io_time_ms);
+
+        // Discard columns without the flush ones (Linux 4.18 - 5.4)
+        assert_eq!("sdb", stats.disks[1].device_name);
+        assert_eq!(Some(312), stats.disks[1].discard_ops);
+        assert_eq!(Some(1048576 * 512), stats.disks[1].discard_bytes);
+        assert_eq!(None, stats.disks[1].flush_ops);
+        assert_eq!(None, stats.disks[1].flush_time_ms);
+    }
+
+    #[tokio::test]
+    async fn test_disk_io_sector_size() {
+        let mut reader = HardcodedReader::new();
+        reader.add_response(Roots::default().proc(PATH_DISK_STATS), DISK_STATS);
+        reader.add_response(
+            Roots::default().sys(&format!("{}/nvme0n1/queue/hw_sector_size", PATH_SYS_BLOCK)),
+            "4096\n",
+        );
+        reader.add_response(
+            Roots::default().sys(&format!("{}/sda/queue/hw_sector_size", PATH_SYS_BLOCK)),
+            "512\n",
+        );
 
-        let ds = DiskIo::new(reader);
+        let ds = DiskIo::new(reader, Roots::default(), true, false);
         let stats = ds.disk_io().await.unwrap();
-        assert_eq!(4, stats.disks.len());
-
-        assert_eq!("nvme0n1", stats.disks[0].device_name);
-        assert_eq!(34485575680, stats.disks[0].bytes_written);
-        assert_eq!(284154700800, stats.disks[0].bytes_read);
-        assert_eq!(1793083, stats.disks[0].write_ops);
-        assert_eq!(2745204, stats.disks[0].read_ops);
-
-        assert_eq!("nvme0n1p1", stats.disks[1].device_name);
-        assert_eq!(34485575680, stats.disks[1].bytes_written);
-        assert_eq!(284153189376, stats.disks[1].bytes_read);
-        assert_eq!(1793083, stats.disks[1].write_ops);
-        assert_eq!(2745099, stats.disks[1].read_ops);
-
-        assert_eq!("sda", stats.disks[2].device_name);
-        assert_eq!(35495936, stats.disks[2].bytes_written);
-        assert_eq!(3554174464, stats.disks[2].bytes_read);
-        assert_eq!(1609, stats.disks[2].write_ops);
-        assert_eq!(90175, stats.disks[2].read_ops);
-
-        assert_eq!("sda1", stats.disks[3].device_name);
-        assert_eq!(35495936, stats.disks[3].bytes_written);
-        assert_eq!(3553498624, stats.disks[3].bytes_read);
-        assert_eq!(1609, stats.disks[3].write_ops);
-        assert_eq!(90130, stats.disks[3].read_ops);
+
+        assert_eq!("nvme0n1", stats.disks[5].device_name);
+        assert_eq!(Some(4096), stats.disks[5].sector_size);
+        // The diskstats sectors are always 512 bytes, even on 4Kn drives
+        assert_eq!(554989650 * 512, stats.disks[5].bytes_read);
+
+        // The partitions do not have a queue directory
+        assert_eq!("nvme0n1p1", stats.disks[6].device_name);
+        assert_eq!(None, stats.disks[6].sector_size);
+
+        assert_eq!("sda", stats.disks[7].device_name);
+        assert_eq!(Some(512), stats.disks[7].sector_size);
+    }
+
+    #[tokio::test]
+    async fn test_disk_io_stable_names() {
+        let mut reader = HardcodedReader::new();
+        reader.add_response(Roots::default().proc(PATH_DISK_STATS), DISK_STATS);
+        reader.add_dir(
+            disk_id::PATH_DISK_BY_ID,
+            &["nvme-Samsung_SSD_980_PRO_2TB_S123"],
+        );
+        reader.add_link(
+            format!(
+                "{}/nvme-Samsung_SSD_980_PRO_2TB_S123",
+                disk_id::PATH_DISK_BY_ID
+            ),
+            "../../nvme0n1",
+        );
+
+        let ds = DiskIo::new(reader, Roots::default(), false, true);
+        let stats = ds.disk_io().await.unwrap();
+
+        assert_eq!("nvme0n1", stats.disks[5].device_name);
+        assert_eq!(
+            Some("nvme-Samsung_SSD_980_PRO_2TB_S123"),
+            stats.disks[5].id.as_deref()
+        );
+        assert_eq!(None, stats.disks[7].id);
     }
 }
@@ -1,73 +1,95 @@
+use crate::datasource::{CommandRunner, Reader, TokioReader, disk_id};
+use crate::domain::Probe;
 use crate::metrics::disk_smart::{DataSource, Device, NvmeDevice, SataDevice, SmartReports};
 use futures::StreamExt;
 use futures::stream::FuturesUnordered;
 use serde_json::Value;
-use tokio::process::Command;
+use std::collections::HashMap;
 use tokio::time::Instant;
 
+const SMARTCTL: &str = "smartctl";
+
+// The exit status of smartctl is a bitmask. Only the first two bits mean that no data could
+// be read, the rest report problems with the disk itself, along with a valid JSON output.
+const STATUS_COMMAND_LINE_ERROR: i32 = 1 << 0;
+const STATUS_DEVICE_OPEN_FAILED: i32 = 1 << 1;
+const STATUS_DISK_FAILING: i32 = 1 << 3;
+
 enum DeviceReport {
     Sata(SataDevice),
     Nvme(NvmeDevice),
 }
 
-pub struct SmartCtl {
-    //
+pub struct SmartCtl<C, R = TokioReader> {
+    runner: C,
+    /// Resolves the stable names of the devices, if set
+    reader: Option<R>,
 }
 
-impl SmartCtl {
-    pub fn new() -> Self {
-        Self {}
+impl<C> SmartCtl<C> {
+    pub fn new(runner: C) -> Self {
+        Self {
+            runner,
+            reader: None,
+        }
     }
+}
 
-    #[tracing::instrument(level = "trace", skip_all)]
-    async fn scan_devices(&self) -> anyhow::Result<Vec<String>> {
-        let output = Command::new("smartctl")
-            .args(["--scan", "--json"])
-            .output()
-            .await?;
-
-        let json: Value = serde_json::from_slice(&output.stdout)?;
-        let mut paths = Vec::new();
-
-        if let Some(devices) = json["devices"].as_array() {
-            for dev in devices {
-                if let Some(name) = dev["name"].as_str() {
-                    paths.push(name.to_string());
-                }
-            }
+impl<C, R> SmartCtl<C, R>
+where
+    C: CommandRunner,
+    R: Reader,
+{
+    /// Labels the devices by their names in `/dev/disk/by-id`
+    pub fn with_stable_names<T: Reader>(self, reader: T) -> SmartCtl<C, T> {
+        SmartCtl {
+            runner: self.runner,
+            reader: Some(reader),
         }
+    }
 
-        Ok(paths)
+    async fn stable_names(&self) -> HashMap<String, String> {
+        match &self.reader {
+            Some(reader) => disk_id::stable_names(reader).await,
+            None => HashMap::new(),
+        }
     }
 
     #[tracing::instrument(level = "trace", skip_all)]
     async fn query_device(&self, path: &str) -> anyhow::Result<Option<DeviceReport>> {
-        let output = Command::new("smartctl")
-            .args(["-a", "--json", "--nocheck", "standby", path])
-            .output()
+        let output = self
+            .runner
+            .run(SMARTCTL, &["-a", "--json", "--nocheck", "standby", path])
             .await?;
 
-        // Check exit code 2 (skipped due to standby/sleep)
-        if !output.status.success() && output.status.code() == Some(2) {
+        let Some(status) = output.status.code() else {
+            return Err(anyhow::anyhow!(
+                "smartctl failed with status: {:?}",
+                output.status
+            ));
+        };
+
+        // With `--nocheck standby`, a sleeping device is reported as failing to open
+        if status == STATUS_DEVICE_OPEN_FAILED {
             return Ok(None);
         }
 
-        if !output.status.success() {
+        if status & (STATUS_COMMAND_LINE_ERROR | STATUS_DEVICE_OPEN_FAILED) != 0 {
             return Err(anyhow::anyhow!(
                 "smartctl failed with status: {:?}",
                 output.status
             ));
         }
 
+        if status != 0 {
+            tracing::debug!(device = %path, status, "smartctl reported problems with the device");
+        }
+
         let json: Value = serde_json::from_slice(&output.stdout)?;
-        let info = Device {
-            device: path.to_string(),
-            model: json["model_name"].as_str().unwrap_or("Unknown").to_string(),
-            serial_number: json["serial_number"]
-                .as_str()
-                .unwrap_or("Unknown")
-                .to_string(),
-        };
+        let mut info = self.parse_device(path, &json);
+        if status & STATUS_DISK_FAILING != 0 {
+            info.health_passed = Some(false);
+        }
 
         let dev_type = json["device"]["type"].as_str().unwrap_or("");
 
@@ -81,9 +103,45 @@ impl SmartCtl {
     }
 
     #[tracing::instrument(level = "trace", skip_all)]
-    fn parse_nvme(&self, info: Device, json: &Value) -> NvmeDevice {
+    fn parse_device(&self, path: &str, json: &Value) -> Device {
+        Device {
+            device: path.to_string(),
+            id: None,
+            model: json["model_name"].as_str().unwrap_or("Unknown").to_string(),
+            serial_number: json["serial_number"]
+                .as_str()
+                .unwrap_or("Unknown")
+                .to_string(),
+            health_passed: json["smart_status"]["passed"].as_bool(),
+            selftest_passed: None,
+            selftest_remaining_percent: None,
+        }
+    }
+
+    #[tracing::instrument(level = "trace", skip_all)]
+    fn parse_nvme(&self, mut info: Device, json: &Value) -> NvmeDevice {
         let health = &json["nvme_smart_health_information_log"];
 
+        let self_test = &json["nvme_self_test_log"];
+        if self_test.is_object() {
+            // The most recent result is the first entry of the log
+            info.selftest_passed = self_test["table"][0]["self_test_result"]["value"]
+                .as_u64()
+                .and_then(|result| match result {
+                    0 => Some(true),
+                    // Aborted tests (1-4) neither passed nor failed
+                    1..=4 => None,
+                    5..=7 => Some(false),
+                    _ => None,
+                });
+
+            info.selftest_remaining_percent = Some(
+                self_test["current_self_test_completion_percent"]
+                    .as_f64()
+                    .map_or(0.0, |completed| 100.0 - completed),
+            );
+        }
+
         NvmeDevice {
             device: info,
             temperature: health["temperature"].as_f64(),
@@ -100,7 +158,15 @@ impl SmartCtl {
     }
 
     #[tracing::instrument(level = "trace", skip_all)]
-    fn parse_sata(&self, info: Device, json: &Value) -> SataDevice {
+    fn parse_sata(&self, mut info: Device, json: &Value) -> SataDevice {
+        let status = &json["ata_smart_data"]["self_test"]["status"];
+        if status.is_object() {
+            // There is no verdict while a test is in progress
+            info.selftest_passed = status["passed"].as_bool();
+            info.selftest_remaining_percent =
+                Some(status["remaining_percent"].as_f64().unwrap_or(0.0));
+        }
+
         let mut device = SataDevice::new(info);
 
         if let Some(table) = json["ata_smart_attributes"]["table"].as_array() {
@@ -130,13 +196,18 @@ impl SmartCtl {
                         198 => device.uncorrectable_errors = Some(raw_val),
                         199 => device.crc_errors = Some(raw_val),
 
-                        // SSD Wear Level (Life Remaining %)
-                        // 231: SSD Life Left (Samsung/Kingston)
-                        // 233: Media Wearout Indicator (Intel/Crucial)
-                        // 202: Percentage Lifetime Used
-                        231 | 233 | 202 => {
-                            device.wear_level = Some(raw_val as f64);
+                        // SSD wear level, normalized to the remaining life ratio
+                        // 231: SSD Life Left (Samsung/Kingston) and 233: Media Wearout
+                        // Indicator (Intel) count down from 100 in the normalized value,
+                        // while their raw values are vendor specific
+                        231 | 233 => {
+                            if let Some(value) = attr["value"].as_f64() {
+                                device.wear_level = Some(remaining_life(100.0 - value));
+                            }
                         }
+                        // 202: Percent Lifetime Used (Crucial/Micron), where the raw
+                        // value is the consumed percentage of the rated endurance
+                        202 => device.wear_level = Some(remaining_life(raw_val as f64)),
                         _ => {}
                     }
                 }
@@ -147,14 +218,50 @@ impl SmartCtl {
     }
 }
 
-impl DataSource for SmartCtl {
+/// Converts the used percentage of the rated endurance into the remaining life ratio
+fn remaining_life(used_percent: f64) -> f64 {
+    (1.0 - used_percent / 100.0).clamp(0.0, 1.0)
+}
+
+impl<C, R> Probe for SmartCtl<C, R>
+where
+    C: CommandRunner,
+    R: Reader,
+{
+    async fn supported(&self) -> bool {
+        // Fails if smartctl is not installed
+        self.runner.run(SMARTCTL, &["--version"]).await.is_ok()
+    }
+}
+
+impl<C, R> DataSource for SmartCtl<C, R>
+where
+    C: CommandRunner,
+    R: Reader,
+{
     #[tracing::instrument(level = "debug", skip_all)]
-    async fn disk_temps(&self) -> anyhow::Result<SmartReports> {
-        let device_paths = self.scan_devices().await?;
+    async fn scan_devices(&self) -> anyhow::Result<Vec<String>> {
+        let output = self.runner.run(SMARTCTL, &["--scan", "--json"]).await?;
 
+        let json: Value = serde_json::from_slice(&output.stdout)?;
+        let mut paths = Vec::new();
+
+        if let Some(devices) = json["devices"].as_array() {
+            for dev in devices {
+                if let Some(name) = dev["name"].as_str() {
+                    paths.push(name.to_string());
+                }
+            }
+        }
+
+        Ok(paths)
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn disk_temps(&self, devices: &[String]) -> anyhow::Result<SmartReports> {
         let mut tasks = FuturesUnordered::new();
-        for path in device_paths {
-            tasks.push(async move { self.query_device(&path).await.map_err(|e| (path, e)) });
+        for path in devices {
+            tasks.push(async move { self.query_device(path).await.map_err(|e| (path, e)) });
         }
 
         let mut sata = Vec::new();
@@ -173,6 +280,12 @@ impl DataSource for SmartCtl {
             }
         }
 
+        let stable_names = self.stable_names().await;
+        let devices = sata.iter_mut().map(|s| &mut s.device);
+        for device in devices.chain(nvme.iter_mut().map(|n| &mut n.device)) {
+            device.id = disk_id::stable_name(&stable_names, &device.device).map(str::to_owned);
+        }
+
         Ok(SmartReports {
             timestamp: Instant::now(),
             sata,
@@ -180,3 +293,295 @@ impl DataSource for SmartCtl {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datasource::tests::{HardcodedCommandRunner, HardcodedReader};
+
+    const SCAN: &str = r#"{
+  "json_format_version": [1, 0],
+  "smartctl": { "version": [7, 4], "exit_status": 0 },
+  "devices": [
+    { "name": "/dev/sda", "info_name": "/dev/sda [SAT]", "type": "sat", "protocol": "ATA" },
+    { "name": "/dev/nvme0", "info_name": "/dev/nvme0", "type": "nvme", "protocol": "NVMe" }
+  ]
+}"#;
+
+    const SATA: &str = r#"{
+  "json_format_version": [1, 0],
+  "device": { "name": "/dev/sda", "info_name": "/dev/sda [SAT]", "type": "sat", "protocol": "ATA" },
+  "model_name": "ST4000VN008-2DR166",
+  "serial_number": "ZGY5ABCD",
+  "smart_status": { "passed": true },
+  "ata_smart_data": {
+    "self_test": {
+      "status": { "value": 0, "string": "completed without error", "passed": true },
+      "polling_minutes": { "short": 1, "extended": 619 }
+    }
+  },
+  "ata_smart_attributes": {
+    "revision": 10,
+    "table": [
+      { "id": 4, "name": "Start_Stop_Count", "value": 100, "raw": { "value": 1234, "string": "1234" } },
+      { "id": 5, "name": "Reallocated_Sector_Ct", "value": 100, "raw": { "value": 8, "string": "8" } },
+      { "id": 9, "name": "Power_On_Hours", "value": 62, "raw": { "value": 33840, "string": "33840" } },
+      { "id": 12, "name": "Power_Cycle_Count", "value": 100, "raw": { "value": 97, "string": "97" } },
+      { "id": 194, "name": "Temperature_Celsius", "value": 30, "raw": { "value": 193274707998, "string": "30 (0 45 0 18 0)" } },
+      { "id": 197, "name": "Current_Pending_Sector", "value": 100, "raw": { "value": 0, "string": "0" } },
+      { "id": 199, "name": "UDMA_CRC_Error_Count", "value": 200, "raw": { "value": 2, "string": "2" } }
+    ]
+  }
+}"#;
+
+    const NVME: &str = r#"{
+  "json_format_version": [1, 0],
+  "device": { "name": "/dev/nvme0", "info_name": "/dev/nvme0", "type": "nvme", "protocol": "NVMe" },
+  "model_name": "Samsung SSD 980 PRO 2TB",
+  "serial_number": "S6B0NL0T123456",
+  "smart_status": { "passed": true, "nvme": { "value": 0 } },
+  "nvme_smart_health_information_log": {
+    "critical_warning": 0,
+    "temperature": 41,
+    "available_spare": 100,
+    "available_spare_threshold": 10,
+    "percentage_used": 3,
+    "data_units_read": 45321987,
+    "data_units_written": 61234567,
+    "host_reads": 512345678,
+    "host_writes": 734567890,
+    "power_on_hours": 8123,
+    "unsafe_shutdowns": 42,
+    "media_errors": 0
+  },
+  "nvme_self_test_log": {
+    "current_self_test_operation": { "value": 0, "string": "No self-test in progress" },
+    "table": [
+      {
+        "self_test_code": { "value": 2, "string": "Extended" },
+        "self_test_result": { "value": 0, "string": "Completed without error" },
+        "power_on_hours": 8100
+      }
+    ]
+  }
+}"#;
+
+    #[tokio::test]
+    async fn test_smartctl_stable_names() {
+        let mut runner = HardcodedCommandRunner::new();
+        runner.add_response("smartctl --scan --json", 0, SCAN);
+        runner.add_response("smartctl -a --json --nocheck standby /dev/sda", 0, SATA);
+        runner.add_response("smartctl -a --json --nocheck standby /dev/nvme0", 0, NVME);
+
+        let mut reader = HardcodedReader::new();
+        let links = [
+            ("ata-ST4000VN008-2DR166_ZGY5ABCD", "../../sda"),
+            (
+                "nvme-Samsung_SSD_980_PRO_1TB_S5GXNX0R123456",
+                "../../nvme0n1",
+            ),
+        ];
+        reader.add_dir(disk_id::PATH_DISK_BY_ID, &links.map(|(id, _)| id));
+        for (id, target) in links {
+            reader.add_link(format!("{}/{}", disk_id::PATH_DISK_BY_ID, id), target);
+        }
+
+        let ds = SmartCtl::new(runner).with_stable_names(reader);
+        let devices = ds.scan_devices().await.unwrap();
+        let reports = ds.disk_temps(&devices).await.unwrap();
+
+        assert_eq!(
+            reports.sata[0].device.id.as_deref(),
+            Some("ata-ST4000VN008-2DR166_ZGY5ABCD")
+        );
+        // smartctl reports the controller, while udev links its namespace
+        assert_eq!(
+            reports.nvme[0].device.id.as_deref(),
+            Some("nvme-Samsung_SSD_980_PRO_1TB_S5GXNX0R123456")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_smartctl_datasource() {
+        let mut runner = HardcodedCommandRunner::new();
+        runner.add_response("smartctl --scan --json", 0, SCAN);
+        runner.add_response("smartctl -a --json --nocheck standby /dev/sda", 0, SATA);
+        runner.add_response("smartctl -a --json --nocheck standby /dev/nvme0", 0, NVME);
+
+        let ds = SmartCtl::new(runner);
+        let devices = ds.scan_devices().await.unwrap();
+        let reports = ds.disk_temps(&devices).await.unwrap();
+        assert_eq!(reports.sata.len(), 1);
+        assert_eq!(reports.nvme.len(), 1);
+
+        let sata = &reports.sata[0];
+        assert_eq!(sata.device.device, "/dev/sda");
+        assert_eq!(sata.device.model, "ST4000VN008-2DR166");
+        assert_eq!(sata.device.serial_number, "ZGY5ABCD");
+        assert_eq!(sata.device.health_passed, Some(true));
+        assert_eq!(sata.device.selftest_passed, Some(true));
+        assert_eq!(sata.device.selftest_remaining_percent, Some(0.0));
+        assert_eq!(sata.temperature, Some(30.0));
+        assert_eq!(sata.temperature_min, Some(18.0));
+        assert_eq!(sata.temperature_max, Some(45.0));
+        assert_eq!(sata.start_stop_count, Some(1234));
+        assert_eq!(sata.reallocated_sectors, Some(8));
+        assert_eq!(sata.power_on_hours, Some(33840));
+        assert_eq!(sata.power_cycle_count, Some(97));
+        assert_eq!(sata.pending_sectors, Some(0));
+        assert_eq!(sata.crc_errors, Some(2));
+        assert_eq!(sata.uncorrectable_errors, None);
+
+        let nvme = &reports.nvme[0];
+        assert_eq!(nvme.device.model, "Samsung SSD 980 PRO 2TB");
+        assert_eq!(nvme.device.health_passed, Some(true));
+        assert_eq!(nvme.device.selftest_passed, Some(true));
+        assert_eq!(nvme.device.selftest_remaining_percent, Some(0.0));
+        assert_eq!(nvme.temperature, Some(41.0));
+        assert_eq!(nvme.available_spare, Some(1.0));
+        assert_eq!(nvme.percent_used, Some(0.03));
+        assert_eq!(nvme.data_units_written, Some(61234567));
+        assert_eq!(nvme.power_on_hours, Some(8123));
+        assert_eq!(nvme.unsafe_shutdowns, Some(42));
+        assert_eq!(nvme.media_errors, Some(0));
+    }
+
+    #[tokio::test]
+    async fn test_smartctl_skips_sleeping_and_failing_devices() {
+        let mut runner = HardcodedCommandRunner::new();
+        runner.add_response("smartctl --scan --json", 0, SCAN);
+        runner.add_response("smartctl -a --json --nocheck standby /dev/sda", 2, "");
+        runner.add_response("smartctl -a --json --nocheck standby /dev/nvme0", 1, "");
+
+        let ds = SmartCtl::new(runner);
+        let devices = ds.scan_devices().await.unwrap();
+        let reports = ds.disk_temps(&devices).await.unwrap();
+        assert!(reports.sata.is_empty());
+        assert!(reports.nvme.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_smartctl_records_failing_devices() {
+        let failing = SATA.replacen(r#""passed": true"#, r#""passed": false"#, 1);
+        assert_ne!(failing, SATA);
+
+        let mut runner = HardcodedCommandRunner::new();
+        runner.add_response("smartctl --scan --json", 0, SCAN);
+        // Disk failing and prefail attributes below the threshold
+        runner.add_response(
+            "smartctl -a --json --nocheck standby /dev/sda",
+            24,
+            &failing,
+        );
+        // SMART command failed and errors in the device log
+        runner.add_response("smartctl -a --json --nocheck standby /dev/nvme0", 68, NVME);
+
+        let ds = SmartCtl::new(runner);
+        let devices = ds.scan_devices().await.unwrap();
+        let reports = ds.disk_temps(&devices).await.unwrap();
+        assert_eq!(reports.sata.len(), 1);
+        assert_eq!(reports.nvme.len(), 1);
+
+        assert_eq!(reports.sata[0].device.health_passed, Some(false));
+        assert_eq!(reports.sata[0].temperature, Some(30.0));
+        assert_eq!(reports.nvme[0].device.health_passed, Some(true));
+        assert_eq!(reports.nvme[0].temperature, Some(41.0));
+    }
+
+    #[tokio::test]
+    async fn test_smartctl_health_from_status() {
+        let mut runner = HardcodedCommandRunner::new();
+        runner.add_response("smartctl --scan --json", 0, SCAN);
+        runner.add_response("smartctl -a --json --nocheck standby /dev/sda", 8, "{}");
+        runner.add_response("smartctl -a --json --nocheck standby /dev/nvme0", 3, "");
+
+        let ds = SmartCtl::new(runner);
+        let devices = ds.scan_devices().await.unwrap();
+        let reports = ds.disk_temps(&devices).await.unwrap();
+        assert_eq!(reports.sata.len(), 1);
+        assert_eq!(reports.sata[0].device.health_passed, Some(false));
+        assert!(reports.nvme.is_empty());
+    }
+
+    fn wear_level(attributes: &str) -> Option<f64> {
+        let json = format!(r#"{{"ata_smart_attributes":{{"table":[{}]}}}}"#, attributes);
+        let json = serde_json::from_str(&json).unwrap();
+
+        let ds = SmartCtl::new(HardcodedCommandRunner::new());
+        let info = ds.parse_device("/dev/sda", &json);
+        ds.parse_sata(info, &json).wear_level
+    }
+
+    #[test]
+    fn test_parse_wear_level_samsung() {
+        let attributes = r#"
+            { "id": 177, "name": "Wear_Leveling_Count", "value": 97, "raw": { "value": 42 } },
+            { "id": 231, "name": "SSD_Life_Left", "value": 97, "raw": { "value": 3 } }
+        "#;
+        assert_eq!(wear_level(attributes), Some(0.97));
+    }
+
+    #[test]
+    fn test_parse_wear_level_crucial() {
+        let attributes = r#"
+            { "id": 173, "name": "Ave_Block-Erase_Count", "value": 98, "raw": { "value": 31 } },
+            { "id": 202, "name": "Percent_Lifetime_Remain", "value": 98, "raw": { "value": 2 } }
+        "#;
+        assert_eq!(wear_level(attributes), Some(0.98));
+    }
+
+    #[test]
+    fn test_parse_wear_level_beyond_rated_endurance() {
+        let attributes = r#"{ "id": 202, "value": 1, "raw": { "value": 115 } }"#;
+        assert_eq!(wear_level(attributes), Some(0.0));
+    }
+
+    #[test]
+    fn test_parse_failing_health() {
+        let json = serde_json::from_str(r#"{"smart_status":{"passed":false}}"#).unwrap();
+
+        let ds = SmartCtl::new(HardcodedCommandRunner::new());
+        let device = ds.parse_device("/dev/sdb", &json);
+        assert_eq!(device.device, "/dev/sdb");
+        assert_eq!(device.model, "Unknown");
+        assert_eq!(device.health_passed, Some(false));
+    }
+
+    #[test]
+    fn test_parse_selftest_in_progress() {
+        let ds = SmartCtl::new(HardcodedCommandRunner::new());
+
+        let sata = serde_json::from_str(
+            r#"{"ata_smart_data":{"self_test":{"status":{"value":244,"string":"in progress, 40% remaining","remaining_percent":40}}}}"#,
+        )
+        .unwrap();
+        let sata = ds.parse_sata(ds.parse_device("/dev/sda", &sata), &sata);
+        assert_eq!(sata.device.selftest_passed, None);
+        assert_eq!(sata.device.selftest_remaining_percent, Some(40.0));
+
+        let nvme = serde_json::from_str(
+            r#"{"nvme_self_test_log":{"current_self_test_operation":{"value":2,"string":"Extended self-test in progress"},"current_self_test_completion_percent":60,"table":[{"self_test_result":{"value":7,"string":"Completed: failed segments"}}]}}"#,
+        )
+        .unwrap();
+        let nvme = ds.parse_nvme(ds.parse_device("/dev/nvme0", &nvme), &nvme);
+        assert_eq!(nvme.device.selftest_passed, Some(false));
+        assert_eq!(nvme.device.selftest_remaining_percent, Some(40.0));
+
+        let nvme = serde_json::from_str(
+            r#"{"nvme_self_test_log":{"current_self_test_completion_percent":0,"table":[{"self_test_result":{"value":4,"string":"Aborted: Format NVM command"}}]}}"#,
+        )
+        .unwrap();
+        let nvme = ds.parse_nvme(ds.parse_device("/dev/nvme0", &nvme), &nvme);
+        assert_eq!(nvme.device.selftest_passed, None);
+    }
+
+    #[test]
+    fn test_parse_selftest_not_supported() {
+        let ds = SmartCtl::new(HardcodedCommandRunner::new());
+        let json = serde_json::from_str(r#"{"model_name":"Old Disk"}"#).unwrap();
+
+        let sata = ds.parse_sata(ds.parse_device("/dev/sda", &json), &json);
+        assert_eq!(sata.device.selftest_passed, None);
+        assert_eq!(sata.device.selftest_remaining_percent, None);
+    }
+}
@@ -1,9 +1,14 @@
+use crate::domain::Probe;
 use crate::metrics::docker::{ContainerStats, DataSource, DockerStats};
 use anyhow::Context;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::path::Path;
 
+use bollard::API_DEFAULT_VERSION;
 use bollard::models::{
-    ContainerCpuStats, ContainerMemoryStats, ContainerNetworkStats, ContainerSummary,
+    ContainerBlkioStats, ContainerCpuStats, ContainerInspectResponse, ContainerMemoryStats,
+    ContainerNetworkStats, ContainerSummary, ContainerSummaryStateEnum, HealthStatusEnum,
 };
 use bollard::query_parameters::{ListContainersOptionsBuilder, StatsOptionsBuilder};
 use futures::StreamExt;
@@ -11,28 +16,144 @@ use tokio::sync::Mutex;
 use tokio::time::Instant;
 use tracing::debug;
 
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+const REQUEST_TIMEOUT_SECS: u64 = 120;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Config {
+    /// Query the container details with an additional inspect request per container.
+    /// Disabled by default, because it increases the collection latency. Required by the
+    /// `docker_container_start_time_seconds`, `docker_container_health` and
+    /// `docker_container_restart_count` metrics, which are not exposed without it.
+    pub inspect_containers: bool,
+    /// Also report the containers which are not running (i.e. exited),
+    /// so that containers stuck in a crash loop can be alerted on.
+    pub include_stopped: bool,
+    /// The docker daemon endpoint, either a socket path (`unix:///run/user/1000/docker.sock`)
+    /// or a TCP one (`tcp://10.0.0.5:2375`). Defaults to `DOCKER_HOST` if it is set, or to
+    /// the local docker socket otherwise.
+    pub host: Option<String>,
+    /// A directory with the `ca.pem`, `cert.pem` and `key.pem` files, used to connect
+    /// to a TCP endpoint over TLS, as with `DOCKER_CERT_PATH`
+    pub tls_cert_dir: Option<String>,
+}
+
+/// The docker daemon endpoint, as parsed from the configured host
+#[derive(Debug, PartialEq, Eq)]
+pub enum DockerHost<'a> {
+    Unix(&'a str),
+    Tcp(&'a str),
+}
+
+impl<'a> DockerHost<'a> {
+    pub fn parse(host: &'a str) -> anyhow::Result<Self> {
+        if host.starts_with("unix://") {
+            return Ok(DockerHost::Unix(host));
+        }
+
+        // A bare path to the socket
+        if host.starts_with('/') {
+            return Ok(DockerHost::Unix(host));
+        }
+
+        if host.starts_with("tcp://") || host.starts_with("http://") {
+            return Ok(DockerHost::Tcp(host));
+        }
+
+        Err(anyhow::anyhow!(
+            "Unsupported docker host [{}], expected a unix:// or a tcp:// URL",
+            host
+        ))
+    }
+}
+
 struct CpuStats {
     total: u64,
     system: u64,
 }
 
 pub struct DockerClient {
+    config: Config,
+    /// Established when probing the daemon at startup, and re-established on the next
+    /// scrape after a failed request
+    docker: std::sync::Mutex<Option<bollard::Docker>>,
     prev_cpu_stats: Mutex<HashMap<String, CpuStats>>,
 }
 
 impl DockerClient {
-    pub fn new() -> Self {
+    /// Fails on an invalid host (including `DOCKER_HOST`) or TLS setup. A missing socket
+    /// is not an error, but the probe then disables the collector, and a daemon started
+    /// after the exporter is picked up only on a configuration reload (SIGHUP).
+    pub fn new(config: Config) -> anyhow::Result<Self> {
+        let docker = match connect(&config) {
+            Ok(docker) => Some(docker),
+            Err(error) if is_socket_not_found(&error) => {
+                debug!(?error, "Failed to connect to the docker daemon");
+                None
+            }
+            Err(error) => return Err(error.context("Invalid docker daemon configuration")),
+        };
+
+        Ok(Self {
+            config,
+            docker: std::sync::Mutex::new(docker),
+            prev_cpu_stats: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Does not connect to the daemon, for when the collector is disabled
+    pub fn unconnected(config: Config) -> Self {
         Self {
+            config,
+            docker: std::sync::Mutex::new(None),
             prev_cpu_stats: Mutex::new(HashMap::new()),
         }
     }
+
+    fn docker(&self) -> anyhow::Result<bollard::Docker> {
+        let mut guard = self.docker.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(docker) = guard.as_ref() {
+            return Ok(docker.clone());
+        }
+
+        let docker = connect(&self.config)?;
+        *guard = Some(docker.clone());
+        Ok(docker)
+    }
+
+    fn disconnect(&self) {
+        let mut guard = self.docker.lock().unwrap_or_else(|e| e.into_inner());
+        *guard = None;
+    }
+
+    async fn inspect_container(
+        &self,
+        docker: &bollard::Docker,
+        id: &str,
+    ) -> Option<ContainerInspectResponse> {
+        if !self.config.inspect_containers {
+            return None;
+        }
+
+        docker
+            .inspect_container(id, None)
+            .await
+            .inspect_err(|error| debug!(?error, container = %id, "Failed to inspect container"))
+            .ok()
+    }
+}
+
+impl Probe for DockerClient {
+    async fn supported(&self) -> bool {
+        // Connecting to a socket fails if it does not exist. The TCP connections are lazy.
+        self.docker().is_ok()
+    }
 }
 
 impl DataSource for DockerClient {
     #[tracing::instrument(level = "debug", skip_all)]
     async fn docker_stats(&self) -> anyhow::Result<DockerStats> {
-        let docker = bollard::Docker::connect_with_unix_defaults()
-            .context("Is the docker daemon running?")?;
+        let docker = self.docker().context("Is the docker daemon running?")?;
 
         let stat_opts = Some(
             StatsOptionsBuilder::new()
@@ -40,8 +161,18 @@ impl DataSource for DockerClient {
                 .one_shot(true)
                 .build(),
         );
-        let list_container_opts = Some(ListContainersOptionsBuilder::new().all(false).build());
-        let containers = docker.list_containers(list_container_opts).await?;
+        let list_container_opts = Some(
+            ListContainersOptionsBuilder::new()
+                .all(self.config.include_stopped)
+                .build(),
+        );
+        let containers = match docker.list_containers(list_container_opts).await {
+            Ok(containers) => containers,
+            Err(e) => {
+                self.disconnect();
+                return Err(e.into());
+            }
+        };
 
         let mut container_stats = Vec::new();
         let mut current_cpu_stats = HashMap::new();
@@ -54,6 +185,8 @@ impl DataSource for DockerClient {
             };
 
             let name = container_name(&container);
+            let image = container.image.clone().unwrap_or_default();
+            let state = container_state(container.state);
             let mut stream = docker.stats(id, stat_opts.clone());
 
             if let Some(stats) = stream.next().await {
@@ -72,13 +205,39 @@ impl DataSource for DockerClient {
 
                 let mem_usage_bytes = calculate_memory_usage(s.memory_stats.as_ref());
                 let (net_rx_bytes, net_tx_bytes) = calculate_network_usage(s.networks.as_ref());
+                let (blk_read_bytes, blk_write_bytes) =
+                    calculate_blkio_usage(s.blkio_stats.as_ref());
+
+                let pids = s.pids_stats.as_ref().and_then(|p| p.current);
+
+                // A failed inspect only omits the metrics derived from it
+                let details = self.inspect_container(&docker, id).await;
+                let start_time = details
+                    .as_ref()
+                    .and_then(|d| d.state.as_ref())
+                    .and_then(|s| s.started_at.as_deref())
+                    .and_then(parse_timestamp);
+                let health = details
+                    .as_ref()
+                    .and_then(|d| d.state.as_ref())
+                    .and_then(|s| s.health.as_ref())
+                    .and_then(|h| health_value(h.status));
+                let restart_count = details.as_ref().and_then(|d| d.restart_count);
 
                 container_stats.push(ContainerStats {
                     name,
+                    image,
+                    state,
+                    health,
                     cpu_usage,
                     mem_usage_bytes,
                     net_rx_bytes,
                     net_tx_bytes,
+                    blk_read_bytes,
+                    blk_write_bytes,
+                    start_time,
+                    pids,
+                    restart_count,
                 });
             }
         }
@@ -91,6 +250,52 @@ impl DataSource for DockerClient {
     }
 }
 
+fn connect(config: &Config) -> anyhow::Result<bollard::Docker> {
+    let host = config
+        .host
+        .clone()
+        .or_else(|| std::env::var("DOCKER_HOST").ok());
+
+    let Some(host) = host else {
+        return Ok(bollard::Docker::connect_with_unix_defaults()?);
+    };
+
+    let docker = match (DockerHost::parse(&host)?, &config.tls_cert_dir) {
+        (DockerHost::Unix(path), _) => {
+            bollard::Docker::connect_with_unix(path, REQUEST_TIMEOUT_SECS, API_DEFAULT_VERSION)?
+        }
+        (DockerHost::Tcp(addr), None) => {
+            bollard::Docker::connect_with_http(addr, REQUEST_TIMEOUT_SECS, API_DEFAULT_VERSION)?
+        }
+        (DockerHost::Tcp(addr), Some(cert_dir)) => {
+            let cert_dir = Path::new(cert_dir);
+            bollard::Docker::connect_with_ssl(
+                addr,
+                &cert_dir.join("key.pem"),
+                &cert_dir.join("cert.pem"),
+                &cert_dir.join("ca.pem"),
+                REQUEST_TIMEOUT_SECS,
+                API_DEFAULT_VERSION,
+            )
+            .with_context(|| {
+                format!(
+                    "Failed to load the TLS certificates from [{}]",
+                    cert_dir.display()
+                )
+            })?
+        }
+    };
+
+    Ok(docker)
+}
+
+fn is_socket_not_found(error: &anyhow::Error) -> bool {
+    matches!(
+        error.downcast_ref::<bollard::errors::Error>(),
+        Some(bollard::errors::Error::SocketNotFoundError(_))
+    )
+}
+
 fn container_name(container: &ContainerSummary) -> String {
     container
         .names
@@ -101,6 +306,23 @@ fn container_name(container: &ContainerSummary) -> String {
         .to_string()
 }
 
+fn container_state(state: Option<ContainerSummaryStateEnum>) -> Option<String> {
+    match state? {
+        ContainerSummaryStateEnum::EMPTY => None,
+        state => Some(state.to_string()),
+    }
+}
+
+/// Maps the health check status to 1 for healthy and to 0 for unhealthy or still starting
+/// containers. Containers without a health check do not have a health value at all.
+fn health_value(status: Option<HealthStatusEnum>) -> Option<f64> {
+    match status? {
+        HealthStatusEnum::HEALTHY => Some(1.0),
+        HealthStatusEnum::UNHEALTHY | HealthStatusEnum::STARTING => Some(0.0),
+        HealthStatusEnum::NONE | HealthStatusEnum::EMPTY => None,
+    }
+}
+
 fn cpu_usage(
     container_name: &str,
     container_stats: Option<&ContainerCpuStats>,
@@ -183,3 +405,265 @@ fn calculate_network_usage(
 
     (Some(rx), Some(tx))
 }
+
+/// Sums the bytes read from and written to all block devices. The operations are named
+/// `Read`/`Write` on cgroup v1 hosts and `read`/`write` on cgroup v2 hosts.
+fn calculate_blkio_usage(stats: Option<&ContainerBlkioStats>) -> (Option<u64>, Option<u64>) {
+    let Some(entries) = stats.and_then(|s| s.io_service_bytes_recursive.as_ref()) else {
+        return (None, None);
+    };
+
+    let sum = |op: &str| {
+        entries
+            .iter()
+            .filter(|e| e.op.as_deref().is_some_and(|o| o.eq_ignore_ascii_case(op)))
+            .map(|e| e.value.unwrap_or_default())
+            .sum()
+    };
+
+    (Some(sum("read")), Some(sum("write")))
+}
+
+/// Converts an RFC 3339 timestamp (i.e. `2024-01-15T10:30:45.123456789Z`), as
+/// returned by the docker API, into seconds since the unix epoch.
+///
+/// Docker reports `0001-01-01T00:00:00Z` for containers that have never been
+/// started, so timestamps before the unix epoch are treated as missing.
+fn parse_timestamp(timestamp: &str) -> Option<f64> {
+    let (date, time) = timestamp.trim().split_once(['T', 't', ' '])?;
+
+    let mut date_parts = date.splitn(3, '-');
+    let year = date_parts.next()?.parse::<i64>().ok()?;
+    let month = date_parts.next()?.parse::<i64>().ok()?;
+    let day = date_parts.next()?.parse::<i64>().ok()?;
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    // Split off the timezone designator: either "Z" or a "+HH:MM"/"-HH:MM" offset
+    let (time, offset_seconds) = if let Some(time) = time.strip_suffix(['Z', 'z']) {
+        (time, 0)
+    } else {
+        let idx = time.rfind(['+', '-'])?;
+        let (time, offset) = time.split_at(idx);
+        let sign = if offset.starts_with('-') { -1 } else { 1 };
+        let (hours, minutes) = offset[1..].split_once(':')?;
+        let offset = hours.parse::<i64>().ok()? * 3600 + minutes.parse::<i64>().ok()? * 60;
+        (time, sign * offset)
+    };
+
+    let mut time_parts = time.splitn(3, ':');
+    let hours = time_parts.next()?.parse::<i64>().ok()?;
+    let minutes = time_parts.next()?.parse::<i64>().ok()?;
+    let seconds = time_parts.next()?.parse::<f64>().ok()?;
+
+    let days = days_from_civil(year, month, day);
+    let timestamp = (days * SECONDS_PER_DAY + hours * 3600 + minutes * 60 - offset_seconds) as f64;
+    let timestamp = timestamp + seconds;
+
+    if timestamp < 0.0 {
+        return None;
+    }
+
+    Some(timestamp)
+}
+
+/// Number of days since 1970-01-01 in the proleptic Gregorian calendar
+/// (see <https://howardhinnant.github.io/date_algorithms.html#days_from_civil>)
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = if year >= 0 { year } else { year - 399 } / 400;
+    let year_of_era = year - era * 400;
+    let month = if month > 2 { month - 3 } else { month + 9 };
+    let day_of_year = (153 * month + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146097 + day_of_era - 719468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bollard::models::ContainerBlkioStatEntry;
+
+    fn host(host: &str) -> Config {
+        Config {
+            host: Some(host.to_owned()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_connect() {
+        assert!(connect(&host("tcp://127.0.0.1:2375")).is_ok());
+        assert!(connect(&host("unix:///nonexistent/docker.sock")).is_err());
+        assert!(connect(&host("ssh://docker@10.0.0.5")).is_err());
+    }
+
+    #[test]
+    fn test_new_fails_fast() {
+        assert!(DockerClient::new(host("unix:///nonexistent/docker.sock")).is_ok());
+        assert!(DockerClient::new(host("ssh://docker@10.0.0.5")).is_err());
+
+        let config = Config {
+            tls_cert_dir: Some("/nonexistent".to_owned()),
+            ..host("tcp://127.0.0.1:2376")
+        };
+        assert!(DockerClient::new(config).is_err());
+    }
+
+    #[test]
+    fn test_connect_tls_missing_certificates() {
+        let config = Config {
+            tls_cert_dir: Some("/nonexistent".to_owned()),
+            ..host("tcp://127.0.0.1:2376")
+        };
+
+        let error = connect(&config).unwrap_err();
+        assert!(error.to_string().contains("/nonexistent"));
+    }
+
+    #[test]
+    fn test_parse_host() {
+        let cases = [
+            (
+                "unix:///run/docker.sock",
+                DockerHost::Unix("unix:///run/docker.sock"),
+            ),
+            (
+                "/run/user/1000/docker.sock",
+                DockerHost::Unix("/run/user/1000/docker.sock"),
+            ),
+            (
+                "tcp://10.0.0.5:2375",
+                DockerHost::Tcp("tcp://10.0.0.5:2375"),
+            ),
+            (
+                "http://10.0.0.5:2375",
+                DockerHost::Tcp("http://10.0.0.5:2375"),
+            ),
+        ];
+
+        for (host, expected) in cases {
+            assert_eq!(DockerHost::parse(host).unwrap(), expected);
+        }
+
+        let error = DockerHost::parse("ssh://docker@10.0.0.5").unwrap_err();
+        assert!(error.to_string().contains("ssh://docker@10.0.0.5"));
+    }
+
+    fn blkio_entry(major: u64, op: &str, value: u64) -> ContainerBlkioStatEntry {
+        ContainerBlkioStatEntry {
+            major: Some(major),
+            minor: Some(0),
+            op: Some(op.to_owned()),
+            value: Some(value),
+        }
+    }
+
+    #[test]
+    fn test_container_state() {
+        let cases = [
+            (Some(ContainerSummaryStateEnum::RUNNING), Some("running")),
+            (Some(ContainerSummaryStateEnum::PAUSED), Some("paused")),
+            (
+                Some(ContainerSummaryStateEnum::RESTARTING),
+                Some("restarting"),
+            ),
+            (Some(ContainerSummaryStateEnum::EXITED), Some("exited")),
+            (Some(ContainerSummaryStateEnum::EMPTY), None),
+            (None, None),
+        ];
+
+        for (input, expected) in cases {
+            assert_eq!(container_state(input).as_deref(), expected, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_health_value() {
+        let cases = [
+            (Some(HealthStatusEnum::HEALTHY), Some(1.0)),
+            (Some(HealthStatusEnum::UNHEALTHY), Some(0.0)),
+            (Some(HealthStatusEnum::STARTING), Some(0.0)),
+            (Some(HealthStatusEnum::NONE), None),
+            (Some(HealthStatusEnum::EMPTY), None),
+            (None, None),
+        ];
+
+        for (input, expected) in cases {
+            assert_eq!(health_value(input), expected, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_calculate_blkio_usage_cgroup_v1() {
+        let stats = ContainerBlkioStats {
+            io_service_bytes_recursive: Some(vec![
+                blkio_entry(8, "Read", 4096),
+                blkio_entry(8, "Write", 1024),
+                blkio_entry(8, "Sync", 5120),
+                blkio_entry(8, "Total", 5120),
+                blkio_entry(259, "Read", 100),
+                blkio_entry(259, "Write", 200),
+            ]),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            calculate_blkio_usage(Some(&stats)),
+            (Some(4196), Some(1224))
+        );
+    }
+
+    #[test]
+    fn test_calculate_blkio_usage_cgroup_v2() {
+        let stats = ContainerBlkioStats {
+            io_service_bytes_recursive: Some(vec![
+                blkio_entry(8, "read", 8192),
+                blkio_entry(8, "write", 2048),
+            ]),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            calculate_blkio_usage(Some(&stats)),
+            (Some(8192), Some(2048))
+        );
+    }
+
+    #[test]
+    fn test_calculate_blkio_usage_missing() {
+        assert_eq!(calculate_blkio_usage(None), (None, None));
+        assert_eq!(
+            calculate_blkio_usage(Some(&ContainerBlkioStats::default())),
+            (None, None)
+        );
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        let cases = [
+            ("1970-01-01T00:00:00Z", Some(0.0)),
+            ("2024-01-15T10:30:45Z", Some(1705314645.0)),
+            ("2024-01-15T10:30:45.5Z", Some(1705314645.5)),
+            ("2024-02-29T00:00:00Z", Some(1709164800.0)),
+            ("2024-01-15T12:30:45+02:00", Some(1705314645.0)),
+            ("2024-01-15T05:30:45-05:00", Some(1705314645.0)),
+            ("0001-01-01T00:00:00Z", None),
+            ("2024-13-01T00:00:00Z", None),
+            ("not a timestamp", None),
+            ("", None),
+        ];
+
+        for (input, expected) in cases {
+            assert_eq!(parse_timestamp(input), expected, "input={:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_parse_timestamp_nanoseconds() {
+        let ts = parse_timestamp("2024-01-15T10:30:45.123456789Z").unwrap();
+        assert!((ts - 1705314645.123457).abs() < 1e-6);
+    }
+}
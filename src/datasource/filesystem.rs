@@ -0,0 +1,264 @@
+use crate::datasource::{Reader, Roots};
+use crate::metrics::filesystem::{DataSource, FilesystemStats, Inodes, Mount};
+use tokio::time::Instant;
+
+const PATH_MOUNTS: &str = "mounts";
+const PATH_EXT4: &str = "fs/ext4";
+
+pub struct ProcMounts<R> {
+    reader: R,
+    roots: Roots,
+}
+
+impl<R> ProcMounts<R>
+where
+    R: Reader,
+{
+    pub fn new(reader: R, roots: Roots) -> Self {
+        Self { reader, roots }
+    }
+
+    /// ext4 counts the errors it has encountered since the filesystem was created,
+    /// e.g. `/sys/fs/ext4/sda1/errors_count`. Other filesystems do not expose it.
+    async fn device_error(&self, mount: &Mount) -> Option<bool> {
+        if mount.fs_type != "ext4" {
+            return None;
+        }
+
+        // The sysfs directory is named after the kernel device, i.e. `dm-0` for `/dev/mapper/vg-lv`
+        let device = match self.reader.read_link(&mount.device).await {
+            Ok(target) => target.to_string_lossy().into_owned(),
+            Err(_) => mount.device.clone(),
+        };
+
+        let name = device.rsplit('/').next()?;
+        let path = self
+            .roots
+            .sys(&format!("{}/{}/errors_count", PATH_EXT4, name));
+
+        let content = self.reader.read_to_string(&path).await.ok()?;
+        content.trim().parse::<u64>().ok().map(|errors| errors > 0)
+    }
+
+    async fn inodes(&self, mount: &Mount) -> Option<Inodes> {
+        match self.reader.statvfs(&mount.mountpoint).await {
+            Ok(stat) => Some(Inodes {
+                total: stat.files,
+                free: stat.files_free,
+            }),
+            Err(e) => {
+                tracing::debug!(error=?e, "Failed to query the inodes of {}", mount.mountpoint);
+                None
+            }
+        }
+    }
+}
+
+impl<R> DataSource for ProcMounts<R>
+where
+    R: Reader,
+{
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn filesystem_stats(
+        &self,
+        ignored_fs_types: &[String],
+    ) -> anyhow::Result<FilesystemStats> {
+        let content = self
+            .reader
+            .read_to_string(self.roots.proc(PATH_MOUNTS))
+            .await?;
+
+        // Filtered before statvfs, which may block on autofs or an unreachable network mount
+        let mut mounts = parse_mounts(&content);
+        mounts.retain(|m| !ignored_fs_types.contains(&m.fs_type));
+        for mount in &mut mounts {
+            mount.device_error = self.device_error(mount).await;
+            mount.inodes = self.inodes(mount).await;
+        }
+
+        Ok(FilesystemStats {
+            timestamp: Instant::now(),
+            mounts,
+        })
+    }
+}
+
+/// Parses `/proc/mounts`, which has one `<device> <mountpoint> <fstype> <options> 0 0`
+/// line per mount. The same mountpoint may be listed several times when filesystems
+/// are stacked on top of each other, in which case only the last one is visible.
+fn parse_mounts(content: &str) -> Vec<Mount> {
+    let mut mounts: Vec<Mount> = vec![];
+
+    for line in content.lines() {
+        let mut parts = line.split_whitespace();
+        let (Some(device), Some(mountpoint), Some(fs_type), Some(options)) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            tracing::debug!("Skipping invalid mount line: {}", line);
+            continue;
+        };
+
+        let mount = Mount {
+            device: unescape(device),
+            mountpoint: unescape(mountpoint),
+            fs_type: fs_type.to_owned(),
+            readonly: options.split(',').any(|option| option == "ro"),
+            device_error: None,
+            inodes: None,
+        };
+
+        mounts.retain(|m| m.mountpoint != mount.mountpoint);
+        mounts.push(mount);
+    }
+
+    mounts
+}
+
+/// The kernel escapes the spaces, tabs, newlines and backslashes in the paths as octal
+/// sequences, e.g. `/mnt/my\040disk`
+pub(crate) fn unescape(field: &str) -> String {
+    let mut result = String::with_capacity(field.len());
+
+    let mut rest = field;
+    while let Some(idx) = rest.find('\\') {
+        result.push_str(&rest[..idx]);
+
+        let escaped = rest.get(idx + 1..idx + 4);
+        match escaped.and_then(|octal| u8::from_str_radix(octal, 8).ok()) {
+            Some(byte) => {
+                result.push(byte as char);
+                rest = &rest[idx + 4..];
+            }
+            None => {
+                result.push('\\');
+                rest = &rest[idx + 1..];
+            }
+        }
+    }
+
+    result.push_str(rest);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datasource::StatVfs;
+    use crate::datasource::tests::HardcodedReader;
+
+    const MOUNTS: &str = r#"sysfs /sys sysfs rw,nosuid,nodev,noexec,relatime 0 0
+proc /proc proc rw,nosuid,nodev,noexec,relatime 0 0
+/dev/nvme0n1p2 / ext4 rw,relatime 0 0
+/dev/nvme0n1p1 /boot/efi vfat rw,relatime,fmask=0077,dmask=0077,codepage=437,iocharset=ascii,shortname=mixed,errors=remount-ro 0 0
+/dev/sda1 /mnt/backup\040disk ext4 ro,relatime 0 0
+tank/data /tank/data zfs rw,xattr,noacl,casesensitive 0 0
+"#;
+
+    #[test]
+    fn test_parse_mounts() {
+        let mounts = parse_mounts(MOUNTS);
+        assert_eq!(mounts.len(), 6);
+
+        assert_eq!(mounts[2].device, "/dev/nvme0n1p2");
+        assert_eq!(mounts[2].mountpoint, "/");
+        assert_eq!(mounts[2].fs_type, "ext4");
+        assert!(!mounts[2].readonly);
+
+        // `errors=remount-ro` is not the `ro` option
+        assert!(!mounts[3].readonly);
+
+        assert_eq!(mounts[4].mountpoint, "/mnt/backup disk");
+        assert!(mounts[4].readonly);
+    }
+
+    #[test]
+    fn test_parse_stacked_mounts() {
+        let content = "/dev/sda1 /srv ext4 rw 0 0\n/dev/sdb1 /srv xfs ro 0 0\n";
+
+        let mounts = parse_mounts(content);
+        assert_eq!(mounts.len(), 1);
+        assert_eq!(mounts[0].device, "/dev/sdb1");
+        assert!(mounts[0].readonly);
+    }
+
+    #[test]
+    fn test_unescape() {
+        assert_eq!(unescape(r"/mnt/a\040b\011c"), "/mnt/a b\tc");
+        assert_eq!(unescape(r"/mnt/a\\b"), r"/mnt/a\\b");
+        assert_eq!(unescape(r"/mnt/a\134b"), r"/mnt/a\b");
+    }
+
+    #[tokio::test]
+    async fn test_filesystem_stats() {
+        let mut reader = HardcodedReader::new();
+        reader.add_response(Roots::default().proc(PATH_MOUNTS), MOUNTS);
+        reader.add_response(
+            Roots::default().sys("fs/ext4/nvme0n1p2/errors_count"),
+            "0\n",
+        );
+        reader.add_response(Roots::default().sys("fs/ext4/sda1/errors_count"), "3\n");
+        reader.add_statvfs(
+            "/",
+            StatVfs {
+                files: 1000,
+                files_free: 250,
+            },
+        );
+
+        let ds = ProcMounts::new(reader, Roots::default());
+        let stats = ds.filesystem_stats(&[]).await.unwrap();
+
+        let errors = stats
+            .mounts
+            .iter()
+            .map(|m| (m.mountpoint.as_str(), m.device_error))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            errors,
+            vec![
+                ("/sys", None),
+                ("/proc", None),
+                ("/", Some(false)),
+                ("/boot/efi", None),
+                ("/mnt/backup disk", Some(true)),
+                ("/tank/data", None),
+            ]
+        );
+
+        let root = stats.mounts.iter().find(|m| m.mountpoint == "/").unwrap();
+        assert_eq!(root.inodes.map(|i| i.used()), Some(750));
+        assert_eq!(stats.mounts[0].inodes, None);
+    }
+
+    #[tokio::test]
+    async fn test_ignored_fs_types_are_skipped() {
+        let mut reader = HardcodedReader::new();
+        reader.add_response(Roots::default().proc(PATH_MOUNTS), MOUNTS);
+
+        let ds = ProcMounts::new(reader, Roots::default());
+        let ignored = ["sysfs", "proc", "vfat", "zfs"].map(str::to_owned);
+        let stats = ds.filesystem_stats(&ignored).await.unwrap();
+
+        let mountpoints = stats
+            .mounts
+            .iter()
+            .map(|m| m.mountpoint.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(mountpoints, vec!["/", "/mnt/backup disk"]);
+    }
+
+    #[tokio::test]
+    async fn test_device_error_of_symlinked_device() {
+        let mut reader = HardcodedReader::new();
+        reader.add_response(
+            Roots::default().proc(PATH_MOUNTS),
+            "/dev/mapper/vg-root / ext4 rw,relatime 0 0\n",
+        );
+        reader.add_link("/dev/mapper/vg-root", "../dm-0");
+        reader.add_response(Roots::default().sys("fs/ext4/dm-0/errors_count"), "2\n");
+
+        let ds = ProcMounts::new(reader, Roots::default());
+        let stats = ds.filesystem_stats(&[]).await.unwrap();
+        assert_eq!(stats.mounts[0].device_error, Some(true));
+    }
+}
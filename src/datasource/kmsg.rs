@@ -0,0 +1,186 @@
+use crate::domain::Probe;
+use crate::metrics::kmsg::{DataSource, KernelMessage, KernelMessages, LEVELS};
+use std::io::{ErrorKind, Read};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use tokio::time::Instant;
+
+const PATH_KMSG: &str = "/dev/kmsg";
+/// The largest record the kernel returns from a single read, i.e. `CONSOLE_EXT_LOG_MAX`
+const MAX_RECORD_SIZE: usize = 8192;
+
+pub struct DevKmsg {
+    path: PathBuf,
+    levels: Vec<String>,
+}
+
+impl DevKmsg {
+    pub fn new(levels: &[String]) -> Self {
+        Self {
+            path: PathBuf::from(PATH_KMSG),
+            levels: levels.to_vec(),
+        }
+    }
+}
+
+impl Probe for DevKmsg {
+    async fn supported(&self) -> bool {
+        // Reading fails with EPERM for the unprivileged users when `kernel.dmesg_restrict` is set
+        std::fs::File::open(&self.path)
+            .inspect_err(|error| tracing::debug!(?error, "Failed to open {}", PATH_KMSG))
+            .is_ok()
+    }
+}
+
+impl DataSource for DevKmsg {
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn kernel_messages(&self) -> anyhow::Result<KernelMessages> {
+        let path = self.path.clone();
+        let content = tokio::task::spawn_blocking(move || read_records(&path)).await??;
+
+        let messages = parse_records(&String::from_utf8_lossy(&content))
+            .into_iter()
+            .filter(|message| self.levels.iter().any(|level| level == message.level))
+            .collect();
+
+        Ok(KernelMessages {
+            timestamp: Instant::now(),
+            messages,
+        })
+    }
+}
+
+/// Reads all records currently in the kernel ring buffer. Each read of `/dev/kmsg` returns
+/// a single record, and the non-blocking reads fail with `EAGAIN` after the last one.
+fn read_records(path: &Path) -> std::io::Result<Vec<u8>> {
+    let mut file = std::fs::OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NONBLOCK)
+        .open(path)?;
+
+    let mut content = vec![];
+    let mut buffer = vec![0; MAX_RECORD_SIZE];
+    loop {
+        match file.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => content.extend_from_slice(&buffer[..read]),
+            Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+            // The record was overwritten before it was read, so continue with the next one
+            Err(e) if e.raw_os_error() == Some(libc::EPIPE) => continue,
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(content)
+}
+
+/// Parses the `/dev/kmsg` records: `<priority>,<sequence>,<timestamp>,<flags>;<message>`.
+/// The lines starting with a space are the key/value continuation lines of the previous one.
+fn parse_records(content: &str) -> Vec<KernelMessage> {
+    let mut messages = vec![];
+
+    for line in content.lines() {
+        if line.is_empty() || line.starts_with(' ') {
+            continue;
+        }
+
+        let mut fields = line.split(';').next().unwrap_or_default().split(',');
+        let (Some(Ok(priority)), Some(Ok(seq))) = (
+            fields.next().map(str::parse::<u32>),
+            fields.next().map(str::parse::<u64>),
+        ) else {
+            tracing::debug!("Skipping invalid kernel message: {}", line);
+            continue;
+        };
+
+        messages.push(KernelMessage {
+            // The facility is in the upper bits of the priority, and the level in the lowest three
+            level: LEVELS[(priority & 0x7) as usize],
+            seq,
+        });
+    }
+
+    messages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KMSG: &str = "4,310,164150,-;software IO TLB: No low mem
+3,1822,4312250117,-;ata3.00: failed command: READ FPDMA QUEUED
+ SUBSYSTEM=scsi
+ DEVICE=+scsi:2:0:0:0
+6,1823,4312250200,-;ata3.00: configured for UDMA/133
+11,1950,5210000042,c;systemd-journald[412]: Failed to write entry
+2,2004,6001871003,-;Out of memory: Killed process 1234 (java)
+";
+
+    #[test]
+    fn test_parse_records() {
+        let messages = parse_records(KMSG);
+
+        assert_eq!(
+            messages,
+            vec![
+                KernelMessage {
+                    level: "warn",
+                    seq: 310
+                },
+                KernelMessage {
+                    level: "err",
+                    seq: 1822
+                },
+                KernelMessage {
+                    level: "info",
+                    seq: 1823
+                },
+                KernelMessage {
+                    level: "err",
+                    seq: 1950
+                },
+                KernelMessage {
+                    level: "crit",
+                    seq: 2004
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid_records() {
+        assert!(parse_records("").is_empty());
+        assert!(parse_records("not a record\n;\n").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_kmsg_datasource() {
+        let path = std::env::temp_dir().join(format!("hephaestus-kmsg-{}", std::process::id()));
+        std::fs::write(&path, KMSG).unwrap();
+
+        let ds = DevKmsg {
+            path: path.clone(),
+            levels: vec!["err".to_owned(), "warn".to_owned()],
+        };
+        assert!(ds.supported().await);
+        let stats = ds.kernel_messages().await;
+        std::fs::remove_file(&path).unwrap();
+
+        let seqs = stats
+            .unwrap()
+            .messages
+            .iter()
+            .map(|m| m.seq)
+            .collect::<Vec<_>>();
+        assert_eq!(seqs, vec![310, 1822, 1950]);
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_without_kmsg() {
+        let ds = DevKmsg {
+            path: PathBuf::from("/nonexistent/kmsg"),
+            levels: vec![],
+        };
+        assert!(!ds.supported().await);
+    }
+}
@@ -1,42 +1,48 @@
-use crate::datasource::Reader;
-use crate::metrics::memory_usage::{DataSource, RamStats, SwapStats};
+use crate::datasource::{Reader, Roots, page_size};
+use crate::metrics::memory_usage::{DataSource, RamStats, SwapStats, ZswapStats};
+use std::io::ErrorKind;
 
-const PATH_MEM_INFO: &str = "/proc/meminfo";
+const PATH_MEM_INFO: &str = "meminfo";
+const PATH_ZSWAP_DEBUGFS: &str = "kernel/debug/zswap";
 
 pub struct MemoryUsage<R> {
     reader: R,
+    roots: Roots,
 }
 
 impl<R> MemoryUsage<R>
 where
     R: Reader,
 {
-    pub fn new(reader: R) -> Self {
-        Self { reader }
+    pub fn new(reader: R, roots: Roots) -> Self {
+        Self { reader, roots }
     }
 
+    /// Parses a `Key: value [unit]` line into the value in bytes. The kernel reports the sizes
+    /// in `kB` (KiB), while the lines without a unit, such as `HugePages_Total`, are counts.
     fn parse_line<'l>(&self, line: &'l str) -> Option<(&'l str, u64)> {
         let Some((key, rest)) = line.split_once(':') else {
             tracing::info!("Skipping invalid mem-info line: {}", line);
             return None;
         };
 
-        let (value, unit) = match rest.trim_start().rsplit_once(' ') {
-            None => (rest, ""),
-            Some((value, unit)) => (value, unit),
+        let (value, unit) = match rest.trim().split_once(' ') {
+            None => (rest.trim(), ""),
+            Some((value, unit)) => (value, unit.trim()),
         };
 
-        let value = value.trim().parse::<u64>().unwrap_or(0);
-        let unit = unit.trim();
+        let Ok(value) = value.parse::<u64>() else {
+            tracing::info!("Skipping invalid mem-info value: {}", line);
+            return None;
+        };
 
         let value = match unit {
             "" => value,
-            "kB" => value * 1024,
-            "mB" => value * 1024 * 1024,
+            unit if unit.eq_ignore_ascii_case("kB") => value * 1024,
             _ => {
-                tracing::info!(
+                tracing::warn!(
                     "unit" = unit,
-                    "Invalid unit found in mem-info line: {}",
+                    "Skipping mem-info line with an unknown unit: {}",
                     line
                 );
                 return None;
@@ -53,36 +59,42 @@ where
 {
     #[tracing::instrument(level = "debug", skip_all)]
     async fn swap(&self) -> anyhow::Result<SwapStats> {
-        let mut total = 0;
-        let mut free = 0;
-
-        let mem_info = self.reader.read_to_string(PATH_MEM_INFO).await?;
-        for line in mem_info.lines() {
-            let Some((key, value)) = self.parse_line(line) else {
-                continue;
-            };
-
-            match key {
-                "SwapTotal" => total = value,
-                "SwapFree" => free = value,
-                _ => {}
-            }
-        }
-
-        let used = total.saturating_sub(free);
-        Ok(SwapStats { total, used, free })
+        let (_, swap) = self.stats().await?;
+        Ok(swap)
     }
 
     #[tracing::instrument(level = "debug", skip_all)]
     async fn ram(&self) -> anyhow::Result<RamStats> {
+        let (ram, _) = self.stats().await?;
+        Ok(ram)
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn stats(&self) -> anyhow::Result<(RamStats, SwapStats)> {
         let mut total = 0;
         let mut free = 0;
         let mut available = 0;
         let mut buffers = 0;
         let mut cached = 0;
         let mut sreclaimable = 0;
+        let mut hugepages_total = 0;
+        let mut hugepages_free = 0;
+        let mut hugepage_size = 0;
+        let mut anon_hugepages = 0;
+        let mut zswap_pool = 0;
+        let mut zswap_stored = 0;
+        let mut swap_total = 0;
+        let mut swap_free = 0;
+        let mut committed = 0;
+        let mut commit_limit = 0;
+        let mut dirty = 0;
+        let mut writeback = 0;
+        let mut shmem = 0;
 
-        let mem_info = self.reader.read_to_string(PATH_MEM_INFO).await?;
+        let mem_info = self
+            .reader
+            .read_to_string(self.roots.proc(PATH_MEM_INFO))
+            .await?;
         for line in mem_info.lines() {
             let Some((key, value)) = self.parse_line(line) else {
                 continue;
@@ -95,6 +107,19 @@ where
                 "Buffers" => buffers = value,
                 "Cached" => cached = value,
                 "SReclaimable" => sreclaimable = value,
+                "HugePages_Total" => hugepages_total = value,
+                "HugePages_Free" => hugepages_free = value,
+                "Hugepagesize" => hugepage_size = value,
+                "AnonHugePages" => anon_hugepages = value,
+                "Zswap" => zswap_pool = value,
+                "Zswapped" => zswap_stored = value,
+                "SwapTotal" => swap_total = value,
+                "SwapFree" => swap_free = value,
+                "Committed_AS" => committed = value,
+                "CommitLimit" => commit_limit = value,
+                "Dirty" => dirty = value,
+                "Writeback" => writeback = value,
+                "Shmem" => shmem = value,
                 _ => {}
             }
         }
@@ -105,24 +130,67 @@ where
             .saturating_sub(buffers)
             .saturating_sub(cache_total);
 
-        Ok(RamStats {
+        let ram = RamStats {
             total,
             used,
             free,
             available,
             buffers,
             cache: cache_total,
-        })
+            hugepages_total,
+            hugepages_free,
+            hugepage_size,
+            anon_hugepages,
+            zswap_pool,
+            zswap_stored,
+            committed,
+            commit_limit,
+            dirty,
+            writeback,
+            shmem,
+        };
+
+        let swap = SwapStats {
+            total: swap_total,
+            used: swap_total.saturating_sub(swap_free),
+            free: swap_free,
+        };
+
+        Ok((ram, swap))
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn zswap(&self) -> anyhow::Result<Option<ZswapStats>> {
+        let mut values = [0; 2];
+        for (idx, file) in ["stored_pages", "pool_total_size"].iter().enumerate() {
+            let path = format!("{}/{}", self.roots.sys(PATH_ZSWAP_DEBUGFS), file);
+
+            values[idx] = match self.reader.read_to_string(&path).await {
+                Ok(content) => content.trim().parse()?,
+                Err(e) if matches!(e.kind(), ErrorKind::NotFound | ErrorKind::PermissionDenied) => {
+                    tracing::debug!(error=?e, "The zswap statistics are not available");
+                    return Ok(None);
+                }
+                Err(e) => return Err(anyhow::anyhow!("Failed to read {}: {}", path, e)),
+            };
+        }
+
+        Ok(Some(ZswapStats {
+            stored_pages: values[0],
+            pool_total_size: values[1],
+            page_size: page_size(),
+        }))
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::datasource::memory_usage::{MemoryUsage, PATH_MEM_INFO};
+    use crate::datasource::memory_usage::{MemoryUsage, PATH_MEM_INFO, PATH_ZSWAP_DEBUGFS};
     use crate::datasource::tests::HardcodedReader;
-    use crate::metrics::memory_usage::DataSource;
+    use crate::datasource::{Roots, page_size};
+    use crate::metrics::memory_usage::{DataSource, ZswapStats};
 
-    const MEM_INFO: &'static str = r#"MemTotal:       61489320 kB
+    const MEM_INFO: &str = r#"MemTotal:       61489320 kB
 MemFree:        44422752 kB
 MemAvailable:   54097832 kB
 Buffers:            1112 kB
@@ -138,8 +206,8 @@ Unevictable:        5764 kB
 Mlocked:            5764 kB
 SwapTotal:       8388604 kB
 SwapFree:        2097152 kB
-Zswap:                 0 kB
-Zswapped:              0 kB
+Zswap:             65536 kB
+Zswapped:         262144 kB
 Dirty:              2004 kB
 Writeback:             0 kB
 AnonPages:       6438384 kB
@@ -183,9 +251,9 @@ DirectMap1G:    49283072 kB
     #[tokio::test]
     async fn test_parse_ram_meminfo() {
         let mut reader = HardcodedReader::new();
-        reader.add_response(PATH_MEM_INFO, MEM_INFO);
+        reader.add_response(Roots::default().proc(PATH_MEM_INFO), MEM_INFO);
 
-        let ds = MemoryUsage::new(reader);
+        let ds = MemoryUsage::new(reader, Roots::default());
         let ram = ds.ram().await.expect("Failed to read RAM usage statistics");
         assert_eq!(ram.total, 62_965_063_680);
         assert_eq!(ram.free, 45_488_898_048);
@@ -193,14 +261,107 @@ DirectMap1G:    49283072 kB
         assert_eq!(ram.cache, 9_604_276_224);
         assert_eq!(ram.buffers, 1_138_688);
         assert_eq!(ram.used, 7_870_750_720);
+        assert_eq!(ram.hugepages_total, 0);
+        assert_eq!(ram.hugepages_free, 0);
+        assert_eq!(ram.hugepage_size, 2_097_152);
+        assert_eq!(ram.anon_hugepages, 3_286_237_184);
+        assert_eq!(ram.zswap_pool, 67_108_864);
+        assert_eq!(ram.zswap_stored, 268_435_456);
+        assert_eq!(ram.committed, 15_181_389_824);
+        assert_eq!(ram.commit_limit, 31_482_531_840);
+        assert_eq!(ram.dirty, 2_052_096);
+        assert_eq!(ram.writeback, 0);
+        assert_eq!(ram.shmem, 60_047_360);
+    }
+
+    #[tokio::test]
+    async fn test_parse_hugepages_meminfo() {
+        let mut reader = HardcodedReader::new();
+        reader.add_response(
+            Roots::default().proc(PATH_MEM_INFO),
+            MEM_INFO
+                .replace("HugePages_Total:       0", "HugePages_Total:     512")
+                .replace("HugePages_Free:        0", "HugePages_Free:      128"),
+        );
+
+        let ds = MemoryUsage::new(reader, Roots::default());
+        let ram = ds.ram().await.expect("Failed to read RAM usage statistics");
+        assert_eq!(ram.hugepages_total, 512);
+        assert_eq!(ram.hugepages_free, 128);
+        assert_eq!(ram.hugepage_size, 2_097_152);
+    }
+
+    #[test]
+    fn test_parse_line_kilobytes() {
+        let ds = MemoryUsage::new(HardcodedReader::new(), Roots::default());
+        assert_eq!(
+            ds.parse_line("Dirty:   2004 kB"),
+            Some(("Dirty", 2_052_096))
+        );
+        assert_eq!(
+            ds.parse_line("Dirty:   2004 KB"),
+            Some(("Dirty", 2_052_096))
+        );
+    }
+
+    #[test]
+    fn test_parse_line_count() {
+        let ds = MemoryUsage::new(HardcodedReader::new(), Roots::default());
+        assert_eq!(
+            ds.parse_line("HugePages_Total:     512"),
+            Some(("HugePages_Total", 512))
+        );
+    }
+
+    #[test]
+    fn test_parse_line_unknown_unit() {
+        let ds = MemoryUsage::new(HardcodedReader::new(), Roots::default());
+        assert_eq!(ds.parse_line("MemTotal:       64 mB"), None);
+        assert_eq!(ds.parse_line("MemTotal:       64 GB"), None);
+        assert_eq!(ds.parse_line("MemTotal:       n/a kB"), None);
+        assert_eq!(ds.parse_line("MemTotal"), None);
+    }
+
+    #[tokio::test]
+    async fn test_zswap_debugfs() {
+        let mut reader = HardcodedReader::new();
+        reader.add_response(
+            format!("{}/stored_pages", Roots::default().sys(PATH_ZSWAP_DEBUGFS)),
+            "65536\n",
+        );
+        reader.add_response(
+            format!(
+                "{}/pool_total_size",
+                Roots::default().sys(PATH_ZSWAP_DEBUGFS)
+            ),
+            "67108864\n",
+        );
+
+        let ds = MemoryUsage::new(reader, Roots::default());
+        let zswap = ds.zswap().await.expect("Failed to read zswap statistics");
+        assert_eq!(
+            zswap,
+            Some(ZswapStats {
+                stored_pages: 65536,
+                pool_total_size: 67_108_864,
+                page_size: page_size(),
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_zswap_debugfs_not_mounted() {
+        let ds = MemoryUsage::new(HardcodedReader::new(), Roots::default());
+        let zswap = ds.zswap().await.expect("Failed to read zswap statistics");
+        assert_eq!(zswap, None);
     }
 
     #[tokio::test]
     async fn test_parse_swap_meminfo() {
         let mut reader = HardcodedReader::new();
-        reader.add_response(PATH_MEM_INFO, MEM_INFO);
+        reader.add_response(Roots::default().proc(PATH_MEM_INFO), MEM_INFO);
 
-        let ds = MemoryUsage::new(reader);
+        let ds = MemoryUsage::new(reader, Roots::default());
         let swap = ds
             .swap()
             .await
@@ -209,4 +370,22 @@ DirectMap1G:    49283072 kB
         assert_eq!(swap.free, 2_147_483_648);
         assert_eq!(swap.used, 6_442_446_848);
     }
+
+    #[tokio::test]
+    async fn test_stats_match_separate_reads() {
+        let data_source = || {
+            let mut reader = HardcodedReader::new();
+            reader.add_response(Roots::default().proc(PATH_MEM_INFO), MEM_INFO);
+            MemoryUsage::new(reader, Roots::default())
+        };
+
+        let (ram, swap) = data_source()
+            .stats()
+            .await
+            .expect("Failed to read memory statistics");
+        assert_eq!(ram, data_source().ram().await.unwrap());
+        assert_eq!(swap, data_source().swap().await.unwrap());
+        assert_eq!(ram.total, 62_965_063_680);
+        assert_eq!(swap.total, 8_589_930_496);
+    }
 }
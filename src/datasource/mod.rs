@@ -1,21 +1,137 @@
-use std::path::Path;
+use std::ffi::CString;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::process::Output;
+use tokio::process::Command;
 
+pub mod btrfs;
+pub mod cgroup;
+pub mod conntrack;
 pub mod cpu_frequency;
+pub mod cpu_throttle;
 pub mod cpu_usage;
+pub mod disk_id;
 pub mod disk_io;
 pub mod disk_smart;
 pub mod docker;
+pub mod filesystem;
+pub mod kmsg;
 pub mod memory_usage;
 pub mod network_io;
+pub mod nfs;
+pub mod numa;
 pub mod nut;
+pub mod os_info;
+pub mod platform;
+pub mod processes_top;
+pub mod rapl;
+pub mod slabinfo;
+pub mod swap;
+pub mod timesync;
+pub mod tokio_runtime;
+pub mod users;
+pub mod vmstat;
+pub mod wifi;
 pub mod zfs_arc;
 pub mod zfs_dataset;
+pub mod zpool;
+pub mod zram;
+
+const PROC_ROOT: &str = "/proc";
+const SYS_ROOT: &str = "/sys";
+
+/// The mount points of procfs and sysfs, which differ from the defaults when running
+/// in a container with the host filesystems mounted elsewhere
+#[derive(Debug, Clone)]
+pub struct Roots {
+    proc: String,
+    sys: String,
+}
+
+impl Roots {
+    pub fn new(proc: impl Into<String>, sys: impl Into<String>) -> Self {
+        Self {
+            proc: proc.into(),
+            sys: sys.into(),
+        }
+    }
+
+    /// Resolves a path relative to the procfs root, e.g. `meminfo`
+    pub fn proc(&self, path: &str) -> String {
+        format!("{}/{}", self.proc.trim_end_matches('/'), path)
+    }
+
+    /// Resolves a path relative to the sysfs root, e.g. `class/net`
+    pub fn sys(&self, path: &str) -> String {
+        format!("{}/{}", self.sys.trim_end_matches('/'), path)
+    }
+}
+
+impl Default for Roots {
+    fn default() -> Self {
+        Self::new(PROC_ROOT, SYS_ROOT)
+    }
+}
 
 pub trait Reader: Send + Sync {
     fn read_to_string(
         &self,
         path: impl AsRef<Path> + Send,
     ) -> impl Future<Output = std::io::Result<String>> + Send;
+
+    /// Returns the names of the entries in the directory
+    fn read_dir(
+        &self,
+        path: impl AsRef<Path> + Send,
+    ) -> impl Future<Output = std::io::Result<Vec<String>>> + Send;
+
+    /// Returns the target of the symbolic link, without resolving it any further
+    fn read_link(
+        &self,
+        path: impl AsRef<Path> + Send,
+    ) -> impl Future<Output = std::io::Result<PathBuf>> + Send;
+
+    /// Returns the statistics of the filesystem containing the path, as reported by statvfs(3)
+    fn statvfs(
+        &self,
+        path: impl AsRef<Path> + Send,
+    ) -> impl Future<Output = std::io::Result<StatVfs>> + Send;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StatVfs {
+    pub files: u64,
+    pub files_free: u64,
+}
+
+/// std has no safe statvfs(3) wrapper
+#[allow(unsafe_code)]
+fn statvfs(path: &CString) -> std::io::Result<StatVfs> {
+    let mut stat = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+
+    // SAFETY: The path is NUL terminated and the struct is initialized on success
+    let stat = unsafe {
+        if libc::statvfs(path.as_ptr(), stat.as_mut_ptr()) != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+
+        stat.assume_init()
+    };
+
+    // The counters are 32 bits wide on some targets
+    #[allow(clippy::unnecessary_cast)]
+    Ok(StatVfs {
+        files: stat.f_files as u64,
+        files_free: stat.f_ffree as u64,
+    })
+}
+
+/// The base page size of the system, as reported by sysconf(3)
+#[allow(unsafe_code)]
+pub(crate) fn page_size() -> u64 {
+    // SAFETY: sysconf has no preconditions and only reads the system configuration
+    let size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    u64::try_from(size).unwrap_or(4096)
 }
 
 pub struct TokioReader {}
@@ -30,28 +146,95 @@ impl Reader for TokioReader {
     async fn read_to_string(&self, path: impl AsRef<Path> + Send) -> std::io::Result<String> {
         tokio::fs::read_to_string(path).await
     }
+
+    async fn read_dir(&self, path: impl AsRef<Path> + Send) -> std::io::Result<Vec<String>> {
+        let mut names = vec![];
+
+        let mut entries = tokio::fs::read_dir(path).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            names.push(entry.file_name().to_string_lossy().into_owned());
+        }
+
+        Ok(names)
+    }
+
+    async fn read_link(&self, path: impl AsRef<Path> + Send) -> std::io::Result<PathBuf> {
+        tokio::fs::read_link(path).await
+    }
+
+    async fn statvfs(&self, path: impl AsRef<Path> + Send) -> std::io::Result<StatVfs> {
+        let path = CString::new(path.as_ref().as_os_str().as_bytes())?;
+
+        // The call blocks while an unresponsive network filesystem is being queried
+        tokio::task::spawn_blocking(move || statvfs(&path))
+            .await
+            .map_err(std::io::Error::other)?
+    }
+}
+
+pub trait CommandRunner: Send + Sync {
+    fn run(
+        &self,
+        program: &str,
+        args: &[&str],
+    ) -> impl Future<Output = std::io::Result<Output>> + Send;
+}
+
+pub struct TokioCommandRunner {}
+
+impl TokioCommandRunner {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl CommandRunner for TokioCommandRunner {
+    async fn run(&self, program: &str, args: &[&str]) -> std::io::Result<Output> {
+        Command::new(program).args(args).output().await
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::datasource::Reader;
+    use crate::datasource::{CommandRunner, Reader, StatVfs};
     use std::collections::HashMap;
     use std::collections::hash_map::Entry;
     use std::io::ErrorKind;
-    use std::path::Path;
+    use std::os::unix::process::ExitStatusExt;
+    use std::path::{Path, PathBuf};
+    use std::process::{ExitStatus, Output};
     use std::sync::Mutex;
 
     pub struct HardcodedReader {
         data: HashMap<String, (Mutex<usize>, Vec<String>)>,
+        dirs: HashMap<String, Vec<String>>,
+        statvfs: HashMap<String, StatVfs>,
+        links: HashMap<String, PathBuf>,
     }
 
     impl HardcodedReader {
         pub fn new() -> Self {
             Self {
                 data: HashMap::new(),
+                dirs: HashMap::new(),
+                statvfs: HashMap::new(),
+                links: HashMap::new(),
             }
         }
 
+        pub fn add_dir(&mut self, path: impl Into<String>, entries: &[&str]) {
+            let entries = entries.iter().map(|e| e.to_string()).collect();
+            self.dirs.insert(path.into(), entries);
+        }
+
+        pub fn add_link(&mut self, path: impl Into<String>, target: impl Into<PathBuf>) {
+            self.links.insert(path.into(), target.into());
+        }
+
+        pub fn add_statvfs(&mut self, path: impl Into<String>, stat: StatVfs) {
+            self.statvfs.insert(path.into(), stat);
+        }
+
         pub fn add_response(&mut self, key: impl Into<String>, value: impl Into<String>) {
             match self.data.entry(key.into()) {
                 Entry::Occupied(mut e) => {
@@ -77,7 +260,7 @@ mod tests {
                 Some((idx, content)) => {
                     let mut idx = idx.lock().unwrap();
                     if *idx >= content.len() {
-                        return Err(std::io::Error::new(ErrorKind::Other, "Response not mocked"));
+                        return Err(std::io::Error::other("Response not mocked"));
                     }
 
                     let response = content[*idx].clone();
@@ -87,5 +270,101 @@ mod tests {
                 }
             }
         }
+
+        async fn read_dir(&self, path: impl AsRef<Path> + Send) -> std::io::Result<Vec<String>> {
+            let path = path.as_ref();
+            let path = path.to_string_lossy();
+
+            self.dirs.get(path.as_ref()).cloned().ok_or_else(|| {
+                std::io::Error::new(
+                    ErrorKind::NotFound,
+                    format!("Directory not found: {}", path),
+                )
+            })
+        }
+
+        async fn read_link(&self, path: impl AsRef<Path> + Send) -> std::io::Result<PathBuf> {
+            let path = path.as_ref();
+            let path = path.to_string_lossy();
+
+            self.links.get(path.as_ref()).cloned().ok_or_else(|| {
+                std::io::Error::new(ErrorKind::NotFound, format!("Link not found: {}", path))
+            })
+        }
+
+        async fn statvfs(&self, path: impl AsRef<Path> + Send) -> std::io::Result<StatVfs> {
+            let path = path.as_ref();
+            let path = path.to_string_lossy();
+
+            self.statvfs.get(path.as_ref()).copied().ok_or_else(|| {
+                std::io::Error::new(
+                    ErrorKind::NotFound,
+                    format!("Filesystem not found: {}", path),
+                )
+            })
+        }
+    }
+
+    // (exit code, stdout)
+    type CommandResponse = (i32, String);
+
+    pub struct HardcodedCommandRunner {
+        data: HashMap<String, (Mutex<usize>, Vec<CommandResponse>)>,
+    }
+
+    impl HardcodedCommandRunner {
+        pub fn new() -> Self {
+            Self {
+                data: HashMap::new(),
+            }
+        }
+
+        pub fn add_response(
+            &mut self,
+            command: impl Into<String>,
+            exit_code: i32,
+            stdout: impl Into<String>,
+        ) {
+            let response = (exit_code, stdout.into());
+            match self.data.entry(command.into()) {
+                Entry::Occupied(mut e) => {
+                    e.get_mut().1.push(response);
+                }
+                Entry::Vacant(e) => {
+                    e.insert((Mutex::new(0), vec![response]));
+                }
+            }
+        }
+    }
+
+    impl CommandRunner for HardcodedCommandRunner {
+        async fn run(&self, program: &str, args: &[&str]) -> std::io::Result<Output> {
+            let command = std::iter::once(program)
+                .chain(args.iter().copied())
+                .collect::<Vec<_>>()
+                .join(" ");
+
+            match self.data.get(&command) {
+                None => Err(std::io::Error::new(
+                    ErrorKind::NotFound,
+                    format!("Command not found: {}", command),
+                )),
+                Some((idx, responses)) => {
+                    let mut idx = idx.lock().unwrap();
+                    if *idx >= responses.len() {
+                        return Err(std::io::Error::other("Response not mocked"));
+                    }
+
+                    let (exit_code, stdout) = responses[*idx].clone();
+                    *idx += 1;
+
+                    Ok(Output {
+                        status: ExitStatus::from_raw(exit_code << 8),
+                        stdout: stdout.into_bytes(),
+                        stderr: vec![],
+                    })
+                }
+            }
+        }
     }
 }
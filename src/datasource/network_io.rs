@@ -1,19 +1,53 @@
-use crate::datasource::Reader;
+use crate::datasource::{Reader, Roots};
 use crate::metrics::network_io::{DataSource, InterfaceStats, NetworkIoStats};
+use std::io::ErrorKind;
 use tokio::time::Instant;
 
-const PATH_NET_DEV: &str = "/proc/net/dev";
+const PATH_NET_DEV: &str = "net/dev";
+const PATH_SYS_NET: &str = "class/net";
 
 pub struct NetworkIo<R> {
     reader: R,
+    roots: Roots,
 }
 
 impl<R> NetworkIo<R>
 where
     R: Reader,
 {
-    pub fn new(reader: R) -> Self {
-        Self { reader }
+    pub fn new(reader: R, roots: Roots) -> Self {
+        Self { reader, roots }
+    }
+
+    /// Returns `None` if the interface has vanished since `/proc/net/dev` was read
+    async fn operstate(&self, interface: &str) -> Option<String> {
+        let path = format!("{}/{}/operstate", self.roots.sys(PATH_SYS_NET), interface);
+        self.read_entry(&path)
+            .await
+            .map(|content| content.trim().to_owned())
+    }
+
+    /// Returns `None` for the interfaces without the counter, such as some virtual devices
+    async fn carrier_changes(&self, interface: &str) -> Option<u64> {
+        let path = format!(
+            "{}/{}/carrier_changes",
+            self.roots.sys(PATH_SYS_NET),
+            interface
+        );
+
+        self.read_entry(&path).await?.trim().parse().ok()
+    }
+
+    /// A single unreadable entry only omits its value, instead of failing the whole collection
+    async fn read_entry(&self, path: &str) -> Option<String> {
+        match self.reader.read_to_string(path).await {
+            Ok(content) => Some(content),
+            Err(e) if e.kind() == ErrorKind::NotFound => None,
+            Err(e) => {
+                tracing::debug!(error = ?e, "Failed to read {}", path);
+                None
+            }
+        }
     }
 }
 
@@ -23,7 +57,10 @@ where
 {
     #[tracing::instrument(level = "debug", skip_all)]
     async fn network_io(&self) -> anyhow::Result<NetworkIoStats> {
-        let content = self.reader.read_to_string(PATH_NET_DEV).await?;
+        let content = self
+            .reader
+            .read_to_string(self.roots.proc(PATH_NET_DEV))
+            .await?;
         let timestamp = Instant::now();
         let mut interfaces = Vec::new();
 
@@ -49,8 +86,11 @@ where
             let bytes_sent = stats.next().and_then(|v| v.parse().ok()).unwrap_or(0);
             let packets_sent = stats.next().and_then(|v| v.parse().ok()).unwrap_or(0);
 
+            let iface = iface.trim();
             interfaces.push(InterfaceStats {
-                interface: iface.trim().to_string(),
+                operstate: self.operstate(iface).await,
+                carrier_changes: self.carrier_changes(iface).await,
+                interface: iface.to_string(),
                 bytes_sent,
                 bytes_received,
                 packets_sent,
@@ -61,13 +101,15 @@ where
         Ok(NetworkIoStats {
             timestamp,
             interfaces,
+            aggregate: None,
         })
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::datasource::network_io::{NetworkIo, PATH_NET_DEV};
+    use crate::datasource::Roots;
+    use crate::datasource::network_io::{NetworkIo, PATH_NET_DEV, PATH_SYS_NET};
     use crate::datasource::tests::HardcodedReader;
     use crate::metrics::network_io::DataSource;
 
@@ -81,9 +123,24 @@ wlp2s0:       0       0    0    0    0     0          0         0        0
     #[tokio::test]
     async fn test_network_io_datasource() {
         let mut reader = HardcodedReader::new();
-        reader.add_response(PATH_NET_DEV, NET_DEV_TEXT);
-
-        let ds = NetworkIo::new(reader);
+        reader.add_response(Roots::default().proc(PATH_NET_DEV), NET_DEV_TEXT);
+        reader.add_response(
+            format!("{}/lo/operstate", Roots::default().sys(PATH_SYS_NET)),
+            "unknown\n",
+        );
+        reader.add_response(
+            format!("{}/enp1s0/operstate", Roots::default().sys(PATH_SYS_NET)),
+            "up\n",
+        );
+        reader.add_response(
+            format!(
+                "{}/enp1s0/carrier_changes",
+                Roots::default().sys(PATH_SYS_NET)
+            ),
+            "7\n",
+        );
+
+        let ds = NetworkIo::new(reader, Roots::default());
         let nio = ds
             .network_io()
             .await
@@ -91,21 +148,45 @@ wlp2s0:       0       0    0    0    0     0          0         0        0
         assert_eq!(nio.interfaces.len(), 3);
 
         assert_eq!(nio.interfaces[0].interface, "lo");
+        assert_eq!(nio.interfaces[0].operstate.as_deref(), Some("unknown"));
         assert_eq!(nio.interfaces[0].bytes_received, 5467426526);
         assert_eq!(nio.interfaces[0].packets_received, 298140);
         assert_eq!(nio.interfaces[0].bytes_sent, 5467426526);
         assert_eq!(nio.interfaces[0].packets_sent, 298140);
 
         assert_eq!(nio.interfaces[1].interface, "enp1s0");
+        assert_eq!(nio.interfaces[1].operstate.as_deref(), Some("up"));
+        assert_eq!(nio.interfaces[1].carrier_changes, Some(7));
         assert_eq!(nio.interfaces[1].bytes_received, 23258276045);
         assert_eq!(nio.interfaces[1].packets_received, 17679116);
         assert_eq!(nio.interfaces[1].bytes_sent, 56878436846);
         assert_eq!(nio.interfaces[1].packets_sent, 2548501);
 
         assert_eq!(nio.interfaces[2].interface, "wlp2s0");
+        assert_eq!(nio.interfaces[2].operstate, None);
+        assert_eq!(nio.interfaces[2].carrier_changes, None);
         assert_eq!(nio.interfaces[2].bytes_received, 0);
         assert_eq!(nio.interfaces[2].packets_received, 0);
         assert_eq!(nio.interfaces[2].bytes_sent, 0);
         assert_eq!(nio.interfaces[2].packets_sent, 0);
     }
+
+    #[tokio::test]
+    async fn test_unreadable_entry_is_skipped() {
+        let mut reader = HardcodedReader::new();
+        reader.add_response(Roots::default().proc(PATH_NET_DEV), NET_DEV_TEXT);
+        reader.add_response(Roots::default().proc(PATH_NET_DEV), NET_DEV_TEXT);
+        reader.add_response(
+            format!("{}/enp1s0/operstate", Roots::default().sys(PATH_SYS_NET)),
+            "up\n",
+        );
+
+        // The second read of the operstate fails with an error other than NotFound
+        let ds = NetworkIo::new(reader, Roots::default());
+        ds.network_io().await.unwrap();
+        let nio = ds.network_io().await.unwrap();
+
+        assert_eq!(nio.interfaces.len(), 3);
+        assert_eq!(nio.interfaces[1].operstate, None);
+    }
 }
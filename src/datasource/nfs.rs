@@ -0,0 +1,240 @@
+use crate::datasource::{Reader, Roots};
+use crate::metrics::nfs::{DataSource, MountStats, NfsStats, OperationStats};
+use tokio::time::Instant;
+
+const PATH_MOUNT_STATS: &str = "self/mountstats";
+
+pub struct MountStatsNfs<R> {
+    reader: R,
+    roots: Roots,
+}
+
+impl<R> MountStatsNfs<R>
+where
+    R: Reader,
+{
+    pub fn new(reader: R, roots: Roots) -> Self {
+        Self { reader, roots }
+    }
+}
+
+impl<R> DataSource for MountStatsNfs<R>
+where
+    R: Reader,
+{
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn nfs_stats(&self) -> anyhow::Result<NfsStats> {
+        let content = self
+            .reader
+            .read_to_string(self.roots.proc(PATH_MOUNT_STATS))
+            .await?;
+
+        Ok(NfsStats {
+            timestamp: Instant::now(),
+            mounts: parse_mount_stats(&content),
+        })
+    }
+}
+
+/// Parses `/proc/self/mountstats`, which consists of one section per mount, starting with a
+/// `device <source> mounted on <mount> with fstype <type> ...` line. NFS mounts are followed
+/// by indented lines, among which is the `bytes:` line and the `per-op statistics` block.
+/// The same mount point may be listed several times when an export is mounted over another
+/// one, in which case only the last, i.e. the visible, mount is kept.
+fn parse_mount_stats(content: &str) -> Vec<MountStats> {
+    let mut mounts = vec![];
+    let mut current: Option<MountStats> = None;
+    let mut in_per_op = false;
+
+    let push = |mounts: &mut Vec<MountStats>, mount: Option<MountStats>| {
+        if let Some(mount) = mount {
+            mounts.retain(|m| m.mount != mount.mount);
+            mounts.push(mount);
+        }
+    };
+
+    for line in content.lines() {
+        if line.starts_with("device ") {
+            push(&mut mounts, current.take());
+            in_per_op = false;
+
+            current = parse_device_line(line).map(|mount| MountStats {
+                mount,
+                read_bytes: 0,
+                write_bytes: 0,
+                operations: vec![],
+            });
+
+            continue;
+        }
+
+        let Some(stats) = current.as_mut() else {
+            continue;
+        };
+
+        let line = line.trim();
+        if let Some(bytes) = line.strip_prefix("bytes:") {
+            // normal read, normal write, direct read, direct write, server read, server write, ...
+            let values = bytes
+                .split_whitespace()
+                .map(|v| v.parse::<u64>().unwrap_or(0))
+                .collect::<Vec<_>>();
+
+            if values.len() >= 4 {
+                stats.read_bytes = values[0] + values[2];
+                stats.write_bytes = values[1] + values[3];
+            }
+        } else if line == "per-op statistics" {
+            in_per_op = true;
+        } else if in_per_op && let Some(op) = parse_operation_line(line) {
+            stats.operations.push(op);
+        }
+    }
+
+    push(&mut mounts, current);
+    mounts
+}
+
+/// Returns the mount point if the line describes an NFS mount
+fn parse_device_line(line: &str) -> Option<String> {
+    let (_, rest) = line.split_once(" mounted on ")?;
+    let (mount, rest) = rest.rsplit_once(" with fstype ")?;
+
+    let fs_type = rest.split_whitespace().next()?;
+    if fs_type != "nfs" && fs_type != "nfs4" {
+        return None;
+    }
+
+    Some(mount.to_owned())
+}
+
+/// Parses lines such as `READ: 1024 1024 0 172032 67239936 12 3112 3198 0`, where the fields are
+/// operations, transmissions, major timeouts, bytes sent, bytes received, cumulative queue time,
+/// cumulative RTT and cumulative execution time. Newer kernels append an error count.
+fn parse_operation_line(line: &str) -> Option<OperationStats> {
+    let (operation, values) = line.split_once(':')?;
+    let values = values
+        .split_whitespace()
+        .map(|v| v.parse::<u64>().ok())
+        .collect::<Option<Vec<_>>>()?;
+
+    if values.len() < 8 {
+        return None;
+    }
+
+    Some(OperationStats {
+        operation: operation.to_owned(),
+        requests: values[0],
+        rtt: values[6],
+        execute: values[7],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datasource::tests::HardcodedReader;
+
+    const MOUNT_STATS: &str = r#"device sysfs mounted on /sys with fstype sysfs
+device proc mounted on /proc with fstype proc
+device /dev/nvme0n1p2 mounted on / with fstype ext4
+device nas:/export/media mounted on /mnt/media with fstype nfs4 statvers=1.1
+	opts:	rw,vers=4.2,rsize=1048576,wsize=1048576,namlen=255,acregmin=3,acregmax=60,acdirmin=30,acdirmax=60,hard,proto=tcp,timeo=600,retrans=2,sec=sys,clientaddr=192.168.1.10,local_lock=none
+	age:	86400
+	impl_id:	name='',domain='',date='0,0'
+	caps:	caps=0x3ffbffff,wtmult=512,dtsize=32768,bsize=0,namlen=255
+	nfsv4:	bm0=0xfdffbfff,bm1=0x40f9be3e,bm2=0x60803,acl=0x3,sessions,pnfs=not configured,lease_time=90,lease_expired=0
+	sec:	flavor=1,pseudoflavor=1
+	events:	2340 91234 12 340 1100 512 94003 2048 0 1024 2048 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0
+	bytes:	734003200 104857600 1048576 0 735051776 104857600 179456 25600
+	RPC iostats version: 1.1  p/v: 100003/4 (nfs)
+	xprt:	tcp 747 1 1 0 0 12345 12345 0 67890 0 32 1024 512
+	per-op statistics
+	        NULL: 1 1 0 44 24 0 0 0 0
+	        READ: 1024 1024 0 172032 735182848 12 3112 3198 0
+	       WRITE: 200 200 0 104896000 28800 3 410 420 0
+	     GETATTR: 5000 5000 0 900000 1200000 10 800 850 0
+device nas:/export/backup mounted on /mnt/backup with fstype nfs statvers=1.1
+	opts:	rw,vers=3,rsize=1048576,wsize=1048576,namlen=255,hard,proto=tcp,timeo=600,retrans=2,sec=sys
+	age:	3600
+	caps:	caps=0x3fc7,wtmult=512,dtsize=32768,bsize=0,namlen=255
+	sec:	flavor=1,pseudoflavor=1
+	events:	10 20 0 0 5 1 30 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0
+	bytes:	4096 8192 0 0 4096 8192 1 2
+	RPC iostats version: 1.1  p/v: 100003/3 (nfs)
+	xprt:	tcp 801 1 1 0 0 50 50 0 60 0 2 0 0
+	per-op statistics
+	        NULL: 1 1 0 40 24 0 0 0
+	     GETATTR: 10 10 0 1200 1120 0 15 16
+	        READ: 1 1 0 128 4200 0 2 2
+device tmpfs mounted on /run/user/1000 with fstype tmpfs
+"#;
+
+    #[test]
+    fn test_parse_mount_stats() {
+        let mounts = parse_mount_stats(MOUNT_STATS);
+        assert_eq!(mounts.len(), 2);
+
+        let media = &mounts[0];
+        assert_eq!(media.mount, "/mnt/media");
+        assert_eq!(media.read_bytes, 734003200 + 1048576);
+        assert_eq!(media.write_bytes, 104857600);
+        assert_eq!(media.operations.len(), 4);
+        assert_eq!(
+            media.operations[1],
+            OperationStats {
+                operation: "READ".to_owned(),
+                requests: 1024,
+                rtt: 3112,
+                execute: 3198,
+            }
+        );
+        assert_eq!(media.operations[2].operation, "WRITE");
+        assert_eq!(media.operations[3].operation, "GETATTR");
+
+        let backup = &mounts[1];
+        assert_eq!(backup.mount, "/mnt/backup");
+        assert_eq!(backup.read_bytes, 4096);
+        assert_eq!(backup.write_bytes, 8192);
+        assert_eq!(backup.operations.len(), 3);
+        assert_eq!(
+            backup.operations[1],
+            OperationStats {
+                operation: "GETATTR".to_owned(),
+                requests: 10,
+                rtt: 15,
+                execute: 16,
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_mount_stats_without_nfs_mounts() {
+        let content = "device proc mounted on /proc with fstype proc\n\
+                       device tmpfs mounted on /tmp with fstype tmpfs\n";
+
+        assert!(parse_mount_stats(content).is_empty());
+    }
+
+    #[test]
+    fn test_parse_stacked_mount_stats() {
+        let content = "device nas:/export/media mounted on /mnt/media with fstype nfs4\n\
+                       \tbytes:\t100 0 0 0 0 0 0 0\n\
+                       device nas:/export/media mounted on /mnt/media with fstype nfs4\n\
+                       \tbytes:\t200 0 0 0 0 0 0 0\n";
+
+        let mounts = parse_mount_stats(content);
+        assert_eq!(mounts.len(), 1);
+        assert_eq!(mounts[0].read_bytes, 200);
+    }
+
+    #[tokio::test]
+    async fn test_nfs_datasource() {
+        let mut reader = HardcodedReader::new();
+        reader.add_response(Roots::default().proc(PATH_MOUNT_STATS), MOUNT_STATS);
+
+        let ds = MountStatsNfs::new(reader, Roots::default());
+        let stats = ds.nfs_stats().await.unwrap();
+        assert_eq!(stats.mounts.len(), 2);
+    }
+}
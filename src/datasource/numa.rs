@@ -0,0 +1,170 @@
+use crate::datasource::{Reader, Roots};
+use crate::metrics::numa::{DataSource, NodeMemoryStats, NumaStats};
+use std::io::ErrorKind;
+use tokio::time::Instant;
+
+const PATH_NODES: &str = "devices/system/node";
+
+pub struct SysfsNuma<R> {
+    reader: R,
+    roots: Roots,
+}
+
+impl<R> SysfsNuma<R>
+where
+    R: Reader,
+{
+    pub fn new(reader: R, roots: Roots) -> Self {
+        Self { reader, roots }
+    }
+}
+
+impl<R> DataSource for SysfsNuma<R>
+where
+    R: Reader,
+{
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn numa_stats(&self) -> anyhow::Result<NumaStats> {
+        let mut entries = match self.reader.read_dir(self.roots.sys(PATH_NODES)).await {
+            Ok(entries) => entries,
+            // Kernels without NUMA support do not have the node directory
+            Err(e) if e.kind() == ErrorKind::NotFound => vec![],
+            Err(e) => return Err(e.into()),
+        };
+
+        entries.retain(|e| {
+            e.strip_prefix("node")
+                .is_some_and(|id| !id.is_empty() && id.bytes().all(|b| b.is_ascii_digit()))
+        });
+        entries.sort_by_key(|e| e[4..].parse::<u32>().unwrap_or(u32::MAX));
+
+        let mut nodes = vec![];
+        for entry in entries {
+            let path = format!("{}/{}/meminfo", self.roots.sys(PATH_NODES), entry);
+            let content = self.reader.read_to_string(&path).await?;
+
+            nodes.push(parse_node_meminfo(&entry[4..], &content));
+        }
+
+        Ok(NumaStats {
+            timestamp: Instant::now(),
+            nodes,
+        })
+    }
+}
+
+/// Parses the per-node meminfo, whose lines are prefixed with the node id,
+/// such as `Node 0 MemTotal:       32766528 kB`
+fn parse_node_meminfo(node: &str, content: &str) -> NodeMemoryStats {
+    let mut stats = NodeMemoryStats {
+        node: node.to_owned(),
+        ..Default::default()
+    };
+
+    for line in content.lines() {
+        let mut parts = line.split_whitespace();
+        let (Some("Node"), Some(_), Some(key), Some(value)) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            continue;
+        };
+
+        let Ok(value) = value.parse::<u64>() else {
+            tracing::debug!("Skipping invalid NUMA node meminfo line: {}", line);
+            continue;
+        };
+
+        let value = match parts.next() {
+            Some("kB") => value * 1024,
+            _ => value,
+        };
+
+        match key {
+            "MemTotal:" => stats.total = value,
+            "MemFree:" => stats.free = value,
+            "MemUsed:" => stats.used = value,
+            _ => {}
+        }
+    }
+
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datasource::tests::HardcodedReader;
+
+    const NODE0_MEMINFO: &str = r#"Node 0 MemTotal:       32766528 kB
+Node 0 MemFree:         1048576 kB
+Node 0 MemUsed:        31717952 kB
+Node 0 SwapCached:            0 kB
+Node 0 Active:         20971520 kB
+Node 0 Inactive:        8388608 kB
+Node 0 FilePages:       9437184 kB
+Node 0 AnonPages:      19922944 kB
+Node 0 HugePages_Total:     0
+Node 0 HugePages_Free:      0
+Node 0 HugePages_Surp:      0
+"#;
+
+    const NODE1_MEMINFO: &str = r#"Node 1 MemTotal:       33030144 kB
+Node 1 MemFree:        24117248 kB
+Node 1 MemUsed:         8912896 kB
+Node 1 SwapCached:            0 kB
+Node 1 Active:          4194304 kB
+Node 1 Inactive:        3145728 kB
+Node 1 HugePages_Total:     0
+Node 1 HugePages_Free:      0
+Node 1 HugePages_Surp:      0
+"#;
+
+    #[test]
+    fn test_parse_node_meminfo() {
+        assert_eq!(
+            parse_node_meminfo("0", NODE0_MEMINFO),
+            NodeMemoryStats {
+                node: "0".to_owned(),
+                total: 32766528 * 1024,
+                free: 1048576 * 1024,
+                used: 31717952 * 1024,
+            }
+        );
+
+        assert_eq!(
+            parse_node_meminfo("1", NODE1_MEMINFO),
+            NodeMemoryStats {
+                node: "1".to_owned(),
+                total: 33030144 * 1024,
+                free: 24117248 * 1024,
+                used: 8912896 * 1024,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_numa_datasource() {
+        let mut reader = HardcodedReader::new();
+        reader.add_dir(
+            Roots::default().sys(PATH_NODES),
+            &["has_cpu", "node1", "online", "node0", "possible", "power"],
+        );
+        reader.add_response(
+            format!("{}/node0/meminfo", Roots::default().sys(PATH_NODES)),
+            NODE0_MEMINFO,
+        );
+        reader.add_response(
+            format!("{}/node1/meminfo", Roots::default().sys(PATH_NODES)),
+            NODE1_MEMINFO,
+        );
+
+        let ds = SysfsNuma::new(reader, Roots::default());
+        let stats = ds.numa_stats().await.unwrap();
+
+        assert_eq!(stats.nodes.len(), 2);
+        assert_eq!(stats.nodes[0].node, "0");
+        assert_eq!(stats.nodes[0].free, 1048576 * 1024);
+        assert_eq!(stats.nodes[1].node, "1");
+        assert_eq!(stats.nodes[1].used, 8912896 * 1024);
+    }
+}
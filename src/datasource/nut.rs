@@ -1,17 +1,37 @@
-use crate::metrics::ups::{DataSource, UpsDeviceStats, UpsStats};
+use crate::domain::Probe;
+use crate::metrics::ups::{DataSource, UpsDeviceStats, UpsStats, UpsStatusFlags};
 use anyhow::Context;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::net::SocketAddr;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
 use tokio::net::TcpStream;
-use tokio::net::tcp::{ReadHalf, WriteHalf};
+use tokio::sync::Mutex;
 use tokio::time::Instant;
+use tokio_rustls::TlsConnector;
+use tokio_rustls::client::TlsStream;
+use tokio_rustls::rustls::client::danger::{
+    HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier,
+};
+use tokio_rustls::rustls::crypto::{CryptoProvider, ring};
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use tokio_rustls::rustls::{ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     pub address: String,
     pub port: u16,
+    /// Upgrade the connection to TLS via `STARTTLS` before querying the server
+    pub tls: bool,
+    /// Accept any server certificate, i.e. self-signed ones
+    pub tls_skip_verify: bool,
+    /// The name the server certificate is verified against. Defaults to the address
+    pub tls_server_name: Option<String>,
+    /// A PEM file with the CA certificates the server certificate is verified against,
+    /// e.g. a private CA on the LAN. Defaults to the public web PKI roots
+    pub tls_ca_file: Option<String>,
 }
 
 impl Default for Config {
@@ -19,12 +39,109 @@ impl Default for Config {
         Self {
             address: "127.0.0.1".to_owned(),
             port: 3493,
+            tls: false,
+            tls_skip_verify: false,
+            tls_server_name: None,
+            tls_ca_file: None,
+        }
+    }
+}
+
+/// Sends a command to the NUT server and returns the lines of its response.
+/// The `BEGIN LIST`/`END LIST` lines delimiting a list are not included.
+trait Transport: Send {
+    fn command(&mut self, line: &str) -> impl Future<Output = anyhow::Result<Vec<String>>> + Send;
+}
+
+struct StreamTransport<S> {
+    stream: BufReader<S>,
+}
+
+impl<S> StreamTransport<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send,
+{
+    fn new(stream: S) -> Self {
+        Self {
+            stream: BufReader::new(stream),
+        }
+    }
+
+    async fn read_line(&mut self) -> anyhow::Result<Option<String>> {
+        let mut line = String::new();
+        match self.stream.read_line(&mut line).await? {
+            0 => Ok(None),
+            _ => Ok(Some(line.trim_end_matches(['\r', '\n']).to_owned())),
+        }
+    }
+}
+
+impl<S> Transport for StreamTransport<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send,
+{
+    async fn command(&mut self, line: &str) -> anyhow::Result<Vec<String>> {
+        self.stream
+            .write_all(format!("{}\n", line).as_bytes())
+            .await
+            .with_context(|| format!("Failed to send the {} command", line))?;
+
+        let Some(first) = self.read_line().await? else {
+            return Err(anyhow::anyhow!("The NUT server closed the connection"));
+        };
+
+        // Only the LIST commands have multi-line responses
+        if !first.starts_with("BEGIN LIST") {
+            return Ok(vec![first]);
+        }
+
+        let mut lines = vec![];
+        while let Some(line) = self.read_line().await? {
+            if line.starts_with("END LIST") {
+                return Ok(lines);
+            }
+
+            lines.push(line);
+        }
+
+        Err(anyhow::anyhow!(
+            "The NUT server closed the connection before the end of the list"
+        ))
+    }
+}
+
+/// Fails if the server has replied with an `ERR <code>` line, e.g. `ERR ACCESS-DENIED`
+fn check_error(lines: &[String]) -> anyhow::Result<()> {
+    match lines.iter().find_map(|line| line.strip_prefix("ERR ")) {
+        Some(code) => Err(anyhow::anyhow!("The NUT server returned [{}]", code.trim())),
+        None => Ok(()),
+    }
+}
+
+struct Tls {
+    connector: TlsConnector,
+    server_name: ServerName<'static>,
+}
+
+/// A connection kept open across scrapes, either in plaintext or upgraded to TLS
+enum Connection {
+    Plain(StreamTransport<TcpStream>),
+    Tls(Box<StreamTransport<TlsStream<TcpStream>>>),
+}
+
+impl Transport for Connection {
+    async fn command(&mut self, line: &str) -> anyhow::Result<Vec<String>> {
+        match self {
+            Connection::Plain(transport) => transport.command(line).await,
+            Connection::Tls(transport) => transport.command(line).await,
         }
     }
 }
 
 pub struct Nut {
     addr: SocketAddr,
+    tls: Option<Tls>,
+    connection: Mutex<Option<Connection>>,
 }
 
 impl Nut {
@@ -35,37 +152,40 @@ impl Nut {
             .parse()
             .with_context(|| format!("Invalid socket address: [{}]", addr))?;
 
-        Ok(Self { addr })
+        let tls = match config.tls {
+            true => Some(create_tls(&config)?),
+            false => None,
+        };
+
+        Ok(Self {
+            addr,
+            tls,
+            connection: Mutex::new(None),
+        })
     }
 
     #[tracing::instrument(level = "trace", skip_all)]
     async fn list_ups_devices(
         &self,
-        reader: &mut BufReader<ReadHalf<'_>>,
-        writer: &mut WriteHalf<'_>,
+        transport: &mut impl Transport,
     ) -> anyhow::Result<Vec<String>> {
-        writer
-            .write_all(b"LIST UPS\n")
+        let lines = transport
+            .command("LIST UPS")
             .await
-            .context("Failed to send LIST UPS command")?;
-
-        let mut names = Vec::new();
-        let mut line = String::new();
-
-        while reader.read_line(&mut line).await? > 0 {
-            let trimmed = line.trim();
-            if trimmed == "END LIST UPS" {
-                break;
-            }
-
-            // Format: UPS <name> "Description"
-            let parts: Vec<&str> = trimmed.split_whitespace().collect();
-            if parts.len() >= 2 && parts[0] == "UPS" {
-                names.push(parts[1].to_owned());
-            }
+            .context("Failed to list the UPS devices")?;
+        check_error(&lines).context("Failed to list the UPS devices")?;
 
-            line.clear();
-        }
+        // Format: UPS <name> "Description"
+        let names = lines
+            .iter()
+            .filter_map(|line| {
+                let mut parts = line.split_whitespace();
+                match (parts.next(), parts.next()) {
+                    (Some("UPS"), Some(name)) => Some(name.to_owned()),
+                    _ => None,
+                }
+            })
+            .collect::<Vec<_>>();
 
         tracing::debug!(?names, "Discovered UPS devices");
         Ok(names)
@@ -74,40 +194,67 @@ impl Nut {
     #[tracing::instrument(level = "trace", skip_all)]
     async fn list_device_parameters(
         &self,
-        reader: &mut BufReader<ReadHalf<'_>>,
-        writer: &mut WriteHalf<'_>,
+        transport: &mut impl Transport,
         ups_name: &str,
     ) -> anyhow::Result<HashMap<String, String>> {
-        let cmd = format!("LIST VAR {}\n", ups_name);
-        writer
-            .write_all(cmd.as_bytes())
+        let lines = transport
+            .command(&format!("LIST VAR {}", ups_name))
             .await
-            .context("Failed to sent LIST VAR command")?;
+            .with_context(|| format!("Failed to list the variables of [{}]", ups_name))?;
+        check_error(&lines)
+            .with_context(|| format!("Failed to list the variables of [{}]", ups_name))?;
 
         let mut params = HashMap::new();
-        let mut line = String::new();
-
-        while reader.read_line(&mut line).await? > 0 {
-            let trimmed = line.trim();
-            if trimmed.starts_with("END LIST VAR") {
-                break;
-            }
-
+        for line in lines {
             // Format: VAR <upsname> <parameter.name> "<value>"
-            let parts: Vec<&str> = trimmed.splitn(4, ' ').collect();
-            if parts.len() >= 4 {
+            let parts: Vec<&str> = line.splitn(4, ' ').collect();
+            if parts.len() >= 4 && parts[0] == "VAR" {
                 let key = parts[2].to_string();
                 let value = parts[3].trim_matches('"').to_string();
                 params.insert(key, value);
             }
-
-            line.clear();
         }
 
         tracing::debug!(?params, ?ups_name, "Discovered UPS parameters");
         Ok(params)
     }
 
+    async fn connect(&self) -> anyhow::Result<Connection> {
+        let stream = TcpStream::connect(&self.addr)
+            .await
+            .with_context(|| format!("Failed to connect to NUT server at [{}]", &self.addr))?;
+
+        let connection = match &self.tls {
+            None => Connection::Plain(StreamTransport::new(stream)),
+            Some(tls) => {
+                let stream = start_tls(stream).await?;
+                let stream = tls
+                    .connector
+                    .connect(tls.server_name.clone(), stream)
+                    .await
+                    .with_context(|| format!("TLS handshake with [{}] failed", &self.addr))?;
+
+                Connection::Tls(Box::new(StreamTransport::new(stream)))
+            }
+        };
+
+        Ok(connection)
+    }
+
+    async fn query(&self, transport: &mut impl Transport) -> anyhow::Result<Vec<UpsDeviceStats>> {
+        let mut devices = vec![];
+        let ups_devices = self.list_ups_devices(transport).await?;
+
+        for device in ups_devices {
+            let parameters = self.list_device_parameters(transport, &device).await?;
+
+            let device_stats = self.collect_device_parameters(device, parameters);
+            devices.push(device_stats);
+        }
+
+        Ok(devices)
+    }
+
     #[tracing::instrument(level = "trace", skip_all)]
     fn collect_device_parameters(
         &self,
@@ -120,14 +267,26 @@ impl Nut {
                 .find_map(|&key| params.get(key).and_then(|v| v.parse::<f64>().ok()))
         };
 
+        let status = params.get("ups.status").map(|s| s.trim().to_owned());
+        let flags = status.as_deref().map(UpsStatusFlags::parse);
         let estimated_runtime = find(&["battery.runtime", "battery.runtime.low"]);
 
         let battery_level =
             find(&["battery.charge", "battery.level", "battery.charge.approx"]).map(as_percents);
 
+        // Modular UPS units may consist of several battery packs
+        let battery_packs = find(&["battery.packs"]);
+        let battery_packs_bad = find(&["battery.packs.bad"]);
+
+        let battery_voltage = find(&["battery.voltage"]);
+        let battery_temperature = find(&["battery.temperature"]);
+
         let load = find(&["ups.load", "output.load"]).map(as_percents);
         let input_voltage = find(&["input.voltage"]);
         let output_voltage = find(&["output.voltage"]);
+        let input_frequency = find(&["input.frequency"]);
+        let output_frequency = find(&["output.frequency"]);
+        let output_current = find(&["output.current"]);
 
         let nominal_apparent_power = find(&["ups.power.nominal", "output.power.nominal"]);
         let nominal_real_power = find(&["ups.realpower.nominal", "output.realpower.nominal"]);
@@ -147,10 +306,19 @@ impl Nut {
 
         UpsDeviceStats {
             device_name,
+            status,
+            flags,
             estimated_runtime,
             battery_level,
+            battery_packs,
+            battery_packs_bad,
+            battery_voltage,
+            battery_temperature,
             input_voltage,
             output_voltage,
+            input_frequency,
+            output_frequency,
+            output_current,
             load,
             real_power,
             apparent_power,
@@ -160,27 +328,52 @@ impl Nut {
     }
 }
 
+impl Probe for Nut {
+    async fn supported(&self) -> bool {
+        // The connection is kept for the first scrape
+        match self.connect().await {
+            Ok(connection) => {
+                *self.connection.lock().await = Some(connection);
+                true
+            }
+            Err(error) => {
+                tracing::debug!(?error, "The NUT server is not reachable");
+                false
+            }
+        }
+    }
+}
+
 impl DataSource for Nut {
     #[tracing::instrument(level = "debug", skip_all)]
     async fn ups_stats(&self) -> anyhow::Result<UpsStats> {
-        let mut stream = TcpStream::connect(&self.addr)
-            .await
-            .with_context(|| format!("Failed to connect to NUT server at [{}]", &self.addr))?;
-
-        let (reader, mut writer) = stream.split();
-        let mut reader = BufReader::new(reader);
+        let mut connection = self.connection.lock().await;
 
-        let mut devices = vec![];
-        let ups_devices = self.list_ups_devices(&mut reader, &mut writer).await?;
-
-        for device in ups_devices {
-            let parameters = self
-                .list_device_parameters(&mut reader, &mut writer, &device)
-                .await?;
+        // The connection is taken out while in use, so that a cancelled query, e.g. on a
+        // collection timeout, does not leave its unread reply behind for the next command.
+        // It is put back only once the exchange has completed.
+        let reused = match connection.take() {
+            Some(mut reused) => match self.query(&mut reused).await {
+                Ok(devices) => Some((reused, devices)),
+                // The server may have dropped the connection while it was idle,
+                // so a failed query over a reused connection is retried once
+                Err(error) => {
+                    tracing::debug!(?error, "Reconnecting to the NUT server");
+                    None
+                }
+            },
+            None => None,
+        };
 
-            let device_stats = self.collect_device_parameters(device, parameters);
-            devices.push(device_stats);
-        }
+        let (used, devices) = match reused {
+            Some(reused) => reused,
+            None => {
+                let mut fresh = self.connect().await?;
+                let devices = self.query(&mut fresh).await?;
+                (fresh, devices)
+            }
+        };
+        *connection = Some(used);
 
         Ok(UpsStats {
             timestamp: Instant::now(),
@@ -188,3 +381,651 @@ impl DataSource for Nut {
         })
     }
 }
+
+impl Drop for Nut {
+    fn drop(&mut self) {
+        let Some(mut connection) = self.connection.get_mut().take() else {
+            return;
+        };
+
+        // Let the server know that the connection is going away, e.g. on a configuration reload
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            runtime.spawn(async move {
+                if let Err(error) = connection.command("LOGOUT").await {
+                    tracing::debug!(?error, "Failed to log out from the NUT server");
+                }
+            });
+        }
+    }
+}
+
+fn create_tls(config: &Config) -> anyhow::Result<Tls> {
+    let server_name = config.tls_server_name.as_ref().unwrap_or(&config.address);
+    let server_name = ServerName::try_from(server_name.clone())
+        .with_context(|| format!("Invalid TLS server name: [{}]", server_name))?;
+
+    let provider = Arc::new(ring::default_provider());
+    let builder = ClientConfig::builder_with_provider(Arc::clone(&provider))
+        .with_safe_default_protocol_versions()?;
+
+    let config = match config.tls_skip_verify {
+        true => builder
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(NoVerification(provider))),
+        false => builder.with_root_certificates(root_certificates(config.tls_ca_file.as_deref())?),
+    };
+
+    Ok(Tls {
+        connector: TlsConnector::from(Arc::new(config.with_no_client_auth())),
+        server_name,
+    })
+}
+
+fn root_certificates(ca_file: Option<&str>) -> anyhow::Result<RootCertStore> {
+    let Some(path) = ca_file else {
+        return Ok(RootCertStore {
+            roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+        });
+    };
+
+    let mut roots = RootCertStore::empty();
+    for certificate in CertificateDer::pem_file_iter(path)
+        .with_context(|| format!("Failed to read the TLS CA file [{}]", path))?
+    {
+        let certificate = certificate.with_context(|| format!("Invalid TLS CA file [{}]", path))?;
+        roots
+            .add(certificate)
+            .with_context(|| format!("Invalid CA certificate in [{}]", path))?;
+    }
+
+    if roots.is_empty() {
+        return Err(anyhow::anyhow!(
+            "The TLS CA file [{}] contains no certificates",
+            path
+        ));
+    }
+
+    Ok(roots)
+}
+
+/// Sends the `STARTTLS` command over the plaintext connection. Once the server
+/// has acknowledged it, the returned stream is ready for the TLS handshake.
+async fn start_tls<S>(stream: S) -> anyhow::Result<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut stream = BufReader::new(stream);
+    stream
+        .write_all(b"STARTTLS\n")
+        .await
+        .context("Failed to send STARTTLS command")?;
+
+    let mut line = String::new();
+    stream.read_line(&mut line).await?;
+
+    let response = line.trim();
+    if response != "OK STARTTLS" {
+        return Err(anyhow::anyhow!(
+            "The NUT server refused to start TLS: [{}]",
+            response
+        ));
+    }
+
+    // Anything buffered past the response would be lost when unwrapping the stream
+    if !stream.buffer().is_empty() {
+        return Err(anyhow::anyhow!(
+            "Unexpected data received from the NUT server after STARTTLS"
+        ));
+    }
+
+    Ok(stream.into_inner())
+}
+
+/// Accepts any server certificate, while still verifying the handshake signatures
+#[derive(Debug)]
+struct NoVerification(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for NoVerification {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, tokio_rustls::rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, tokio_rustls::rustls::Error> {
+        tokio_rustls::rustls::crypto::verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, tokio_rustls::rustls::Error> {
+        tokio_rustls::rustls::crypto::verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+    use tokio::time::Duration;
+
+    /// Replies to each command with a canned response, as if the server sent it
+    struct ScriptedTransport {
+        responses: HashMap<String, Vec<String>>,
+    }
+
+    impl ScriptedTransport {
+        fn new(script: &[(&str, &[&str])]) -> Self {
+            let responses = script
+                .iter()
+                .map(|(command, lines)| {
+                    let lines = lines.iter().map(|l| l.to_string()).collect();
+                    (command.to_string(), lines)
+                })
+                .collect();
+
+            Self { responses }
+        }
+    }
+
+    impl Transport for ScriptedTransport {
+        async fn command(&mut self, line: &str) -> anyhow::Result<Vec<String>> {
+            self.responses
+                .get(line)
+                .cloned()
+                .ok_or_else(|| anyhow::anyhow!("Unexpected command: {}", line))
+        }
+    }
+
+    fn params(kv: &[(&str, &str)]) -> HashMap<String, String> {
+        kv.iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_collect_battery_packs() {
+        let nut = Nut::new(Config::default()).unwrap();
+        let params = params(&[
+            ("battery.charge", "100"),
+            ("battery.packs", "4"),
+            ("battery.packs.bad", "1"),
+            ("device.mfr", "EATON"),
+            ("device.model", "9PX 6000i"),
+            ("device.type", "ups"),
+            ("ups.load", "25"),
+            ("ups.status", "OL CHRG"),
+        ]);
+
+        let stats = nut.collect_device_parameters("eaton".to_owned(), params);
+        assert_eq!(stats.device_name, "eaton");
+        assert_eq!(stats.status.as_deref(), Some("OL CHRG"));
+        assert!(
+            stats
+                .flags
+                .is_some_and(|f| f.online && f.charging && !f.on_battery)
+        );
+        assert_eq!(stats.battery_level, Some(1.0));
+        assert_eq!(stats.battery_packs, Some(4.0));
+        assert_eq!(stats.battery_packs_bad, Some(1.0));
+        assert_eq!(stats.load, Some(0.25));
+    }
+
+    #[test]
+    fn test_collect_without_battery_packs() {
+        let nut = Nut::new(Config::default()).unwrap();
+        let params = params(&[("battery.charge", "80"), ("battery.runtime", "1200")]);
+
+        let stats = nut.collect_device_parameters("ups".to_owned(), params);
+        assert_eq!(stats.battery_level, Some(0.8));
+        assert_eq!(stats.estimated_runtime, Some(1200.0));
+        assert_eq!(stats.battery_packs, None);
+        assert_eq!(stats.battery_packs_bad, None);
+    }
+
+    /// Plays the server side of a conversation, replying to each expected request
+    async fn serve<S>(mut server: S, conversation: &[(&str, &str)])
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        for (request, response) in conversation {
+            let mut buffer = vec![0; request.len()];
+            server.read_exact(&mut buffer).await.unwrap();
+            assert_eq!(String::from_utf8(buffer).unwrap(), *request);
+
+            server.write_all(response.as_bytes()).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_start_tls() {
+        let (client, server) = tokio::io::duplex(1024);
+        let server = tokio::spawn(async move {
+            serve(server, &[("STARTTLS\n", "OK STARTTLS\n")]).await;
+        });
+
+        assert!(start_tls(client).await.is_ok());
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_start_tls_not_configured() {
+        let (client, server) = tokio::io::duplex(1024);
+        let server = tokio::spawn(async move {
+            serve(server, &[("STARTTLS\n", "ERR FEATURE-NOT-CONFIGURED\n")]).await;
+        });
+
+        let error = start_tls(client).await.unwrap_err();
+        assert!(error.to_string().contains("FEATURE-NOT-CONFIGURED"));
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_query() {
+        let (client, server) = tokio::io::duplex(1024);
+        let server = tokio::spawn(async move {
+            serve(
+                server,
+                &[
+                    (
+                        "LIST UPS\n",
+                        "BEGIN LIST UPS\nUPS eaton \"Eaton 9PX\"\nEND LIST UPS\n",
+                    ),
+                    (
+                        "LIST VAR eaton\n",
+                        "BEGIN LIST VAR eaton\nVAR eaton battery.charge \"90\"\nEND LIST VAR eaton\n",
+                    ),
+                ],
+            )
+            .await;
+        });
+
+        let nut = Nut::new(Config::default()).unwrap();
+        let devices = nut.query(&mut StreamTransport::new(client)).await.unwrap();
+        assert_eq!(devices.len(), 1);
+        assert_eq!(devices[0].device_name, "eaton");
+        assert_eq!(devices[0].battery_level, Some(0.9));
+        server.await.unwrap();
+    }
+
+    const QUERY: &[(&str, &str)] = &[
+        (
+            "LIST UPS\n",
+            "BEGIN LIST UPS\nUPS eaton \"Eaton 9PX\"\nEND LIST UPS\n",
+        ),
+        (
+            "LIST VAR eaton\n",
+            "BEGIN LIST VAR eaton\nVAR eaton battery.charge \"90\"\nEND LIST VAR eaton\n",
+        ),
+    ];
+
+    #[tokio::test]
+    async fn test_persistent_connection() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let server = tokio::spawn(async move {
+            // Two scrapes over the same connection, then an idle disconnect
+            let (stream, _) = listener.accept().await.unwrap();
+            serve(stream, &[QUERY, QUERY].concat()).await;
+
+            // The next scrape has to reconnect
+            let (stream, _) = listener.accept().await.unwrap();
+            serve(stream, QUERY).await;
+        });
+
+        let nut = Nut::new(Config {
+            port,
+            ..Default::default()
+        })
+        .unwrap();
+
+        for _ in 0..3 {
+            let stats = nut.ups_stats().await.unwrap();
+            assert_eq!(stats.devices.len(), 1);
+            assert_eq!(stats.devices[0].battery_level, Some(0.9));
+        }
+
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_supported_keeps_the_connection() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let server = tokio::spawn(async move {
+            // The probe connection is the one used by the first scrape
+            let (stream, _) = listener.accept().await.unwrap();
+            serve(stream, QUERY).await;
+        });
+
+        let nut = Nut::new(Config {
+            port,
+            ..Default::default()
+        })
+        .unwrap();
+
+        assert!(nut.supported().await);
+        assert_eq!(nut.ups_stats().await.unwrap().devices.len(), 1);
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_without_server() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        drop(listener);
+
+        let nut = Nut::new(Config {
+            port,
+            ..Default::default()
+        })
+        .unwrap();
+
+        assert!(!nut.supported().await);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_query_drops_the_connection() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (cancelled, on_cancelled) = tokio::sync::oneshot::channel::<()>();
+
+        let server = tokio::spawn(async move {
+            // The second query over the connection is answered only after the
+            // client has given up on it
+            let (mut stream, _) = listener.accept().await.unwrap();
+            serve(&mut stream, QUERY).await;
+            let mut buffer = vec![0; "LIST UPS\n".len()];
+            stream.read_exact(&mut buffer).await.unwrap();
+            on_cancelled.await.unwrap();
+            let _ = stream.write_all(QUERY[0].1.as_bytes()).await;
+
+            // The stale reply must not be read as the response to the next query
+            let (stream, _) = listener.accept().await.unwrap();
+            serve(stream, QUERY).await;
+        });
+
+        let nut = Nut::new(Config {
+            port,
+            ..Default::default()
+        })
+        .unwrap();
+
+        assert!(nut.ups_stats().await.is_ok());
+
+        let timeout = Duration::from_millis(50);
+        assert!(
+            tokio::time::timeout(timeout, nut.ups_stats())
+                .await
+                .is_err()
+        );
+        cancelled.send(()).unwrap();
+
+        let stats = nut.ups_stats().await.unwrap();
+        assert_eq!(stats.devices[0].battery_level, Some(0.9));
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_list_ups_devices() {
+        let mut transport = ScriptedTransport::new(&[(
+            "LIST UPS",
+            &[
+                r#"UPS eaton "Eaton 9PX 6000i""#,
+                r#"UPS apc "APC Back-UPS""#,
+            ],
+        )]);
+
+        let nut = Nut::new(Config::default()).unwrap();
+        let names = nut.list_ups_devices(&mut transport).await.unwrap();
+        assert_eq!(names, vec!["eaton", "apc"]);
+    }
+
+    #[tokio::test]
+    async fn test_list_device_parameters() {
+        let mut transport = ScriptedTransport::new(&[(
+            "LIST VAR apc",
+            &[
+                r#"VAR apc battery.charge "100""#,
+                r#"VAR apc device.model "Back-UPS XS 700U""#,
+                r#"VAR apc ups.status "OL""#,
+            ],
+        )]);
+
+        let nut = Nut::new(Config::default()).unwrap();
+        let params = nut
+            .list_device_parameters(&mut transport, "apc")
+            .await
+            .unwrap();
+
+        assert_eq!(params.len(), 3);
+        assert_eq!(params["battery.charge"], "100");
+        assert_eq!(params["device.model"], "Back-UPS XS 700U");
+    }
+
+    #[tokio::test]
+    async fn test_query_scripted() {
+        let mut transport = ScriptedTransport::new(&[
+            ("LIST UPS", &[r#"UPS apc "APC""#]),
+            (
+                "LIST VAR apc",
+                &[
+                    r#"VAR apc battery.charge "42""#,
+                    r#"VAR apc ups.load "50""#,
+                    r#"VAR apc ups.realpower.nominal "400""#,
+                    r#"VAR apc ups.power.nominal "700""#,
+                    r#"VAR apc ups.power "320""#,
+                ],
+            ),
+        ]);
+
+        let nut = Nut::new(Config::default()).unwrap();
+        let devices = nut.query(&mut transport).await.unwrap();
+        assert_eq!(devices.len(), 1);
+        assert_eq!(devices[0].battery_level, Some(0.42));
+        assert_eq!(devices[0].load, Some(0.5));
+        // Reported directly by the UPS
+        assert_eq!(devices[0].apparent_power, Some(320.0));
+        // Estimated from the nominal power and the load
+        assert_eq!(devices[0].real_power, Some(200.0));
+    }
+
+    #[tokio::test]
+    async fn test_query_line_quality() {
+        let mut transport = ScriptedTransport::new(&[
+            ("LIST UPS", &[r#"UPS apc "APC Smart-UPS 1500""#]),
+            (
+                "LIST VAR apc",
+                &[
+                    r#"VAR apc battery.charge "100""#,
+                    r#"VAR apc battery.runtime "2820""#,
+                    r#"VAR apc battery.temperature "29.7""#,
+                    r#"VAR apc battery.voltage "27.3""#,
+                    r#"VAR apc battery.voltage.nominal "24.0""#,
+                    r#"VAR apc device.mfr "American Power Conversion""#,
+                    r#"VAR apc device.model "Smart-UPS 1500""#,
+                    r#"VAR apc input.frequency "49.9""#,
+                    r#"VAR apc input.voltage "232.4""#,
+                    r#"VAR apc output.current "1.20""#,
+                    r#"VAR apc output.frequency "50.0""#,
+                    r#"VAR apc output.voltage "230.1""#,
+                    r#"VAR apc ups.load "18.2""#,
+                    r#"VAR apc ups.status "OL""#,
+                ],
+            ),
+        ]);
+
+        let nut = Nut::new(Config::default()).unwrap();
+        let devices = nut.query(&mut transport).await.unwrap();
+        assert_eq!(devices.len(), 1);
+
+        let apc = &devices[0];
+        assert_eq!(apc.battery_voltage, Some(27.3));
+        assert_eq!(apc.battery_temperature, Some(29.7));
+        assert_eq!(apc.input_frequency, Some(49.9));
+        assert_eq!(apc.output_frequency, Some(50.0));
+        assert_eq!(apc.output_current, Some(1.2));
+        assert_eq!(apc.input_voltage, Some(232.4));
+    }
+
+    #[test]
+    fn test_collect_line_quality_absent() {
+        let nut = Nut::new(Config::default()).unwrap();
+        let stats = nut.collect_device_parameters("ups".to_owned(), params(&[("ups.load", "5")]));
+
+        assert_eq!(stats.battery_voltage, None);
+        assert_eq!(stats.battery_temperature, None);
+        assert_eq!(stats.input_frequency, None);
+        assert_eq!(stats.output_frequency, None);
+        assert_eq!(stats.output_current, None);
+    }
+
+    #[test]
+    fn test_collect_power_fallback() {
+        let nut = Nut::new(Config::default()).unwrap();
+
+        let stats = nut.collect_device_parameters(
+            "ups".to_owned(),
+            params(&[("output.realpower", "150"), ("output.power", "180")]),
+        );
+        assert_eq!(stats.real_power, Some(150.0));
+        assert_eq!(stats.apparent_power, Some(180.0));
+
+        // Without a load, the power cannot be estimated from the nominal values
+        let stats = nut.collect_device_parameters(
+            "ups".to_owned(),
+            params(&[
+                ("ups.realpower.nominal", "400"),
+                ("ups.power.nominal", "700"),
+            ]),
+        );
+        assert_eq!(stats.real_power, None);
+        assert_eq!(stats.apparent_power, None);
+        assert_eq!(stats.nominal_real_power, Some(400.0));
+    }
+
+    #[test]
+    fn test_tls_config() {
+        let config = Config {
+            tls: true,
+            tls_skip_verify: true,
+            ..Default::default()
+        };
+        assert!(Nut::new(config).unwrap().tls.is_some());
+
+        let config = Config {
+            tls: true,
+            tls_server_name: Some("ups.example.com".to_owned()),
+            ..Default::default()
+        };
+        assert!(Nut::new(config).unwrap().tls.is_some());
+    }
+
+    const CA_CERTIFICATE: &str = "-----BEGIN CERTIFICATE-----
+MIIBkjCCATegAwIBAgIUOrEOwDHm+fNJ9UHZiHpH96B8Kt0wCgYIKoZIzj0EAwIw
+HTEbMBkGA1UEAwwSaGVwaGFlc3R1cy10ZXN0LWNhMCAXDTI2MTAxNTEwMjE0NVoY
+DzIxMjYwOTIxMTAyMTQ1WjAdMRswGQYDVQQDDBJoZXBoYWVzdHVzLXRlc3QtY2Ew
+WTATBgcqhkjOPQIBBggqhkjOPQMBBwNCAAT6ZBA9TV+dP0KrapPRWzdVwu6JMYHA
+PkSKbyWvE2UQSGRjQSbZgas4x7yhfzwt88YE9il9GqM7LWiyLYH0ROBno1MwUTAd
+BgNVHQ4EFgQUio0rpwAiAj8fk+06NHBuQkqokY8wHwYDVR0jBBgwFoAUio0rpwAi
+Aj8fk+06NHBuQkqokY8wDwYDVR0TAQH/BAUwAwEB/zAKBggqhkjOPQQDAgNJADBG
+AiEAx0gvcnG77Ozt8T+PSEPBGbJHZO1CwDSkoriupk9HDhwCIQD+sLIki+9Qwrxs
+1LkuP4kMytPqel0JIiCFSbQmzTpXAA==
+-----END CERTIFICATE-----
+";
+
+    #[test]
+    fn test_tls_ca_file() {
+        let path =
+            std::env::temp_dir().join(format!("hephaestus-nut-ca-{}.pem", std::process::id()));
+        std::fs::write(&path, CA_CERTIFICATE).unwrap();
+
+        let roots = root_certificates(path.to_str());
+        let config = Config {
+            tls: true,
+            tls_ca_file: Some(path.to_string_lossy().into_owned()),
+            ..Default::default()
+        };
+        let nut = Nut::new(config);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(roots.unwrap().len(), 1);
+        assert!(nut.unwrap().tls.is_some());
+
+        let error = root_certificates(Some("/nonexistent/ca.pem")).unwrap_err();
+        assert!(error.to_string().contains("/nonexistent/ca.pem"));
+    }
+
+    #[tokio::test]
+    async fn test_access_denied() {
+        let mut transport = ScriptedTransport::new(&[("LIST UPS", &["ERR ACCESS-DENIED"])]);
+
+        let nut = Nut::new(Config::default()).unwrap();
+        let error = nut.list_ups_devices(&mut transport).await.unwrap_err();
+        assert!(format!("{:#}", error).contains("ACCESS-DENIED"));
+    }
+
+    #[tokio::test]
+    async fn test_unknown_ups() {
+        let mut transport = ScriptedTransport::new(&[
+            ("LIST UPS", &[r#"UPS apc "APC""#]),
+            ("LIST VAR apc", &["ERR UNKNOWN-UPS"]),
+        ]);
+
+        let nut = Nut::new(Config::default()).unwrap();
+        let error = nut.query(&mut transport).await.unwrap_err();
+        assert!(format!("{:#}", error).contains("UNKNOWN-UPS"));
+    }
+
+    #[tokio::test]
+    async fn test_truncated_response() {
+        let (client, server) = tokio::io::duplex(1024);
+        let server = tokio::spawn(async move {
+            serve(
+                server,
+                &[(
+                    "LIST VAR eaton\n",
+                    "BEGIN LIST VAR eaton\nVAR eaton battery.charge \"90\"\n",
+                )],
+            )
+            .await;
+        });
+
+        let mut transport = StreamTransport::new(client);
+        let nut = Nut::new(Config::default()).unwrap();
+
+        // The server goes away in the middle of the list
+        let result = nut.list_device_parameters(&mut transport, "eaton").await;
+        assert!(result.is_err());
+        server.await.unwrap();
+    }
+}
@@ -0,0 +1,184 @@
+use crate::datasource::{Reader, Roots};
+use crate::metrics::os_info::{DataSource, OsInfoStats};
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use tokio::time::Instant;
+
+const PATH_OSRELEASE: &str = "sys/kernel/osrelease";
+const PATH_HOSTNAME: &str = "sys/kernel/hostname";
+// Available since Linux 6.1
+const PATH_ARCH: &str = "sys/kernel/arch";
+
+pub struct ProcOsInfo<R> {
+    reader: R,
+    roots: Roots,
+    os_release_paths: Vec<String>,
+}
+
+impl<R> ProcOsInfo<R>
+where
+    R: Reader,
+{
+    pub fn new(reader: R, roots: Roots, os_release_paths: Vec<String>) -> Self {
+        Self {
+            reader,
+            roots,
+            os_release_paths,
+        }
+    }
+
+    async fn read_trimmed(&self, path: &str) -> std::io::Result<String> {
+        let content = self.reader.read_to_string(path).await?;
+        Ok(content.trim().to_owned())
+    }
+
+    async fn os_release(&self) -> anyhow::Result<Option<HashMap<String, String>>> {
+        for path in &self.os_release_paths {
+            match self.reader.read_to_string(path).await {
+                Ok(content) => return Ok(Some(parse_os_release(&content))),
+                Err(e) if e.kind() == ErrorKind::NotFound => continue,
+                Err(e) => return Err(anyhow::anyhow!("Failed to read {}: {}", path, e)),
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+impl<R> DataSource for ProcOsInfo<R>
+where
+    R: Reader,
+{
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn os_info(&self) -> anyhow::Result<OsInfoStats> {
+        let kernel = self.read_trimmed(&self.roots.proc(PATH_OSRELEASE)).await?;
+        let hostname = self.read_trimmed(&self.roots.proc(PATH_HOSTNAME)).await?;
+
+        // Older kernels lack the sysctl, so use the architecture we were built for
+        let machine = match self.read_trimmed(&self.roots.proc(PATH_ARCH)).await {
+            Ok(arch) => arch,
+            Err(e) if e.kind() == ErrorKind::NotFound => std::env::consts::ARCH.to_owned(),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut os_release = self.os_release().await?.unwrap_or_default();
+        let os_version = os_release
+            .remove("VERSION_ID")
+            .or_else(|| os_release.remove("VERSION"));
+
+        Ok(OsInfoStats {
+            timestamp: Instant::now(),
+            kernel,
+            hostname,
+            machine,
+            os_name: os_release.remove("NAME"),
+            os_version,
+        })
+    }
+}
+
+/// Parses the `KEY=value` lines of os-release(5). The values may be quoted
+/// with single or double quotes, and may contain backslash escapes.
+fn parse_os_release(content: &str) -> HashMap<String, String> {
+    let mut values = HashMap::new();
+
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let Some((key, value)) = line.split_once('=') else {
+            tracing::debug!("Skipping unexpected os-release line: {}", line);
+            continue;
+        };
+
+        values.insert(key.trim().to_owned(), unquote(value.trim()));
+    }
+
+    values
+}
+
+fn unquote(value: &str) -> String {
+    let unquoted = ['"', '\'']
+        .iter()
+        .find_map(|q| value.strip_prefix(*q)?.strip_suffix(*q))
+        .unwrap_or(value);
+
+    let mut result = String::with_capacity(unquoted.len());
+    let mut chars = unquoted.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => result.extend(chars.next()),
+            c => result.push(c),
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datasource::tests::HardcodedReader;
+
+    const OS_RELEASE: &str = r#"PRETTY_NAME="Debian GNU/Linux 12 (bookworm)"
+NAME="Debian GNU/Linux"
+VERSION_ID="12"
+VERSION="12 (bookworm)"
+# A comment
+ID=debian
+HOME_URL='https://www.debian.org/'
+QUOTED="say \"hi\""
+"#;
+
+    #[test]
+    fn test_parse_os_release() {
+        let values = parse_os_release(OS_RELEASE);
+
+        assert_eq!(values["NAME"], "Debian GNU/Linux");
+        assert_eq!(values["VERSION_ID"], "12");
+        assert_eq!(values["ID"], "debian");
+        assert_eq!(values["HOME_URL"], "https://www.debian.org/");
+        assert_eq!(values["QUOTED"], r#"say "hi""#);
+        assert_eq!(values.len(), 7);
+    }
+
+    #[tokio::test]
+    async fn test_os_info() -> anyhow::Result<()> {
+        let roots = Roots::default();
+        let mut reader = HardcodedReader::new();
+        reader.add_response(roots.proc(PATH_OSRELEASE), "6.1.0-25-amd64\n");
+        reader.add_response(roots.proc(PATH_HOSTNAME), "nas\n");
+        reader.add_response(roots.proc(PATH_ARCH), "x86_64\n");
+        reader.add_response("/usr/lib/os-release", OS_RELEASE);
+
+        let paths = vec!["/etc/os-release".into(), "/usr/lib/os-release".into()];
+        let info = ProcOsInfo::new(reader, roots, paths).os_info().await?;
+
+        assert_eq!(info.kernel, "6.1.0-25-amd64");
+        assert_eq!(info.hostname, "nas");
+        assert_eq!(info.machine, "x86_64");
+        assert_eq!(info.os_name.as_deref(), Some("Debian GNU/Linux"));
+        assert_eq!(info.os_version.as_deref(), Some("12"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_os_info_without_os_release() -> anyhow::Result<()> {
+        let roots = Roots::default();
+        let mut reader = HardcodedReader::new();
+        reader.add_response(roots.proc(PATH_OSRELEASE), "6.1.0\n");
+        reader.add_response(roots.proc(PATH_HOSTNAME), "nas\n");
+
+        let paths = vec!["/etc/os-release".into()];
+        let info = ProcOsInfo::new(reader, roots, paths).os_info().await?;
+
+        assert_eq!(info.machine, std::env::consts::ARCH);
+        assert_eq!(info.os_name, None);
+        assert_eq!(info.os_version, None);
+
+        Ok(())
+    }
+}
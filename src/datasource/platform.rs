@@ -0,0 +1,99 @@
+use crate::datasource::{Reader, Roots};
+use crate::metrics::platform::{DataSource, PlatformStats};
+use std::io::ErrorKind;
+use tokio::time::Instant;
+
+const PATH_PLATFORM_PROFILE: &str = "firmware/acpi/platform_profile";
+const PATH_SCALING_DRIVER: &str = "devices/system/cpu/cpu0/cpufreq/scaling_driver";
+
+pub struct SysfsPlatform<R> {
+    reader: R,
+    roots: Roots,
+}
+
+impl<R> SysfsPlatform<R>
+where
+    R: Reader,
+{
+    pub fn new(reader: R, roots: Roots) -> Self {
+        Self { reader, roots }
+    }
+
+    /// Returns `None` if the file does not exist, which is the case
+    /// on systems without platform profile or cpufreq support
+    async fn read_attribute(&self, path: &str) -> anyhow::Result<Option<String>> {
+        match self.reader.read_to_string(path).await {
+            Ok(content) => Ok(parse_attribute(&content)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(anyhow::anyhow!("Failed to read {}: {}", path, e)),
+        }
+    }
+}
+
+impl<R> DataSource for SysfsPlatform<R>
+where
+    R: Reader,
+{
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn platform_stats(&self) -> anyhow::Result<PlatformStats> {
+        Ok(PlatformStats {
+            timestamp: Instant::now(),
+            profile: self
+                .read_attribute(&self.roots.sys(PATH_PLATFORM_PROFILE))
+                .await?,
+            scaling_driver: self
+                .read_attribute(&self.roots.sys(PATH_SCALING_DRIVER))
+                .await?,
+        })
+    }
+}
+
+fn parse_attribute(content: &str) -> Option<String> {
+    let value = content.trim();
+    if value.is_empty() {
+        return None;
+    }
+
+    Some(value.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datasource::tests::HardcodedReader;
+
+    #[test]
+    fn test_parse_attribute() {
+        assert_eq!(parse_attribute("balanced\n"), Some("balanced".to_owned()));
+        assert_eq!(
+            parse_attribute("  intel_pstate \n"),
+            Some("intel_pstate".to_owned())
+        );
+        assert_eq!(parse_attribute("\n"), None);
+    }
+
+    #[tokio::test]
+    async fn test_platform_datasource() {
+        let mut reader = HardcodedReader::new();
+        reader.add_response(Roots::default().sys(PATH_PLATFORM_PROFILE), "low-power\n");
+        reader.add_response(
+            Roots::default().sys(PATH_SCALING_DRIVER),
+            "amd-pstate-epp\n",
+        );
+
+        let ds = SysfsPlatform::new(reader, Roots::default());
+        let stats = ds.platform_stats().await.unwrap();
+
+        assert_eq!(stats.profile.as_deref(), Some("low-power"));
+        assert_eq!(stats.scaling_driver.as_deref(), Some("amd-pstate-epp"));
+    }
+
+    #[tokio::test]
+    async fn test_platform_datasource_not_supported() {
+        let ds = SysfsPlatform::new(HardcodedReader::new(), Roots::default());
+        let stats = ds.platform_stats().await.unwrap();
+
+        assert!(stats.profile.is_none());
+        assert!(stats.scaling_driver.is_none());
+    }
+}
@@ -0,0 +1,443 @@
+use crate::datasource::{Reader, Roots};
+use crate::metrics::processes_top::{DataSource, ProcessStats, TopProcessesStats};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tokio::time::{Duration, Instant};
+
+const PATH_PROC_STAT: &str = "stat";
+const DEFAULT_MIN_SAMPLE_INTERVAL: Duration = Duration::from_millis(250);
+
+// The user through steal columns of the "cpu" line, as guest is included in user
+const CPU_TOTAL_COLUMNS: usize = 8;
+
+// The utime, stime and starttime columns of /proc/<pid>/stat, counted after the comm field
+const PROC_UTIME: usize = 11;
+const PROC_STIME: usize = 12;
+const PROC_STARTTIME: usize = 19;
+
+#[derive(Debug, Clone, PartialEq)]
+struct ProcessTimes {
+    comm: String,
+    // The CPU time (utime + stime) in jiffies
+    cpu_time: u64,
+    // The start time in jiffies since boot, which tells the reused PIDs apart
+    start_time: u64,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Sample {
+    // The total CPU time of the machine in jiffies
+    total: u64,
+    processes: HashMap<u32, ProcessTimes>,
+}
+
+impl Sample {
+    /// Any process started later than the newest one did not exist when the sample was taken
+    fn newest_start_time(&self) -> Option<u64> {
+        self.processes.values().map(|p| p.start_time).max()
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Measurement {
+    timestamp: Instant,
+    sample: Sample,
+    stats: TopProcessesStats,
+}
+
+pub struct ProcProcesses<R> {
+    reader: R,
+    roots: Roots,
+    min_sample_interval: Duration,
+    reuse_previous: bool,
+    measurement: Mutex<Option<Measurement>>,
+}
+
+impl<R> ProcProcesses<R>
+where
+    R: Reader,
+{
+    pub fn new(reader: R, roots: Roots) -> Self {
+        Self {
+            reader,
+            roots,
+            min_sample_interval: DEFAULT_MIN_SAMPLE_INTERVAL,
+            reuse_previous: false,
+            measurement: Mutex::new(None),
+        }
+    }
+
+    /// The minimum time between the two samples the usage is computed from
+    pub fn with_min_sample_interval(mut self, interval: Duration) -> Self {
+        self.min_sample_interval = interval;
+        self
+    }
+
+    /// Never sleep while measuring. A measurement taken sooner than the minimum sample
+    /// interval returns the previous processes, and the very first one returns the
+    /// average usage since boot.
+    pub fn with_reuse_previous(mut self, reuse_previous: bool) -> Self {
+        self.reuse_previous = reuse_previous;
+        self
+    }
+
+    async fn wait_for_interval(&self, since: Instant) {
+        if self.reuse_previous {
+            return;
+        }
+
+        let elapsed = since.elapsed();
+        if elapsed < self.min_sample_interval {
+            tokio::time::sleep(self.min_sample_interval.saturating_sub(elapsed)).await;
+        }
+    }
+
+    async fn sample(&self) -> anyhow::Result<Sample> {
+        let content = self
+            .reader
+            .read_to_string(self.roots.proc(PATH_PROC_STAT))
+            .await?;
+        let total = parse_total_jiffies(&content)
+            .ok_or_else(|| anyhow::anyhow!("Missing the cpu line in /proc/stat"))?;
+
+        let mut processes = HashMap::new();
+        for entry in self.reader.read_dir(self.roots.proc("")).await? {
+            let Ok(pid) = entry.parse::<u32>() else {
+                continue;
+            };
+
+            // The process may exit while we are iterating
+            let path = self.roots.proc(&format!("{pid}/stat"));
+            let Ok(content) = self.reader.read_to_string(&path).await else {
+                continue;
+            };
+
+            match parse_process_stat(&content) {
+                Some(process) => {
+                    processes.insert(pid, process);
+                }
+                None => tracing::debug!("Skipping unexpected {} content: {}", path, content),
+            }
+        }
+
+        Ok(Sample { total, processes })
+    }
+
+    async fn memory_bytes(&self, pid: u32) -> Option<u64> {
+        let path = self.roots.proc(&format!("{pid}/status"));
+        let content = self.reader.read_to_string(&path).await.ok()?;
+        parse_rss(&content)
+    }
+}
+
+impl<R> DataSource for ProcProcesses<R>
+where
+    R: Reader,
+{
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn top_processes(&self, n: usize) -> anyhow::Result<TopProcessesStats> {
+        let Ok(previous) = self.measurement.lock().map(|x| x.clone()) else {
+            return Err(anyhow::anyhow!(
+                "Failed to retrieve previous process measurement snapshot due to a poisoned lock"
+            ));
+        };
+
+        let previous = match previous {
+            Some(measurement)
+                if self.reuse_previous
+                    && measurement.timestamp.elapsed() < self.min_sample_interval =>
+            {
+                return Ok(measurement.stats);
+            }
+            Some(measurement) => {
+                self.wait_for_interval(measurement.timestamp).await;
+                measurement.sample
+            }
+            // Without a previous sample, the usage is averaged since boot
+            None if self.reuse_previous => Sample::default(),
+            None => {
+                let sample = self.sample().await?;
+                self.wait_for_interval(Instant::now()).await;
+                sample
+            }
+        };
+
+        let current = self.sample().await?;
+        let now = Instant::now();
+
+        let newest = previous.newest_start_time();
+        let usage = current
+            .processes
+            .iter()
+            .filter_map(|(pid, process)| {
+                let prev = match previous.processes.get(pid) {
+                    Some(prev) if prev.start_time == process.start_time => prev.cpu_time,
+                    // Processes started since the previous sample used all of their time in between
+                    _ if newest.is_none_or(|newest| process.start_time > newest) => 0,
+                    // The process could not be read at the previous sample, so its usage is unknown
+                    _ => return None,
+                };
+
+                let cpu = cpu_ratio(prev, process.cpu_time, previous.total, current.total);
+                Some((*pid, process.comm.clone(), cpu))
+            })
+            .collect();
+
+        let mut processes = Vec::with_capacity(n);
+        for (pid, comm, cpu) in top_by_cpu(usage, n) {
+            processes.push(ProcessStats {
+                pid,
+                comm,
+                cpu,
+                memory_bytes: self.memory_bytes(pid).await,
+            });
+        }
+
+        let stats = TopProcessesStats {
+            timestamp: now,
+            processes,
+        };
+
+        match self.measurement.lock() {
+            Ok(mut guard) => {
+                *guard = Some(Measurement {
+                    timestamp: now,
+                    sample: current,
+                    stats: stats.clone(),
+                });
+            }
+            Err(_) => {
+                return Err(anyhow::anyhow!(
+                    "Failed to update process measurement snapshot due to a poisoned lock"
+                ));
+            }
+        }
+
+        Ok(stats)
+    }
+}
+
+/// Calculates the share of the machine's CPU time used by a process between
+/// two samples. Both the process and the total times are in jiffies, and the
+/// total is summed across all cores, so the result is in the range `[0, 1]`.
+/// A PID reused by a new process may have a lower time than before, which is
+/// reported as no usage instead of a bogus spike.
+fn cpu_ratio(prev_process: u64, curr_process: u64, prev_total: u64, curr_total: u64) -> f64 {
+    let total = curr_total.saturating_sub(prev_total);
+    if total == 0 {
+        return 0.0;
+    }
+
+    let process = curr_process.saturating_sub(prev_process);
+    (process as f64 / total as f64).clamp(0.0, 1.0)
+}
+
+/// Keeps the `n` processes with the highest CPU usage, breaking ties by PID
+fn top_by_cpu(mut usage: Vec<(u32, String, f64)>, n: usize) -> Vec<(u32, String, f64)> {
+    usage.sort_by(|a, b| b.2.total_cmp(&a.2).then_with(|| a.0.cmp(&b.0)));
+    usage.truncate(n);
+    usage
+}
+
+fn parse_total_jiffies(content: &str) -> Option<u64> {
+    let line = content.lines().find(|l| l.starts_with("cpu "))?;
+    let total = line
+        .split_whitespace()
+        .skip(1)
+        .take(CPU_TOTAL_COLUMNS)
+        .filter_map(|v| v.parse::<u64>().ok())
+        .sum();
+
+    Some(total)
+}
+
+/// Parses the comm, the CPU time and the start time from `/proc/<pid>/stat`. The
+/// comm is enclosed in parentheses and may contain both spaces and parentheses.
+fn parse_process_stat(content: &str) -> Option<ProcessTimes> {
+    let (_, rest) = content.split_once(" (")?;
+    let (comm, fields) = rest.rsplit_once(") ")?;
+
+    let fields = fields.split_whitespace().collect::<Vec<_>>();
+    let utime = fields.get(PROC_UTIME)?.parse::<u64>().ok()?;
+    let stime = fields.get(PROC_STIME)?.parse::<u64>().ok()?;
+    let start_time = fields.get(PROC_STARTTIME)?.parse::<u64>().ok()?;
+
+    Some(ProcessTimes {
+        comm: comm.to_owned(),
+        cpu_time: utime + stime,
+        start_time,
+    })
+}
+
+fn parse_rss(content: &str) -> Option<u64> {
+    let value = content.lines().find_map(|l| l.strip_prefix("VmRSS:"))?;
+    let kib = value
+        .trim()
+        .strip_suffix("kB")?
+        .trim()
+        .parse::<u64>()
+        .ok()?;
+    Some(kib * 1024)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datasource::tests::HardcodedReader;
+
+    const PROC_STAT: &str = "cpu  1000 0 500 8000 100 0 0 0 0 0\n";
+    const PROC_STAT_LATER: &str = "cpu  1400 0 600 8500 100 0 0 0 0 0\n";
+
+    fn process_stat(pid: u32, comm: &str, utime: u64, stime: u64) -> String {
+        started_process_stat(pid, comm, utime, stime, 100)
+    }
+
+    fn started_process_stat(pid: u32, comm: &str, utime: u64, stime: u64, start: u64) -> String {
+        format!(
+            "{pid} ({comm}) S 1 {pid} {pid} 0 -1 4194560 100 0 0 0 {utime} {stime} 0 0 20 0 1 0 {start}"
+        )
+    }
+
+    #[test]
+    fn test_cpu_ratio() {
+        assert_eq!(cpu_ratio(100, 150, 1000, 1200), 0.25);
+        assert_eq!(cpu_ratio(0, 0, 1000, 1200), 0.0);
+        assert_eq!(cpu_ratio(100, 150, 1000, 1000), 0.0);
+        // A reused PID must not produce a bogus value
+        assert_eq!(cpu_ratio(500, 20, 1000, 1200), 0.0);
+    }
+
+    #[test]
+    fn test_top_by_cpu() {
+        let usage = vec![
+            (1, "init".to_owned(), 0.01),
+            (42, "postgres".to_owned(), 0.5),
+            (7, "rustc".to_owned(), 0.75),
+            (8, "idle".to_owned(), 0.0),
+        ];
+
+        let top = top_by_cpu(usage, 2);
+        assert_eq!(top.iter().map(|p| p.0).collect::<Vec<_>>(), [7, 42]);
+    }
+
+    #[test]
+    fn test_parse_process_stat() {
+        let stat = started_process_stat(10, "tmux: server (1)", 30, 12, 4200);
+        assert_eq!(
+            parse_process_stat(&stat),
+            Some(ProcessTimes {
+                comm: "tmux: server (1)".to_owned(),
+                cpu_time: 42,
+                start_time: 4200,
+            })
+        );
+
+        assert_eq!(parse_process_stat("10 (short) S 1"), None);
+    }
+
+    #[test]
+    fn test_parse_rss() {
+        let status = "Name:\tbash\nVmPeak:\t   10000 kB\nVmRSS:\t    5120 kB\n";
+        assert_eq!(parse_rss(status), Some(5 * 1024 * 1024));
+        // Kernel threads have no memory of their own
+        assert_eq!(parse_rss("Name:\tkthreadd\n"), None);
+    }
+
+    #[tokio::test]
+    async fn test_top_processes() -> anyhow::Result<()> {
+        let roots = Roots::default();
+        let mut reader = HardcodedReader::new();
+        reader.add_dir(roots.proc(""), &["1", "42", "99", "self", "net"]);
+
+        reader.add_response(roots.proc("stat"), PROC_STAT);
+        reader.add_response(roots.proc("1/stat"), process_stat(1, "init", 10, 10));
+        reader.add_response(roots.proc("42/stat"), process_stat(42, "worker", 100, 0));
+        reader.add_response(roots.proc("99/stat"), process_stat(99, "short", 5, 5));
+
+        // The second sample is taken 1000 jiffies later and PID 99 has exited
+        reader.add_response(roots.proc("stat"), PROC_STAT_LATER);
+        reader.add_response(roots.proc("1/stat"), process_stat(1, "init", 20, 10));
+        reader.add_response(roots.proc("42/stat"), process_stat(42, "worker", 500, 100));
+        reader.add_response(roots.proc("42/status"), "VmRSS:\t    2048 kB\n");
+
+        let data_source = ProcProcesses::new(reader, roots);
+        let stats = data_source.top_processes(1).await?;
+
+        assert_eq!(
+            stats.processes,
+            [ProcessStats {
+                pid: 42,
+                comm: "worker".into(),
+                cpu: 0.5,
+                memory_bytes: Some(2 * 1024 * 1024),
+            }]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_unread_processes_are_not_new() -> anyhow::Result<()> {
+        let roots = Roots::default();
+        let mut reader = HardcodedReader::new();
+        reader.add_dir(roots.proc(""), &["1", "7", "8"]);
+
+        // Neither PID 7 nor PID 8 can be read at the first sample, but only PID 8 started since
+        reader.add_response(roots.proc("stat"), PROC_STAT);
+        reader.add_response(
+            roots.proc("1/stat"),
+            started_process_stat(1, "init", 10, 10, 5),
+        );
+        reader.add_response(roots.proc("7/stat"), "7 (old");
+        reader.add_response(roots.proc("8/stat"), "8 (new");
+
+        reader.add_response(roots.proc("stat"), PROC_STAT_LATER);
+        reader.add_response(
+            roots.proc("1/stat"),
+            started_process_stat(1, "init", 20, 10, 5),
+        );
+        reader.add_response(
+            roots.proc("7/stat"),
+            started_process_stat(7, "old", 900, 0, 3),
+        );
+        reader.add_response(
+            roots.proc("8/stat"),
+            started_process_stat(8, "new", 200, 0, 60),
+        );
+
+        let data_source = ProcProcesses::new(reader, roots);
+        let stats = data_source.top_processes(3).await?;
+
+        let pids = stats.processes.iter().map(|p| p.pid).collect::<Vec<_>>();
+        assert_eq!(pids, [8, 1]);
+        assert_eq!(stats.processes[0].cpu, 0.2);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_top_processes_reuse_previous() -> anyhow::Result<()> {
+        let roots = Roots::default();
+        let mut reader = HardcodedReader::new();
+        reader.add_dir(roots.proc(""), &["42"]);
+        reader.add_response(roots.proc("stat"), PROC_STAT);
+        reader.add_response(roots.proc("42/stat"), process_stat(42, "worker", 960, 0));
+
+        let data_source = ProcProcesses::new(reader, roots)
+            .with_min_sample_interval(Duration::from_secs(1))
+            .with_reuse_previous(true);
+        tokio::time::pause();
+
+        // Nothing to compare against, so the usage is averaged since boot
+        let start = Instant::now();
+        let stats = data_source.top_processes(1).await?;
+        assert_eq!(start, Instant::now(), "must not sleep");
+        assert_eq!(stats.processes[0].cpu, 0.1);
+
+        // Too soon for a new sample, so /proc is not read again
+        let reused = data_source.top_processes(1).await?;
+        assert_eq!(reused.processes, stats.processes);
+
+        Ok(())
+    }
+}
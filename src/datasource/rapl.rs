@@ -0,0 +1,183 @@
+use crate::datasource::{Reader, Roots};
+use crate::metrics::rapl::{DataSource, RaplStats, ZoneStats};
+use std::collections::HashSet;
+use std::io::ErrorKind;
+use std::sync::Mutex;
+use tokio::time::Instant;
+
+const PATH_POWERCAP: &str = "class/powercap";
+const RAPL_ZONE_PREFIX: &str = "intel-rapl:";
+
+pub struct PowercapRapl<R> {
+    reader: R,
+    roots: Roots,
+    // The zones already warned about as unreadable
+    unreadable: Mutex<HashSet<String>>,
+}
+
+impl<R> PowercapRapl<R>
+where
+    R: Reader,
+{
+    pub fn new(reader: R, roots: Roots) -> Self {
+        Self {
+            reader,
+            roots,
+            unreadable: Mutex::new(HashSet::new()),
+        }
+    }
+
+    async fn read_zone(&self, entry: &str) -> anyhow::Result<Option<ZoneStats>> {
+        let Some(index) = entry.strip_prefix(RAPL_ZONE_PREFIX) else {
+            return Ok(None);
+        };
+
+        let path = format!("{}/{}/energy_uj", self.roots.sys(PATH_POWERCAP), entry);
+        let energy_uj = match self.reader.read_to_string(&path).await {
+            Ok(content) => content.trim().parse()?,
+            // energy_uj is readable only by root on the recent kernels. As this is the
+            // case on every scrape, warn only once per zone to not flood the logs
+            Err(e) if e.kind() == ErrorKind::PermissionDenied => {
+                let mut unreadable = self.unreadable.lock().unwrap_or_else(|e| e.into_inner());
+                match unreadable.insert(entry.to_owned()) {
+                    true => tracing::warn!("Skipping RAPL zone {}: {}", entry, e),
+                    false => tracing::debug!("Skipping RAPL zone {}: {}", entry, e),
+                }
+                return Ok(None);
+            }
+            Err(e) => return Err(anyhow::anyhow!("Failed to read {}: {}", path, e)),
+        };
+
+        let path = format!("{}/{}/name", self.roots.sys(PATH_POWERCAP), entry);
+        let zone = self.reader.read_to_string(&path).await?;
+
+        let path = format!(
+            "{}/{}/max_energy_range_uj",
+            self.roots.sys(PATH_POWERCAP),
+            entry
+        );
+        let max_energy_range_uj = self
+            .reader
+            .read_to_string(&path)
+            .await
+            .ok()
+            .and_then(|content| content.trim().parse().ok());
+
+        Ok(Some(ZoneStats {
+            index: index.to_owned(),
+            zone: zone.trim().to_owned(),
+            energy_uj,
+            max_energy_range_uj,
+        }))
+    }
+}
+
+impl<R> DataSource for PowercapRapl<R>
+where
+    R: Reader,
+{
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn rapl_stats(&self) -> anyhow::Result<RaplStats> {
+        let mut entries = match self.reader.read_dir(self.roots.sys(PATH_POWERCAP)).await {
+            Ok(entries) => entries,
+            // The powercap framework is not available, so there is nothing to report
+            Err(e) if e.kind() == ErrorKind::NotFound => vec![],
+            Err(e) => return Err(e.into()),
+        };
+        entries.sort();
+
+        let mut zones = vec![];
+        for entry in entries {
+            if let Some(zone) = self.read_zone(&entry).await? {
+                zones.push(zone);
+            }
+        }
+
+        Ok(RaplStats {
+            timestamp: Instant::now(),
+            zones,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datasource::tests::HardcodedReader;
+
+    fn add_zone(reader: &mut HardcodedReader, entry: &str, name: &str, energy: &str) {
+        reader.add_response(
+            format!("{}/{}/name", Roots::default().sys(PATH_POWERCAP), entry),
+            name,
+        );
+        reader.add_response(
+            format!(
+                "{}/{}/energy_uj",
+                Roots::default().sys(PATH_POWERCAP),
+                entry
+            ),
+            energy,
+        );
+        reader.add_response(
+            format!(
+                "{}/{}/max_energy_range_uj",
+                Roots::default().sys(PATH_POWERCAP),
+                entry
+            ),
+            "262143328850\n",
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rapl() {
+        let mut reader = HardcodedReader::new();
+        reader.add_dir(
+            Roots::default().sys(PATH_POWERCAP),
+            &[
+                "intel-rapl",
+                "intel-rapl:0",
+                "intel-rapl:0:0",
+                "intel-rapl:0:1",
+                "intel-rapl-mmio:0",
+            ],
+        );
+        add_zone(&mut reader, "intel-rapl:0", "package-0\n", "83194023415\n");
+        add_zone(&mut reader, "intel-rapl:0:0", "core\n", "51234567890\n");
+        add_zone(&mut reader, "intel-rapl:0:1", "dram\n", "7000000\n");
+
+        let ds = PowercapRapl::new(reader, Roots::default());
+        let stats = ds.rapl_stats().await.unwrap();
+
+        assert_eq!(
+            stats.zones,
+            vec![
+                ZoneStats {
+                    index: "0".to_owned(),
+                    zone: "package-0".to_owned(),
+                    energy_uj: 83194023415,
+                    max_energy_range_uj: Some(262143328850),
+                },
+                ZoneStats {
+                    index: "0:0".to_owned(),
+                    zone: "core".to_owned(),
+                    energy_uj: 51234567890,
+                    max_energy_range_uj: Some(262143328850),
+                },
+                ZoneStats {
+                    index: "0:1".to_owned(),
+                    zone: "dram".to_owned(),
+                    energy_uj: 7000000,
+                    max_energy_range_uj: Some(262143328850),
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rapl_without_powercap() {
+        let ds = PowercapRapl::new(HardcodedReader::new(), Roots::default());
+        let stats = ds.rapl_stats().await.unwrap();
+
+        assert!(stats.zones.is_empty());
+    }
+}
@@ -0,0 +1,136 @@
+use crate::datasource::{Reader, Roots};
+use crate::domain::Probe;
+use crate::metrics::slabinfo::{DataSource, Slab, SlabStats};
+use tokio::time::Instant;
+
+const PATH_SLABINFO: &str = "slabinfo";
+
+pub struct ProcSlabInfo<R> {
+    reader: R,
+    roots: Roots,
+}
+
+impl<R> ProcSlabInfo<R>
+where
+    R: Reader,
+{
+    pub fn new(reader: R, roots: Roots) -> Self {
+        Self { reader, roots }
+    }
+}
+
+impl<R> Probe for ProcSlabInfo<R>
+where
+    R: Reader,
+{
+    async fn supported(&self) -> bool {
+        // The file is readable only by root, so a permission denial means unsupported
+        self.reader
+            .read_to_string(self.roots.proc(PATH_SLABINFO))
+            .await
+            .is_ok()
+    }
+}
+
+impl<R> DataSource for ProcSlabInfo<R>
+where
+    R: Reader,
+{
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn slab_stats(&self) -> anyhow::Result<SlabStats> {
+        let content = self
+            .reader
+            .read_to_string(self.roots.proc(PATH_SLABINFO))
+            .await?;
+
+        Ok(SlabStats {
+            timestamp: Instant::now(),
+            slabs: parse_slabinfo(&content),
+        })
+    }
+}
+
+/// Parses the body of `/proc/slabinfo`:
+///
+/// ```text
+/// slabinfo - version: 2.1
+/// # name <active_objs> <num_objs> <objsize> <objperslab> <pagesperslab> : tunables ...
+/// dentry 112455 117054 192 21 1 : tunables 0 0 0 : slabdata 5574 5574 0
+/// ```
+fn parse_slabinfo(content: &str) -> Vec<Slab> {
+    let mut slabs = Vec::new();
+
+    for line in content.lines() {
+        if line.starts_with("slabinfo -") || line.starts_with('#') {
+            continue;
+        }
+
+        let mut parts = line.split_whitespace();
+        let Some(name) = parts.next() else { continue };
+        let mut next = || parts.next().and_then(|v| v.parse::<u64>().ok());
+        let (Some(active_objects), Some(objects), Some(object_size)) = (next(), next(), next())
+        else {
+            tracing::debug!(line, "Skipping malformed slabinfo line");
+            continue;
+        };
+
+        slabs.push(Slab {
+            name: name.to_owned(),
+            active_objects,
+            objects,
+            object_size,
+        });
+    }
+
+    slabs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datasource::tests::HardcodedReader;
+
+    const SLABINFO: &str = "slabinfo - version: 2.1
+# name            <active_objs> <num_objs> <objsize> <objperslab> <pagesperslab> : tunables <limit> <batchcount> <sharedfactor> : slabdata <active_slabs> <num_slabs> <sharedavail>
+ext4_inode_cache   45120  46410   1112   29    8 : tunables    0    0    0 : slabdata   1601   1601      0
+dentry            112455 117054    192   21    1 : tunables    0    0    0 : slabdata   5574   5574      0
+kmalloc-64         18944  19008     64   64    1 : tunables    0    0    0 : slabdata    297    297      0
+";
+
+    #[test]
+    fn test_parse_slabinfo() {
+        let slabs = parse_slabinfo(SLABINFO);
+
+        assert_eq!(slabs.len(), 3);
+        assert_eq!(
+            slabs[1],
+            Slab {
+                name: "dentry".into(),
+                active_objects: 112455,
+                objects: 117054,
+                object_size: 192,
+            }
+        );
+        assert_eq!(slabs[0].size(), 46410 * 1112);
+    }
+
+    #[test]
+    fn test_parse_slabinfo_skips_malformed_lines() {
+        let slabs = parse_slabinfo("slabinfo - version: 2.1\nbroken line\nkmalloc-8 1 2 8 1 1\n");
+
+        assert_eq!(slabs.len(), 1);
+        assert_eq!(slabs[0].name, "kmalloc-8");
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_without_permission() {
+        // The mock reader fails for unknown paths, like it would on a permission denial
+        let data_source = ProcSlabInfo::new(HardcodedReader::new(), Roots::default());
+        assert!(!data_source.supported().await);
+
+        let mut reader = HardcodedReader::new();
+        reader.add_response(Roots::default().proc(PATH_SLABINFO), SLABINFO.to_owned());
+        let data_source = ProcSlabInfo::new(reader, Roots::default());
+        assert!(data_source.supported().await);
+    }
+}
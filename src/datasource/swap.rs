@@ -0,0 +1,119 @@
+use crate::datasource::filesystem::unescape;
+use crate::datasource::{Reader, Roots};
+use crate::metrics::swap::{DataSource, SwapDevice, SwapStats};
+use tokio::time::Instant;
+
+const PATH_SWAPS: &str = "swaps";
+
+pub struct ProcSwaps<R> {
+    reader: R,
+    roots: Roots,
+}
+
+impl<R> ProcSwaps<R>
+where
+    R: Reader,
+{
+    pub fn new(reader: R, roots: Roots) -> Self {
+        Self { reader, roots }
+    }
+}
+
+impl<R> DataSource for ProcSwaps<R>
+where
+    R: Reader,
+{
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn swap_stats(&self) -> anyhow::Result<SwapStats> {
+        let content = self
+            .reader
+            .read_to_string(self.roots.proc(PATH_SWAPS))
+            .await?;
+
+        Ok(SwapStats {
+            timestamp: Instant::now(),
+            devices: parse_swaps(&content),
+        })
+    }
+}
+
+/// Parses `/proc/swaps`, which has a header followed by one line per swap area.
+/// The sizes are in KiB and the paths are escaped like in `/proc/mounts`.
+fn parse_swaps(content: &str) -> Vec<SwapDevice> {
+    let mut devices = Vec::new();
+
+    for line in content.lines().skip(1) {
+        let mut parts = line.split_whitespace();
+        let (Some(device), Some(kind), Some(size), Some(used)) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            tracing::debug!("Skipping invalid swaps line: {}", line);
+            continue;
+        };
+
+        let (Ok(size), Ok(used)) = (size.parse::<u64>(), used.parse::<u64>()) else {
+            tracing::debug!("Skipping invalid swaps line: {}", line);
+            continue;
+        };
+
+        devices.push(SwapDevice {
+            device: unescape(device),
+            kind: kind.to_owned(),
+            size: size * 1024,
+            used: used * 1024,
+        });
+    }
+
+    devices
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datasource::tests::HardcodedReader;
+
+    const SWAPS: &str = "Filename\t\t\t\tType\t\tSize\t\tUsed\t\tPriority
+/dev/nvme0n1p3                          partition\t8388604\t\t524288\t\t-2
+/var/swap\\040file                       file\t\t2097148\t\t0\t\t-3
+/dev/zram0                              partition\t4194300\t\tbroken\t\t100
+";
+
+    #[test]
+    fn test_parse_swaps() {
+        let devices = parse_swaps(SWAPS);
+
+        assert_eq!(
+            devices,
+            [
+                SwapDevice {
+                    device: "/dev/nvme0n1p3".into(),
+                    kind: "partition".into(),
+                    size: 8388604 * 1024,
+                    used: 512 * 1024 * 1024,
+                },
+                SwapDevice {
+                    device: "/var/swap file".into(),
+                    kind: "file".into(),
+                    size: 2097148 * 1024,
+                    used: 0,
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_swap_stats_without_swap() -> anyhow::Result<()> {
+        let mut reader = HardcodedReader::new();
+        reader.add_response(
+            Roots::default().proc(PATH_SWAPS),
+            "Filename\t\t\t\tType\t\tSize\t\tUsed\t\tPriority\n",
+        );
+
+        let stats = ProcSwaps::new(reader, Roots::default())
+            .swap_stats()
+            .await?;
+        assert!(stats.devices.is_empty());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,134 @@
+use crate::datasource::CommandRunner;
+use crate::domain::Probe;
+use crate::metrics::timesync::{DataSource, TimesyncStats};
+use tokio::time::Instant;
+
+const CHRONYC: &str = "chronyc";
+const CHRONYC_TRACKING_ARGS: [&str; 2] = ["-c", "tracking"];
+
+// The columns of `chronyc -c tracking`
+const COLUMN_STRATUM: usize = 2;
+const COLUMN_SYSTEM_TIME: usize = 4;
+const COLUMN_FREQUENCY: usize = 7;
+const COLUMN_COUNT: usize = 14;
+
+pub struct Chrony<C> {
+    runner: C,
+}
+
+impl<C> Chrony<C>
+where
+    C: CommandRunner,
+{
+    pub fn new(runner: C) -> Self {
+        Self { runner }
+    }
+}
+
+impl<C> Probe for Chrony<C>
+where
+    C: CommandRunner,
+{
+    async fn supported(&self) -> bool {
+        // Fails if chrony is not installed
+        self.runner.run(CHRONYC, &["-v"]).await.is_ok()
+    }
+}
+
+impl<C> DataSource for Chrony<C>
+where
+    C: CommandRunner,
+{
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn timesync_stats(&self) -> anyhow::Result<TimesyncStats> {
+        let output = self.runner.run(CHRONYC, &CHRONYC_TRACKING_ARGS).await?;
+        if !output.status.success() {
+            return Err(anyhow::anyhow!(
+                "chronyc tracking failed with status {:?}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        let content = String::from_utf8_lossy(&output.stdout);
+        parse_tracking(&content)
+    }
+}
+
+/// Parses the output of `chronyc -c tracking`, which is a single CSV line:
+///
+/// ```text
+/// A29FC87B,162.159.200.123,3,1700000000.123456789,0.000012345,-0.000023456,0.000034567,-12.345,-0.012,0.045,0.001234567,0.000567890,64.2,Normal
+/// ```
+///
+/// The columns are the reference ID, the reference name, the stratum, the reference time,
+/// the system time offset, the last offset, the RMS offset, the frequency, the residual
+/// frequency, the skew, the root delay, the root dispersion, the update interval and the
+/// leap status. Unlike the human-readable output, the values are signed instead of being
+/// suffixed with "fast" or "slow".
+fn parse_tracking(content: &str) -> anyhow::Result<TimesyncStats> {
+    let line = content.trim();
+    let columns = line.split(',').collect::<Vec<_>>();
+    if columns.len() < COLUMN_COUNT {
+        return Err(anyhow::anyhow!(
+            "Unexpected chronyc tracking output: {}",
+            line
+        ));
+    }
+
+    let parse = |idx: usize| {
+        columns[idx]
+            .parse::<f64>()
+            .map_err(|e| anyhow::anyhow!("Invalid chronyc tracking column {}: {}", idx, e))
+    };
+
+    Ok(TimesyncStats {
+        timestamp: Instant::now(),
+        offset_seconds: parse(COLUMN_SYSTEM_TIME)?,
+        frequency_ppm: parse(COLUMN_FREQUENCY)?,
+        stratum: columns[COLUMN_STRATUM].parse()?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datasource::tests::HardcodedCommandRunner;
+
+    const TRACKING: &str = "A29FC87B,162.159.200.123,3,1700000000.123456789,0.000012345,-0.000023456,0.000034567,-12.345,-0.012,0.045,0.001234567,0.000567890,64.2,Normal\n";
+
+    #[test]
+    fn test_parse_tracking() -> anyhow::Result<()> {
+        let stats = parse_tracking(TRACKING)?;
+
+        assert_eq!(stats.offset_seconds, 0.000012345);
+        assert_eq!(stats.frequency_ppm, -12.345);
+        assert_eq!(stats.stratum, 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_tracking_rejects_malformed_output() {
+        assert!(parse_tracking("").is_err());
+        assert!(parse_tracking("506 Cannot talk to daemon").is_err());
+        assert!(parse_tracking(&TRACKING.replace(",3,", ",x,")).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_chrony_datasource() {
+        let mut runner = HardcodedCommandRunner::new();
+        runner.add_response("chronyc -c tracking", 0, TRACKING);
+        runner.add_response("chronyc -c tracking", 1, "");
+
+        let ds = Chrony::new(runner);
+        assert_eq!(ds.timesync_stats().await.unwrap().stratum, 3);
+        assert!(ds.timesync_stats().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_without_chrony() {
+        let ds = Chrony::new(HardcodedCommandRunner::new());
+        assert!(!ds.supported().await);
+    }
+}
@@ -0,0 +1,56 @@
+use crate::metrics::tokio_runtime::{DataSource, RuntimeStats};
+use tokio::runtime::Handle;
+use tokio::time::Instant;
+
+pub struct TokioRuntime {
+    handle: Handle,
+}
+
+impl TokioRuntime {
+    /// Must be called from within the runtime to be observed
+    pub fn new() -> Self {
+        Self {
+            handle: Handle::current(),
+        }
+    }
+}
+
+impl DataSource for TokioRuntime {
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn runtime_stats(&self) -> anyhow::Result<RuntimeStats> {
+        let metrics = self.handle.metrics();
+
+        #[cfg(tokio_unstable)]
+        let (blocking_threads, blocking_queue_depth, io_driver_ready_events) = (
+            Some(metrics.num_blocking_threads()),
+            Some(metrics.blocking_queue_depth()),
+            Some(metrics.io_driver_ready_count()),
+        );
+        #[cfg(not(tokio_unstable))]
+        let (blocking_threads, blocking_queue_depth, io_driver_ready_events) = (None, None, None);
+
+        Ok(RuntimeStats {
+            timestamp: Instant::now(),
+            workers: metrics.num_workers(),
+            alive_tasks: metrics.num_alive_tasks(),
+            global_queue_depth: metrics.global_queue_depth(),
+            blocking_threads,
+            blocking_queue_depth,
+            io_driver_ready_events,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_current_thread_runtime() {
+        let ds = TokioRuntime::new();
+        let stats = ds.runtime_stats().await.unwrap();
+
+        assert_eq!(stats.workers, 1);
+        assert_eq!(stats.blocking_threads.is_some(), cfg!(tokio_unstable));
+    }
+}
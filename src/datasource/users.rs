@@ -0,0 +1,92 @@
+use crate::datasource::CommandRunner;
+use crate::metrics::users::{DataSource, UserStats};
+use std::collections::HashSet;
+use tokio::time::Instant;
+
+const WHO: &str = "who";
+
+pub struct WhoUsers<C> {
+    runner: C,
+}
+
+impl<C> WhoUsers<C>
+where
+    C: CommandRunner,
+{
+    pub fn new(runner: C) -> Self {
+        Self { runner }
+    }
+}
+
+impl<C> DataSource for WhoUsers<C>
+where
+    C: CommandRunner,
+{
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn user_stats(&self) -> anyhow::Result<UserStats> {
+        let output = self.runner.run(WHO, &["-q"]).await?;
+        if !output.status.success() {
+            return Err(anyhow::anyhow!(
+                "who -q failed with status {:?}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        let content = String::from_utf8_lossy(&output.stdout);
+        let (users, sessions) = parse_who_quick(&content);
+
+        Ok(UserStats {
+            timestamp: Instant::now(),
+            users,
+            sessions,
+        })
+    }
+}
+
+/// Parses the output of `who -q`, which lists the login names of all sessions,
+/// followed by a `# users=<N>` line with the number of sessions. Returns the
+/// number of distinct users and the number of sessions.
+fn parse_who_quick(content: &str) -> (u64, u64) {
+    let mut names = vec![];
+    let mut sessions = None;
+
+    for line in content.lines() {
+        if let Some(count) = line.trim().strip_prefix("# users=") {
+            sessions = count.trim().parse().ok();
+        } else {
+            names.extend(line.split_whitespace());
+        }
+    }
+
+    let users = names.iter().collect::<HashSet<_>>().len() as u64;
+    (users, sessions.unwrap_or(names.len() as u64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datasource::tests::HardcodedCommandRunner;
+
+    #[test]
+    fn test_parse_who_quick() {
+        let content = "alice bob alice root\n# users=4\n";
+        assert_eq!(parse_who_quick(content), (3, 4));
+    }
+
+    #[test]
+    fn test_parse_who_quick_without_users() {
+        assert_eq!(parse_who_quick("\n# users=0\n"), (0, 0));
+    }
+
+    #[tokio::test]
+    async fn test_users_datasource() {
+        let mut runner = HardcodedCommandRunner::new();
+        runner.add_response("who -q", 0, "alice\talice\n# users=2\n");
+
+        let ds = WhoUsers::new(runner);
+        let stats = ds.user_stats().await.unwrap();
+        assert_eq!(stats.users, 1);
+        assert_eq!(stats.sessions, 2);
+    }
+}
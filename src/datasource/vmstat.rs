@@ -0,0 +1,133 @@
+use crate::datasource::{Reader, Roots};
+use crate::metrics::vmstat::{DataSource, VmStats};
+use std::collections::{HashMap, HashSet};
+use tokio::time::Instant;
+
+const PATH_VMSTAT: &str = "vmstat";
+
+pub struct ProcVmStat<R> {
+    reader: R,
+    roots: Roots,
+    keys: HashSet<String>,
+}
+
+impl<R> ProcVmStat<R>
+where
+    R: Reader,
+{
+    pub fn new(reader: R, roots: Roots, keys: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            reader,
+            roots,
+            keys: keys.into_iter().collect(),
+        }
+    }
+}
+
+impl<R> DataSource for ProcVmStat<R>
+where
+    R: Reader,
+{
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn vmstat(&self) -> anyhow::Result<VmStats> {
+        let content = self
+            .reader
+            .read_to_string(self.roots.proc(PATH_VMSTAT))
+            .await?;
+
+        Ok(VmStats {
+            timestamp: Instant::now(),
+            values: parse_vmstat(&content, &self.keys),
+        })
+    }
+}
+
+/// Parses the `key value` lines of `/proc/vmstat`, keeping only the allowed keys
+fn parse_vmstat(content: &str, keys: &HashSet<String>) -> HashMap<String, u64> {
+    let mut values = HashMap::with_capacity(keys.len());
+
+    for line in content.lines() {
+        let Some((key, value)) = line.split_once(' ') else {
+            continue;
+        };
+
+        if !keys.contains(key) {
+            continue;
+        }
+
+        match value.trim().parse::<u64>() {
+            Ok(value) => {
+                values.insert(key.to_owned(), value);
+            }
+            Err(_) => tracing::debug!("Skipping invalid vmstat line: {}", line),
+        }
+    }
+
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datasource::tests::HardcodedReader;
+
+    const VMSTAT: &str = r#"nr_free_pages 1960349
+nr_zone_inactive_anon 49857
+nr_zone_active_anon 1102306
+nr_dirty 1317
+nr_writeback 0
+pgpgin 18474813
+pgpgout 62013684
+pswpin 1024
+pswpout 4096
+pgalloc_dma 0
+pgfree 1210933017
+pgfault 745325861
+pgmajfault 41234
+oom_kill 2
+compact_stall 0
+"#;
+
+    fn keys(keys: &[&str]) -> HashSet<String> {
+        keys.iter().map(|k| k.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_vmstat() {
+        let values = parse_vmstat(
+            VMSTAT,
+            &keys(&["pgfault", "pgmajfault", "pswpin", "pswpout", "oom_kill"]),
+        );
+
+        assert_eq!(values.len(), 5);
+        assert_eq!(values["pgfault"], 745325861);
+        assert_eq!(values["pgmajfault"], 41234);
+        assert_eq!(values["pswpin"], 1024);
+        assert_eq!(values["pswpout"], 4096);
+        assert_eq!(values["oom_kill"], 2);
+    }
+
+    #[test]
+    fn test_parse_vmstat_missing_keys() {
+        let values = parse_vmstat(VMSTAT, &keys(&["nr_dirty", "thp_fault_alloc"]));
+
+        assert_eq!(values.len(), 1);
+        assert_eq!(values["nr_dirty"], 1317);
+    }
+
+    #[tokio::test]
+    async fn test_vmstat_datasource() {
+        let mut reader = HardcodedReader::new();
+        reader.add_response(Roots::default().proc(PATH_VMSTAT), VMSTAT);
+
+        let ds = ProcVmStat::new(
+            reader,
+            Roots::default(),
+            ["pgfault".to_owned(), "pgpgin".to_owned()],
+        );
+        let stats = ds.vmstat().await.unwrap();
+
+        assert_eq!(stats.values.len(), 2);
+        assert_eq!(stats.values["pgpgin"], 18474813);
+    }
+}
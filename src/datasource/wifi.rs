@@ -0,0 +1,150 @@
+use crate::datasource::{Reader, Roots};
+use crate::domain::Probe;
+use crate::metrics::wifi::{DataSource, WifiStats, WirelessInterface};
+use tokio::time::Instant;
+
+const PATH_WIRELESS: &str = "net/wireless";
+
+/// The maximum link quality reported by the drivers, as shown by `iwconfig`
+const MAX_LINK_QUALITY: f64 = 70.0;
+
+/// Reported by the drivers which do not measure the level
+const NO_LEVEL: f64 = -256.0;
+
+pub struct ProcWireless<R> {
+    reader: R,
+    roots: Roots,
+}
+
+impl<R> ProcWireless<R>
+where
+    R: Reader,
+{
+    pub fn new(reader: R, roots: Roots) -> Self {
+        Self { reader, roots }
+    }
+}
+
+impl<R> Probe for ProcWireless<R>
+where
+    R: Reader,
+{
+    async fn supported(&self) -> bool {
+        // The file exists only when the kernel has wireless support
+        self.reader
+            .read_to_string(self.roots.proc(PATH_WIRELESS))
+            .await
+            .is_ok()
+    }
+}
+
+impl<R> DataSource for ProcWireless<R>
+where
+    R: Reader,
+{
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn wifi_stats(&self) -> anyhow::Result<WifiStats> {
+        let content = self
+            .reader
+            .read_to_string(self.roots.proc(PATH_WIRELESS))
+            .await?;
+
+        Ok(WifiStats {
+            timestamp: Instant::now(),
+            interfaces: parse_wireless(&content),
+        })
+    }
+}
+
+/// Parses the body of `/proc/net/wireless`:
+///
+/// ```text
+/// Inter-| sta-|   Quality        |   Discarded packets               | Missed | WE
+///  face | tus | link level noise |  nwid  crypt   frag  retry   misc | beacon | 22
+///  wlan0: 0000   70.  -40.  -256        0      0      0      0      0        0
+/// ```
+///
+/// The values have a trailing dot when they were updated since the last read.
+fn parse_wireless(content: &str) -> Vec<WirelessInterface> {
+    let mut interfaces = Vec::new();
+
+    for line in content.lines().skip(2) {
+        let Some((device, values)) = line.split_once(':') else {
+            continue;
+        };
+
+        let mut parts = values.split_whitespace().skip(1); // skip status
+        let mut next = || {
+            parts
+                .next()
+                .and_then(|v| v.trim_end_matches('.').parse::<f64>().ok())
+        };
+        let (Some(link), Some(level), Some(noise)) = (next(), next(), next()) else {
+            tracing::debug!(line, "Skipping malformed wireless line");
+            continue;
+        };
+
+        let measured = |v: f64| (v != NO_LEVEL).then_some(v);
+        interfaces.push(WirelessInterface {
+            device: device.trim().to_owned(),
+            link_quality: (link / MAX_LINK_QUALITY).clamp(0.0, 1.0),
+            signal_dbm: measured(level_dbm(level)),
+            noise_dbm: measured(level_dbm(noise)),
+        });
+    }
+
+    interfaces
+}
+
+/// Older drivers report the level as an unsigned byte, i.e. -40 dBm as 216
+fn level_dbm(value: f64) -> f64 {
+    if value > 0.0 { value - 256.0 } else { value }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datasource::tests::HardcodedReader;
+
+    const WIRELESS: &str =
+        "Inter-| sta-|   Quality        |   Discarded packets               | Missed | WE
+ face | tus | link level noise |  nwid  crypt   frag  retry   misc | beacon | 22
+ wlan0: 0000   52.  -58.  -256        0      0      0      0     11        0
+  wlp3s0: 0000   70   216   -92        0      0      0      0      0        0
+";
+
+    #[test]
+    fn test_parse_wireless() {
+        let interfaces = parse_wireless(WIRELESS);
+
+        assert_eq!(
+            interfaces,
+            [
+                WirelessInterface {
+                    device: "wlan0".into(),
+                    link_quality: 52.0 / 70.0,
+                    signal_dbm: Some(-58.0),
+                    noise_dbm: None,
+                },
+                WirelessInterface {
+                    device: "wlp3s0".into(),
+                    link_quality: 1.0,
+                    signal_dbm: Some(-40.0),
+                    noise_dbm: Some(-92.0),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_wireless_without_interfaces() {
+        let header = WIRELESS.lines().take(2).collect::<Vec<_>>().join("\n");
+        assert!(parse_wireless(&header).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_without_wireless() {
+        let data_source = ProcWireless::new(HardcodedReader::new(), Roots::default());
+        assert!(!data_source.supported().await);
+    }
+}
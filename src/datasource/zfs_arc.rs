@@ -1,19 +1,35 @@
-use crate::datasource::Reader;
+use crate::datasource::{Reader, Roots};
+use crate::domain::Probe;
 use crate::metrics::zfs_arc::{ArcStats, DataSource};
 use tokio::time::Instant;
 
-const PATH_ARCSTATS: &str = "/proc/spl/kstat/zfs/arcstats";
+const PATH_KSTAT_ZFS: &str = "spl/kstat/zfs";
+const PATH_ARCSTATS: &str = "spl/kstat/zfs/arcstats";
 
 pub struct KstatZfs<R> {
     reader: R,
+    roots: Roots,
 }
 
 impl<R> KstatZfs<R>
 where
     R: Reader,
 {
-    pub fn new(reader: R) -> Self {
-        Self { reader }
+    pub fn new(reader: R, roots: Roots) -> Self {
+        Self { reader, roots }
+    }
+}
+
+impl<R> Probe for KstatZfs<R>
+where
+    R: Reader,
+{
+    async fn supported(&self) -> bool {
+        // The kstats are present only while the ZFS module is loaded
+        self.reader
+            .read_dir(self.roots.proc(PATH_KSTAT_ZFS))
+            .await
+            .is_ok()
     }
 }
 
@@ -23,7 +39,10 @@ where
 {
     #[tracing::instrument(level = "debug", skip_all)]
     async fn arc_stats(&self) -> anyhow::Result<ArcStats> {
-        let content = self.reader.read_to_string(PATH_ARCSTATS).await?;
+        let content = self
+            .reader
+            .read_to_string(self.roots.proc(PATH_ARCSTATS))
+            .await?;
         let timestamp = Instant::now();
         let mut stats = ArcStats {
             timestamp,
@@ -32,6 +51,18 @@ where
             size: 0,
             target_size: 0,
             max_size: 0,
+            mru_size: 0,
+            mfu_size: 0,
+            data_size: 0,
+            metadata_size: 0,
+            mru_ghost_hits: 0,
+            mfu_ghost_hits: 0,
+            l2_hits: None,
+            l2_misses: None,
+            l2_size: None,
+            l2_asize: None,
+            l2_read_bytes: None,
+            l2_write_bytes: None,
         };
 
         // arcstats format:
@@ -57,6 +88,18 @@ where
                 "size" => stats.size = value,
                 "c" => stats.target_size = value,
                 "c_max" => stats.max_size = value,
+                "mru_size" => stats.mru_size = value,
+                "mfu_size" => stats.mfu_size = value,
+                "data_size" => stats.data_size = value,
+                "metadata_size" => stats.metadata_size = value,
+                "mru_ghost_hits" => stats.mru_ghost_hits = value,
+                "mfu_ghost_hits" => stats.mfu_ghost_hits = value,
+                "l2_hits" => stats.l2_hits = Some(value),
+                "l2_misses" => stats.l2_misses = Some(value),
+                "l2_size" => stats.l2_size = Some(value),
+                "l2_asize" => stats.l2_asize = Some(value),
+                "l2_read_bytes" => stats.l2_read_bytes = Some(value),
+                "l2_write_bytes" => stats.l2_write_bytes = Some(value),
                 _ => {}
             }
         }
@@ -67,8 +110,10 @@ where
 
 #[cfg(test)]
 mod tests {
+    use crate::datasource::Roots;
     use crate::datasource::tests::HardcodedReader;
-    use crate::datasource::zfs_arc::{KstatZfs, PATH_ARCSTATS};
+    use crate::datasource::zfs_arc::{KstatZfs, PATH_ARCSTATS, PATH_KSTAT_ZFS};
+    use crate::domain::Probe;
     use crate::metrics::zfs_arc::DataSource;
 
     fn mock_arcstats_body(hits: u64, misses: u64, size: u64) -> String {
@@ -87,9 +132,9 @@ mod tests {
     async fn test_arc_stats_parsing_success() -> anyhow::Result<()> {
         let mut reader = HardcodedReader::new();
         let content = mock_arcstats_body(500, 100, 1024);
-        reader.add_response(PATH_ARCSTATS, content);
+        reader.add_response(Roots::default().proc(PATH_ARCSTATS), content);
 
-        let data_source = KstatZfs::new(reader);
+        let data_source = KstatZfs::new(reader, Roots::default());
         let stats = data_source.arc_stats().await?;
 
         assert_eq!(stats.hits, 500);
@@ -101,13 +146,97 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_arc_stats_mru_mfu_breakdown() -> anyhow::Result<()> {
+        let mut reader = HardcodedReader::new();
+        let content = format!(
+            "{}\n\
+             mru_size 4 1000\n\
+             mfu_size 4 3000\n\
+             data_size 4 3500\n\
+             metadata_size 4 500\n\
+             mru_ghost_hits 4 42\n\
+             mfu_ghost_hits 4 7",
+            mock_arcstats_body(500, 100, 1024)
+        );
+        reader.add_response(Roots::default().proc(PATH_ARCSTATS), content);
+
+        let data_source = KstatZfs::new(reader, Roots::default());
+        let stats = data_source.arc_stats().await?;
+
+        assert_eq!(stats.mru_size, 1000);
+        assert_eq!(stats.mfu_size, 3000);
+        assert_eq!(stats.data_size, 3500);
+        assert_eq!(stats.metadata_size, 500);
+        assert_eq!(stats.mru_ghost_hits, 42);
+        assert_eq!(stats.mfu_ghost_hits, 7);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_arc_stats_l2arc() -> anyhow::Result<()> {
+        let mut reader = HardcodedReader::new();
+        let content = format!(
+            "{}\n\
+             l2_hits 4 300\n\
+             l2_misses 4 200\n\
+             l2_size 4 8192\n\
+             l2_asize 4 4096\n\
+             l2_read_bytes 4 65536\n\
+             l2_write_bytes 4 131072",
+            mock_arcstats_body(500, 100, 1024)
+        );
+        reader.add_response(Roots::default().proc(PATH_ARCSTATS), content);
+
+        let data_source = KstatZfs::new(reader, Roots::default());
+        let stats = data_source.arc_stats().await?;
+
+        assert_eq!(stats.hits, 500);
+        assert_eq!(stats.l2_hits, Some(300));
+        assert_eq!(stats.l2_misses, Some(200));
+        assert_eq!(stats.l2_size, Some(8192));
+        assert_eq!(stats.l2_asize, Some(4096));
+        assert_eq!(stats.l2_read_bytes, Some(65536));
+        assert_eq!(stats.l2_write_bytes, Some(131072));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_arc_stats_without_l2arc() -> anyhow::Result<()> {
+        let mut reader = HardcodedReader::new();
+        reader.add_response(
+            Roots::default().proc(PATH_ARCSTATS),
+            mock_arcstats_body(500, 100, 1024),
+        );
+
+        let data_source = KstatZfs::new(reader, Roots::default());
+        let stats = data_source.arc_stats().await?;
+
+        assert_eq!(stats.l2_hits, None);
+        assert_eq!(stats.l2_misses, None);
+        assert_eq!(stats.l2_size, None);
+        assert_eq!(stats.l2_asize, None);
+        assert_eq!(stats.l2_read_bytes, None);
+        assert_eq!(stats.l2_write_bytes, None);
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_arc_stats_sequential_reads() -> anyhow::Result<()> {
         let mut reader = HardcodedReader::new();
-        reader.add_response(PATH_ARCSTATS, mock_arcstats_body(10, 5, 100));
-        reader.add_response(PATH_ARCSTATS, mock_arcstats_body(20, 10, 200));
+        reader.add_response(
+            Roots::default().proc(PATH_ARCSTATS),
+            mock_arcstats_body(10, 5, 100),
+        );
+        reader.add_response(
+            Roots::default().proc(PATH_ARCSTATS),
+            mock_arcstats_body(20, 10, 200),
+        );
 
-        let data_source = KstatZfs::new(reader);
+        let data_source = KstatZfs::new(reader, Roots::default());
 
         let stats1 = data_source.arc_stats().await?;
         assert_eq!(stats1.hits, 10);
@@ -121,7 +250,7 @@ mod tests {
     #[tokio::test]
     async fn test_arc_stats_missing_file() {
         let reader = HardcodedReader::new(); // No response added
-        let data_source = KstatZfs::new(reader);
+        let data_source = KstatZfs::new(reader, Roots::default());
 
         let result = data_source.arc_stats().await;
 
@@ -130,13 +259,24 @@ mod tests {
         assert!(err_msg.contains("File not found"));
     }
 
+    #[tokio::test]
+    async fn test_supported() {
+        let mut reader = HardcodedReader::new();
+        reader.add_dir(Roots::default().proc(PATH_KSTAT_ZFS), &["arcstats", "tank"]);
+        assert!(KstatZfs::new(reader, Roots::default()).supported().await);
+
+        // The module is not loaded
+        let reader = HardcodedReader::new();
+        assert!(!KstatZfs::new(reader, Roots::default()).supported().await);
+    }
+
     #[tokio::test]
     async fn test_arc_stats_malformed_values() -> anyhow::Result<()> {
         let mut reader = HardcodedReader::new();
         let malformed = "header\nheader\nhits 4 NOT_A_NUMBER\nmisses 4 50";
-        reader.add_response(PATH_ARCSTATS, malformed);
+        reader.add_response(Roots::default().proc(PATH_ARCSTATS), malformed);
 
-        let data_source = KstatZfs::new(reader);
+        let data_source = KstatZfs::new(reader, Roots::default());
         let stats = data_source.arc_stats().await?;
 
         assert_eq!(stats.hits, 0);
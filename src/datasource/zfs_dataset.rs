@@ -1,19 +1,21 @@
-use crate::datasource::Reader;
+use crate::datasource::{Reader, Roots};
+use crate::domain::Probe;
 use crate::metrics::zfs_dataset::{DataSource, DatasetIoStats, ZfsIoStats};
 use tokio::fs;
 
-const KSTAT_ZFS: &str = "/proc/spl/kstat/zfs";
+const KSTAT_ZFS: &str = "spl/kstat/zfs";
 
 pub struct KstatZfsDatasetIo<R> {
     reader: R,
+    roots: Roots,
 }
 
 impl<R> KstatZfsDatasetIo<R>
 where
     R: Reader,
 {
-    pub fn new(reader: R) -> Self {
-        Self { reader }
+    pub fn new(reader: R, roots: Roots) -> Self {
+        Self { reader, roots }
     }
 
     fn parse_objset(&self, pool: &str, content: &str) -> Option<DatasetIoStats> {
@@ -53,6 +55,19 @@ where
     }
 }
 
+impl<R> Probe for KstatZfsDatasetIo<R>
+where
+    R: Reader,
+{
+    async fn supported(&self) -> bool {
+        // The kstats are present only while the ZFS module is loaded
+        self.reader
+            .read_dir(self.roots.proc(KSTAT_ZFS))
+            .await
+            .is_ok()
+    }
+}
+
 impl<R> DataSource for KstatZfsDatasetIo<R>
 where
     R: Reader,
@@ -61,7 +76,7 @@ where
     async fn dataset_io(&self) -> anyhow::Result<ZfsIoStats> {
         let mut datasets = Vec::new();
 
-        let mut pool_entries = fs::read_dir(KSTAT_ZFS).await?;
+        let mut pool_entries = fs::read_dir(self.roots.proc(KSTAT_ZFS)).await?;
         while let Some(pool_entry) = pool_entries.next_entry().await? {
             let path = pool_entry.path();
             if !path.is_dir() {
@@ -115,7 +130,7 @@ mod tests {
     #[tokio::test]
     async fn test_parse_objset_logic() {
         let reader = HardcodedReader::new();
-        let ds = KstatZfsDatasetIo::new(reader);
+        let ds = KstatZfsDatasetIo::new(reader, Roots::default());
 
         // 1. Test valid filesystem
         let content = mock_objset_body("tank/home", 100, 50);
@@ -143,7 +158,7 @@ mod tests {
         // Scenario: Dataset IO increases over two scrapes
         reader.add_response(path, mock_objset_body("tank/data", 100, 50));
         reader.add_response(path, mock_objset_body("tank/data", 110, 55));
-        let ds = KstatZfsDatasetIo::new(reader);
+        let ds = KstatZfsDatasetIo::new(reader, Roots::default());
 
         let content1 = ds.reader.read_to_string(path).await?;
         let stats1 = ds.parse_objset("tank", &content1).unwrap();
@@ -159,7 +174,7 @@ mod tests {
     #[tokio::test]
     async fn test_dataset_io_not_found() {
         let reader = HardcodedReader::new(); // No paths added
-        let ds = KstatZfsDatasetIo::new(reader);
+        let ds = KstatZfsDatasetIo::new(reader, Roots::default());
 
         let result = ds.reader.read_to_string("/non/existent/path").await;
         assert!(result.is_err());
@@ -169,7 +184,7 @@ mod tests {
     #[tokio::test]
     async fn test_parsing_with_other_kstats_present() {
         let reader = HardcodedReader::new();
-        let ds = KstatZfsDatasetIo::new(reader);
+        let ds = KstatZfsDatasetIo::new(reader, Roots::default());
 
         // Ensure that extra kstat fields don't break the parser
         let content = format!(
@@ -0,0 +1,179 @@
+use crate::datasource::CommandRunner;
+use crate::domain::Probe;
+use crate::metrics::zpool::{DataSource, PoolStats, ZpoolStats};
+use tokio::time::Instant;
+
+const ZPOOL: &str = "zpool";
+const ZPOOL_LIST_ARGS: [&str; 4] = ["list", "-Hp", "-o", ZPOOL_LIST_COLUMNS];
+const ZPOOL_LIST_COLUMNS: &str = "name,health,size,alloc,free,frag,cap,dedup";
+
+pub struct ZpoolCli<C> {
+    runner: C,
+}
+
+impl<C> ZpoolCli<C>
+where
+    C: CommandRunner,
+{
+    pub fn new(runner: C) -> Self {
+        Self { runner }
+    }
+}
+
+impl<C> Probe for ZpoolCli<C>
+where
+    C: CommandRunner,
+{
+    async fn supported(&self) -> bool {
+        // Listing the pools fails if the tools are not installed or the module is not loaded
+        self.pool_stats().await.is_ok()
+    }
+}
+
+impl<C> DataSource for ZpoolCli<C>
+where
+    C: CommandRunner,
+{
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn pool_stats(&self) -> anyhow::Result<ZpoolStats> {
+        let output = self.runner.run(ZPOOL, &ZPOOL_LIST_ARGS).await?;
+        if !output.status.success() {
+            return Err(anyhow::anyhow!(
+                "zpool list failed with status {:?}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        let content = String::from_utf8_lossy(&output.stdout);
+        Ok(ZpoolStats {
+            timestamp: Instant::now(),
+            pools: parse_zpool_list(&content),
+        })
+    }
+}
+
+/// Parses the output of `zpool list -Hp -o name,health,size,alloc,free,frag,cap,dedup`.
+///
+/// The `-H` flag produces tab-separated lines without a header, while `-p` prints the sizes
+/// as exact byte counts and the fragmentation/capacity as plain percentages. Properties
+/// that are not available for a pool are reported by zpool as `-`.
+fn parse_zpool_list(content: &str) -> Vec<PoolStats> {
+    let mut pools = Vec::new();
+
+    for line in content.lines() {
+        let columns = line.split('\t').map(str::trim).collect::<Vec<_>>();
+        if columns.len() < 8 {
+            tracing::debug!("Skipping unexpected zpool list line: {}", line);
+            continue;
+        }
+
+        let as_ratio = |x: f64| x / 100.0;
+
+        pools.push(PoolStats {
+            pool: columns[0].to_string(),
+            health: columns[1].to_string(),
+            size: columns[2].parse().ok(),
+            allocated: columns[3].parse().ok(),
+            free: columns[4].parse().ok(),
+            fragmentation: columns[5].trim_end_matches('%').parse().ok().map(as_ratio),
+            capacity: columns[6].trim_end_matches('%').parse().ok().map(as_ratio),
+            dedup: columns[7].trim_end_matches('x').parse().ok(),
+        });
+    }
+
+    pools
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datasource::tests::HardcodedCommandRunner;
+
+    const ZPOOL_LIST: &str =
+        "tank\tONLINE\t3985729650688\t1204465676288\t2781263974400\t3\t30\t1.00
+backup\tDEGRADED\t1992864825344\t1793578342809\t199286482535\t41\t90\t1.25
+scratch\tFAULTED\t-\t-\t-\t-\t-\t-
+";
+
+    #[test]
+    fn test_parse_zpool_list() {
+        let pools = parse_zpool_list(ZPOOL_LIST);
+        assert_eq!(pools.len(), 3);
+
+        assert_eq!(pools[0].pool, "tank");
+        assert_eq!(pools[0].health, "ONLINE");
+        assert_eq!(pools[0].size, Some(3985729650688));
+        assert_eq!(pools[0].allocated, Some(1204465676288));
+        assert_eq!(pools[0].free, Some(2781263974400));
+        assert_eq!(pools[0].fragmentation, Some(0.03));
+        assert_eq!(pools[0].capacity, Some(0.30));
+        assert_eq!(pools[0].dedup, Some(1.0));
+
+        assert_eq!(pools[1].pool, "backup");
+        assert_eq!(pools[1].health, "DEGRADED");
+        assert_eq!(pools[1].fragmentation, Some(0.41));
+        assert_eq!(pools[1].capacity, Some(0.90));
+        assert_eq!(pools[1].dedup, Some(1.25));
+
+        assert_eq!(pools[2].pool, "scratch");
+        assert_eq!(pools[2].health, "FAULTED");
+        assert_eq!(pools[2].size, None);
+        assert_eq!(pools[2].allocated, None);
+        assert_eq!(pools[2].free, None);
+        assert_eq!(pools[2].fragmentation, None);
+        assert_eq!(pools[2].capacity, None);
+        assert_eq!(pools[2].dedup, None);
+    }
+
+    #[test]
+    fn test_parse_zpool_list_skips_malformed_lines() {
+        let content = "tank\tONLINE\n\nstorage\tONLINE\t100\t50\t50\t0\t50\t1.00\n";
+
+        let pools = parse_zpool_list(content);
+        assert_eq!(pools.len(), 1);
+        assert_eq!(pools[0].pool, "storage");
+    }
+
+    #[tokio::test]
+    async fn test_zpool_datasource() {
+        let mut runner = HardcodedCommandRunner::new();
+        runner.add_response(
+            "zpool list -Hp -o name,health,size,alloc,free,frag,cap,dedup",
+            0,
+            ZPOOL_LIST,
+        );
+
+        let ds = ZpoolCli::new(runner);
+        let stats = ds.pool_stats().await.unwrap();
+        assert_eq!(stats.pools.len(), 3);
+        assert_eq!(stats.pools[0].pool, "tank");
+    }
+
+    #[tokio::test]
+    async fn test_zpool_datasource_command_failure() {
+        let mut runner = HardcodedCommandRunner::new();
+        runner.add_response(
+            "zpool list -Hp -o name,health,size,alloc,free,frag,cap,dedup",
+            1,
+            "",
+        );
+
+        let ds = ZpoolCli::new(runner);
+        assert!(ds.pool_stats().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_without_zpool() {
+        let ds = ZpoolCli::new(HardcodedCommandRunner::new());
+        assert!(!ds.supported().await);
+
+        let mut runner = HardcodedCommandRunner::new();
+        runner.add_response(
+            "zpool list -Hp -o name,health,size,alloc,free,frag,cap,dedup",
+            0,
+            ZPOOL_LIST,
+        );
+        assert!(ZpoolCli::new(runner).supported().await);
+    }
+}
@@ -0,0 +1,142 @@
+use crate::datasource::{Reader, Roots};
+use crate::domain::Probe;
+use crate::metrics::zram::{DataSource, MmStat, ZramDevice, ZramStats};
+use std::io::ErrorKind;
+use tokio::time::Instant;
+
+const PATH_BLOCK: &str = "block";
+// Present while the zram module is loaded, even without any devices
+const PATH_ZRAM_CONTROL: &str = "class/zram-control";
+
+pub struct SysfsZram<R> {
+    reader: R,
+    roots: Roots,
+}
+
+impl<R> SysfsZram<R>
+where
+    R: Reader,
+{
+    pub fn new(reader: R, roots: Roots) -> Self {
+        Self { reader, roots }
+    }
+
+    /// Returns `None` if the device has been removed since the directory was listed
+    async fn device(&self, device: &str) -> anyhow::Result<Option<ZramDevice>> {
+        let path = format!("{}/{}/mm_stat", self.roots.sys(PATH_BLOCK), device);
+        let content = match self.reader.read_to_string(&path).await {
+            Ok(content) => content,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(anyhow::anyhow!("Failed to read {}: {}", path, e)),
+        };
+
+        let Some(mm_stat) = parse_mm_stat(&content) else {
+            return Err(anyhow::anyhow!("Unexpected {} content: {}", path, content));
+        };
+
+        let path = format!("{}/{}/disksize", self.roots.sys(PATH_BLOCK), device);
+        let disk_size = self
+            .reader
+            .read_to_string(&path)
+            .await
+            .ok()
+            .and_then(|v| v.trim().parse().ok());
+
+        Ok(Some(ZramDevice {
+            device: device.to_owned(),
+            disk_size,
+            mm_stat,
+        }))
+    }
+}
+
+impl<R> Probe for SysfsZram<R>
+where
+    R: Reader,
+{
+    async fn supported(&self) -> bool {
+        self.reader
+            .read_dir(self.roots.sys(PATH_ZRAM_CONTROL))
+            .await
+            .is_ok()
+    }
+}
+
+impl<R> DataSource for SysfsZram<R>
+where
+    R: Reader,
+{
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn zram_stats(&self) -> anyhow::Result<ZramStats> {
+        let mut names = self.reader.read_dir(self.roots.sys(PATH_BLOCK)).await?;
+        names.retain(|name| name.starts_with("zram"));
+        names.sort_unstable();
+
+        let mut devices = Vec::with_capacity(names.len());
+        for name in names {
+            devices.extend(self.device(&name).await?);
+        }
+
+        Ok(ZramStats {
+            timestamp: Instant::now(),
+            devices,
+        })
+    }
+}
+
+/// Parses `mm_stat`, whose leading columns are `orig_data_size`, `compr_data_size` and
+/// `mem_used_total`. Newer kernels append more columns, which are ignored.
+fn parse_mm_stat(content: &str) -> Option<MmStat> {
+    let mut columns = content.split_whitespace().map(|v| v.parse::<u64>().ok());
+
+    Some(MmStat {
+        original: columns.next()??,
+        compressed: columns.next()??,
+        memory_used: columns.next()??,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datasource::tests::HardcodedReader;
+
+    const MM_STAT: &str =
+        "  4194304   1048576   1310720        0   1310720        12        0        0        0\n";
+
+    #[test]
+    fn test_parse_mm_stat() {
+        assert_eq!(
+            parse_mm_stat(MM_STAT),
+            Some(MmStat {
+                original: 4194304,
+                compressed: 1048576,
+                memory_used: 1310720,
+            })
+        );
+
+        assert_eq!(parse_mm_stat("4194304 1048576"), None);
+        assert_eq!(parse_mm_stat("4194304 x 1310720"), None);
+    }
+
+    #[tokio::test]
+    async fn test_zram_stats() -> anyhow::Result<()> {
+        let block = Roots::default().sys(PATH_BLOCK);
+        let mut reader = HardcodedReader::new();
+        reader.add_dir(&block, &["sda", "zram1", "zram0", "loop0"]);
+        reader.add_response(format!("{block}/zram0/mm_stat"), MM_STAT);
+        reader.add_response(format!("{block}/zram0/disksize"), "8589934592\n");
+
+        let stats = SysfsZram::new(reader, Roots::default())
+            .zram_stats()
+            .await?;
+
+        // zram1 has been hot-removed
+        assert_eq!(stats.devices.len(), 1);
+        assert_eq!(stats.devices[0].device, "zram0");
+        assert_eq!(stats.devices[0].disk_size, Some(8589934592));
+        assert_eq!(stats.devices[0].mm_stat.compression_ratio(), Some(4.0));
+
+        Ok(())
+    }
+}
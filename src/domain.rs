@@ -1,10 +1,47 @@
+use crate::metrics::MetricOptions;
 use prometheus::Registry;
 
 pub trait Metric {
-    fn register(self, registry: &Registry) -> anyhow::Result<Box<dyn Collector>>;
+    fn name(&self) -> &'static str;
+
+    /// Checks whether the subsystem backing the metric is available on this machine.
+    /// Unsupported metrics are replaced by a no-op collector, instead of failing on each scrape.
+    fn supported(&self) -> impl Future<Output = bool> + Send {
+        async { true }
+    }
+
+    fn register(
+        self,
+        registry: &Registry,
+        options: &MetricOptions,
+    ) -> anyhow::Result<Box<dyn Collector>>;
+}
+
+/// Implemented by the data sources whose backing subsystem may be absent,
+/// e.g. a kernel module which is not loaded or a daemon which is not installed
+pub trait Probe {
+    /// Checks whether the backing subsystem is available on this machine
+    fn supported(&self) -> impl Future<Output = bool> + Send {
+        async { true }
+    }
 }
 
 #[async_trait::async_trait]
 pub trait Collector: Send + Sync + 'static {
+    fn name(&self) -> &'static str;
+
     async fn collect(&self) -> anyhow::Result<()>;
+
+    /// The last measurement as JSON, exposed by the debug endpoint for troubleshooting
+    fn snapshot(&self) -> Option<serde_json::Value> {
+        None
+    }
+}
+
+/// A collector along with the registry that holds only its own metrics
+pub struct RegisteredCollector {
+    pub collector: Box<dyn Collector>,
+    pub registry: Registry,
+    /// Collectors with higher priority are executed first
+    pub priority: i32,
 }
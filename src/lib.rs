@@ -1,4 +1,3 @@
-#![deny(unsafe_code)]
 #![deny(warnings)]
 #![deny(unused_must_use)]
 #![deny(unexpected_cfgs)]
@@ -8,4 +7,6 @@ pub(crate) mod datasource;
 pub mod domain;
 pub mod logging;
 pub mod metrics;
+pub mod self_check;
 pub mod server;
+pub mod statsd;
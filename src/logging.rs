@@ -1,4 +1,4 @@
-use crate::config::Log;
+use crate::config::{Configuration, Log};
 use std::error::Error;
 use std::str::FromStr;
 use tracing::Subscriber;
@@ -9,15 +9,54 @@ use tracing_subscriber::fmt::MakeWriter;
 use tracing_subscriber::fmt::format::FmtSpan;
 use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::util::SubscriberInitExt;
-use tracing_subscriber::{EnvFilter, Layer, Registry, fmt, registry};
+use tracing_subscriber::{EnvFilter, Layer, Registry, fmt, registry, reload};
 
 pub struct Guard {
     _stdout_guard: Option<WorkerGuard>,
     _file_guard: Option<WorkerGuard>,
+    reloader: LogReloader,
+}
+
+impl Guard {
+    pub fn reloader(&self) -> LogReloader {
+        self.reloader.clone()
+    }
+}
+
+/// Applies a new log level and directives without touching the log outputs
+#[derive(Clone)]
+pub struct LogReloader {
+    handle: reload::Handle<EnvFilter, Registry>,
+}
+
+impl LogReloader {
+    pub fn reload(&self, cfg: &Log) -> anyhow::Result<()> {
+        self.handle.reload(construct_env_filter(cfg))?;
+        Ok(())
+    }
+}
+
+/// Re-reads the `log` section of the configuration on SIGUSR1 and applies it. The rest
+/// of the configuration is ignored, so the collectors and the listener stay untouched.
+#[cfg(unix)]
+pub async fn reload_on_signal(config_path: String, reloader: LogReloader) -> anyhow::Result<()> {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    let mut signal = signal(SignalKind::user_defined1())?;
+    while signal.recv().await.is_some() {
+        let result = Configuration::load(&config_path).and_then(|cfg| reloader.reload(&cfg.log));
+
+        match result {
+            Ok(()) => tracing::info!("SIGUSR1 received, reloaded the log configuration"),
+            Err(e) => tracing::error!(error=?e, "Failed to reload the log configuration"),
+        }
+    }
+
+    Ok(())
 }
 
 pub fn setup_logging(cfg: &Log) -> Result<Guard, Box<dyn Error + Send + Sync>> {
-    let filter = construct_env_filter(cfg);
+    let (filter, handle) = reload::Layer::new(construct_env_filter(cfg));
 
     let (stdout_layer, stdout_guard) = construct_stdout_layer(cfg)
         .map(|(l, g)| (Some(l), Some(g)))
@@ -36,6 +75,7 @@ pub fn setup_logging(cfg: &Log) -> Result<Guard, Box<dyn Error + Send + Sync>> {
     Ok(Guard {
         _stdout_guard: stdout_guard,
         _file_guard: file_guard,
+        reloader: LogReloader { handle },
     })
 }
 
@@ -135,3 +175,40 @@ where
         .with_span_events(FmtSpan::CLOSE)
         .boxed()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing::Level;
+
+    #[test]
+    fn test_reload_log_level() {
+        let cfg = Log {
+            level: "INFO".to_owned(),
+            ..Default::default()
+        };
+
+        let (filter, handle) = reload::Layer::new(construct_env_filter(&cfg));
+        let reloader = LogReloader { handle };
+        let subscriber = Registry::default().with(filter);
+
+        tracing::subscriber::with_default(subscriber, || {
+            assert!(tracing::enabled!(Level::INFO));
+            assert!(!tracing::enabled!(Level::DEBUG));
+
+            let cfg = Log {
+                level: "DEBUG".to_owned(),
+                ..Default::default()
+            };
+            reloader.reload(&cfg).unwrap();
+            assert!(tracing::enabled!(Level::DEBUG));
+
+            let cfg = Log {
+                level: "WARN".to_owned(),
+                ..Default::default()
+            };
+            reloader.reload(&cfg).unwrap();
+            assert!(!tracing::enabled!(Level::INFO));
+        });
+    }
+}
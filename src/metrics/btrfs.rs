@@ -0,0 +1,301 @@
+use crate::domain::{Collector, Metric, Probe};
+use crate::metrics::no_operation::NoOpCollector;
+use crate::metrics::util::{
+    MetricOptions, counter, gauge, into_labels, probe, snapshot, update_measurement_if,
+};
+use anyhow::Context;
+use prometheus::Registry;
+use prometheus::core::Desc;
+use prometheus::proto::MetricFamily;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use tokio::time::Instant;
+
+const NAME: &str = "btrfs";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Config {
+    pub enabled: bool,
+    pub priority: Option<i32>,
+    pub max_series: Option<usize>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            priority: None,
+            max_series: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct DeviceStats {
+    pub mount: String,
+    pub device: String,
+    pub write_errors: u64,
+    pub read_errors: u64,
+    pub flush_errors: u64,
+    pub corruption_errors: u64,
+    pub generation_errors: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AllocationStats {
+    pub mount: String,
+    /// The block group type: data, metadata or system
+    pub kind: String,
+    /// The RAID profile of the block group: single, dup, raid1, etc
+    pub profile: String,
+    pub size: u64,
+    pub used: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BtrfsStats {
+    #[serde(skip)]
+    pub timestamp: Instant,
+    pub devices: Vec<DeviceStats>,
+    pub allocations: Vec<AllocationStats>,
+}
+
+pub trait DataSource: Probe {
+    fn btrfs_stats(&self) -> impl Future<Output = anyhow::Result<BtrfsStats>> + Send;
+}
+
+#[derive(Clone)]
+struct Metrics {
+    state: Arc<Mutex<Option<BtrfsStats>>>,
+    write_errors: Desc,
+    read_errors: Desc,
+    flush_errors: Desc,
+    corruption_errors: Desc,
+    generation_errors: Desc,
+    allocation_size: Desc,
+    allocation_used: Desc,
+}
+
+impl Metrics {
+    pub fn new(
+        state: Arc<Mutex<Option<BtrfsStats>>>,
+        options: &MetricOptions,
+    ) -> anyhow::Result<Self> {
+        let device_labels = vec!["mount".to_owned(), "device".to_owned()];
+        let allocation_labels = vec!["mount".to_owned(), "type".to_owned(), "profile".to_owned()];
+
+        Ok(Self {
+            state,
+            write_errors: Desc::new(
+                options.name("btrfs_device_write_errors_total"),
+                "Number of failed writes to the device".into(),
+                device_labels.clone(),
+                options.const_labels(),
+            )?,
+            read_errors: Desc::new(
+                options.name("btrfs_device_read_errors_total"),
+                "Number of failed reads from the device".into(),
+                device_labels.clone(),
+                options.const_labels(),
+            )?,
+            flush_errors: Desc::new(
+                options.name("btrfs_device_flush_errors_total"),
+                "Number of failed flushes of the device".into(),
+                device_labels.clone(),
+                options.const_labels(),
+            )?,
+            corruption_errors: Desc::new(
+                options.name("btrfs_device_corruption_errors_total"),
+                "Number of checksum mismatches detected on the device".into(),
+                device_labels.clone(),
+                options.const_labels(),
+            )?,
+            generation_errors: Desc::new(
+                options.name("btrfs_device_generation_errors_total"),
+                "Number of blocks with an unexpected generation on the device".into(),
+                device_labels,
+                options.const_labels(),
+            )?,
+            allocation_size: Desc::new(
+                options.name("btrfs_allocation_size_bytes"),
+                "Amount of space allocated to the block group type".into(),
+                allocation_labels.clone(),
+                options.const_labels(),
+            )?,
+            allocation_used: Desc::new(
+                options.name("btrfs_allocation_used_bytes"),
+                "Amount of the allocated space used by the block group type".into(),
+                allocation_labels,
+                options.const_labels(),
+            )?,
+        })
+    }
+
+    pub fn register(&self, registry: &Registry) -> anyhow::Result<()> {
+        registry.register(Box::new(self.clone()))?;
+        Ok(())
+    }
+}
+
+impl prometheus::core::Collector for Metrics {
+    fn desc(&self) -> Vec<&Desc> {
+        vec![
+            &self.write_errors,
+            &self.read_errors,
+            &self.flush_errors,
+            &self.corruption_errors,
+            &self.generation_errors,
+            &self.allocation_size,
+            &self.allocation_used,
+        ]
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        let guard = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(stats) = guard.as_ref() else {
+            return vec![];
+        };
+
+        let mut mf = Vec::with_capacity(stats.devices.len() * 5 + stats.allocations.len() * 2);
+        for device in &stats.devices {
+            let l = into_labels(&[("mount", &device.mount), ("device", &device.device)]);
+
+            mf.push(counter(
+                &self.write_errors,
+                l.clone(),
+                device.write_errors as f64,
+            ));
+            mf.push(counter(
+                &self.read_errors,
+                l.clone(),
+                device.read_errors as f64,
+            ));
+            mf.push(counter(
+                &self.flush_errors,
+                l.clone(),
+                device.flush_errors as f64,
+            ));
+            mf.push(counter(
+                &self.corruption_errors,
+                l.clone(),
+                device.corruption_errors as f64,
+            ));
+            mf.push(counter(
+                &self.generation_errors,
+                l,
+                device.generation_errors as f64,
+            ));
+        }
+
+        for allocation in &stats.allocations {
+            let l = into_labels(&[
+                ("mount", &allocation.mount),
+                ("type", &allocation.kind),
+                ("profile", &allocation.profile),
+            ]);
+
+            mf.push(gauge(
+                &self.allocation_size,
+                l.clone(),
+                allocation.size as f64,
+            ));
+            mf.push(gauge(&self.allocation_used, l, allocation.used as f64));
+        }
+
+        mf
+    }
+}
+
+pub struct Btrfs<T> {
+    config: Config,
+    data_source: T,
+}
+
+impl<T> Btrfs<T>
+where
+    T: DataSource + Send + Sync + 'static,
+{
+    pub fn new(config: Config, data_source: T) -> Self {
+        Self {
+            config,
+            data_source,
+        }
+    }
+}
+
+impl<T> Metric for Btrfs<T>
+where
+    T: DataSource + Send + Sync + 'static,
+{
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    async fn supported(&self) -> bool {
+        probe(self.config.enabled, &self.data_source).await
+    }
+
+    fn register(
+        self,
+        registry: &Registry,
+        options: &MetricOptions,
+    ) -> anyhow::Result<Box<dyn Collector>> {
+        if !self.config.enabled {
+            return Ok(Box::new(NoOpCollector::new(NAME)));
+        }
+
+        let collector = BtrfsCollector::new(self.data_source);
+        let metrics = Metrics::new(collector.measurements(), options)?;
+        metrics.register(registry)?;
+
+        Ok(Box::new(collector))
+    }
+}
+
+struct BtrfsCollector<T> {
+    measurement: Arc<Mutex<Option<BtrfsStats>>>,
+    data_source: T,
+}
+
+impl<T> BtrfsCollector<T>
+where
+    T: DataSource,
+{
+    fn new(data_source: T) -> Self {
+        Self {
+            measurement: Arc::new(Mutex::new(None)),
+            data_source,
+        }
+    }
+
+    fn measurements(&self) -> Arc<Mutex<Option<BtrfsStats>>> {
+        Arc::clone(&self.measurement)
+    }
+}
+
+#[async_trait::async_trait]
+impl<T> Collector for BtrfsCollector<T>
+where
+    T: DataSource + Send + Sync + 'static,
+{
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn collect(&self) -> anyhow::Result<()> {
+        let stats = self
+            .data_source
+            .btrfs_stats()
+            .await
+            .context("Failed to collect btrfs statistics");
+
+        update_measurement_if(&self.measurement, stats, |old, new| {
+            old.timestamp < new.timestamp
+        })
+    }
+
+    fn snapshot(&self) -> Option<serde_json::Value> {
+        snapshot(&self.measurement)
+    }
+}
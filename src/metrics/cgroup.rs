@@ -0,0 +1,279 @@
+use crate::domain::{Collector, Metric, Probe};
+use crate::metrics::no_operation::NoOpCollector;
+use crate::metrics::util::{
+    MetricOptions, into_labels, maybe_counter, maybe_gauge, probe, snapshot, update_measurement_if,
+};
+use anyhow::Context;
+use prometheus::Registry;
+use prometheus::core::Desc;
+use prometheus::proto::MetricFamily;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use tokio::time::Instant;
+
+const NAME: &str = "cgroup";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Config {
+    pub enabled: bool,
+    pub priority: Option<i32>,
+    pub max_series: Option<usize>,
+    /// The cgroups to watch, relative to the cgroup v2 mount, e.g. `system.slice`.
+    /// Only the listed ones are reported, because there is one per service.
+    pub cgroups: Vec<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            priority: None,
+            max_series: None,
+            cgroups: vec!["system.slice".to_owned(), "user.slice".to_owned()],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CgroupStats {
+    pub cgroup: String,
+    pub memory_used: Option<u64>,
+    /// Absent when the memory is not limited
+    pub memory_limit: Option<u64>,
+    pub cpu_usage_seconds: Option<f64>,
+    pub io_read_bytes: Option<u64>,
+    pub io_write_bytes: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CgroupsStats {
+    #[serde(skip)]
+    pub timestamp: Instant,
+    pub cgroups: Vec<CgroupStats>,
+}
+
+pub trait DataSource: Probe {
+    /// Reads the stats of the given cgroups, skipping the ones which do not exist
+    fn cgroup_stats(
+        &self,
+        cgroups: &[String],
+    ) -> impl Future<Output = anyhow::Result<CgroupsStats>> + Send;
+}
+
+#[derive(Clone)]
+struct Metrics {
+    state: Arc<Mutex<Option<CgroupsStats>>>,
+    zero_if_absent: HashSet<String>,
+    memory_used: Desc,
+    memory_limit: Desc,
+    cpu_usage: Desc,
+    io_read: Desc,
+    io_write: Desc,
+}
+
+impl Metrics {
+    pub fn new(
+        state: Arc<Mutex<Option<CgroupsStats>>>,
+        options: &MetricOptions,
+    ) -> anyhow::Result<Self> {
+        let labels = vec!["cgroup".into()];
+
+        Ok(Self {
+            state,
+            zero_if_absent: options.zero_if_absent.clone(),
+            memory_used: Desc::new(
+                options.name("system_cgroup_memory_used_bytes"),
+                "Memory used by the cgroup and its descendants".into(),
+                labels.clone(),
+                options.const_labels(),
+            )?,
+            memory_limit: Desc::new(
+                options.name("system_cgroup_memory_limit_bytes"),
+                "Memory limit of the cgroup".into(),
+                labels.clone(),
+                options.const_labels(),
+            )?,
+            cpu_usage: Desc::new(
+                options.name("system_cgroup_cpu_usage_seconds_total"),
+                "Total CPU time used by the cgroup".into(),
+                labels.clone(),
+                options.const_labels(),
+            )?,
+            io_read: Desc::new(
+                options.name("system_cgroup_io_read_bytes_total"),
+                "Total bytes read by the cgroup from all block devices".into(),
+                labels.clone(),
+                options.const_labels(),
+            )?,
+            io_write: Desc::new(
+                options.name("system_cgroup_io_write_bytes_total"),
+                "Total bytes written by the cgroup to all block devices".into(),
+                labels,
+                options.const_labels(),
+            )?,
+        })
+    }
+
+    pub fn register(&self, registry: &Registry) -> anyhow::Result<()> {
+        registry.register(Box::new(self.clone()))?;
+        Ok(())
+    }
+}
+
+impl prometheus::core::Collector for Metrics {
+    fn desc(&self) -> Vec<&Desc> {
+        vec![
+            &self.memory_used,
+            &self.memory_limit,
+            &self.cpu_usage,
+            &self.io_read,
+            &self.io_write,
+        ]
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        let guard = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(stats) = guard.as_ref() else {
+            return vec![];
+        };
+
+        let mut mf = Vec::new();
+        for cgroup in &stats.cgroups {
+            let l = into_labels(&[("cgroup", &cgroup.cgroup)]);
+            maybe_gauge(
+                &self.zero_if_absent,
+                &mut mf,
+                &self.memory_used,
+                &l,
+                cgroup.memory_used,
+            );
+            maybe_gauge(
+                &self.zero_if_absent,
+                &mut mf,
+                &self.memory_limit,
+                &l,
+                cgroup.memory_limit,
+            );
+            maybe_counter(
+                &self.zero_if_absent,
+                &mut mf,
+                &self.cpu_usage,
+                &l,
+                cgroup.cpu_usage_seconds,
+            );
+            maybe_counter(
+                &self.zero_if_absent,
+                &mut mf,
+                &self.io_read,
+                &l,
+                cgroup.io_read_bytes,
+            );
+            maybe_counter(
+                &self.zero_if_absent,
+                &mut mf,
+                &self.io_write,
+                &l,
+                cgroup.io_write_bytes,
+            );
+        }
+
+        mf
+    }
+}
+
+pub struct Cgroup<T> {
+    config: Config,
+    data_source: T,
+}
+
+impl<T> Cgroup<T>
+where
+    T: DataSource + Send + Sync + 'static,
+{
+    pub fn new(config: Config, data_source: T) -> Self {
+        Self {
+            config,
+            data_source,
+        }
+    }
+}
+
+impl<T> Metric for Cgroup<T>
+where
+    T: DataSource + Send + Sync + 'static,
+{
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    async fn supported(&self) -> bool {
+        probe(self.config.enabled, &self.data_source).await
+    }
+
+    fn register(
+        self,
+        registry: &Registry,
+        options: &MetricOptions,
+    ) -> anyhow::Result<Box<dyn Collector>> {
+        if !self.config.enabled {
+            return Ok(Box::new(NoOpCollector::new(NAME)));
+        }
+
+        let collector = CgroupCollector::new(self.data_source, self.config.cgroups);
+        let metrics = Metrics::new(collector.measurements(), options)?;
+        metrics.register(registry)?;
+
+        Ok(Box::new(collector))
+    }
+}
+
+struct CgroupCollector<T> {
+    measurement: Arc<Mutex<Option<CgroupsStats>>>,
+    data_source: T,
+    cgroups: Vec<String>,
+}
+
+impl<T> CgroupCollector<T>
+where
+    T: DataSource,
+{
+    fn new(data_source: T, cgroups: Vec<String>) -> Self {
+        Self {
+            measurement: Arc::new(Mutex::new(None)),
+            data_source,
+            cgroups,
+        }
+    }
+
+    fn measurements(&self) -> Arc<Mutex<Option<CgroupsStats>>> {
+        Arc::clone(&self.measurement)
+    }
+}
+
+#[async_trait::async_trait]
+impl<T> Collector for CgroupCollector<T>
+where
+    T: DataSource + Send + Sync + 'static,
+{
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn collect(&self) -> anyhow::Result<()> {
+        let stats = self
+            .data_source
+            .cgroup_stats(&self.cgroups)
+            .await
+            .context("Failed to collect cgroup statistics");
+
+        update_measurement_if(&self.measurement, stats, |old, new| {
+            old.timestamp < new.timestamp
+        })
+    }
+
+    fn snapshot(&self) -> Option<serde_json::Value> {
+        snapshot(&self.measurement)
+    }
+}
@@ -0,0 +1,282 @@
+use crate::domain::{Collector, Metric, Probe};
+use crate::metrics::no_operation::NoOpCollector;
+use crate::metrics::util::{
+    MetricOptions, maybe_counter, maybe_gauge, probe, snapshot, update_measurement_if,
+};
+use anyhow::Context;
+use prometheus::Registry;
+use prometheus::core::Desc;
+use prometheus::proto::MetricFamily;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use tokio::time::Instant;
+
+const NAME: &str = "conntrack";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Config {
+    pub enabled: bool,
+    pub priority: Option<i32>,
+    pub max_series: Option<usize>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            priority: None,
+            max_series: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ConntrackStats {
+    #[serde(skip)]
+    pub timestamp: Instant,
+    pub entries: u64,
+    pub max_entries: Option<u64>,
+    /// The counters below are summed across all CPUs and are absent on
+    /// kernels which do not report them
+    pub inserted: Option<u64>,
+    pub dropped: Option<u64>,
+    pub invalid: Option<u64>,
+}
+
+impl ConntrackStats {
+    pub fn usage(&self) -> Option<f64> {
+        self.max_entries
+            .filter(|max| *max > 0)
+            .map(|max| self.entries as f64 / max as f64)
+    }
+}
+
+pub trait DataSource: Probe {
+    fn conntrack_stats(&self) -> impl Future<Output = anyhow::Result<ConntrackStats>> + Send;
+}
+
+#[derive(Clone)]
+struct Metrics {
+    state: Arc<Mutex<Option<ConntrackStats>>>,
+    zero_if_absent: HashSet<String>,
+    entries: Desc,
+    max_entries: Desc,
+    usage: Desc,
+    inserted: Desc,
+    dropped: Desc,
+    invalid: Desc,
+}
+
+impl Metrics {
+    pub fn new(
+        state: Arc<Mutex<Option<ConntrackStats>>>,
+        options: &MetricOptions,
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
+            state,
+            zero_if_absent: options.zero_if_absent.clone(),
+            entries: Desc::new(
+                options.name("system_conntrack_entries"),
+                "Number of tracked connections".into(),
+                vec![],
+                options.const_labels(),
+            )?,
+            max_entries: Desc::new(
+                options.name("system_conntrack_entries_limit"),
+                "Maximum number of tracked connections".into(),
+                vec![],
+                options.const_labels(),
+            )?,
+            usage: Desc::new(
+                options.name("system_conntrack_usage_ratio"),
+                "Used share of the connection tracking table".into(),
+                vec![],
+                options.const_labels(),
+            )?,
+            inserted: Desc::new(
+                options.name("system_conntrack_insert_total"),
+                "Total connections inserted into the tracking table".into(),
+                vec![],
+                options.const_labels(),
+            )?,
+            dropped: Desc::new(
+                options.name("system_conntrack_drop_total"),
+                "Total packets dropped because a connection could not be tracked".into(),
+                vec![],
+                options.const_labels(),
+            )?,
+            invalid: Desc::new(
+                options.name("system_conntrack_invalid_total"),
+                "Total packets which could not be associated with a connection".into(),
+                vec![],
+                options.const_labels(),
+            )?,
+        })
+    }
+
+    pub fn register(&self, registry: &Registry) -> anyhow::Result<()> {
+        registry.register(Box::new(self.clone()))?;
+        Ok(())
+    }
+}
+
+impl prometheus::core::Collector for Metrics {
+    fn desc(&self) -> Vec<&Desc> {
+        vec![
+            &self.entries,
+            &self.max_entries,
+            &self.usage,
+            &self.inserted,
+            &self.dropped,
+            &self.invalid,
+        ]
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        let guard = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(stats) = guard.as_ref() else {
+            return vec![];
+        };
+
+        let mut mf = Vec::with_capacity(6);
+        maybe_gauge(
+            &self.zero_if_absent,
+            &mut mf,
+            &self.entries,
+            &[],
+            Some(stats.entries),
+        );
+        maybe_gauge(
+            &self.zero_if_absent,
+            &mut mf,
+            &self.max_entries,
+            &[],
+            stats.max_entries,
+        );
+        maybe_gauge(
+            &self.zero_if_absent,
+            &mut mf,
+            &self.usage,
+            &[],
+            stats.usage(),
+        );
+        maybe_counter(
+            &self.zero_if_absent,
+            &mut mf,
+            &self.inserted,
+            &[],
+            stats.inserted,
+        );
+        maybe_counter(
+            &self.zero_if_absent,
+            &mut mf,
+            &self.dropped,
+            &[],
+            stats.dropped,
+        );
+        maybe_counter(
+            &self.zero_if_absent,
+            &mut mf,
+            &self.invalid,
+            &[],
+            stats.invalid,
+        );
+
+        mf
+    }
+}
+
+pub struct Conntrack<T> {
+    config: Config,
+    data_source: T,
+}
+
+impl<T> Conntrack<T>
+where
+    T: DataSource + Send + Sync + 'static,
+{
+    pub fn new(config: Config, data_source: T) -> Self {
+        Self {
+            config,
+            data_source,
+        }
+    }
+}
+
+impl<T> Metric for Conntrack<T>
+where
+    T: DataSource + Send + Sync + 'static,
+{
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    async fn supported(&self) -> bool {
+        probe(self.config.enabled, &self.data_source).await
+    }
+
+    fn register(
+        self,
+        registry: &Registry,
+        options: &MetricOptions,
+    ) -> anyhow::Result<Box<dyn Collector>> {
+        if !self.config.enabled {
+            return Ok(Box::new(NoOpCollector::new(NAME)));
+        }
+
+        let collector = ConntrackCollector::new(self.data_source);
+        let metrics = Metrics::new(collector.measurements(), options)?;
+        metrics.register(registry)?;
+
+        Ok(Box::new(collector))
+    }
+}
+
+struct ConntrackCollector<T> {
+    measurement: Arc<Mutex<Option<ConntrackStats>>>,
+    data_source: T,
+}
+
+impl<T> ConntrackCollector<T>
+where
+    T: DataSource,
+{
+    fn new(data_source: T) -> Self {
+        Self {
+            measurement: Arc::new(Mutex::new(None)),
+            data_source,
+        }
+    }
+
+    fn measurements(&self) -> Arc<Mutex<Option<ConntrackStats>>> {
+        Arc::clone(&self.measurement)
+    }
+}
+
+#[async_trait::async_trait]
+impl<T> Collector for ConntrackCollector<T>
+where
+    T: DataSource + Send + Sync + 'static,
+{
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn collect(&self) -> anyhow::Result<()> {
+        let stats = self
+            .data_source
+            .conntrack_stats()
+            .await
+            .context("Failed to collect conntrack statistics");
+
+        update_measurement_if(&self.measurement, stats, |old, new| {
+            old.timestamp < new.timestamp
+        })
+    }
+
+    fn snapshot(&self) -> Option<serde_json::Value> {
+        snapshot(&self.measurement)
+    }
+}
@@ -1,22 +1,41 @@
 use crate::domain::{Collector, Metric};
 use crate::metrics::no_operation::NoOpCollector;
-use prometheus::{IntGaugeVec, Opts, Registry};
+use crate::metrics::util::{LabelTracker, MetricOptions};
+use prometheus::{IntGaugeVec, Registry};
 use serde::{Deserialize, Serialize};
 
+const NAME: &str = "cpu_frequency";
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     pub enabled: bool,
+    pub priority: Option<i32>,
+    pub max_series: Option<usize>,
 }
 
 impl Default for Config {
     fn default() -> Self {
-        Self { enabled: true }
+        Self {
+            enabled: true,
+            priority: None,
+            max_series: None,
+        }
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct CoreFreqStats {
+    /// The index of the logical CPU, which may have gaps due to offline cores
+    pub core: usize,
+    pub current: u64,
+    pub min: Option<u64>,
+    pub max: Option<u64>,
+    pub governor: Option<String>,
+}
+
 #[derive(Debug, Clone)]
 pub struct CpuFreqStats {
-    pub cores: Vec<u64>,
+    pub cores: Vec<CoreFreqStats>,
 }
 
 pub trait DataSource {
@@ -26,11 +45,14 @@ pub trait DataSource {
 #[derive(Clone)]
 struct Metrics {
     core_freq: IntGaugeVec,
+    core_freq_min: IntGaugeVec,
+    core_freq_max: IntGaugeVec,
+    governor: IntGaugeVec,
 }
 
 impl Metrics {
-    fn register(registry: &Registry) -> anyhow::Result<Self> {
-        let core_freq_opts = Opts::new(
+    fn register(registry: &Registry, options: &MetricOptions) -> anyhow::Result<Self> {
+        let core_freq_opts = options.opts(
             "system_cpu_core_frequency_hertz",
             "Current frequency of the CPU core in Hertz",
         );
@@ -38,7 +60,36 @@ impl Metrics {
         let core_freq = IntGaugeVec::new(core_freq_opts, &["core"])?;
         registry.register(Box::new(core_freq.clone()))?;
 
-        Ok(Self { core_freq })
+        let core_freq_min_opts = options.opts(
+            "system_cpu_core_frequency_min_hertz",
+            "Minimum frequency the CPU core is allowed to run at in Hertz",
+        );
+
+        let core_freq_min = IntGaugeVec::new(core_freq_min_opts, &["core"])?;
+        registry.register(Box::new(core_freq_min.clone()))?;
+
+        let core_freq_max_opts = options.opts(
+            "system_cpu_core_frequency_max_hertz",
+            "Maximum frequency the CPU core is allowed to run at in Hertz",
+        );
+
+        let core_freq_max = IntGaugeVec::new(core_freq_max_opts, &["core"])?;
+        registry.register(Box::new(core_freq_max.clone()))?;
+
+        let governor_opts = options.opts(
+            "system_cpu_scaling_governor",
+            "The frequency scaling governor of the CPU core. Always 1",
+        );
+
+        let governor = IntGaugeVec::new(governor_opts, &["core", "governor"])?;
+        registry.register(Box::new(governor.clone()))?;
+
+        Ok(Self {
+            core_freq,
+            core_freq_min,
+            core_freq_max,
+            governor,
+        })
     }
 }
 
@@ -63,12 +114,20 @@ impl<T> Metric for CpuFrequency<T>
 where
     T: DataSource + Send + Sync + 'static,
 {
-    fn register(self, registry: &Registry) -> anyhow::Result<Box<dyn Collector>> {
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    fn register(
+        self,
+        registry: &Registry,
+        options: &MetricOptions,
+    ) -> anyhow::Result<Box<dyn Collector>> {
         if !self.config.enabled {
-            return Ok(Box::new(NoOpCollector::new()));
+            return Ok(Box::new(NoOpCollector::new(NAME)));
         }
 
-        let metrics = Metrics::register(registry)?;
+        let metrics = Metrics::register(registry, options)?;
         Ok(Box::new(CpuFrequencyCollector::new(
             metrics,
             self.data_source,
@@ -78,6 +137,7 @@ where
 
 struct CpuFrequencyCollector<T> {
     metrics: Metrics,
+    cores: LabelTracker,
     data_source: T,
 }
 
@@ -88,6 +148,7 @@ where
     pub fn new(metrics: Metrics, data_source: T) -> Self {
         Self {
             metrics,
+            cores: LabelTracker::default(),
             data_source,
         }
     }
@@ -98,14 +159,52 @@ impl<T> Collector for CpuFrequencyCollector<T>
 where
     T: DataSource + Send + Sync + 'static,
 {
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
     #[tracing::instrument(level = "debug", skip_all)]
     async fn collect(&self) -> anyhow::Result<()> {
         let stats = self.data_source.cpu_freq().await?;
-        for (core, &freq) in stats.cores.iter().enumerate() {
+
+        // The governor is part of the labels, so drop the series of the previous governor
+        self.metrics.governor.reset();
+
+        let cores = stats.cores.iter().map(|freq| freq.core.to_string());
+        for core in self.cores.vanished(cores) {
+            let _ = self.metrics.core_freq.remove_label_values(&[&core]);
+            let _ = self.metrics.core_freq_min.remove_label_values(&[&core]);
+            let _ = self.metrics.core_freq_max.remove_label_values(&[&core]);
+        }
+
+        for freq in &stats.cores {
+            let core = freq.core.to_string();
+
             self.metrics
                 .core_freq
-                .with_label_values(&[format!("{}", core)])
-                .set(freq as i64);
+                .with_label_values(&[&core])
+                .set(freq.current as i64);
+
+            if let Some(min) = freq.min {
+                self.metrics
+                    .core_freq_min
+                    .with_label_values(&[&core])
+                    .set(min as i64);
+            }
+
+            if let Some(max) = freq.max {
+                self.metrics
+                    .core_freq_max
+                    .with_label_values(&[&core])
+                    .set(max as i64);
+            }
+
+            if let Some(governor) = &freq.governor {
+                self.metrics
+                    .governor
+                    .with_label_values(&[&core, governor])
+                    .set(1);
+            }
         }
 
         Ok(())
@@ -0,0 +1,208 @@
+use crate::domain::{Collector, Metric};
+use crate::metrics::no_operation::NoOpCollector;
+use crate::metrics::util::{MetricOptions, counter, into_labels, snapshot, update_measurement_if};
+use anyhow::Context;
+use prometheus::Registry;
+use prometheus::core::Desc;
+use prometheus::proto::MetricFamily;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use tokio::time::Instant;
+
+const NAME: &str = "cpu_throttle";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Config {
+    pub enabled: bool,
+    pub priority: Option<i32>,
+    pub max_series: Option<usize>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            priority: None,
+            max_series: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CoreThrottleStats {
+    pub core: usize,
+    pub throttle_count: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PackageThrottleStats {
+    pub package: u64,
+    pub throttle_count: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CpuThrottleStats {
+    #[serde(skip)]
+    pub timestamp: Instant,
+    pub cores: Vec<CoreThrottleStats>,
+    pub packages: Vec<PackageThrottleStats>,
+}
+
+pub trait DataSource {
+    fn throttle_stats(&self) -> impl Future<Output = anyhow::Result<CpuThrottleStats>> + Send;
+}
+
+#[derive(Clone)]
+struct Metrics {
+    state: Arc<Mutex<Option<CpuThrottleStats>>>,
+    core_throttle: Desc,
+    package_throttle: Desc,
+}
+
+impl Metrics {
+    pub fn new(
+        state: Arc<Mutex<Option<CpuThrottleStats>>>,
+        options: &MetricOptions,
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
+            state,
+            core_throttle: Desc::new(
+                options.name("system_cpu_core_throttle_total"),
+                "Number of times the CPU core has been throttled due to high temperature".into(),
+                vec!["core".to_owned()],
+                options.const_labels(),
+            )?,
+            package_throttle: Desc::new(
+                options.name("system_cpu_package_throttle_total"),
+                "Number of times the CPU package has been throttled due to high temperature".into(),
+                vec!["package".to_owned()],
+                options.const_labels(),
+            )?,
+        })
+    }
+
+    pub fn register(&self, registry: &Registry) -> anyhow::Result<()> {
+        registry.register(Box::new(self.clone()))?;
+        Ok(())
+    }
+}
+
+impl prometheus::core::Collector for Metrics {
+    fn desc(&self) -> Vec<&Desc> {
+        vec![&self.core_throttle, &self.package_throttle]
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        let guard = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(stats) = guard.as_ref() else {
+            return vec![];
+        };
+
+        let mut mf = Vec::with_capacity(stats.cores.len() + stats.packages.len());
+        for core in &stats.cores {
+            let l = into_labels(&[("core", &core.core.to_string())]);
+            mf.push(counter(&self.core_throttle, l, core.throttle_count as f64));
+        }
+
+        for package in &stats.packages {
+            let l = into_labels(&[("package", &package.package.to_string())]);
+            mf.push(counter(
+                &self.package_throttle,
+                l,
+                package.throttle_count as f64,
+            ));
+        }
+
+        mf
+    }
+}
+
+pub struct CpuThrottle<T> {
+    config: Config,
+    data_source: T,
+}
+
+impl<T> CpuThrottle<T>
+where
+    T: DataSource + Send + Sync + 'static,
+{
+    pub fn new(config: Config, data_source: T) -> Self {
+        Self {
+            config,
+            data_source,
+        }
+    }
+}
+
+impl<T> Metric for CpuThrottle<T>
+where
+    T: DataSource + Send + Sync + 'static,
+{
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    fn register(
+        self,
+        registry: &Registry,
+        options: &MetricOptions,
+    ) -> anyhow::Result<Box<dyn Collector>> {
+        if !self.config.enabled {
+            return Ok(Box::new(NoOpCollector::new(NAME)));
+        }
+
+        let collector = CpuThrottleCollector::new(self.data_source);
+        let metrics = Metrics::new(collector.measurements(), options)?;
+        metrics.register(registry)?;
+
+        Ok(Box::new(collector))
+    }
+}
+
+struct CpuThrottleCollector<T> {
+    measurement: Arc<Mutex<Option<CpuThrottleStats>>>,
+    data_source: T,
+}
+
+impl<T> CpuThrottleCollector<T>
+where
+    T: DataSource,
+{
+    fn new(data_source: T) -> Self {
+        Self {
+            measurement: Arc::new(Mutex::new(None)),
+            data_source,
+        }
+    }
+
+    fn measurements(&self) -> Arc<Mutex<Option<CpuThrottleStats>>> {
+        Arc::clone(&self.measurement)
+    }
+}
+
+#[async_trait::async_trait]
+impl<T> Collector for CpuThrottleCollector<T>
+where
+    T: DataSource + Send + Sync + 'static,
+{
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn collect(&self) -> anyhow::Result<()> {
+        let stats = self
+            .data_source
+            .throttle_stats()
+            .await
+            .context("Failed to collect CPU throttling statistics");
+
+        update_measurement_if(&self.measurement, stats, |old, new| {
+            old.timestamp < new.timestamp
+        })
+    }
+
+    fn snapshot(&self) -> Option<serde_json::Value> {
+        snapshot(&self.measurement)
+    }
+}
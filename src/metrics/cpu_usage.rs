@@ -1,17 +1,34 @@
 use crate::domain::{Collector, Metric};
-
 use crate::metrics::no_operation::NoOpCollector;
-use prometheus::{Gauge, GaugeVec, Opts, Registry};
+use crate::metrics::util::{LabelTracker, MetricOptions};
+use prometheus::{Gauge, GaugeVec, IntCounter, Registry};
 use serde::{Deserialize, Serialize};
 
+const NAME: &str = "cpu";
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     pub enabled: bool,
+    pub priority: Option<i32>,
+    pub max_series: Option<usize>,
+    /// The minimum time between the two `/proc/stat` snapshots the usage is computed from.
+    /// The collection waits for it to elapse, unless `reuse_previous_sample` is set.
+    pub min_sample_interval_ms: u64,
+    /// Never wait while collecting. The usage is computed against the previous sample,
+    /// which is reused as-is while it is younger than `min_sample_interval_ms`. Intended
+    /// for the background collection, which keeps the previous sample fresh.
+    pub reuse_previous_sample: bool,
 }
 
 impl Default for Config {
     fn default() -> Self {
-        Self { enabled: true }
+        Self {
+            enabled: true,
+            priority: None,
+            max_series: None,
+            min_sample_interval_ms: 250,
+            reuse_previous_sample: false,
+        }
     }
 }
 
@@ -24,6 +41,8 @@ pub struct CpuUsageStats {
     pub total_usage: f64,
     pub total_breakdown: CoreStats,
     pub cores: Vec<CoreUsageStats>,
+    /// Number of softirqs serviced since boot, across all cores and softirq types
+    pub softirqs: Option<u64>,
 }
 
 #[derive(Debug, Clone)]
@@ -68,12 +87,20 @@ impl<T> Metric for CpuUsage<T>
 where
     T: DataSource + Send + Sync + 'static,
 {
-    fn register(self, registry: &Registry) -> anyhow::Result<Box<dyn Collector>> {
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    fn register(
+        self,
+        registry: &Registry,
+        options: &MetricOptions,
+    ) -> anyhow::Result<Box<dyn Collector>> {
         if !self.config.enabled {
-            return Ok(Box::new(NoOpCollector::new()));
+            return Ok(Box::new(NoOpCollector::new(NAME)));
         }
 
-        let metrics = Metrics::register(registry)?;
+        let metrics = Metrics::register(registry, options)?;
         Ok(Box::new(CpuUsageCollector::new(metrics, self.data_source)))
     }
 }
@@ -86,15 +113,18 @@ struct Metrics {
     // Per-core metrics
     core_usage: GaugeVec,     // Labels: ["core"]
     core_breakdown: GaugeVec, // Labels: ["core", "type"]
+
+    softirqs: IntCounter,
 }
 
 impl Metrics {
-    fn register(registry: &Registry) -> anyhow::Result<Self> {
-        let total_usage = Gauge::new("system_cpu_usage_ratio", "Overall CPU usage ratio")?;
+    fn register(registry: &Registry, options: &MetricOptions) -> anyhow::Result<Self> {
+        let total_usage =
+            Gauge::with_opts(options.opts("system_cpu_usage_ratio", "Overall CPU usage ratio"))?;
         registry.register(Box::new(total_usage.clone()))?;
 
         let total_breakdown = GaugeVec::new(
-            Opts::new(
+            options.opts(
                 "system_cpu_time_type_ratio",
                 "Overall CPU time breakdown by type",
             ),
@@ -103,13 +133,13 @@ impl Metrics {
         registry.register(Box::new(total_breakdown.clone()))?;
 
         let core_usage = GaugeVec::new(
-            Opts::new("system_cpu_core_usage_ratio", "Per-core CPU usage ratio"),
+            options.opts("system_cpu_core_usage_ratio", "Per-core CPU usage ratio"),
             &["core"],
         )?;
         registry.register(Box::new(core_usage.clone()))?;
 
         let core_breakdown = GaugeVec::new(
-            Opts::new(
+            options.opts(
                 "system_cpu_core_time_type_ratio",
                 "Per-core CPU time breakdown by type",
             ),
@@ -117,16 +147,38 @@ impl Metrics {
         )?;
         registry.register(Box::new(core_breakdown.clone()))?;
 
+        let softirqs = IntCounter::with_opts(options.opts(
+            "system_softirq_total",
+            "Number of softirqs serviced across all cores",
+        ))?;
+        registry.register(Box::new(softirqs.clone()))?;
+
         Ok(Self {
             total_usage,
             total_breakdown,
             core_usage,
             core_breakdown,
+            softirqs,
         })
     }
 }
+
+const TIME_TYPES: [&str; 10] = [
+    "user",
+    "nice",
+    "system",
+    "idle",
+    "iowait",
+    "irq",
+    "softirq",
+    "steal",
+    "guest",
+    "guest_nice",
+];
+
 struct CpuUsageCollector<T> {
     metrics: Metrics,
+    cores: LabelTracker,
     data_source: T,
 }
 
@@ -137,6 +189,7 @@ where
     fn new(metrics: Metrics, data_source: T) -> Self {
         Self {
             metrics,
+            cores: LabelTracker::default(),
             data_source,
         }
     }
@@ -150,16 +203,32 @@ where
             }
         };
 
-        set_val("user", stats.user);
-        set_val("nice", stats.nice);
-        set_val("system", stats.system);
-        set_val("idle", stats.idle);
-        set_val("iowait", stats.iowait);
-        set_val("irq", stats.irq);
-        set_val("softirq", stats.softirq);
-        set_val("steal", stats.steal);
-        set_val("guest", stats.guest);
-        set_val("guest_nice", stats.guest_nice);
+        let values = [
+            stats.user,
+            stats.nice,
+            stats.system,
+            stats.idle,
+            stats.iowait,
+            stats.irq,
+            stats.softirq,
+            stats.steal,
+            stats.guest,
+            stats.guest_nice,
+        ];
+
+        for (time_type, value) in TIME_TYPES.iter().zip(values) {
+            set_val(time_type, value);
+        }
+    }
+
+    fn remove_core(&self, core_label: &str) {
+        let _ = self.metrics.core_usage.remove_label_values(&[core_label]);
+        for time_type in TIME_TYPES {
+            let _ = self
+                .metrics
+                .core_breakdown
+                .remove_label_values(&[core_label, time_type]);
+        }
     }
 }
 
@@ -168,6 +237,10 @@ impl<T> Collector for CpuUsageCollector<T>
 where
     T: DataSource + Send + Sync + 'static,
 {
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
     #[tracing::instrument(level = "debug", skip_all)]
     async fn collect(&self) -> anyhow::Result<()> {
         let stats = self.data_source.cpu_usage().await?;
@@ -175,6 +248,18 @@ where
         self.metrics.total_usage.set(stats.total_usage);
         self.update_gauge(&self.metrics.total_breakdown, &stats.total_breakdown, None);
 
+        // The kernel counter is monotonic, so only its increase since the previous collection
+        // has to be applied to the prometheus counter
+        if let Some(softirqs) = stats.softirqs {
+            let counter = &self.metrics.softirqs;
+            counter.inc_by(softirqs.saturating_sub(counter.get()));
+        }
+
+        let cores = stats.cores.iter().map(|c| c.core.to_string());
+        for core_label in self.cores.vanished(cores) {
+            self.remove_core(&core_label);
+        }
+
         for core_stat in stats.cores {
             let core_label = core_stat.core.to_string();
 
@@ -193,3 +278,70 @@ where
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+    use std::sync::Mutex;
+
+    struct Snapshots {
+        cores: Mutex<VecDeque<Vec<usize>>>,
+    }
+
+    impl DataSource for Snapshots {
+        async fn cpu_usage(&self) -> anyhow::Result<CpuUsageStats> {
+            let cores = self.cores.lock().unwrap().pop_front().unwrap();
+            let cores = cores
+                .into_iter()
+                .map(|core| CoreUsageStats {
+                    core,
+                    total_usage: 0.25,
+                    breakdown: CoreStats::default(),
+                })
+                .collect();
+
+            Ok(CpuUsageStats {
+                total_usage: 0.25,
+                total_breakdown: CoreStats::default(),
+                cores,
+                softirqs: None,
+            })
+        }
+    }
+
+    fn series_count(registry: &Registry, name: &str) -> usize {
+        registry
+            .gather()
+            .iter()
+            .filter(|mf| mf.name() == name)
+            .map(|mf| mf.get_metric().len())
+            .sum()
+    }
+
+    #[tokio::test]
+    async fn test_offline_core_is_removed() {
+        let data_source = Snapshots {
+            cores: Mutex::new(VecDeque::from([vec![0, 1, 2, 3], vec![0, 1, 3]])),
+        };
+
+        let registry = Registry::new();
+        let collector = CpuUsage::new(Config::default(), data_source)
+            .register(&registry, &MetricOptions::default())
+            .unwrap();
+
+        collector.collect().await.unwrap();
+        assert_eq!(series_count(&registry, "system_cpu_core_usage_ratio"), 4);
+        assert_eq!(
+            series_count(&registry, "system_cpu_core_time_type_ratio"),
+            40
+        );
+
+        collector.collect().await.unwrap();
+        assert_eq!(series_count(&registry, "system_cpu_core_usage_ratio"), 3);
+        assert_eq!(
+            series_count(&registry, "system_cpu_core_time_type_ratio"),
+            30
+        );
+    }
+}
@@ -1,36 +1,83 @@
 use crate::domain::{Collector, Metric};
 use crate::metrics::no_operation::NoOpCollector;
-use crate::metrics::util::{into_labels, maybe_counter, update_measurement_if};
+use crate::metrics::util::{
+    MetricOptions, device_alias, into_labels, maybe_counter, maybe_gauge, snapshot,
+    update_measurement_if,
+};
+use anyhow::Context;
 use prometheus::Registry;
 use prometheus::core::Desc;
 use prometheus::proto::{LabelPair, MetricFamily};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, Mutex};
 use tokio::time::Instant;
 
+const NAME: &str = "disk";
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     pub enabled: bool,
+    pub priority: Option<i32>,
+    pub max_series: Option<usize>,
+    /// Friendly names of the devices, exposed via an additional `alias` label,
+    /// which is empty for the devices without one
+    pub device_aliases: HashMap<String, String>,
+    /// Expose the transferred bytes as a single `system_disk_bytes_total` metric with
+    /// a `direction` label, instead of separate metrics for the read and written bytes
+    pub combined_direction_label: bool,
+    /// Read the hardware sector size of each device from sysfs and expose it
+    /// as `system_disk_sector_size_bytes`. The byte counters do not depend on it.
+    pub sector_size: bool,
+    /// Resolve the stable names of the devices from `/dev/disk/by-id`, which do not
+    /// change across reboots, and expose them via an additional `id` label
+    pub use_stable_names: bool,
+    /// The devices whose names start with any of these prefixes are not collected,
+    /// e.g. the loop devices, which are created for each mounted snap package
+    pub ignore_prefixes: Vec<String>,
 }
 
 impl Default for Config {
     fn default() -> Self {
-        Self { enabled: true }
+        Self {
+            enabled: true,
+            priority: None,
+            max_series: None,
+            device_aliases: HashMap::new(),
+            combined_direction_label: false,
+            sector_size: false,
+            use_stable_names: false,
+            ignore_prefixes: ["loop", "zram", "ram", "fd"].map(String::from).to_vec(),
+        }
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct DeviceIoStats {
     pub device_name: String,
+    /// The name of the device in `/dev/disk/by-id`, if requested and available
+    pub id: Option<String>,
     pub bytes_read: u64,
     pub bytes_written: u64,
     pub read_ops: u64,
     pub write_ops: u64,
+    // The discard and flush columns are not reported by older kernels
+    pub discard_ops: Option<u64>,
+    pub discard_bytes: Option<u64>,
+    pub flush_ops: Option<u64>,
+    pub flush_time_ms: Option<u64>,
+    /// Time spent doing I/O, i.e. while at least one request was in flight
+    pub io_time_ms: Option<u64>,
+    /// The hardware sector size, if requested and available
+    pub sector_size: Option<u64>,
+    /// The share of the time spent doing I/O since the previous measurement.
+    /// It is computed by the collector, rather than reported by the data source.
+    pub utilization: Option<f64>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct DiskIoStats {
+    #[serde(skip)]
     pub timestamp: Instant,
     pub disks: Vec<DeviceIoStats>,
 }
@@ -42,50 +89,127 @@ pub trait DataSource {
 #[derive(Clone)]
 struct Metrics {
     state: Arc<Mutex<Option<DiskIoStats>>>,
+    zero_if_absent: HashSet<String>,
+    device_aliases: HashMap<String, String>,
+    combined_direction_label: bool,
+    bytes: Desc,
     bytes_read: Desc,
     bytes_written: Desc,
     read_ops: Desc,
     write_ops: Desc,
+    discard_ops: Desc,
+    discard_bytes: Desc,
+    flush_ops: Desc,
+    flush_time: Desc,
+    sector_size: Desc,
+    utilization: Desc,
 }
 
 impl Metrics {
-    pub fn new(state: Arc<Mutex<Option<DiskIoStats>>>) -> anyhow::Result<Self> {
-        let labels = vec!["device".to_owned()];
+    pub fn new(
+        state: Arc<Mutex<Option<DiskIoStats>>>,
+        device_aliases: HashMap<String, String>,
+        combined_direction_label: bool,
+        options: &MetricOptions,
+    ) -> anyhow::Result<Self> {
+        let mut labels = vec!["device".to_owned()];
+        if !device_aliases.is_empty() {
+            labels.push("alias".to_owned());
+        }
+
+        let bytes = Desc::new(
+            options.name("system_disk_bytes_total"),
+            "Total bytes transferred".into(),
+            [labels.clone(), vec!["direction".to_owned()]].concat(),
+            options.const_labels(),
+        )?;
 
         let bytes_read = Desc::new(
-            "system_disk_read_bytes_total".into(),
+            options.name("system_disk_read_bytes_total"),
             "Total bytes read".into(),
             labels.clone(),
-            HashMap::new(),
+            options.const_labels(),
         )?;
 
         let bytes_written = Desc::new(
-            "system_disk_written_bytes_total".into(),
+            options.name("system_disk_written_bytes_total"),
             "Total bytes written".into(),
             labels.clone(),
-            HashMap::new(),
+            options.const_labels(),
         )?;
 
         let read_ops = Desc::new(
-            "system_disk_read_ops_total".into(),
+            options.name("system_disk_read_ops_total"),
             "Total read ops".into(),
             labels.clone(),
-            HashMap::new(),
+            options.const_labels(),
         )?;
 
         let write_ops = Desc::new(
-            "system_disk_write_ops_total".into(),
+            options.name("system_disk_write_ops_total"),
             "Total write ops".into(),
             labels.clone(),
-            HashMap::new(),
+            options.const_labels(),
+        )?;
+
+        let discard_ops = Desc::new(
+            options.name("system_disk_discard_ops_total"),
+            "Total discard ops".into(),
+            labels.clone(),
+            options.const_labels(),
+        )?;
+
+        let discard_bytes = Desc::new(
+            options.name("system_disk_discarded_bytes_total"),
+            "Total bytes discarded".into(),
+            labels.clone(),
+            options.const_labels(),
+        )?;
+
+        let flush_ops = Desc::new(
+            options.name("system_disk_flush_ops_total"),
+            "Total flush ops".into(),
+            labels.clone(),
+            options.const_labels(),
+        )?;
+
+        let flush_time = Desc::new(
+            options.name("system_disk_flush_time_seconds_total"),
+            "Total time spent flushing".into(),
+            labels.clone(),
+            options.const_labels(),
+        )?;
+
+        let sector_size = Desc::new(
+            options.name("system_disk_sector_size_bytes"),
+            "Hardware sector size of the device".into(),
+            labels.clone(),
+            options.const_labels(),
+        )?;
+
+        let utilization = Desc::new(
+            options.name("system_disk_utilization_ratio"),
+            "Share of the time the device was busy doing I/O (0-1)".into(),
+            labels.clone(),
+            options.const_labels(),
         )?;
 
         Ok(Self {
             state,
+            zero_if_absent: options.zero_if_absent.clone(),
+            device_aliases,
+            combined_direction_label,
+            bytes,
             bytes_read,
             bytes_written,
             read_ops,
             write_ops,
+            discard_ops,
+            discard_bytes,
+            flush_ops,
+            flush_time,
+            sector_size,
+            utilization,
         })
     }
 
@@ -95,18 +219,41 @@ impl Metrics {
     }
 
     fn make_labels(&self, device: &DeviceIoStats) -> Vec<LabelPair> {
-        into_labels(&[("device", &device.device_name)])
+        let mut labels = vec![("device", device.device_name.as_str())];
+        if let Some(id) = &device.id {
+            labels.push(("id", id));
+        }
+        // Every series of a metric must have the same label names, so the
+        // devices without an alias get an empty one
+        if !self.device_aliases.is_empty() {
+            let alias = device_alias(&self.device_aliases, &device.device_name);
+            labels.push(("alias", alias.unwrap_or_default()));
+        }
+
+        into_labels(&labels)
     }
 }
 
 impl prometheus::core::Collector for Metrics {
     fn desc(&self) -> Vec<&Desc> {
-        vec![
-            &self.bytes_read,
-            &self.bytes_written,
-            &self.read_ops,
-            &self.write_ops,
-        ]
+        let bytes = match self.combined_direction_label {
+            true => vec![&self.bytes],
+            false => vec![&self.bytes_read, &self.bytes_written],
+        };
+
+        bytes
+            .into_iter()
+            .chain([
+                &self.read_ops,
+                &self.write_ops,
+                &self.discard_ops,
+                &self.discard_bytes,
+                &self.flush_ops,
+                &self.flush_time,
+                &self.sector_size,
+                &self.utilization,
+            ])
+            .collect()
     }
 
     fn collect(&self) -> Vec<MetricFamily> {
@@ -118,10 +265,89 @@ impl prometheus::core::Collector for Metrics {
         let mut mf = Vec::with_capacity(stats.disks.len());
         for device in &stats.disks {
             let l = self.make_labels(device);
-            maybe_counter(&mut mf, &self.bytes_read, &l, Some(device.bytes_read));
-            maybe_counter(&mut mf, &self.bytes_written, &l, Some(device.bytes_written));
-            maybe_counter(&mut mf, &self.read_ops, &l, Some(device.read_ops));
-            maybe_counter(&mut mf, &self.write_ops, &l, Some(device.write_ops));
+            if self.combined_direction_label {
+                for (direction, value) in
+                    [("read", device.bytes_read), ("write", device.bytes_written)]
+                {
+                    let mut l = l.clone();
+                    l.extend(into_labels(&[("direction", direction)]));
+                    maybe_counter(&self.zero_if_absent, &mut mf, &self.bytes, &l, Some(value));
+                }
+            } else {
+                maybe_counter(
+                    &self.zero_if_absent,
+                    &mut mf,
+                    &self.bytes_read,
+                    &l,
+                    Some(device.bytes_read),
+                );
+                maybe_counter(
+                    &self.zero_if_absent,
+                    &mut mf,
+                    &self.bytes_written,
+                    &l,
+                    Some(device.bytes_written),
+                );
+            }
+
+            maybe_counter(
+                &self.zero_if_absent,
+                &mut mf,
+                &self.read_ops,
+                &l,
+                Some(device.read_ops),
+            );
+            maybe_counter(
+                &self.zero_if_absent,
+                &mut mf,
+                &self.write_ops,
+                &l,
+                Some(device.write_ops),
+            );
+            maybe_counter(
+                &self.zero_if_absent,
+                &mut mf,
+                &self.discard_ops,
+                &l,
+                device.discard_ops,
+            );
+            maybe_counter(
+                &self.zero_if_absent,
+                &mut mf,
+                &self.discard_bytes,
+                &l,
+                device.discard_bytes,
+            );
+            maybe_counter(
+                &self.zero_if_absent,
+                &mut mf,
+                &self.flush_ops,
+                &l,
+                device.flush_ops,
+            );
+
+            let flush_time = device.flush_time_ms.map(|ms| ms as f64 / 1000.0);
+            maybe_counter(
+                &self.zero_if_absent,
+                &mut mf,
+                &self.flush_time,
+                &l,
+                flush_time,
+            );
+            maybe_gauge(
+                &self.zero_if_absent,
+                &mut mf,
+                &self.sector_size,
+                &l,
+                device.sector_size,
+            );
+            maybe_gauge(
+                &self.zero_if_absent,
+                &mut mf,
+                &self.utilization,
+                &l,
+                device.utilization,
+            );
         }
 
         mf
@@ -149,15 +375,28 @@ impl<T> Metric for DiskIo<T>
 where
     T: DataSource + Send + Sync + 'static,
 {
-    fn register(self, registry: &Registry) -> anyhow::Result<Box<dyn Collector>> {
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    fn register(
+        self,
+        registry: &Registry,
+        options: &MetricOptions,
+    ) -> anyhow::Result<Box<dyn Collector>> {
         if !self.config.enabled {
-            return Ok(Box::new(NoOpCollector::new()));
+            return Ok(Box::new(NoOpCollector::new(NAME)));
         }
 
-        let collector = DiskIoCollector::new(self.data_source);
+        let collector = DiskIoCollector::new(self.data_source, self.config.ignore_prefixes);
         let measurements = collector.measurements();
 
-        let metrics = Metrics::new(measurements)?;
+        let metrics = Metrics::new(
+            measurements,
+            self.config.device_aliases,
+            self.config.combined_direction_label,
+            options,
+        )?;
         metrics.register(registry)?;
 
         Ok(Box::new(collector))
@@ -167,16 +406,18 @@ where
 struct DiskIoCollector<T> {
     measurement: Arc<Mutex<Option<DiskIoStats>>>,
     data_source: T,
+    ignore_prefixes: Vec<String>,
 }
 
 impl<T> DiskIoCollector<T>
 where
     T: DataSource,
 {
-    fn new(data_source: T) -> Self {
+    fn new(data_source: T, ignore_prefixes: Vec<String>) -> Self {
         Self {
             measurement: Arc::new(Mutex::new(None)),
             data_source,
+            ignore_prefixes,
         }
     }
 
@@ -185,7 +426,11 @@ where
     }
 
     fn should_collect(&self, device_name: &str) -> bool {
-        if device_name.starts_with("loop") || device_name.starts_with("zram") {
+        if self
+            .ignore_prefixes
+            .iter()
+            .any(|prefix| device_name.starts_with(prefix.as_str()))
+        {
             return false;
         }
 
@@ -211,6 +456,10 @@ impl<T> Collector for DiskIoCollector<T>
 where
     T: DataSource + Send + Sync + 'static,
 {
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
     #[tracing::instrument(level = "debug", skip_all)]
     async fn collect(&self) -> anyhow::Result<()> {
         let stats = self
@@ -223,13 +472,301 @@ where
                     .retain(|disk| self.should_collect(&disk.device_name));
                 stats
             })
-            .inspect_err(|err| tracing::error!(error=?err, "Failed to collect disk IO statistics"))
-            .ok();
+            .context("Failed to collect disk IO statistics")
+            .map(|mut stats| {
+                let guard = self.measurement.lock().unwrap_or_else(|e| e.into_inner());
+                if let Some(previous) = guard.as_ref() {
+                    compute_utilization(previous, &mut stats);
+                }
+                stats
+            });
 
         update_measurement_if(&self.measurement, stats, |old, new| {
             old.timestamp < new.timestamp
-        });
+        })
+    }
 
-        Ok(())
+    fn snapshot(&self) -> Option<serde_json::Value> {
+        snapshot(&self.measurement)
+    }
+}
+
+/// Computes the utilization of the devices present in both measurements,
+/// from the growth of their I/O time over the elapsed wall-clock time
+fn compute_utilization(previous: &DiskIoStats, current: &mut DiskIoStats) {
+    let elapsed = current
+        .timestamp
+        .saturating_duration_since(previous.timestamp)
+        .as_secs_f64()
+        * 1000.0;
+    if elapsed <= 0.0 {
+        return;
+    }
+
+    for disk in &mut current.disks {
+        let Some(prev) = previous
+            .disks
+            .iter()
+            .find(|d| d.device_name == disk.device_name)
+        else {
+            continue;
+        };
+
+        if let (Some(prev_io), Some(curr_io)) = (prev.io_time_ms, disk.io_time_ms) {
+            let busy = curr_io.saturating_sub(prev_io) as f64;
+            disk.utilization = Some((busy / elapsed).clamp(0.0, 1.0));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    struct TwoDisks;
+
+    impl DataSource for TwoDisks {
+        async fn disk_io(&self) -> anyhow::Result<DiskIoStats> {
+            let disk = |name: &str| DeviceIoStats {
+                device_name: name.to_owned(),
+                id: None,
+                bytes_read: 1024,
+                bytes_written: 2048,
+                read_ops: 1,
+                write_ops: 2,
+                discard_ops: None,
+                discard_bytes: None,
+                flush_ops: None,
+                flush_time_ms: None,
+                io_time_ms: None,
+                sector_size: None,
+                utilization: None,
+            };
+
+            Ok(DiskIoStats {
+                timestamp: Instant::now(),
+                disks: vec![disk("nvme0n1"), disk("sda")],
+            })
+        }
+    }
+
+    /// Simulates a USB disk which is unplugged after the first read
+    struct UnpluggedDisk(std::sync::atomic::AtomicU64);
+
+    impl DataSource for UnpluggedDisk {
+        async fn disk_io(&self) -> anyhow::Result<DiskIoStats> {
+            let mut stats = TwoDisks.disk_io().await?;
+            let reads = self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+
+            stats.timestamp += Duration::from_secs(reads);
+            if reads > 0 {
+                stats.disks.retain(|disk| disk.device_name != "sda");
+            }
+
+            Ok(stats)
+        }
+    }
+
+    fn gathered_labels(registry: &Registry, name: &str) -> Vec<Vec<(String, String)>> {
+        let families = registry.gather();
+        let family = families.iter().find(|mf| mf.name() == name).unwrap();
+
+        family
+            .get_metric()
+            .iter()
+            .map(|m| {
+                m.get_label()
+                    .iter()
+                    .map(|l| (l.name().to_owned(), l.value().to_owned()))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>()
+    }
+
+    #[tokio::test]
+    async fn test_device_aliases() {
+        let config = Config {
+            device_aliases: HashMap::from([("nvme0n1".to_owned(), "system-ssd".to_owned())]),
+            ..Default::default()
+        };
+
+        let registry = Registry::new();
+        let collector = DiskIo::new(config, TwoDisks)
+            .register(&registry, &MetricOptions::default())
+            .unwrap();
+        collector.collect().await.unwrap();
+
+        let labels = gathered_labels(&registry, "system_disk_read_bytes_total");
+        assert_eq!(
+            labels,
+            vec![
+                vec![
+                    ("device".to_owned(), "nvme0n1".to_owned()),
+                    ("alias".to_owned(), "system-ssd".to_owned()),
+                ],
+                vec![
+                    ("device".to_owned(), "sda".to_owned()),
+                    ("alias".to_owned(), "".to_owned()),
+                ],
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_combined_direction_label() {
+        let config = Config {
+            combined_direction_label: true,
+            ..Default::default()
+        };
+
+        let registry = Registry::new();
+        let collector = DiskIo::new(config, TwoDisks)
+            .register(&registry, &MetricOptions::default())
+            .unwrap();
+        collector.collect().await.unwrap();
+
+        let families = registry.gather();
+        assert!(
+            families
+                .iter()
+                .all(|mf| mf.name() != "system_disk_read_bytes_total")
+        );
+        assert!(
+            families
+                .iter()
+                .all(|mf| mf.name() != "system_disk_written_bytes_total")
+        );
+
+        let label = |k: &str, v: &str| (k.to_owned(), v.to_owned());
+        assert_eq!(
+            gathered_labels(&registry, "system_disk_bytes_total"),
+            vec![
+                vec![label("device", "nvme0n1"), label("direction", "read")],
+                vec![label("device", "nvme0n1"), label("direction", "write")],
+                vec![label("device", "sda"), label("direction", "read")],
+                vec![label("device", "sda"), label("direction", "write")],
+            ]
+        );
+
+        let bytes = families
+            .iter()
+            .find(|mf| mf.name() == "system_disk_bytes_total")
+            .unwrap();
+        let values = bytes
+            .get_metric()
+            .iter()
+            .map(|m| m.get_counter().value())
+            .collect::<Vec<_>>();
+        assert_eq!(values, vec![1024.0, 2048.0, 1024.0, 2048.0]);
+    }
+
+    #[test]
+    fn test_compute_utilization() {
+        let disk = |name: &str, io_time_ms| DeviceIoStats {
+            device_name: name.to_owned(),
+            id: None,
+            bytes_read: 0,
+            bytes_written: 0,
+            read_ops: 0,
+            write_ops: 0,
+            discard_ops: None,
+            discard_bytes: None,
+            flush_ops: None,
+            flush_time_ms: None,
+            io_time_ms,
+            sector_size: None,
+            utilization: None,
+        };
+
+        let timestamp = Instant::now();
+        let previous = DiskIoStats {
+            timestamp,
+            disks: vec![disk("nvme0n1", Some(1_000)), disk("sda", Some(5_000))],
+        };
+        let mut current = DiskIoStats {
+            timestamp: timestamp + Duration::from_secs(1),
+            disks: vec![
+                disk("nvme0n1", Some(1_250)),
+                // Reported more busy time than has elapsed, e.g. due to timer skew
+                disk("sda", Some(6_200)),
+                disk("sdb", Some(100)),
+            ],
+        };
+
+        compute_utilization(&previous, &mut current);
+        assert_eq!(current.disks[0].utilization, Some(0.25));
+        assert_eq!(current.disks[1].utilization, Some(1.0));
+        // There is no previous sample of the new device
+        assert_eq!(current.disks[2].utilization, None);
+    }
+
+    #[tokio::test]
+    async fn test_unplugged_device_is_removed() {
+        let registry = Registry::new();
+        let collector = DiskIo::new(Config::default(), UnpluggedDisk(Default::default()))
+            .register(&registry, &MetricOptions::default())
+            .unwrap();
+
+        let devices = |registry: &Registry| {
+            gathered_labels(registry, "system_disk_read_bytes_total")
+                .into_iter()
+                .map(|labels| labels[0].1.clone())
+                .collect::<Vec<_>>()
+        };
+
+        collector.collect().await.unwrap();
+        assert_eq!(devices(&registry), ["nvme0n1", "sda"]);
+
+        collector.collect().await.unwrap();
+        assert_eq!(devices(&registry), ["nvme0n1"]);
+    }
+
+    #[test]
+    fn test_default_ignore_prefixes() {
+        let collector = DiskIoCollector::new(TwoDisks, Config::default().ignore_prefixes);
+
+        for device in ["loop0", "zram0", "ram15", "fd0", "nvme0n1p1", "sda1"] {
+            assert!(!collector.should_collect(device), "{}", device);
+        }
+
+        for device in ["nvme0n1", "sda", "md0", "md1p1", "vda"] {
+            assert!(collector.should_collect(device), "{}", device);
+        }
+    }
+
+    #[test]
+    fn test_custom_ignore_prefixes() {
+        let ignore_prefixes = vec!["md".to_owned(), "sdb".to_owned()];
+        let collector = DiskIoCollector::new(TwoDisks, ignore_prefixes);
+
+        assert!(!collector.should_collect("md0"));
+        assert!(!collector.should_collect("md1p1"));
+        assert!(!collector.should_collect("sdb"));
+        assert!(collector.should_collect("sda"));
+        // The defaults are replaced, rather than extended
+        assert!(collector.should_collect("loop0"));
+
+        let collector = DiskIoCollector::new(TwoDisks, vec![]);
+        assert!(collector.should_collect("zram0"));
+        // The partitions are skipped regardless of the ignore list
+        assert!(!collector.should_collect("sda1"));
+    }
+
+    #[tokio::test]
+    async fn test_ignore_prefixes_filter_series() {
+        let config = Config {
+            ignore_prefixes: vec!["nvme".to_owned()],
+            ..Default::default()
+        };
+
+        let registry = Registry::new();
+        let collector = DiskIo::new(config, TwoDisks)
+            .register(&registry, &MetricOptions::default())
+            .unwrap();
+        collector.collect().await.unwrap();
+
+        let labels = gathered_labels(&registry, "system_disk_read_bytes_total");
+        assert_eq!(labels, vec![vec![("device".to_owned(), "sda".to_owned())]]);
     }
 }
@@ -1,33 +1,70 @@
-use crate::domain::{Collector, Metric};
+use crate::domain::{Collector, Metric, Probe};
 use crate::metrics::no_operation::NoOpCollector;
-use crate::metrics::util::{into_labels, maybe_counter, maybe_gauge, update_measurement_if};
+use crate::metrics::util::{
+    MetricOptions, TemperatureUnit, device_alias, gauge, into_labels, maybe_counter, maybe_gauge,
+    probe, register_threshold, snapshot, update_measurement_if,
+};
+use anyhow::Context;
 use prometheus::Registry;
 use prometheus::core::Desc;
 use prometheus::proto::{LabelPair, MetricFamily};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, Mutex};
 use tokio::time::Instant;
 
+const NAME: &str = "smart";
+
+/// NVMe reports the data read and written in units of 1000 512-byte blocks
+const NVME_DATA_UNIT_BYTES: f64 = 512_000.0;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     pub enabled: bool,
+    pub priority: Option<i32>,
+    pub max_series: Option<usize>,
+    /// Friendly names of the devices, exposed via an additional `alias` label,
+    /// which is empty for the devices without one
+    pub device_aliases: HashMap<String, String>,
+    /// Disk temperature (in Celsius) above which alerts should fire
+    pub temperature_warning_threshold: Option<f64>,
+    /// Also put the `model` and `serial_number` labels on every series, as in the
+    /// older releases, instead of only on `system_smart_device_info`
+    pub legacy_device_labels: bool,
+    /// Resolve the stable names of the devices from `/dev/disk/by-id`, which do not
+    /// change across reboots, and expose them via an additional `id` label
+    pub use_stable_names: bool,
+    /// Collect only these devices, e.g. `sda` or `/dev/nvme0`. Takes precedence over `ignore_devices`.
+    pub watch_devices: Option<Vec<String>>,
+    /// Do not collect these devices, e.g. USB card readers. The ignored devices are not woken up.
+    pub ignore_devices: Option<Vec<String>>,
 }
 
 impl Default for Config {
     fn default() -> Self {
-        Self { enabled: true }
+        Self {
+            enabled: true,
+            priority: None,
+            max_series: None,
+            device_aliases: HashMap::new(),
+            temperature_warning_threshold: None,
+            legacy_device_labels: false,
+            use_stable_names: false,
+            watch_devices: None,
+            ignore_devices: None,
+        }
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct SmartReports {
+    #[serde(skip)]
     pub timestamp: Instant,
     pub sata: Vec<SataDevice>,
     pub nvme: Vec<NvmeDevice>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct SataDevice {
     pub device: Device,
     pub temperature: Option<f64>,
@@ -41,6 +78,7 @@ pub struct SataDevice {
     pub pending_sectors: Option<u64>,
     pub uncorrectable_errors: Option<u64>,
     pub crc_errors: Option<u64>,
+    /// The remaining life ratio, normalized across the vendor specific attributes
     pub wear_level: Option<f64>,
 }
 
@@ -64,7 +102,7 @@ impl SataDevice {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct NvmeDevice {
     pub device: Device,
     pub temperature: Option<f64>,
@@ -97,20 +135,46 @@ impl NvmeDevice {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Device {
     pub device: String,
+    /// The name of the device in `/dev/disk/by-id`, if requested and available
+    pub id: Option<String>,
     pub model: String,
     pub serial_number: String,
+    /// The overall-health self-assessment of the device
+    pub health_passed: Option<bool>,
+    /// The result of the most recent self-test. `None` if it was aborted, and on the ATA
+    /// devices also while a test is in progress, as NVMe only logs the completed tests.
+    pub selftest_passed: Option<bool>,
+    pub selftest_remaining_percent: Option<f64>,
 }
 
-pub trait DataSource {
-    fn disk_temps(&self) -> impl Future<Output = anyhow::Result<SmartReports>> + Send;
+fn terabytes(data_units: u64) -> f64 {
+    data_units as f64 * NVME_DATA_UNIT_BYTES / 1e12
+}
+
+pub trait DataSource: Probe {
+    /// Lists the paths of the devices, without querying them
+    fn scan_devices(&self) -> impl Future<Output = anyhow::Result<Vec<String>>> + Send;
+    fn disk_temps(
+        &self,
+        devices: &[String],
+    ) -> impl Future<Output = anyhow::Result<SmartReports>> + Send;
 }
 
 #[derive(Clone)]
 pub struct Metrics {
     state: Arc<Mutex<Option<SmartReports>>>,
+    zero_if_absent: HashSet<String>,
+    device_aliases: HashMap<String, String>,
+    legacy_device_labels: bool,
+    temperature_unit: TemperatureUnit,
+
+    device_info: Desc,
+    health_passed: Desc,
+    selftest_passed: Desc,
+    selftest_remaining: Desc,
 
     sata_temp: Desc,
     sata_temp_min: Desc,
@@ -130,6 +194,7 @@ pub struct Metrics {
     nvme_percent_used: Desc,
     nvme_data_read: Desc,
     nvme_data_written: Desc,
+    nvme_terabytes_written: Desc,
     nvme_host_reads: Desc,
     nvme_host_writes: Desc,
     nvme_power_on: Desc,
@@ -138,146 +203,201 @@ pub struct Metrics {
 }
 
 impl Metrics {
-    pub fn new(state: Arc<Mutex<Option<SmartReports>>>) -> anyhow::Result<Self> {
-        let labels = vec!["device".into(), "model".into(), "serial_number".into()];
+    pub fn new(
+        state: Arc<Mutex<Option<SmartReports>>>,
+        device_aliases: HashMap<String, String>,
+        legacy_device_labels: bool,
+        options: &MetricOptions,
+    ) -> anyhow::Result<Self> {
+        let mut labels = match legacy_device_labels {
+            true => vec!["device".into(), "model".into(), "serial_number".into()],
+            false => vec!["device".into()],
+        };
+        let mut info_labels = vec![
+            "device".into(),
+            "model".into(),
+            "serial_number".into(),
+            "type".into(),
+        ];
+        if !device_aliases.is_empty() {
+            labels.push("alias".into());
+            info_labels.push("alias".into());
+        }
 
         Ok(Self {
             state,
+            zero_if_absent: options.zero_if_absent.clone(),
+            device_aliases,
+            temperature_unit: options.temperature_unit,
+            legacy_device_labels,
+
+            device_info: Desc::new(
+                options.name("system_smart_device_info"),
+                "The model and the serial number of the device".into(),
+                info_labels,
+                options.const_labels(),
+            )?,
+
+            health_passed: Desc::new(
+                options.name("system_smart_health_passed"),
+                "SMART overall-health self-assessment (1 = passed, 0 = failed)".into(),
+                labels.clone(),
+                options.const_labels(),
+            )?,
+
+            selftest_passed: Desc::new(
+                options.name("system_smart_selftest_passed"),
+                "Result of the most recent SMART self-test (1 = passed, 0 = failed)".into(),
+                labels.clone(),
+                options.const_labels(),
+            )?,
+            selftest_remaining: Desc::new(
+                options.name("system_smart_selftest_remaining_percent"),
+                "Remaining percent of the SMART self-test in progress".into(),
+                labels.clone(),
+                options.const_labels(),
+            )?,
 
             // --- SATA Descriptors ---
             sata_temp: Desc::new(
-                "system_smart_sata_temperature_celsius".into(),
+                options.temperature_name("system_smart_sata_temperature"),
                 "Current SATA disk temperature".into(),
                 labels.clone(),
-                HashMap::new(),
+                options.const_labels(),
             )?,
             sata_temp_min: Desc::new(
-                "smart_sata_temperature_min_celsius".into(),
+                options.temperature_name("smart_sata_temperature_min"),
                 "Minimum temperature recorded by the SATA device".into(),
                 labels.clone(),
-                HashMap::new(),
+                options.const_labels(),
             )?,
             sata_temp_max: Desc::new(
-                "smart_sata_temperature_max_celsius".into(),
+                options.temperature_name("smart_sata_temperature_max"),
                 "Maximum temperature recorded by the SATA device".into(),
                 labels.clone(),
-                HashMap::new(),
+                options.const_labels(),
             )?,
             sata_start_stop: Desc::new(
-                "system_smart_sata_start_stop_count_total".into(),
+                options.name("system_smart_sata_start_stop_count_total"),
                 "Total SATA start/stop cycles".into(),
                 labels.clone(),
-                HashMap::new(),
+                options.const_labels(),
             )?,
             sata_power_on: Desc::new(
-                "system_smart_sata_power_on_hours_total".into(),
+                options.name("system_smart_sata_power_on_hours_total"),
                 "Total SATA power on hours".into(),
                 labels.clone(),
-                HashMap::new(),
+                options.const_labels(),
             )?,
             sata_power_cycle: Desc::new(
-                "system_smart_sata_power_cycle_count_total".into(),
+                options.name("system_smart_sata_power_cycle_count_total"),
                 "Total SATA power cycles".into(),
                 labels.clone(),
-                HashMap::new(),
+                options.const_labels(),
             )?,
             sata_load_cycle: Desc::new(
-                "system_smart_sata_load_cycle_count_total".into(),
+                options.name("system_smart_sata_load_cycle_count_total"),
                 "Total SATA load/unload cycles".into(),
                 labels.clone(),
-                HashMap::new(),
+                options.const_labels(),
             )?,
             sata_reallocated: Desc::new(
-                "system_smart_sata_reallocated_sectors_total".into(),
+                options.name("system_smart_sata_reallocated_sectors_total"),
                 "Total SATA reallocated sectors count".into(),
                 labels.clone(),
-                HashMap::new(),
+                options.const_labels(),
             )?,
             sata_pending: Desc::new(
-                "system_smart_sata_pending_sectors_total".into(),
+                options.name("system_smart_sata_pending_sectors_total"),
                 "Total SATA pending sectors count".into(),
                 labels.clone(),
-                HashMap::new(),
+                options.const_labels(),
             )?,
             sata_uncorrectable: Desc::new(
-                "system_smart_sata_uncorrectable_errors_total".into(),
+                options.name("system_smart_sata_uncorrectable_errors_total"),
                 "Total SATA uncorrectable errors count".into(),
                 labels.clone(),
-                HashMap::new(),
+                options.const_labels(),
             )?,
             sata_crc_errors: Desc::new(
-                "system_smart_sata_crc_errors_total".into(),
+                options.name("system_smart_sata_crc_errors_total"),
                 "Total SATA interface CRC errors (UDMA_CRC_Error_Count)".into(),
                 labels.clone(),
-                HashMap::new(),
+                options.const_labels(),
             )?,
             sata_wear_level: Desc::new(
-                "system_smart_sata_wear_level_ratio".into(),
+                options.name("system_smart_sata_wear_level_ratio"),
                 "SATA SSD wear level (1.0 is new, 0.0 is end of life)".into(),
                 labels.clone(),
-                HashMap::new(),
+                options.const_labels(),
             )?,
 
             // --- NVMe Descriptors ---
             nvme_temp: Desc::new(
-                "system_smart_nvme_temperature_celsius".into(),
+                options.temperature_name("system_smart_nvme_temperature"),
                 "Current NVMe disk temperature".into(),
                 labels.clone(),
-                HashMap::new(),
+                options.const_labels(),
             )?,
             nvme_available_spare: Desc::new(
-                "system_smart_nvme_available_spare_ratio".into(),
+                options.name("system_smart_nvme_available_spare_ratio"),
                 "NVMe remaining spare capacity ratio (0-1)".into(),
                 labels.clone(),
-                HashMap::new(),
+                options.const_labels(),
             )?,
             nvme_percent_used: Desc::new(
-                "system_smart_nvme_percent_used_ratio".into(),
+                options.name("system_smart_nvme_percent_used_ratio"),
                 "NVMe life used ratio (0-1, can exceed 1)".into(),
                 labels.clone(),
-                HashMap::new(),
+                options.const_labels(),
             )?,
             nvme_data_read: Desc::new(
-                "system_smart_nvme_data_units_read_total".into(),
-                "Total NVMe data units read (512 byte units)".into(),
+                options.name("system_smart_nvme_data_units_read_total"),
+                "Total NVMe data units read (units of 1000 512-byte blocks)".into(),
                 labels.clone(),
-                HashMap::new(),
+                options.const_labels(),
             )?,
             nvme_data_written: Desc::new(
-                "system_smart_nvme_data_units_written_total".into(),
-                "Total NVMe data units written (512 byte units)".into(),
+                options.name("system_smart_nvme_data_units_written_total"),
+                "Total NVMe data units written (units of 1000 512-byte blocks)".into(),
+                labels.clone(),
+                options.const_labels(),
+            )?,
+            nvme_terabytes_written: Desc::new(
+                options.name("system_smart_nvme_terabytes_written"),
+                "Total data written to the NVMe device, in terabytes (TBW)".into(),
                 labels.clone(),
-                HashMap::new(),
+                options.const_labels(),
             )?,
             nvme_host_reads: Desc::new(
-                "system_smart_nvme_host_reads_total".into(),
+                options.name("system_smart_nvme_host_reads_total"),
                 "Total NVMe host read commands".into(),
                 labels.clone(),
-                HashMap::new(),
+                options.const_labels(),
             )?,
             nvme_host_writes: Desc::new(
-                "system_smart_nvme_host_writes_total".into(),
+                options.name("system_smart_nvme_host_writes_total"),
                 "Total NVMe host write commands".into(),
                 labels.clone(),
-                HashMap::new(),
+                options.const_labels(),
             )?,
             nvme_power_on: Desc::new(
-                "system_smart_nvme_power_on_hours_total".into(),
+                options.name("system_smart_nvme_power_on_hours_total"),
                 "Total NVMe power on hours".into(),
                 labels.clone(),
-                HashMap::new(),
+                options.const_labels(),
             )?,
             nvme_unsafe_shutdowns: Desc::new(
-                "system_smart_nvme_unsafe_shutdowns_total".into(),
+                options.name("system_smart_nvme_unsafe_shutdowns_total"),
                 "Total NVMe unsafe shutdowns".into(),
                 labels.clone(),
-                HashMap::new(),
+                options.const_labels(),
             )?,
             nvme_media_errors: Desc::new(
-                "system_smart_nvme_media_errors_total".into(),
+                options.name("system_smart_nvme_media_errors_total"),
                 "Total NVMe media and data integrity errors".into(),
                 labels.clone(),
-                HashMap::new(),
+                options.const_labels(),
             )?,
         })
     }
@@ -287,18 +407,63 @@ impl Metrics {
         Ok(())
     }
 
+    /// The data series are identified only by the device, so that the serial
+    /// numbers do not multiply the cardinality. Join on the info metric instead.
     fn make_labels(&self, device: &Device) -> Vec<LabelPair> {
-        into_labels(&[
+        let mut labels = match self.legacy_device_labels {
+            true => into_labels(&[
+                ("device", &device.device),
+                ("model", &device.model),
+                ("serial_number", &device.serial_number),
+            ]),
+            false => into_labels(&[("device", &device.device)]),
+        };
+
+        if let Some(id) = &device.id {
+            labels.extend(into_labels(&[("id", id)]));
+        }
+
+        labels.extend(self.alias_label(device));
+
+        labels
+    }
+
+    /// Every series of a metric must have the same label names, so the
+    /// devices without an alias get an empty one
+    fn alias_label(&self, device: &Device) -> Vec<LabelPair> {
+        if self.device_aliases.is_empty() {
+            return vec![];
+        }
+
+        let alias = device_alias(&self.device_aliases, &device.device);
+        into_labels(&[("alias", alias.unwrap_or_default())])
+    }
+
+    fn info_labels(&self, device: &Device, kind: &str) -> Vec<LabelPair> {
+        let mut labels = into_labels(&[
             ("device", &device.device),
             ("model", &device.model),
             ("serial_number", &device.serial_number),
-        ])
+            ("type", kind),
+        ]);
+
+        if let Some(id) = &device.id {
+            labels.extend(into_labels(&[("id", id)]));
+        }
+
+        labels.extend(self.alias_label(device));
+
+        labels
     }
 }
 
 impl prometheus::core::Collector for Metrics {
     fn desc(&self) -> Vec<&Desc> {
         vec![
+            &self.device_info,
+            &self.health_passed,
+            &self.selftest_passed,
+            &self.selftest_remaining,
             &self.sata_temp,
             &self.sata_temp_min,
             &self.sata_temp_max,
@@ -316,6 +481,7 @@ impl prometheus::core::Collector for Metrics {
             &self.nvme_percent_used,
             &self.nvme_data_read,
             &self.nvme_data_written,
+            &self.nvme_terabytes_written,
             &self.nvme_host_reads,
             &self.nvme_host_writes,
             &self.nvme_power_on,
@@ -332,38 +498,211 @@ impl prometheus::core::Collector for Metrics {
 
         let mut families = Vec::new();
 
+        let nvme = stats.nvme.iter().map(|n| (&n.device, "nvme"));
+        for (device, kind) in nvme.chain(stats.sata.iter().map(|s| (&s.device, "sata"))) {
+            let l = self.info_labels(device, kind);
+            families.push(gauge(&self.device_info, l, 1.0));
+        }
+
+        let devices = stats.nvme.iter().map(|n| &n.device);
+        for device in devices.chain(stats.sata.iter().map(|s| &s.device)) {
+            let l = self.make_labels(device);
+            let f = &mut families;
+
+            maybe_gauge(
+                &self.zero_if_absent,
+                f,
+                &self.health_passed,
+                &l,
+                device.health_passed.map(u8::from),
+            );
+            maybe_gauge(
+                &self.zero_if_absent,
+                f,
+                &self.selftest_passed,
+                &l,
+                device.selftest_passed.map(u8::from),
+            );
+            maybe_gauge(
+                &self.zero_if_absent,
+                f,
+                &self.selftest_remaining,
+                &l,
+                device.selftest_remaining_percent,
+            );
+        }
+
         for n in &stats.nvme {
             let l = self.make_labels(&n.device);
             let f = &mut families;
 
-            maybe_gauge(f, &self.nvme_temp, &l, n.temperature);
-            maybe_gauge(f, &self.nvme_available_spare, &l, n.available_spare);
-            maybe_gauge(f, &self.nvme_percent_used, &l, n.percent_used);
-            maybe_counter(f, &self.nvme_data_read, &l, n.data_units_read);
-            maybe_counter(f, &self.nvme_data_written, &l, n.data_units_written);
-            maybe_counter(f, &self.nvme_host_reads, &l, n.host_reads);
-            maybe_counter(f, &self.nvme_host_writes, &l, n.host_writes);
-            maybe_counter(f, &self.nvme_power_on, &l, n.power_on_hours);
-            maybe_counter(f, &self.nvme_unsafe_shutdowns, &l, n.unsafe_shutdowns);
-            maybe_counter(f, &self.nvme_media_errors, &l, n.media_errors);
+            maybe_gauge(
+                &self.zero_if_absent,
+                f,
+                &self.nvme_temp,
+                &l,
+                n.temperature.map(|t| self.temperature_unit.convert(t)),
+            );
+            maybe_gauge(
+                &self.zero_if_absent,
+                f,
+                &self.nvme_available_spare,
+                &l,
+                n.available_spare,
+            );
+            maybe_gauge(
+                &self.zero_if_absent,
+                f,
+                &self.nvme_percent_used,
+                &l,
+                n.percent_used,
+            );
+            maybe_counter(
+                &self.zero_if_absent,
+                f,
+                &self.nvme_data_read,
+                &l,
+                n.data_units_read,
+            );
+            maybe_counter(
+                &self.zero_if_absent,
+                f,
+                &self.nvme_data_written,
+                &l,
+                n.data_units_written,
+            );
+            maybe_gauge(
+                &self.zero_if_absent,
+                f,
+                &self.nvme_terabytes_written,
+                &l,
+                n.data_units_written.map(terabytes),
+            );
+            maybe_counter(
+                &self.zero_if_absent,
+                f,
+                &self.nvme_host_reads,
+                &l,
+                n.host_reads,
+            );
+            maybe_counter(
+                &self.zero_if_absent,
+                f,
+                &self.nvme_host_writes,
+                &l,
+                n.host_writes,
+            );
+            maybe_counter(
+                &self.zero_if_absent,
+                f,
+                &self.nvme_power_on,
+                &l,
+                n.power_on_hours,
+            );
+            maybe_counter(
+                &self.zero_if_absent,
+                f,
+                &self.nvme_unsafe_shutdowns,
+                &l,
+                n.unsafe_shutdowns,
+            );
+            maybe_counter(
+                &self.zero_if_absent,
+                f,
+                &self.nvme_media_errors,
+                &l,
+                n.media_errors,
+            );
         }
 
         for s in &stats.sata {
             let l = self.make_labels(&s.device);
             let f = &mut families;
 
-            maybe_gauge(f, &self.sata_temp, &l, s.temperature);
-            maybe_gauge(f, &self.sata_temp_min, &l, s.temperature_min);
-            maybe_gauge(f, &self.sata_temp_max, &l, s.temperature_max);
-            maybe_gauge(f, &self.sata_pending, &l, s.pending_sectors);
-            maybe_gauge(f, &self.sata_reallocated, &l, s.reallocated_sectors);
-            maybe_gauge(f, &self.sata_wear_level, &l, s.wear_level);
-            maybe_counter(f, &self.sata_start_stop, &l, s.start_stop_count);
-            maybe_counter(f, &self.sata_power_on, &l, s.power_on_hours);
-            maybe_counter(f, &self.sata_power_cycle, &l, s.power_cycle_count);
-            maybe_counter(f, &self.sata_load_cycle, &l, s.load_cycle_count);
-            maybe_counter(f, &self.sata_uncorrectable, &l, s.uncorrectable_errors);
-            maybe_counter(f, &self.sata_crc_errors, &l, s.crc_errors);
+            maybe_gauge(
+                &self.zero_if_absent,
+                f,
+                &self.sata_temp,
+                &l,
+                s.temperature.map(|t| self.temperature_unit.convert(t)),
+            );
+            maybe_gauge(
+                &self.zero_if_absent,
+                f,
+                &self.sata_temp_min,
+                &l,
+                s.temperature_min.map(|t| self.temperature_unit.convert(t)),
+            );
+            maybe_gauge(
+                &self.zero_if_absent,
+                f,
+                &self.sata_temp_max,
+                &l,
+                s.temperature_max.map(|t| self.temperature_unit.convert(t)),
+            );
+            maybe_gauge(
+                &self.zero_if_absent,
+                f,
+                &self.sata_pending,
+                &l,
+                s.pending_sectors,
+            );
+            maybe_gauge(
+                &self.zero_if_absent,
+                f,
+                &self.sata_reallocated,
+                &l,
+                s.reallocated_sectors,
+            );
+            maybe_gauge(
+                &self.zero_if_absent,
+                f,
+                &self.sata_wear_level,
+                &l,
+                s.wear_level,
+            );
+            maybe_counter(
+                &self.zero_if_absent,
+                f,
+                &self.sata_start_stop,
+                &l,
+                s.start_stop_count,
+            );
+            maybe_counter(
+                &self.zero_if_absent,
+                f,
+                &self.sata_power_on,
+                &l,
+                s.power_on_hours,
+            );
+            maybe_counter(
+                &self.zero_if_absent,
+                f,
+                &self.sata_power_cycle,
+                &l,
+                s.power_cycle_count,
+            );
+            maybe_counter(
+                &self.zero_if_absent,
+                f,
+                &self.sata_load_cycle,
+                &l,
+                s.load_cycle_count,
+            );
+            maybe_counter(
+                &self.zero_if_absent,
+                f,
+                &self.sata_uncorrectable,
+                &l,
+                s.uncorrectable_errors,
+            );
+            maybe_counter(
+                &self.zero_if_absent,
+                f,
+                &self.sata_crc_errors,
+                &l,
+                s.crc_errors,
+            );
         }
 
         families
@@ -391,17 +730,50 @@ impl<T> Metric for Smart<T>
 where
     T: DataSource + Send + Sync + 'static,
 {
-    fn register(self, registry: &Registry) -> anyhow::Result<Box<dyn Collector>> {
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    async fn supported(&self) -> bool {
+        probe(self.config.enabled, &self.data_source).await
+    }
+
+    fn register(
+        self,
+        registry: &Registry,
+        options: &MetricOptions,
+    ) -> anyhow::Result<Box<dyn Collector>> {
         if !self.config.enabled {
-            return Ok(Box::new(NoOpCollector::new()));
+            return Ok(Box::new(NoOpCollector::new(NAME)));
         }
 
-        let collector = SmartCollector::new(self.data_source);
+        let collector = SmartCollector::new(
+            self.data_source,
+            self.config.watch_devices,
+            self.config.ignore_devices,
+        );
         let measurements = collector.measurements();
 
-        let metrics = Metrics::new(measurements)?;
+        let metrics = Metrics::new(
+            measurements,
+            self.config.device_aliases,
+            self.config.legacy_device_labels,
+            options,
+        )?;
         registry.register(Box::new(metrics))?;
 
+        register_threshold(
+            registry,
+            options,
+            &options
+                .temperature_unit
+                .metric_name("system_smart_temperature_warning_threshold"),
+            "Configured disk temperature warning threshold",
+            self.config
+                .temperature_warning_threshold
+                .map(|t| options.temperature_unit.convert(t)),
+        )?;
+
         Ok(Box::new(collector))
     }
 }
@@ -409,22 +781,53 @@ where
 struct SmartCollector<T> {
     measurement: Arc<Mutex<Option<SmartReports>>>,
     data_source: T,
+    watch_devices: Option<Vec<String>>,
+    ignore_devices: Option<Vec<String>>,
 }
 
 impl<T> SmartCollector<T>
 where
     T: DataSource + Send + Sync + 'static,
 {
-    fn new(data_source: T) -> Self {
+    fn new(
+        data_source: T,
+        watch_devices: Option<Vec<String>>,
+        ignore_devices: Option<Vec<String>>,
+    ) -> Self {
         Self {
             data_source,
             measurement: Arc::new(Mutex::new(None)),
+            watch_devices,
+            ignore_devices,
         }
     }
 
     fn measurements(&self) -> Arc<Mutex<Option<SmartReports>>> {
         self.measurement.clone()
     }
+
+    fn should_collect(&self, device: &str) -> bool {
+        // The devices can be configured either by their name or by their path
+        let name = device.strip_prefix("/dev/").unwrap_or(device);
+        let listed = |devices: &Vec<String>| devices.iter().any(|d| d == device || d == name);
+
+        if let Some(watch) = &self.watch_devices {
+            return listed(watch);
+        }
+
+        if let Some(ignore) = &self.ignore_devices {
+            return !listed(ignore);
+        }
+
+        true
+    }
+
+    async fn query_devices(&self) -> anyhow::Result<SmartReports> {
+        let mut devices = self.data_source.scan_devices().await?;
+        devices.retain(|device| self.should_collect(device));
+
+        self.data_source.disk_temps(&devices).await
+    }
 }
 
 #[async_trait::async_trait]
@@ -432,19 +835,231 @@ impl<T> Collector for SmartCollector<T>
 where
     T: DataSource + Send + Sync + 'static,
 {
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
     #[tracing::instrument(level = "debug", skip_all)]
     async fn collect(&self) -> anyhow::Result<()> {
         let stats = self
-            .data_source
-            .disk_temps()
+            .query_devices()
             .await
-            .inspect_err(|e| tracing::error!(error=?e, "Failed to collect disk SMART statistics"))
-            .ok();
+            .context("Failed to collect disk SMART statistics");
 
         update_measurement_if(&self.measurement, stats, |old, new| {
             old.timestamp < new.timestamp
-        });
+        })
+    }
 
-        Ok(())
+    fn snapshot(&self) -> Option<serde_json::Value> {
+        snapshot(&self.measurement)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct NoDisks;
+
+    impl Probe for NoDisks {}
+
+    impl DataSource for NoDisks {
+        async fn scan_devices(&self) -> anyhow::Result<Vec<String>> {
+            Ok(vec![])
+        }
+
+        async fn disk_temps(&self, _devices: &[String]) -> anyhow::Result<SmartReports> {
+            Ok(SmartReports {
+                timestamp: Instant::now(),
+                sata: vec![],
+                nvme: vec![],
+            })
+        }
+    }
+
+    fn find_threshold(registry: &Registry) -> Option<f64> {
+        registry
+            .gather()
+            .iter()
+            .find(|mf| mf.name() == "system_smart_temperature_warning_threshold_celsius")
+            .map(|mf| mf.get_metric()[0].get_gauge().value())
+    }
+
+    #[test]
+    fn test_temperature_warning_threshold() {
+        let config = Config {
+            temperature_warning_threshold: Some(55.0),
+            ..Default::default()
+        };
+
+        let registry = Registry::new();
+        Smart::new(config, NoDisks)
+            .register(&registry, &MetricOptions::default())
+            .unwrap();
+
+        assert_eq!(find_threshold(&registry), Some(55.0));
+    }
+
+    #[test]
+    fn test_temperature_warning_threshold_not_configured() {
+        let registry = Registry::new();
+        Smart::new(Config::default(), NoDisks)
+            .register(&registry, &MetricOptions::default())
+            .unwrap();
+
+        assert_eq!(find_threshold(&registry), None);
+    }
+
+    #[test]
+    fn test_terabytes_written() {
+        assert_eq!(terabytes(0), 0.0);
+        assert_eq!(terabytes(61_234_567), 31.352098304);
+    }
+
+    struct OneNvme;
+
+    impl Probe for OneNvme {}
+
+    impl DataSource for OneNvme {
+        async fn scan_devices(&self) -> anyhow::Result<Vec<String>> {
+            Ok(vec!["/dev/nvme0".to_owned()])
+        }
+
+        async fn disk_temps(&self, _devices: &[String]) -> anyhow::Result<SmartReports> {
+            let mut nvme = NvmeDevice::new(Device {
+                device: "/dev/nvme0".to_owned(),
+                id: Some("nvme-Samsung_SSD_980_PRO_1TB_S5GXNX0R123456".to_owned()),
+                model: "Samsung SSD 980 PRO 1TB".to_owned(),
+                serial_number: "S5GXNX0R123456".to_owned(),
+                health_passed: Some(true),
+                selftest_passed: None,
+                selftest_remaining_percent: None,
+            });
+            nvme.temperature = Some(41.0);
+
+            Ok(SmartReports {
+                timestamp: Instant::now(),
+                sata: vec![],
+                nvme: vec![nvme],
+            })
+        }
+    }
+
+    async fn label_names(config: Config, metric: &str) -> Vec<String> {
+        let registry = Registry::new();
+        let collector = Smart::new(config, OneNvme)
+            .register(&registry, &MetricOptions::default())
+            .unwrap();
+        collector.collect().await.unwrap();
+
+        let families = registry.gather();
+        let mf = families.iter().find(|mf| mf.name() == metric).unwrap();
+        mf.get_metric()[0]
+            .get_label()
+            .iter()
+            .map(|l| l.name().to_owned())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_device_info() {
+        let info = label_names(Config::default(), "system_smart_device_info").await;
+        assert_eq!(info, ["device", "model", "serial_number", "type", "id"]);
+
+        let health = label_names(Config::default(), "system_smart_health_passed").await;
+        assert_eq!(health, ["device", "id"]);
+    }
+
+    #[tokio::test]
+    async fn test_legacy_device_labels() {
+        let config = Config {
+            legacy_device_labels: true,
+            ..Default::default()
+        };
+
+        let health = label_names(config, "system_smart_health_passed").await;
+        assert_eq!(health, ["device", "model", "serial_number", "id"]);
+    }
+
+    #[tokio::test]
+    async fn test_device_aliases() {
+        let config = Config {
+            device_aliases: HashMap::from([("sda".to_owned(), "data-hdd".to_owned())]),
+            ..Default::default()
+        };
+
+        let registry = Registry::new();
+        let collector = Smart::new(config, OneNvme)
+            .register(&registry, &MetricOptions::default())
+            .unwrap();
+        collector.collect().await.unwrap();
+
+        let families = registry.gather();
+        let mf = families
+            .iter()
+            .find(|mf| mf.name() == "system_smart_health_passed")
+            .unwrap();
+        let alias = mf.get_metric()[0]
+            .get_label()
+            .iter()
+            .find(|l| l.name() == "alias")
+            .map(|l| l.value().to_owned());
+        assert_eq!(alias.as_deref(), Some(""));
+    }
+
+    /// Records the queried devices, to verify that the filtered ones are not woken up
+    #[derive(Default)]
+    struct ScannedDevices(Mutex<Vec<String>>);
+
+    impl Probe for ScannedDevices {}
+
+    impl DataSource for ScannedDevices {
+        async fn scan_devices(&self) -> anyhow::Result<Vec<String>> {
+            Ok(["/dev/sda", "/dev/sdb", "/dev/nvme0"]
+                .map(String::from)
+                .to_vec())
+        }
+
+        async fn disk_temps(&self, devices: &[String]) -> anyhow::Result<SmartReports> {
+            *self.0.lock().unwrap() = devices.to_vec();
+
+            Ok(SmartReports {
+                timestamp: Instant::now(),
+                sata: vec![],
+                nvme: vec![],
+            })
+        }
+    }
+
+    async fn queried_devices(watch: Option<&[&str]>, ignore: Option<&[&str]>) -> Vec<String> {
+        let to_vec = |devices: &[&str]| devices.iter().map(|d| d.to_string()).collect();
+        let collector = SmartCollector::new(
+            ScannedDevices::default(),
+            watch.map(to_vec),
+            ignore.map(to_vec),
+        );
+
+        collector.collect().await.unwrap();
+        collector.data_source.0.into_inner().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_watch_devices() {
+        let devices = queried_devices(Some(&["sdb", "/dev/nvme0"]), None).await;
+        assert_eq!(devices, ["/dev/sdb", "/dev/nvme0"]);
+
+        // The watched devices take precedence over the ignored ones
+        let devices = queried_devices(Some(&["sda"]), Some(&["sda"])).await;
+        assert_eq!(devices, ["/dev/sda"]);
+    }
+
+    #[tokio::test]
+    async fn test_ignore_devices() {
+        let devices = queried_devices(None, Some(&["/dev/sda", "nvme0"])).await;
+        assert_eq!(devices, ["/dev/sdb"]);
+
+        let devices = queried_devices(None, None).await;
+        assert_eq!(devices, ["/dev/sda", "/dev/sdb", "/dev/nvme0"]);
     }
 }
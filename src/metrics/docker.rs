@@ -1,71 +1,149 @@
-use crate::domain::{Collector, Metric};
+use crate::domain::{Collector, Metric, Probe};
 use crate::metrics::no_operation::NoOpCollector;
-use crate::metrics::util::{into_labels, maybe_counter, maybe_gauge, update_measurement_if};
+use crate::metrics::util::{
+    MetricOptions, gauge, into_labels, maybe_counter, maybe_gauge, probe, snapshot,
+    update_measurement_if,
+};
+use anyhow::Context;
 use prometheus::Registry;
 use prometheus::core::Desc;
 use prometheus::proto::{LabelPair, MetricFamily};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::HashSet;
 use std::sync::{Arc, Mutex};
 use tokio::time::Instant;
 
-#[derive(Debug, Clone)]
+const NAME: &str = "docker";
+
+#[derive(Debug, Clone, Serialize)]
 pub struct ContainerStats {
     pub name: String,
+    pub image: String,
+    /// The container state, such as `running`, `paused` or `exited`
+    pub state: Option<String>,
+    /// 1 if the container is healthy, 0 if it is unhealthy or still starting.
+    /// Only available when the containers are inspected.
+    pub health: Option<f64>,
     pub cpu_usage: Option<f64>,
     pub mem_usage_bytes: Option<u64>,
     pub net_rx_bytes: Option<u64>,
     pub net_tx_bytes: Option<u64>,
+    pub blk_read_bytes: Option<u64>,
+    pub blk_write_bytes: Option<u64>,
+    /// Only available when the containers are inspected
+    pub start_time: Option<f64>,
+    pub pids: Option<u64>,
+    /// Only available when the containers are inspected
+    pub restart_count: Option<i64>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct DockerStats {
+    #[serde(skip)]
     pub timestamp: Instant,
     pub containers: Vec<ContainerStats>,
 }
 
-pub trait DataSource {
+pub trait DataSource: Probe {
     fn docker_stats(&self) -> impl Future<Output = anyhow::Result<DockerStats>> + Send;
 }
 
 #[derive(Clone)]
 struct Metrics {
     state: Arc<Mutex<Option<DockerStats>>>,
+    zero_if_absent: HashSet<String>,
     cpu_usage: Desc,
     mem_usage: Desc,
     net_rx: Desc,
     net_tx: Desc,
+    blk_read: Desc,
+    blk_write: Desc,
+    start_time: Desc,
+    container_state: Desc,
+    health: Desc,
+    pids: Desc,
+    restart_count: Desc,
 }
 
 impl Metrics {
-    pub fn new(state: Arc<Mutex<Option<DockerStats>>>) -> anyhow::Result<Self> {
+    pub fn new(
+        state: Arc<Mutex<Option<DockerStats>>>,
+        options: &MetricOptions,
+    ) -> anyhow::Result<Self> {
         let labels = vec!["container".to_owned()];
 
         Ok(Self {
             state,
+            zero_if_absent: options.zero_if_absent.clone(),
             cpu_usage: Desc::new(
-                "docker_cpu_usage_percent".into(),
+                options.name("docker_cpu_usage_percent"),
                 "CPU usage percentage".into(),
                 labels.clone(),
-                HashMap::new(),
+                options.const_labels(),
             )?,
             mem_usage: Desc::new(
-                "docker_memory_usage_bytes".into(),
+                options.name("docker_memory_usage_bytes"),
                 "Memory usage in bytes".into(),
                 labels.clone(),
-                HashMap::new(),
+                options.const_labels(),
             )?,
             net_rx: Desc::new(
-                "docker_network_receive_bytes_total".into(),
+                options.name("docker_network_receive_bytes_total"),
                 "Total bytes received".into(),
                 labels.clone(),
-                HashMap::new(),
+                options.const_labels(),
             )?,
             net_tx: Desc::new(
-                "docker_network_transmit_bytes_total".into(),
+                options.name("docker_network_transmit_bytes_total"),
                 "Total bytes transmitted".into(),
+                labels.clone(),
+                options.const_labels(),
+            )?,
+            blk_read: Desc::new(
+                options.name("docker_blkio_read_bytes_total"),
+                "Total bytes read from block devices".into(),
+                labels.clone(),
+                options.const_labels(),
+            )?,
+            blk_write: Desc::new(
+                options.name("docker_blkio_write_bytes_total"),
+                "Total bytes written to block devices".into(),
+                labels.clone(),
+                options.const_labels(),
+            )?,
+            start_time: Desc::new(
+                options.name("docker_container_start_time_seconds"),
+                "Start time of the container since unix epoch in seconds".into(),
+                labels.clone(),
+                options.const_labels(),
+            )?,
+            container_state: Desc::new(
+                options.name("docker_container_state"),
+                "The state of the container, always set to 1".into(),
+                vec![
+                    "container".to_owned(),
+                    "image".to_owned(),
+                    "state".to_owned(),
+                ],
+                options.const_labels(),
+            )?,
+            health: Desc::new(
+                options.name("docker_container_health"),
+                "Health check status of the container: 1 if healthy, 0 otherwise".into(),
+                labels.clone(),
+                options.const_labels(),
+            )?,
+            pids: Desc::new(
+                options.name("docker_container_pids"),
+                "Number of processes and threads in the container".into(),
+                labels.clone(),
+                options.const_labels(),
+            )?,
+            restart_count: Desc::new(
+                options.name("docker_container_restart_count"),
+                "Number of times the container has been restarted by docker".into(),
                 labels,
-                HashMap::new(),
+                options.const_labels(),
             )?,
         })
     }
@@ -82,7 +160,19 @@ impl Metrics {
 
 impl prometheus::core::Collector for Metrics {
     fn desc(&self) -> Vec<&Desc> {
-        vec![&self.cpu_usage, &self.mem_usage, &self.net_rx, &self.net_tx]
+        vec![
+            &self.cpu_usage,
+            &self.mem_usage,
+            &self.net_rx,
+            &self.net_tx,
+            &self.blk_read,
+            &self.blk_write,
+            &self.start_time,
+            &self.container_state,
+            &self.health,
+            &self.pids,
+            &self.restart_count,
+        ]
     }
 
     fn collect(&self) -> Vec<MetricFamily> {
@@ -91,13 +181,88 @@ impl prometheus::core::Collector for Metrics {
             return vec![];
         };
 
-        let mut mf = Vec::with_capacity(stats.containers.len() * 4);
+        let mut mf = Vec::with_capacity(stats.containers.len() * 11);
         for container in &stats.containers {
             let l = self.make_labels(container);
-            maybe_gauge(&mut mf, &self.cpu_usage, &l, container.cpu_usage);
-            maybe_gauge(&mut mf, &self.mem_usage, &l, container.mem_usage_bytes);
-            maybe_counter(&mut mf, &self.net_rx, &l, container.net_rx_bytes);
-            maybe_counter(&mut mf, &self.net_tx, &l, container.net_tx_bytes);
+            maybe_gauge(
+                &self.zero_if_absent,
+                &mut mf,
+                &self.cpu_usage,
+                &l,
+                container.cpu_usage,
+            );
+            maybe_gauge(
+                &self.zero_if_absent,
+                &mut mf,
+                &self.mem_usage,
+                &l,
+                container.mem_usage_bytes,
+            );
+            maybe_counter(
+                &self.zero_if_absent,
+                &mut mf,
+                &self.net_rx,
+                &l,
+                container.net_rx_bytes,
+            );
+            maybe_counter(
+                &self.zero_if_absent,
+                &mut mf,
+                &self.net_tx,
+                &l,
+                container.net_tx_bytes,
+            );
+            maybe_counter(
+                &self.zero_if_absent,
+                &mut mf,
+                &self.blk_read,
+                &l,
+                container.blk_read_bytes,
+            );
+            maybe_counter(
+                &self.zero_if_absent,
+                &mut mf,
+                &self.blk_write,
+                &l,
+                container.blk_write_bytes,
+            );
+            maybe_gauge(
+                &self.zero_if_absent,
+                &mut mf,
+                &self.start_time,
+                &l,
+                container.start_time,
+            );
+            maybe_gauge(
+                &self.zero_if_absent,
+                &mut mf,
+                &self.health,
+                &l,
+                container.health,
+            );
+            maybe_gauge(
+                &self.zero_if_absent,
+                &mut mf,
+                &self.pids,
+                &l,
+                container.pids,
+            );
+            maybe_gauge(
+                &self.zero_if_absent,
+                &mut mf,
+                &self.restart_count,
+                &l,
+                container.restart_count,
+            );
+
+            if let Some(state) = container.state.as_deref() {
+                let l = into_labels(&[
+                    ("container", &container.name),
+                    ("image", &container.image),
+                    ("state", state),
+                ]);
+                mf.push(gauge(&self.container_state, l, 1.0));
+            }
         }
 
         mf
@@ -107,11 +272,17 @@ impl prometheus::core::Collector for Metrics {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     pub enabled: bool,
+    pub priority: Option<i32>,
+    pub max_series: Option<usize>,
 }
 
 impl Default for Config {
     fn default() -> Self {
-        Self { enabled: true }
+        Self {
+            enabled: true,
+            priority: None,
+            max_series: None,
+        }
     }
 }
 
@@ -136,15 +307,27 @@ impl<T> Metric for Docker<T>
 where
     T: DataSource + Send + Sync + 'static,
 {
-    fn register(self, registry: &Registry) -> anyhow::Result<Box<dyn Collector>> {
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    async fn supported(&self) -> bool {
+        probe(self.config.enabled, &self.data_source).await
+    }
+
+    fn register(
+        self,
+        registry: &Registry,
+        options: &MetricOptions,
+    ) -> anyhow::Result<Box<dyn Collector>> {
         if !self.config.enabled {
-            return Ok(Box::new(NoOpCollector::new()));
+            return Ok(Box::new(NoOpCollector::new(NAME)));
         }
 
         let collector = DockerCollector::new(self.data_source);
         let measurements = collector.measurements();
 
-        let metrics = Metrics::new(measurements)?;
+        let metrics = Metrics::new(measurements, options)?;
         metrics.register(registry)?;
 
         Ok(Box::new(collector))
@@ -177,19 +360,121 @@ impl<T> Collector for DockerCollector<T>
 where
     T: DataSource + Send + Sync + 'static,
 {
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
     #[tracing::instrument(level = "debug", skip_all)]
     async fn collect(&self) -> anyhow::Result<()> {
         let stats = self
             .data_source
             .docker_stats()
             .await
-            .inspect_err(|e| tracing::error!(error=?e, "Failed to collect docker statistics"))
-            .ok();
+            .context("Failed to collect docker statistics");
 
         update_measurement_if(&self.measurement, stats, |old, new| {
             old.timestamp < new.timestamp
-        });
+        })
+    }
 
-        Ok(())
+    fn snapshot(&self) -> Option<serde_json::Value> {
+        snapshot(&self.measurement)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+    use std::time::Duration;
+
+    struct Snapshots {
+        containers: Mutex<VecDeque<Vec<&'static str>>>,
+    }
+
+    impl Probe for Snapshots {}
+
+    impl DataSource for Snapshots {
+        async fn docker_stats(&self) -> anyhow::Result<DockerStats> {
+            let names = self.containers.lock().unwrap().pop_front().unwrap();
+            let containers = names
+                .into_iter()
+                .map(|name| ContainerStats {
+                    name: name.to_owned(),
+                    image: "image".to_owned(),
+                    state: Some("running".to_owned()),
+                    health: None,
+                    cpu_usage: Some(0.5),
+                    mem_usage_bytes: Some(1024),
+                    net_rx_bytes: None,
+                    net_tx_bytes: None,
+                    blk_read_bytes: None,
+                    blk_write_bytes: None,
+                    start_time: None,
+                    pids: Some(4),
+                    restart_count: None,
+                })
+                .collect();
+
+            Ok(DockerStats {
+                timestamp: Instant::now(),
+                containers,
+            })
+        }
+    }
+
+    fn containers(registry: &Registry) -> Vec<String> {
+        registry
+            .gather()
+            .iter()
+            .filter(|mf| mf.name() == "docker_memory_usage_bytes")
+            .flat_map(|mf| mf.get_metric())
+            .map(|m| m.get_label()[0].value().to_owned())
+            .collect()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_vanished_container_is_removed() {
+        let data_source = Snapshots {
+            containers: Mutex::new(VecDeque::from([vec!["nginx", "postgres"], vec!["nginx"]])),
+        };
+
+        let registry = Registry::new();
+        let collector = Docker::new(Config::default(), data_source)
+            .register(&registry, &MetricOptions::default())
+            .unwrap();
+
+        collector.collect().await.unwrap();
+        assert_eq!(containers(&registry), ["nginx", "postgres"]);
+
+        tokio::time::advance(Duration::from_secs(1)).await;
+        collector.collect().await.unwrap();
+        assert_eq!(containers(&registry), ["nginx"]);
+    }
+
+    #[tokio::test]
+    async fn test_missing_restart_count_is_omitted() {
+        let data_source = Snapshots {
+            containers: Mutex::new(VecDeque::from([vec!["nginx"]])),
+        };
+
+        let registry = Registry::new();
+        let collector = Docker::new(Config::default(), data_source)
+            .register(&registry, &MetricOptions::default())
+            .unwrap();
+        collector.collect().await.unwrap();
+
+        let families = registry.gather();
+        let pids = families
+            .iter()
+            .find(|mf| mf.name() == "docker_container_pids")
+            .unwrap();
+        assert_eq!(pids.get_metric()[0].get_gauge().value(), 4.0);
+
+        assert!(
+            families
+                .iter()
+                .all(|mf| mf.name() != "docker_container_restart_count")
+        );
     }
 }
@@ -0,0 +1,472 @@
+use crate::domain::{Collector, Metric};
+use crate::metrics::no_operation::NoOpCollector;
+use crate::metrics::util::{
+    MetricOptions, gauge, into_labels, maybe_gauge, snapshot, update_measurement_if,
+};
+use anyhow::Context;
+use prometheus::Registry;
+use prometheus::core::Desc;
+use prometheus::proto::MetricFamily;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use tokio::time::Instant;
+
+const NAME: &str = "filesystem";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Config {
+    pub enabled: bool,
+    pub priority: Option<i32>,
+    pub max_series: Option<usize>,
+    /// The filesystem types which are not reported. Defaults to the pseudo and
+    /// the read-only by design filesystems, which would only add noise.
+    pub ignored_fs_types: Vec<String>,
+    /// Also query the network filesystems, e.g. NFS and CIFS. Disabled by default, because
+    /// statvfs blocks for as long as the server is unreachable, tying up a thread each scrape.
+    pub network_filesystems: bool,
+}
+
+/// The filesystems backed by a remote server, which are skipped unless `network_filesystems`
+const NETWORK_FS_TYPES: [&str; 10] = [
+    "9p",
+    "afs",
+    "ceph",
+    "cifs",
+    "fuse.sshfs",
+    "glusterfs",
+    "ncpfs",
+    "nfs",
+    "nfs4",
+    "smb3",
+];
+
+impl Default for Config {
+    fn default() -> Self {
+        let ignored_fs_types = [
+            "autofs",
+            "binfmt_misc",
+            "bpf",
+            "cgroup",
+            "cgroup2",
+            "configfs",
+            "debugfs",
+            "devpts",
+            "devtmpfs",
+            "efivarfs",
+            "fusectl",
+            "hugetlbfs",
+            "iso9660",
+            "mqueue",
+            "nsfs",
+            "overlay",
+            "proc",
+            "pstore",
+            "ramfs",
+            "rpc_pipefs",
+            "securityfs",
+            "squashfs",
+            "sysfs",
+            "tmpfs",
+            "tracefs",
+        ];
+
+        Self {
+            enabled: true,
+            priority: None,
+            max_series: None,
+            ignored_fs_types: ignored_fs_types.map(str::to_owned).to_vec(),
+            network_filesystems: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Mount {
+    pub device: String,
+    pub mountpoint: String,
+    pub fs_type: String,
+    pub readonly: bool,
+    /// Whether the filesystem has recorded errors. Only ext4 reports it.
+    pub device_error: Option<bool>,
+    /// Absent when the filesystem could not be queried, e.g. due to permissions
+    pub inodes: Option<Inodes>,
+}
+
+/// The inode counts of a filesystem, as reported by statvfs(3)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Inodes {
+    pub total: u64,
+    pub free: u64,
+}
+
+impl Inodes {
+    /// A filesystem may run out of inodes while it still has free space
+    pub fn used(&self) -> u64 {
+        self.total.saturating_sub(self.free)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FilesystemStats {
+    #[serde(skip)]
+    pub timestamp: Instant,
+    pub mounts: Vec<Mount>,
+}
+
+pub trait DataSource {
+    /// The mounts of the ignored filesystem types are skipped before they are queried
+    fn filesystem_stats(
+        &self,
+        ignored_fs_types: &[String],
+    ) -> impl Future<Output = anyhow::Result<FilesystemStats>> + Send;
+}
+
+#[derive(Clone)]
+struct Metrics {
+    state: Arc<Mutex<Option<FilesystemStats>>>,
+    zero_if_absent: HashSet<String>,
+    readonly: Desc,
+    device_error: Desc,
+    files: Desc,
+    files_free: Desc,
+    files_used: Desc,
+}
+
+impl Metrics {
+    pub fn new(
+        state: Arc<Mutex<Option<FilesystemStats>>>,
+        options: &MetricOptions,
+    ) -> anyhow::Result<Self> {
+        let labels = vec!["device".into(), "mountpoint".into(), "fstype".into()];
+
+        Ok(Self {
+            state,
+            zero_if_absent: options.zero_if_absent.clone(),
+            readonly: Desc::new(
+                options.name("system_filesystem_readonly"),
+                "Whether the filesystem is mounted read-only".into(),
+                labels.clone(),
+                options.const_labels(),
+            )?,
+            device_error: Desc::new(
+                options.name("system_filesystem_device_error"),
+                "Whether the filesystem has recorded errors".into(),
+                labels.clone(),
+                options.const_labels(),
+            )?,
+            files: Desc::new(
+                options.name("system_filesystem_files"),
+                "Total number of inodes in the filesystem".into(),
+                labels.clone(),
+                options.const_labels(),
+            )?,
+            files_free: Desc::new(
+                options.name("system_filesystem_files_free"),
+                "Number of free inodes in the filesystem".into(),
+                labels.clone(),
+                options.const_labels(),
+            )?,
+            files_used: Desc::new(
+                options.name("system_filesystem_files_used"),
+                "Number of used inodes in the filesystem".into(),
+                labels,
+                options.const_labels(),
+            )?,
+        })
+    }
+
+    pub fn register(&self, registry: &Registry) -> anyhow::Result<()> {
+        registry.register(Box::new(self.clone()))?;
+        Ok(())
+    }
+}
+
+impl prometheus::core::Collector for Metrics {
+    fn desc(&self) -> Vec<&Desc> {
+        vec![
+            &self.readonly,
+            &self.device_error,
+            &self.files,
+            &self.files_free,
+            &self.files_used,
+        ]
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        let guard = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(stats) = guard.as_ref() else {
+            return vec![];
+        };
+
+        let mut mf = Vec::new();
+        for mount in &stats.mounts {
+            let l = into_labels(&[
+                ("device", &mount.device),
+                ("mountpoint", &mount.mountpoint),
+                ("fstype", &mount.fs_type),
+            ]);
+
+            mf.push(gauge(&self.readonly, l.clone(), f64::from(mount.readonly)));
+            let device_error = mount.device_error.map(u8::from);
+            maybe_gauge(
+                &self.zero_if_absent,
+                &mut mf,
+                &self.device_error,
+                &l,
+                device_error,
+            );
+
+            let inodes = mount.inodes;
+            maybe_gauge(
+                &self.zero_if_absent,
+                &mut mf,
+                &self.files,
+                &l,
+                inodes.map(|i| i.total),
+            );
+            maybe_gauge(
+                &self.zero_if_absent,
+                &mut mf,
+                &self.files_free,
+                &l,
+                inodes.map(|i| i.free),
+            );
+            maybe_gauge(
+                &self.zero_if_absent,
+                &mut mf,
+                &self.files_used,
+                &l,
+                inodes.map(|i| i.used()),
+            );
+        }
+
+        mf
+    }
+}
+
+pub struct Filesystem<T> {
+    config: Config,
+    data_source: T,
+}
+
+impl<T> Filesystem<T>
+where
+    T: DataSource + Send + Sync + 'static,
+{
+    pub fn new(config: Config, data_source: T) -> Self {
+        Self {
+            config,
+            data_source,
+        }
+    }
+}
+
+impl<T> Metric for Filesystem<T>
+where
+    T: DataSource + Send + Sync + 'static,
+{
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    fn register(
+        self,
+        registry: &Registry,
+        options: &MetricOptions,
+    ) -> anyhow::Result<Box<dyn Collector>> {
+        if !self.config.enabled {
+            return Ok(Box::new(NoOpCollector::new(NAME)));
+        }
+
+        let mut ignored_fs_types = self.config.ignored_fs_types;
+        if !self.config.network_filesystems {
+            ignored_fs_types.extend(NETWORK_FS_TYPES.map(str::to_owned));
+        }
+
+        let collector = FilesystemCollector::new(self.data_source, ignored_fs_types);
+        let metrics = Metrics::new(collector.measurements(), options)?;
+        metrics.register(registry)?;
+
+        Ok(Box::new(collector))
+    }
+}
+
+struct FilesystemCollector<T> {
+    measurement: Arc<Mutex<Option<FilesystemStats>>>,
+    data_source: T,
+    ignored_fs_types: Vec<String>,
+}
+
+impl<T> FilesystemCollector<T>
+where
+    T: DataSource,
+{
+    fn new(data_source: T, ignored_fs_types: Vec<String>) -> Self {
+        Self {
+            measurement: Arc::new(Mutex::new(None)),
+            data_source,
+            ignored_fs_types,
+        }
+    }
+
+    fn measurements(&self) -> Arc<Mutex<Option<FilesystemStats>>> {
+        Arc::clone(&self.measurement)
+    }
+}
+
+#[async_trait::async_trait]
+impl<T> Collector for FilesystemCollector<T>
+where
+    T: DataSource + Send + Sync + 'static,
+{
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn collect(&self) -> anyhow::Result<()> {
+        let stats = self
+            .data_source
+            .filesystem_stats(&self.ignored_fs_types)
+            .await
+            .context("Failed to collect filesystem statistics");
+
+        update_measurement_if(&self.measurement, stats, |old, new| {
+            old.timestamp < new.timestamp
+        })
+    }
+
+    fn snapshot(&self) -> Option<serde_json::Value> {
+        snapshot(&self.measurement)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Mounts;
+
+    impl DataSource for Mounts {
+        async fn filesystem_stats(
+            &self,
+            ignored_fs_types: &[String],
+        ) -> anyhow::Result<FilesystemStats> {
+            let mount = |device: &str, mountpoint: &str, fs_type: &str, readonly| Mount {
+                device: device.to_owned(),
+                mountpoint: mountpoint.to_owned(),
+                fs_type: fs_type.to_owned(),
+                readonly,
+                device_error: None,
+                inodes: None,
+            };
+
+            let mut mounts = vec![
+                mount("/dev/sda1", "/", "ext4", false),
+                mount("/dev/sdb1", "/srv", "xfs", true),
+                mount("/dev/loop0", "/snap/core/1", "squashfs", true),
+                mount("nas:/export", "/mnt/nas", "nfs4", false),
+            ];
+            mounts.retain(|m| !ignored_fs_types.contains(&m.fs_type));
+
+            Ok(FilesystemStats {
+                timestamp: Instant::now(),
+                mounts,
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ignored_fs_types() {
+        let registry = Registry::new();
+        let collector = Filesystem::new(Config::default(), Mounts)
+            .register(&registry, &MetricOptions::default())
+            .unwrap();
+        collector.collect().await.unwrap();
+
+        let families = registry.gather();
+        let readonly = families
+            .iter()
+            .find(|mf| mf.name() == "system_filesystem_readonly")
+            .unwrap();
+
+        let values = readonly
+            .get_metric()
+            .iter()
+            .map(|m| (m.get_label()[1].value().to_owned(), m.get_gauge().value()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            values,
+            vec![("/".to_owned(), 0.0), ("/srv".to_owned(), 1.0)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_network_filesystems_opt_in() {
+        let config = Config {
+            network_filesystems: true,
+            ..Default::default()
+        };
+
+        let registry = Registry::new();
+        let collector = Filesystem::new(config, Mounts)
+            .register(&registry, &MetricOptions::default())
+            .unwrap();
+        collector.collect().await.unwrap();
+
+        let families = registry.gather();
+        let readonly = families
+            .iter()
+            .find(|mf| mf.name() == "system_filesystem_readonly")
+            .unwrap();
+        assert_eq!(readonly.get_metric().len(), 3);
+    }
+
+    struct NearlyFullInodes;
+
+    impl DataSource for NearlyFullInodes {
+        async fn filesystem_stats(&self, _: &[String]) -> anyhow::Result<FilesystemStats> {
+            Ok(FilesystemStats {
+                timestamp: Instant::now(),
+                mounts: vec![Mount {
+                    device: "/dev/sda1".to_owned(),
+                    mountpoint: "/var".to_owned(),
+                    fs_type: "ext4".to_owned(),
+                    readonly: false,
+                    device_error: None,
+                    inodes: Some(Inodes {
+                        total: 1_000_000,
+                        free: 12,
+                    }),
+                }],
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_inodes() {
+        let registry = Registry::new();
+        let collector = Filesystem::new(Config::default(), NearlyFullInodes)
+            .register(&registry, &MetricOptions::default())
+            .unwrap();
+        collector.collect().await.unwrap();
+
+        let families = registry.gather();
+        let value = |name: &str| {
+            let mf = families.iter().find(|mf| mf.name() == name).unwrap();
+            mf.get_metric()[0].get_gauge().value()
+        };
+
+        assert_eq!(value("system_filesystem_files"), 1_000_000.0);
+        assert_eq!(value("system_filesystem_files_free"), 12.0);
+        assert_eq!(value("system_filesystem_files_used"), 999_988.0);
+        assert_eq!(value("system_filesystem_readonly"), 0.0);
+    }
+
+    #[test]
+    fn test_inodes_used_never_underflows() {
+        let inodes = Inodes { total: 0, free: 5 };
+        assert_eq!(inodes.used(), 0);
+    }
+}
@@ -0,0 +1,274 @@
+use crate::domain::{Collector, Metric, Probe};
+use crate::metrics::no_operation::NoOpCollector;
+use crate::metrics::util::{
+    MetricOptions, counter, into_labels, probe, snapshot, update_measurement_if,
+};
+use anyhow::Context;
+use prometheus::Registry;
+use prometheus::core::Desc;
+use prometheus::proto::MetricFamily;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use tokio::time::Instant;
+
+const NAME: &str = "kmsg";
+
+/// The syslog severities, indexed by their numeric level
+pub const LEVELS: [&str; 8] = [
+    "emerg", "alert", "crit", "err", "warn", "notice", "info", "debug",
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Config {
+    pub enabled: bool,
+    pub priority: Option<i32>,
+    pub max_series: Option<usize>,
+    /// The severities of the kernel messages to count
+    pub levels: Vec<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            priority: None,
+            max_series: None,
+            levels: vec!["err".to_owned(), "warn".to_owned()],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct KernelMessage {
+    pub level: &'static str,
+    /// The sequence number of the message, which is unique since boot
+    pub seq: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct KernelMessages {
+    #[serde(skip)]
+    pub timestamp: Instant,
+    /// The messages currently in the kernel ring buffer, in chronological order
+    pub messages: Vec<KernelMessage>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct KmsgStats {
+    #[serde(skip)]
+    pub timestamp: Instant,
+    pub counts: BTreeMap<String, u64>,
+}
+
+pub trait DataSource: Probe {
+    fn kernel_messages(&self) -> impl Future<Output = anyhow::Result<KernelMessages>> + Send;
+}
+
+#[derive(Clone)]
+struct Metrics {
+    state: Arc<Mutex<Option<KmsgStats>>>,
+    messages: Desc,
+}
+
+impl Metrics {
+    pub fn new(
+        state: Arc<Mutex<Option<KmsgStats>>>,
+        options: &MetricOptions,
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
+            state,
+            messages: Desc::new(
+                options.name("system_kernel_messages_total"),
+                "Number of kernel log messages by severity".into(),
+                vec!["level".to_owned()],
+                options.const_labels(),
+            )?,
+        })
+    }
+
+    pub fn register(&self, registry: &Registry) -> anyhow::Result<()> {
+        registry.register(Box::new(self.clone()))?;
+        Ok(())
+    }
+}
+
+impl prometheus::core::Collector for Metrics {
+    fn desc(&self) -> Vec<&Desc> {
+        vec![&self.messages]
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        let guard = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(stats) = guard.as_ref() else {
+            return vec![];
+        };
+
+        stats
+            .counts
+            .iter()
+            .map(|(level, count)| {
+                counter(
+                    &self.messages,
+                    into_labels(&[("level", level)]),
+                    *count as f64,
+                )
+            })
+            .collect()
+    }
+}
+
+pub struct Kmsg<T> {
+    config: Config,
+    data_source: T,
+}
+
+impl<T> Kmsg<T>
+where
+    T: DataSource + Send + Sync + 'static,
+{
+    pub fn new(config: Config, data_source: T) -> Self {
+        Self {
+            config,
+            data_source,
+        }
+    }
+}
+
+impl<T> Metric for Kmsg<T>
+where
+    T: DataSource + Send + Sync + 'static,
+{
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    async fn supported(&self) -> bool {
+        probe(self.config.enabled, &self.data_source).await
+    }
+
+    fn register(
+        self,
+        registry: &Registry,
+        options: &MetricOptions,
+    ) -> anyhow::Result<Box<dyn Collector>> {
+        if !self.config.enabled {
+            return Ok(Box::new(NoOpCollector::new(NAME)));
+        }
+
+        let collector = KmsgCollector::new(self.data_source, &self.config.levels);
+        let metrics = Metrics::new(collector.measurements(), options)?;
+        metrics.register(registry)?;
+
+        Ok(Box::new(collector))
+    }
+}
+
+#[derive(Debug, Default)]
+struct Counts {
+    /// The sequence number of the last counted message, so that it's not counted again
+    cursor: Option<u64>,
+    counts: BTreeMap<String, u64>,
+}
+
+impl Counts {
+    fn add(&mut self, messages: &[KernelMessage]) {
+        for message in messages {
+            if self.cursor.is_some_and(|cursor| message.seq <= cursor) {
+                continue;
+            }
+
+            *self.counts.entry(message.level.to_owned()).or_default() += 1;
+            self.cursor = Some(message.seq);
+        }
+    }
+}
+
+struct KmsgCollector<T> {
+    measurement: Arc<Mutex<Option<KmsgStats>>>,
+    counts: Mutex<Counts>,
+    data_source: T,
+}
+
+impl<T> KmsgCollector<T>
+where
+    T: DataSource,
+{
+    fn new(data_source: T, levels: &[String]) -> Self {
+        // Expose the configured levels from the start, so that rate() works for the first message
+        let counts = Counts {
+            cursor: None,
+            counts: levels.iter().map(|level| (level.clone(), 0)).collect(),
+        };
+
+        Self {
+            measurement: Arc::new(Mutex::new(None)),
+            counts: Mutex::new(counts),
+            data_source,
+        }
+    }
+
+    fn measurements(&self) -> Arc<Mutex<Option<KmsgStats>>> {
+        Arc::clone(&self.measurement)
+    }
+}
+
+#[async_trait::async_trait]
+impl<T> Collector for KmsgCollector<T>
+where
+    T: DataSource + Send + Sync + 'static,
+{
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn collect(&self) -> anyhow::Result<()> {
+        let stats = self
+            .data_source
+            .kernel_messages()
+            .await
+            .context("Failed to collect kernel messages")
+            .map(|messages| {
+                let mut counts = self.counts.lock().unwrap_or_else(|e| e.into_inner());
+                counts.add(&messages.messages);
+
+                KmsgStats {
+                    timestamp: messages.timestamp,
+                    counts: counts.counts.clone(),
+                }
+            });
+
+        update_measurement_if(&self.measurement, stats, |old, new| {
+            old.timestamp < new.timestamp
+        })
+    }
+
+    fn snapshot(&self) -> Option<serde_json::Value> {
+        snapshot(&self.measurement)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(level: &'static str, seq: u64) -> KernelMessage {
+        KernelMessage { level, seq }
+    }
+
+    #[test]
+    fn test_counts_skip_seen_messages() {
+        let mut counts = Counts::default();
+        counts.add(&[message("err", 15), message("warn", 20)]);
+
+        // The ring buffer still contains the already counted messages
+        counts.add(&[message("err", 15), message("warn", 20), message("err", 32)]);
+
+        assert_eq!(counts.cursor, Some(32));
+        assert_eq!(
+            counts.counts,
+            BTreeMap::from([("err".to_owned(), 2), ("warn".to_owned(), 1)])
+        );
+    }
+}
@@ -1,11 +1,16 @@
 use crate::domain::{Collector, Metric};
 use crate::metrics::no_operation::NoOpCollector;
-use prometheus::{IntGauge, Registry};
+use crate::metrics::util::MetricOptions;
+use prometheus::{GaugeVec, IntGauge, Registry};
 use serde::{Deserialize, Serialize};
 
+const NAME: &str = "memory";
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     pub enabled: bool,
+    pub priority: Option<i32>,
+    pub max_series: Option<usize>,
     pub report_swap: bool,
 }
 
@@ -13,6 +18,8 @@ impl Default for Config {
     fn default() -> Self {
         Self {
             enabled: true,
+            priority: None,
+            max_series: None,
             report_swap: false,
         }
     }
@@ -33,11 +40,44 @@ pub struct RamStats {
     pub available: u64,
     pub buffers: u64,
     pub cache: u64,
+    pub hugepages_total: u64,
+    pub hugepages_free: u64,
+    pub hugepage_size: u64,
+    pub anon_hugepages: u64,
+    /// Amount of memory consumed by the zswap pool
+    pub zswap_pool: u64,
+    /// Amount of (uncompressed) anonymous memory stored in zswap
+    pub zswap_stored: u64,
+    /// Memory allocated by the processes, even if not used yet (`Committed_AS`)
+    pub committed: u64,
+    /// The limit of the committed memory under strict overcommit accounting
+    pub commit_limit: u64,
+    pub dirty: u64,
+    pub writeback: u64,
+    pub shmem: u64,
+}
+
+#[derive(Default, Debug, Clone, Eq, PartialEq)]
+pub struct ZswapStats {
+    pub stored_pages: u64,
+    pub pool_total_size: u64,
+    /// The base page size of the system, e.g. 16 KiB or 64 KiB on some arm64 and ppc64 hosts
+    pub page_size: u64,
 }
 
 pub trait DataSource {
     fn swap(&self) -> impl Future<Output = anyhow::Result<SwapStats>> + Send;
     fn ram(&self) -> impl Future<Output = anyhow::Result<RamStats>> + Send;
+    /// Returns both the RAM and the swap statistics, which come from the same source
+    fn stats(&self) -> impl Future<Output = anyhow::Result<(RamStats, SwapStats)>> + Send
+    where
+        Self: Sync,
+    {
+        async { Ok((self.ram().await?, self.swap().await?)) }
+    }
+    /// Returns `None` when the zswap statistics are not available, for instance
+    /// when debugfs is not mounted or the process lacks the permissions to read it
+    fn zswap(&self) -> impl Future<Output = anyhow::Result<Option<ZswapStats>>> + Send;
 }
 
 #[derive(Debug, Clone)]
@@ -62,17 +102,25 @@ impl<T> Metric for MemoryUsage<T>
 where
     T: DataSource + Send + Sync + 'static,
 {
-    fn register(self, registry: &Registry) -> anyhow::Result<Box<dyn Collector>> {
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    fn register(
+        self,
+        registry: &Registry,
+        options: &MetricOptions,
+    ) -> anyhow::Result<Box<dyn Collector>> {
         if !self.config.enabled {
-            return Ok(Box::new(NoOpCollector::new()));
+            return Ok(Box::new(NoOpCollector::new(NAME)));
         }
 
         let mut swap_metrics = None;
         if self.config.report_swap {
-            swap_metrics = Some(SwapMetrics::register(registry)?);
+            swap_metrics = Some(SwapMetrics::register(registry, options)?);
         }
 
-        let ram_metrics = RamMetrics::register(registry)?;
+        let ram_metrics = RamMetrics::register(registry, options)?;
 
         Ok(Box::new(MemoryUsageCollector::new(
             ram_metrics,
@@ -89,23 +137,23 @@ struct SwapMetrics {
 }
 
 impl SwapMetrics {
-    fn register(registry: &Registry) -> anyhow::Result<Self> {
-        let total = IntGauge::new(
+    fn register(registry: &Registry, options: &MetricOptions) -> anyhow::Result<Self> {
+        let total = IntGauge::with_opts(options.opts(
             "system_swap_total_bytes",
             "Total amount of swap space available",
-        )?;
+        ))?;
         registry.register(Box::new(total.clone()))?;
 
-        let free = IntGauge::new(
+        let free = IntGauge::with_opts(options.opts(
             "system_swap_free_bytes",
             "Amount of swap space currently unused",
-        )?;
+        ))?;
         registry.register(Box::new(free.clone()))?;
 
-        let used = IntGauge::new(
+        let used = IntGauge::with_opts(options.opts(
             "system_swap_used_bytes",
             "Amount of swap space currently in use",
-        )?;
+        ))?;
         registry.register(Box::new(used.clone()))?;
 
         Ok(Self { total, free, used })
@@ -120,46 +168,128 @@ struct RamMetrics {
     avail: IntGauge,
     buffers: IntGauge,
     cache: IntGauge,
+    hugepages_total: IntGauge,
+    hugepages_free: IntGauge,
+    anon_hugepages: IntGauge,
+    zswap_pool: IntGauge,
+    zswap_stored: IntGauge,
+    zswap_compression_ratio: GaugeVec,
+    committed: IntGauge,
+    commit_limit: IntGauge,
+    dirty: IntGauge,
+    writeback: IntGauge,
+    shmem: IntGauge,
 }
 
 impl RamMetrics {
-    fn register(registry: &Registry) -> anyhow::Result<Self> {
-        let total = IntGauge::new(
+    fn register(registry: &Registry, options: &MetricOptions) -> anyhow::Result<Self> {
+        let total = IntGauge::with_opts(options.opts(
             "system_memory_total_bytes",
             "Total physical RAM installed on the system",
-        )?;
+        ))?;
         registry.register(Box::new(total.clone()))?;
 
-        let used = IntGauge::new(
+        let used = IntGauge::with_opts(options.opts(
             "system_memory_used_bytes",
             "Amount of memory currently used by programs (Non-reclaimable)",
-        )?;
+        ))?;
         registry.register(Box::new(used.clone()))?;
 
-        let free = IntGauge::new(
+        let free = IntGauge::with_opts(options.opts(
             "system_memory_free_bytes",
             "Amount of memory that is completely unused (does not include cache/buffers)",
-        )?;
+        ))?;
         registry.register(Box::new(free.clone()))?;
 
-        let avail = IntGauge::new(
+        let avail = IntGauge::with_opts(options.opts(
             "system_memory_available_bytes",
             "Estimate of how much memory is available for starting new applications without swapping",
-        )?;
+        ))?;
         registry.register(Box::new(avail.clone()))?;
 
-        let buffers = IntGauge::new(
+        let buffers = IntGauge::with_opts(options.opts(
             "system_memory_buffers_bytes",
             "Memory used by kernel buffers (metadata/raw block storage)",
-        )?;
+        ))?;
         registry.register(Box::new(buffers.clone()))?;
 
-        let cache = IntGauge::new(
+        let cache = IntGauge::with_opts(options.opts(
             "system_memory_cache_bytes",
             "Memory used by the page cache and reclaimable slab objects",
-        )?;
+        ))?;
         registry.register(Box::new(cache.clone()))?;
 
+        let hugepages_total = IntGauge::with_opts(options.opts(
+            "system_memory_hugepages_total",
+            "Number of huge pages in the pool",
+        ))?;
+        registry.register(Box::new(hugepages_total.clone()))?;
+
+        let hugepages_free = IntGauge::with_opts(options.opts(
+            "system_memory_hugepages_free_bytes",
+            "Amount of memory in the huge page pool that is not yet allocated",
+        ))?;
+        registry.register(Box::new(hugepages_free.clone()))?;
+
+        let anon_hugepages = IntGauge::with_opts(options.opts(
+            "system_memory_anon_hugepages_bytes",
+            "Amount of anonymous memory backed by transparent huge pages",
+        ))?;
+        registry.register(Box::new(anon_hugepages.clone()))?;
+
+        let zswap_pool = IntGauge::with_opts(options.opts(
+            "system_zswap_pool_bytes",
+            "Amount of memory consumed by the compressed zswap pool",
+        ))?;
+        registry.register(Box::new(zswap_pool.clone()))?;
+
+        let zswap_stored = IntGauge::with_opts(options.opts(
+            "system_zswap_stored_bytes",
+            "Amount of anonymous memory stored (uncompressed) in zswap",
+        ))?;
+        registry.register(Box::new(zswap_stored.clone()))?;
+
+        // A label-less vector, so that the ratio can be
+        // omitted when the zswap statistics are not available
+        let zswap_compression_ratio = GaugeVec::new(
+            options.opts(
+                "system_zswap_compression_ratio",
+                "Ratio between the stored and the compressed size of the zswap pages",
+            ),
+            &[],
+        )?;
+        registry.register(Box::new(zswap_compression_ratio.clone()))?;
+
+        let committed = IntGauge::with_opts(options.opts(
+            "system_memory_committed_bytes",
+            "Amount of memory allocated by the processes, even if not used yet",
+        ))?;
+        registry.register(Box::new(committed.clone()))?;
+
+        let commit_limit = IntGauge::with_opts(options.opts(
+            "system_memory_commit_limit_bytes",
+            "Amount of memory that can be allocated under strict overcommit accounting",
+        ))?;
+        registry.register(Box::new(commit_limit.clone()))?;
+
+        let dirty = IntGauge::with_opts(options.opts(
+            "system_memory_dirty_bytes",
+            "Amount of memory waiting to be written back to the disk",
+        ))?;
+        registry.register(Box::new(dirty.clone()))?;
+
+        let writeback = IntGauge::with_opts(options.opts(
+            "system_memory_writeback_bytes",
+            "Amount of memory actively being written back to the disk",
+        ))?;
+        registry.register(Box::new(writeback.clone()))?;
+
+        let shmem = IntGauge::with_opts(options.opts(
+            "system_memory_shmem_bytes",
+            "Amount of memory used by shared memory and tmpfs",
+        ))?;
+        registry.register(Box::new(shmem.clone()))?;
+
         Ok(Self {
             total,
             used,
@@ -167,6 +297,17 @@ impl RamMetrics {
             avail,
             buffers,
             cache,
+            hugepages_total,
+            hugepages_free,
+            anon_hugepages,
+            zswap_pool,
+            zswap_stored,
+            zswap_compression_ratio,
+            committed,
+            commit_limit,
+            dirty,
+            writeback,
+            shmem,
         })
     }
 }
@@ -195,22 +336,53 @@ impl<T> Collector for MemoryUsageCollector<T>
 where
     T: DataSource + Send + Sync + 'static,
 {
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
     #[tracing::instrument(level = "debug", skip_all)]
     async fn collect(&self) -> anyhow::Result<()> {
+        let (stats, swap) = self.data_source.stats().await?;
         if let Some(swap_metrics) = &self.swap_metrics {
-            let stats = self.data_source.swap().await?;
-            swap_metrics.free.set(stats.free as i64);
-            swap_metrics.used.set(stats.used as i64);
-            swap_metrics.total.set(stats.total as i64);
+            swap_metrics.free.set(swap.free as i64);
+            swap_metrics.used.set(swap.used as i64);
+            swap_metrics.total.set(swap.total as i64);
         }
 
-        let stats = self.data_source.ram().await?;
         self.ram_metrics.free.set(stats.free as i64);
         self.ram_metrics.used.set(stats.used as i64);
         self.ram_metrics.total.set(stats.total as i64);
         self.ram_metrics.avail.set(stats.available as i64);
         self.ram_metrics.buffers.set(stats.buffers as i64);
         self.ram_metrics.cache.set(stats.cache as i64);
+        self.ram_metrics
+            .hugepages_total
+            .set(stats.hugepages_total as i64);
+        self.ram_metrics
+            .hugepages_free
+            .set((stats.hugepages_free * stats.hugepage_size) as i64);
+        self.ram_metrics
+            .anon_hugepages
+            .set(stats.anon_hugepages as i64);
+        self.ram_metrics.zswap_pool.set(stats.zswap_pool as i64);
+        self.ram_metrics.zswap_stored.set(stats.zswap_stored as i64);
+        self.ram_metrics.committed.set(stats.committed as i64);
+        self.ram_metrics.commit_limit.set(stats.commit_limit as i64);
+        self.ram_metrics.dirty.set(stats.dirty as i64);
+        self.ram_metrics.writeback.set(stats.writeback as i64);
+        self.ram_metrics.shmem.set(stats.shmem as i64);
+
+        match self.data_source.zswap().await? {
+            Some(zswap) if zswap.pool_total_size > 0 => {
+                let ratio =
+                    (zswap.stored_pages * zswap.page_size) as f64 / zswap.pool_total_size as f64;
+                self.ram_metrics
+                    .zswap_compression_ratio
+                    .with_label_values(&[] as &[&str])
+                    .set(ratio);
+            }
+            _ => self.ram_metrics.zswap_compression_ratio.reset(),
+        }
 
         Ok(())
     }
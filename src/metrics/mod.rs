@@ -1,12 +1,35 @@
+pub mod btrfs;
+pub mod cgroup;
+pub mod conntrack;
 pub mod cpu_frequency;
+pub mod cpu_throttle;
 pub mod cpu_usage;
 pub mod disk_io;
 pub mod disk_smart;
 pub mod docker;
+pub mod filesystem;
+pub mod kmsg;
 pub mod memory_usage;
 pub mod network_io;
+pub mod nfs;
 pub mod no_operation;
+pub mod numa;
+pub mod os_info;
+pub mod platform;
+pub mod processes_top;
+pub mod rapl;
+pub mod slabinfo;
+pub mod swap;
+pub mod timesync;
+pub mod tokio_runtime;
 pub mod ups;
+pub mod users;
 mod util;
+pub mod vmstat;
+pub mod wifi;
 pub mod zfs_arc;
 pub mod zfs_dataset;
+pub mod zpool;
+pub mod zram;
+
+pub use util::{MetricOptions, TemperatureUnit};
@@ -1,43 +1,74 @@
 use crate::domain::{Collector, Metric};
 use crate::metrics::no_operation::NoOpCollector;
-use crate::metrics::util::{into_labels, maybe_counter, update_measurement_if};
+use crate::metrics::util::{
+    MetricOptions, into_labels, maybe_counter, maybe_gauge, snapshot, update_measurement_if,
+};
+use anyhow::Context;
 use prometheus::Registry;
 use prometheus::core::Desc;
 use prometheus::proto::{LabelPair, MetricFamily};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::HashSet;
 use std::sync::{Arc, Mutex};
 use tokio::time::Instant;
 
+const NAME: &str = "network";
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     pub enabled: bool,
+    pub priority: Option<i32>,
+    pub max_series: Option<usize>,
     pub watch_interfaces: Option<Vec<String>>,
     pub ignore_interfaces: Option<Vec<String>>,
+    /// Skip the interfaces which are known to be down, i.e. after a cable pull
+    pub only_up_interfaces: bool,
+    /// Additionally export the totals summed across all collected interfaces. They are
+    /// gauges, as the sum drops when an interface disappears, so `rate()` should be
+    /// applied to the per-interface counters instead.
+    pub aggregate: bool,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
             enabled: true,
+            priority: None,
+            max_series: None,
             watch_interfaces: Some(vec!["bond0".to_owned(), "tailscale1".to_owned()]),
             ignore_interfaces: None,
+            only_up_interfaces: false,
+            aggregate: false,
         }
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct InterfaceStats {
     pub interface: String,
+    /// The RFC 2863 operational state from sysfs, such as `up`, `down` or `unknown`
+    pub operstate: Option<String>,
+    /// How many times the link went up or down, absent for some virtual devices
+    pub carrier_changes: Option<u64>,
     pub bytes_sent: u64,
     pub bytes_received: u64,
     pub packets_sent: u64,
     pub packets_received: u64,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AggregateStats {
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+}
+
+#[derive(Serialize)]
 pub struct NetworkIoStats {
+    #[serde(skip)]
     pub timestamp: Instant,
     pub interfaces: Vec<InterfaceStats>,
+    /// Computed by the collector, after the interfaces are filtered
+    pub aggregate: Option<AggregateStats>,
 }
 
 pub trait DataSource {
@@ -46,40 +77,66 @@ pub trait DataSource {
 #[derive(Clone)]
 pub struct Metrics {
     state: Arc<Mutex<Option<NetworkIoStats>>>,
+    zero_if_absent: HashSet<String>,
     bytes_sent: Desc,
     bytes_received: Desc,
     packets_sent: Desc,
     packets_received: Desc,
+    carrier_changes: Desc,
+    aggregate_bytes_sent: Desc,
+    aggregate_bytes_received: Desc,
 }
 
 impl Metrics {
-    pub fn new(state: Arc<Mutex<Option<NetworkIoStats>>>) -> anyhow::Result<Self> {
+    pub fn new(
+        state: Arc<Mutex<Option<NetworkIoStats>>>,
+        options: &MetricOptions,
+    ) -> anyhow::Result<Self> {
         let labels = vec!["device".to_string()];
         Ok(Self {
             state,
+            zero_if_absent: options.zero_if_absent.clone(),
             bytes_sent: Desc::new(
-                "system_network_transmit_bytes_total".into(),
+                options.name("system_network_transmit_bytes_total"),
                 "Total bytes sent".into(),
                 labels.clone(),
-                HashMap::new(),
+                options.const_labels(),
             )?,
             bytes_received: Desc::new(
-                "system_network_receive_bytes_total".into(),
+                options.name("system_network_receive_bytes_total"),
                 "Total bytes received".into(),
                 labels.clone(),
-                HashMap::new(),
+                options.const_labels(),
             )?,
             packets_sent: Desc::new(
-                "system_network_transmit_packets_total".into(),
+                options.name("system_network_transmit_packets_total"),
                 "Total packets sent".into(),
                 labels.clone(),
-                HashMap::new(),
+                options.const_labels(),
             )?,
             packets_received: Desc::new(
-                "system_network_receive_packets_total".into(),
+                options.name("system_network_receive_packets_total"),
                 "Total packets received".into(),
+                labels.clone(),
+                options.const_labels(),
+            )?,
+            carrier_changes: Desc::new(
+                options.name("system_network_carrier_changes_total"),
+                "Total number of link state changes".into(),
                 labels,
-                HashMap::new(),
+                options.const_labels(),
+            )?,
+            aggregate_bytes_sent: Desc::new(
+                options.name("system_network_transmit_bytes_aggregate"),
+                "Sum of the bytes sent by the currently collected interfaces".into(),
+                vec![],
+                options.const_labels(),
+            )?,
+            aggregate_bytes_received: Desc::new(
+                options.name("system_network_receive_bytes_aggregate"),
+                "Sum of the bytes received by the currently collected interfaces".into(),
+                vec![],
+                options.const_labels(),
             )?,
         })
     }
@@ -101,6 +158,9 @@ impl prometheus::core::Collector for Metrics {
             &self.bytes_received,
             &self.packets_sent,
             &self.packets_received,
+            &self.carrier_changes,
+            &self.aggregate_bytes_sent,
+            &self.aggregate_bytes_received,
         ]
     }
 
@@ -113,20 +173,60 @@ impl prometheus::core::Collector for Metrics {
         let mut mf = vec![];
         for device in &stats.interfaces {
             let l = self.make_labels(device);
-            maybe_counter(&mut mf, &self.bytes_sent, &l, Some(device.bytes_sent));
             maybe_counter(
+                &self.zero_if_absent,
+                &mut mf,
+                &self.bytes_sent,
+                &l,
+                Some(device.bytes_sent),
+            );
+            maybe_counter(
+                &self.zero_if_absent,
                 &mut mf,
                 &self.bytes_received,
                 &l,
                 Some(device.bytes_received),
             );
-            maybe_counter(&mut mf, &self.packets_sent, &l, Some(device.packets_sent));
             maybe_counter(
+                &self.zero_if_absent,
+                &mut mf,
+                &self.packets_sent,
+                &l,
+                Some(device.packets_sent),
+            );
+            maybe_counter(
+                &self.zero_if_absent,
                 &mut mf,
                 &self.packets_received,
                 &l,
                 Some(device.packets_received),
             );
+            maybe_counter(
+                &self.zero_if_absent,
+                &mut mf,
+                &self.carrier_changes,
+                &l,
+                device.carrier_changes,
+            );
+        }
+
+        if let Some(aggregate) = &stats.aggregate {
+            let sent = Some(aggregate.bytes_sent);
+            let received = Some(aggregate.bytes_received);
+            maybe_gauge(
+                &self.zero_if_absent,
+                &mut mf,
+                &self.aggregate_bytes_sent,
+                &[],
+                sent,
+            );
+            maybe_gauge(
+                &self.zero_if_absent,
+                &mut mf,
+                &self.aggregate_bytes_received,
+                &[],
+                received,
+            );
         }
 
         mf
@@ -152,15 +252,23 @@ impl<T> Metric for NetworkIo<T>
 where
     T: DataSource + Send + Sync + 'static,
 {
-    fn register(self, registry: &Registry) -> anyhow::Result<Box<dyn Collector>> {
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    fn register(
+        self,
+        registry: &Registry,
+        options: &MetricOptions,
+    ) -> anyhow::Result<Box<dyn Collector>> {
         if !self.config.enabled {
-            return Ok(Box::new(NoOpCollector::new()));
+            return Ok(Box::new(NoOpCollector::new(NAME)));
         }
 
         let collector = NetworkIoCollector::new(self.config, self.data_source);
         let measurements = collector.measurements();
 
-        let metrics = Metrics::new(measurements)?;
+        let metrics = Metrics::new(measurements, options)?;
         metrics.register(registry)?;
 
         Ok(Box::new(collector))
@@ -186,7 +294,18 @@ impl<T> NetworkIoCollector<T> {
         Arc::clone(&self.measurement)
     }
 
-    fn should_collect(&self, interface_name: &str) -> bool {
+    fn should_collect(&self, interface: &InterfaceStats) -> bool {
+        // Virtual interfaces such as the loopback and tun devices report `unknown`
+        if self.config.only_up_interfaces
+            && !matches!(
+                interface.operstate.as_deref(),
+                None | Some("up" | "unknown")
+            )
+        {
+            return false;
+        }
+
+        let interface_name = interface.interface.as_str();
         if let Some(watch) = &self.config.watch_interfaces {
             return watch.iter().any(|i| i == interface_name);
         }
@@ -199,11 +318,28 @@ impl<T> NetworkIoCollector<T> {
     }
 }
 
+fn aggregate(interfaces: &[InterfaceStats]) -> AggregateStats {
+    interfaces.iter().fold(
+        AggregateStats {
+            bytes_sent: 0,
+            bytes_received: 0,
+        },
+        |acc, i| AggregateStats {
+            bytes_sent: acc.bytes_sent.wrapping_add(i.bytes_sent),
+            bytes_received: acc.bytes_received.wrapping_add(i.bytes_received),
+        },
+    )
+}
+
 #[async_trait::async_trait]
 impl<T> Collector for NetworkIoCollector<T>
 where
     T: DataSource + Send + Sync + 'static,
 {
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
     #[tracing::instrument(level = "debug", skip_all)]
     async fn collect(&self) -> anyhow::Result<()> {
         let stats = self
@@ -211,18 +347,170 @@ where
             .network_io()
             .await
             .map(|mut stats| {
-                stats
-                    .interfaces
-                    .retain(|iface| self.should_collect(iface.interface.as_str()));
+                stats.interfaces.retain(|iface| self.should_collect(iface));
+                if self.config.aggregate {
+                    stats.aggregate = Some(aggregate(&stats.interfaces));
+                }
                 stats
             })
-            .inspect_err(|e| tracing::error!(error=?e, "Failed to collect network IO statistics"))
-            .ok();
+            .context("Failed to collect network IO statistics");
 
         update_measurement_if(&self.measurement, stats, |old, new| {
             old.timestamp < new.timestamp
-        });
+        })
+    }
 
-        Ok(())
+    fn snapshot(&self) -> Option<serde_json::Value> {
+        snapshot(&self.measurement)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TwoInterfaces;
+
+    impl DataSource for TwoInterfaces {
+        async fn network_io(&self) -> anyhow::Result<NetworkIoStats> {
+            let interface = |name: &str, operstate: &str| InterfaceStats {
+                interface: name.to_owned(),
+                operstate: Some(operstate.to_owned()),
+                carrier_changes: None,
+                bytes_sent: 1024,
+                bytes_received: 2048,
+                packets_sent: 1,
+                packets_received: 2,
+            };
+
+            Ok(NetworkIoStats {
+                timestamp: Instant::now(),
+                interfaces: vec![interface("eth0", "up"), interface("eth1", "down")],
+                aggregate: None,
+            })
+        }
+    }
+
+    /// Simulates an interface which is removed after the first read, e.g. a VPN tunnel
+    struct RemovedInterface(std::sync::atomic::AtomicU64);
+
+    impl DataSource for RemovedInterface {
+        async fn network_io(&self) -> anyhow::Result<NetworkIoStats> {
+            let mut stats = TwoInterfaces.network_io().await?;
+            let reads = self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+
+            stats.timestamp += std::time::Duration::from_secs(reads);
+            if reads > 0 {
+                stats.interfaces.retain(|i| i.interface != "eth1");
+            }
+
+            Ok(stats)
+        }
+    }
+
+    async fn collected_interfaces(only_up_interfaces: bool) -> Vec<String> {
+        let config = Config {
+            watch_interfaces: None,
+            only_up_interfaces,
+            ..Default::default()
+        };
+
+        let registry = Registry::new();
+        let collector = NetworkIo::new(config, TwoInterfaces)
+            .register(&registry, &MetricOptions::default())
+            .unwrap();
+        collector.collect().await.unwrap();
+
+        let families = registry.gather();
+        let bytes_sent = families
+            .iter()
+            .find(|mf| mf.name() == "system_network_transmit_bytes_total")
+            .unwrap();
+
+        bytes_sent
+            .get_metric()
+            .iter()
+            .map(|m| m.get_label()[0].value().to_owned())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_down_interfaces_are_kept_by_default() {
+        assert_eq!(collected_interfaces(false).await, vec!["eth0", "eth1"]);
+    }
+
+    #[tokio::test]
+    async fn test_down_interfaces_are_suppressed() {
+        assert_eq!(collected_interfaces(true).await, vec!["eth0"]);
+    }
+
+    #[tokio::test]
+    async fn test_removed_interface_is_not_exported() {
+        let config = Config {
+            watch_interfaces: None,
+            ..Default::default()
+        };
+
+        let registry = Registry::new();
+        let collector = NetworkIo::new(config, RemovedInterface(Default::default()))
+            .register(&registry, &MetricOptions::default())
+            .unwrap();
+
+        let exported = |registry: &Registry| {
+            registry
+                .gather()
+                .iter()
+                .filter(|mf| mf.name() == "system_network_transmit_bytes_total")
+                .flat_map(|mf| mf.get_metric())
+                .map(|m| m.get_label()[0].value().to_owned())
+                .collect::<Vec<_>>()
+        };
+
+        collector.collect().await.unwrap();
+        assert_eq!(exported(&registry), ["eth0", "eth1"]);
+
+        collector.collect().await.unwrap();
+        assert_eq!(exported(&registry), ["eth0"]);
+    }
+
+    #[tokio::test]
+    async fn test_aggregate_sums_the_collected_interfaces() {
+        let config = Config {
+            watch_interfaces: None,
+            aggregate: true,
+            ..Default::default()
+        };
+
+        let registry = Registry::new();
+        let collector = NetworkIo::new(config, TwoInterfaces)
+            .register(&registry, &MetricOptions::default())
+            .unwrap();
+        collector.collect().await.unwrap();
+
+        let value = |name: &str| {
+            let families = registry.gather();
+            let mf = families.iter().find(|mf| mf.name() == name).unwrap();
+            assert!(mf.get_metric()[0].get_label().is_empty());
+            mf.get_metric()[0].get_gauge().value()
+        };
+
+        assert_eq!(value("system_network_transmit_bytes_aggregate"), 2048.0);
+        assert_eq!(value("system_network_receive_bytes_aggregate"), 4096.0);
+    }
+
+    #[tokio::test]
+    async fn test_aggregate_is_disabled_by_default() {
+        let registry = Registry::new();
+        let collector = NetworkIo::new(Config::default(), TwoInterfaces)
+            .register(&registry, &MetricOptions::default())
+            .unwrap();
+        collector.collect().await.unwrap();
+
+        assert!(
+            registry
+                .gather()
+                .iter()
+                .all(|mf| !mf.name().contains("aggregate"))
+        );
     }
 }
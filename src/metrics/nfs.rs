@@ -0,0 +1,248 @@
+use crate::domain::{Collector, Metric};
+use crate::metrics::no_operation::NoOpCollector;
+use crate::metrics::util::{MetricOptions, counter, into_labels, snapshot, update_measurement_if};
+use anyhow::Context;
+use prometheus::Registry;
+use prometheus::core::Desc;
+use prometheus::proto::MetricFamily;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use tokio::time::Instant;
+
+const NAME: &str = "nfs";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Config {
+    pub enabled: bool,
+    pub priority: Option<i32>,
+    pub max_series: Option<usize>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            priority: None,
+            max_series: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct OperationStats {
+    pub operation: String,
+    pub requests: u64,
+    /// Cumulative round trip time in milliseconds
+    pub rtt: u64,
+    /// Cumulative execution time (including the queueing time) in milliseconds
+    pub execute: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MountStats {
+    pub mount: String,
+    pub read_bytes: u64,
+    pub write_bytes: u64,
+    pub operations: Vec<OperationStats>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NfsStats {
+    #[serde(skip)]
+    pub timestamp: Instant,
+    pub mounts: Vec<MountStats>,
+}
+
+pub trait DataSource {
+    fn nfs_stats(&self) -> impl Future<Output = anyhow::Result<NfsStats>> + Send;
+}
+
+#[derive(Clone)]
+struct Metrics {
+    state: Arc<Mutex<Option<NfsStats>>>,
+    read_bytes: Desc,
+    write_bytes: Desc,
+    requests: Desc,
+    rtt: Desc,
+    execute: Desc,
+}
+
+impl Metrics {
+    pub fn new(
+        state: Arc<Mutex<Option<NfsStats>>>,
+        options: &MetricOptions,
+    ) -> anyhow::Result<Self> {
+        let mount_labels = vec!["mount".to_owned()];
+        let operation_labels = vec!["mount".to_owned(), "operation".to_owned()];
+
+        Ok(Self {
+            state,
+            read_bytes: Desc::new(
+                options.name("system_nfs_read_bytes_total"),
+                "Number of bytes read by applications from the NFS mount".into(),
+                mount_labels.clone(),
+                options.const_labels(),
+            )?,
+            write_bytes: Desc::new(
+                options.name("system_nfs_write_bytes_total"),
+                "Number of bytes written by applications to the NFS mount".into(),
+                mount_labels,
+                options.const_labels(),
+            )?,
+            requests: Desc::new(
+                options.name("system_nfs_operation_requests_total"),
+                "Number of requests performed for the NFS operation".into(),
+                operation_labels.clone(),
+                options.const_labels(),
+            )?,
+            rtt: Desc::new(
+                options.name("system_nfs_operation_rtt_seconds_total"),
+                "Cumulative round trip time of the NFS operation".into(),
+                operation_labels.clone(),
+                options.const_labels(),
+            )?,
+            execute: Desc::new(
+                options.name("system_nfs_operation_execute_seconds_total"),
+                "Cumulative execution time of the NFS operation, including queueing".into(),
+                operation_labels,
+                options.const_labels(),
+            )?,
+        })
+    }
+
+    pub fn register(&self, registry: &Registry) -> anyhow::Result<()> {
+        registry.register(Box::new(self.clone()))?;
+        Ok(())
+    }
+}
+
+impl prometheus::core::Collector for Metrics {
+    fn desc(&self) -> Vec<&Desc> {
+        vec![
+            &self.read_bytes,
+            &self.write_bytes,
+            &self.requests,
+            &self.rtt,
+            &self.execute,
+        ]
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        let guard = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(stats) = guard.as_ref() else {
+            return vec![];
+        };
+
+        let as_seconds = |ms: u64| ms as f64 / 1000.0;
+
+        let mut mf = Vec::new();
+        for mount in &stats.mounts {
+            let l = into_labels(&[("mount", &mount.mount)]);
+            mf.push(counter(
+                &self.read_bytes,
+                l.clone(),
+                mount.read_bytes as f64,
+            ));
+            mf.push(counter(&self.write_bytes, l, mount.write_bytes as f64));
+
+            for op in &mount.operations {
+                let l = into_labels(&[("mount", &mount.mount), ("operation", &op.operation)]);
+                mf.push(counter(&self.requests, l.clone(), op.requests as f64));
+                mf.push(counter(&self.rtt, l.clone(), as_seconds(op.rtt)));
+                mf.push(counter(&self.execute, l, as_seconds(op.execute)));
+            }
+        }
+
+        mf
+    }
+}
+
+pub struct Nfs<T> {
+    config: Config,
+    data_source: T,
+}
+
+impl<T> Nfs<T>
+where
+    T: DataSource + Send + Sync + 'static,
+{
+    pub fn new(config: Config, data_source: T) -> Self {
+        Self {
+            config,
+            data_source,
+        }
+    }
+}
+
+impl<T> Metric for Nfs<T>
+where
+    T: DataSource + Send + Sync + 'static,
+{
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    fn register(
+        self,
+        registry: &Registry,
+        options: &MetricOptions,
+    ) -> anyhow::Result<Box<dyn Collector>> {
+        if !self.config.enabled {
+            return Ok(Box::new(NoOpCollector::new(NAME)));
+        }
+
+        let collector = NfsCollector::new(self.data_source);
+        let metrics = Metrics::new(collector.measurements(), options)?;
+        metrics.register(registry)?;
+
+        Ok(Box::new(collector))
+    }
+}
+
+struct NfsCollector<T> {
+    measurement: Arc<Mutex<Option<NfsStats>>>,
+    data_source: T,
+}
+
+impl<T> NfsCollector<T>
+where
+    T: DataSource,
+{
+    fn new(data_source: T) -> Self {
+        Self {
+            measurement: Arc::new(Mutex::new(None)),
+            data_source,
+        }
+    }
+
+    fn measurements(&self) -> Arc<Mutex<Option<NfsStats>>> {
+        Arc::clone(&self.measurement)
+    }
+}
+
+#[async_trait::async_trait]
+impl<T> Collector for NfsCollector<T>
+where
+    T: DataSource + Send + Sync + 'static,
+{
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn collect(&self) -> anyhow::Result<()> {
+        let stats = self
+            .data_source
+            .nfs_stats()
+            .await
+            .context("Failed to collect NFS statistics");
+
+        update_measurement_if(&self.measurement, stats, |old, new| {
+            old.timestamp < new.timestamp
+        })
+    }
+
+    fn snapshot(&self) -> Option<serde_json::Value> {
+        snapshot(&self.measurement)
+    }
+}
@@ -1,18 +1,21 @@
 use crate::domain::Collector;
 
-#[derive(Default)]
 pub struct NoOpCollector {
-    //
+    name: &'static str,
 }
 
 impl NoOpCollector {
-    pub fn new() -> Self {
-        Self::default()
+    pub fn new(name: &'static str) -> Self {
+        Self { name }
     }
 }
 
 #[async_trait::async_trait]
 impl Collector for NoOpCollector {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
     async fn collect(&self) -> anyhow::Result<()> {
         // do nothing by design
         Ok(())
@@ -0,0 +1,205 @@
+use crate::domain::{Collector, Metric};
+use crate::metrics::no_operation::NoOpCollector;
+use crate::metrics::util::{MetricOptions, gauge, into_labels, snapshot, update_measurement_if};
+use anyhow::Context;
+use prometheus::Registry;
+use prometheus::core::Desc;
+use prometheus::proto::MetricFamily;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use tokio::time::Instant;
+
+const NAME: &str = "numa";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Config {
+    pub enabled: bool,
+    pub priority: Option<i32>,
+    pub max_series: Option<usize>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            priority: None,
+            max_series: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct NodeMemoryStats {
+    pub node: String,
+    pub total: u64,
+    pub free: u64,
+    pub used: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NumaStats {
+    #[serde(skip)]
+    pub timestamp: Instant,
+    pub nodes: Vec<NodeMemoryStats>,
+}
+
+pub trait DataSource {
+    fn numa_stats(&self) -> impl Future<Output = anyhow::Result<NumaStats>> + Send;
+}
+
+#[derive(Clone)]
+struct Metrics {
+    state: Arc<Mutex<Option<NumaStats>>>,
+    total: Desc,
+    free: Desc,
+    used: Desc,
+}
+
+impl Metrics {
+    pub fn new(
+        state: Arc<Mutex<Option<NumaStats>>>,
+        options: &MetricOptions,
+    ) -> anyhow::Result<Self> {
+        let labels = vec!["node".to_owned()];
+
+        Ok(Self {
+            state,
+            total: Desc::new(
+                options.name("system_numa_memory_total_bytes"),
+                "Total amount of memory attached to the NUMA node".into(),
+                labels.clone(),
+                options.const_labels(),
+            )?,
+            free: Desc::new(
+                options.name("system_numa_memory_free_bytes"),
+                "Amount of unused memory on the NUMA node".into(),
+                labels.clone(),
+                options.const_labels(),
+            )?,
+            used: Desc::new(
+                options.name("system_numa_memory_used_bytes"),
+                "Amount of used memory on the NUMA node".into(),
+                labels,
+                options.const_labels(),
+            )?,
+        })
+    }
+
+    pub fn register(&self, registry: &Registry) -> anyhow::Result<()> {
+        registry.register(Box::new(self.clone()))?;
+        Ok(())
+    }
+}
+
+impl prometheus::core::Collector for Metrics {
+    fn desc(&self) -> Vec<&Desc> {
+        vec![&self.total, &self.free, &self.used]
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        let guard = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(stats) = guard.as_ref() else {
+            return vec![];
+        };
+
+        let mut mf = Vec::with_capacity(stats.nodes.len() * 3);
+        for node in &stats.nodes {
+            let l = into_labels(&[("node", &node.node)]);
+            mf.push(gauge(&self.total, l.clone(), node.total as f64));
+            mf.push(gauge(&self.free, l.clone(), node.free as f64));
+            mf.push(gauge(&self.used, l, node.used as f64));
+        }
+
+        mf
+    }
+}
+
+pub struct Numa<T> {
+    config: Config,
+    data_source: T,
+}
+
+impl<T> Numa<T>
+where
+    T: DataSource + Send + Sync + 'static,
+{
+    pub fn new(config: Config, data_source: T) -> Self {
+        Self {
+            config,
+            data_source,
+        }
+    }
+}
+
+impl<T> Metric for Numa<T>
+where
+    T: DataSource + Send + Sync + 'static,
+{
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    fn register(
+        self,
+        registry: &Registry,
+        options: &MetricOptions,
+    ) -> anyhow::Result<Box<dyn Collector>> {
+        if !self.config.enabled {
+            return Ok(Box::new(NoOpCollector::new(NAME)));
+        }
+
+        let collector = NumaCollector::new(self.data_source);
+        let metrics = Metrics::new(collector.measurements(), options)?;
+        metrics.register(registry)?;
+
+        Ok(Box::new(collector))
+    }
+}
+
+struct NumaCollector<T> {
+    measurement: Arc<Mutex<Option<NumaStats>>>,
+    data_source: T,
+}
+
+impl<T> NumaCollector<T>
+where
+    T: DataSource,
+{
+    fn new(data_source: T) -> Self {
+        Self {
+            measurement: Arc::new(Mutex::new(None)),
+            data_source,
+        }
+    }
+
+    fn measurements(&self) -> Arc<Mutex<Option<NumaStats>>> {
+        Arc::clone(&self.measurement)
+    }
+}
+
+#[async_trait::async_trait]
+impl<T> Collector for NumaCollector<T>
+where
+    T: DataSource + Send + Sync + 'static,
+{
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn collect(&self) -> anyhow::Result<()> {
+        let stats = self
+            .data_source
+            .numa_stats()
+            .await
+            .context("Failed to collect NUMA memory statistics");
+
+        update_measurement_if(&self.measurement, stats, |old, new| {
+            old.timestamp < new.timestamp
+        })
+    }
+
+    fn snapshot(&self) -> Option<serde_json::Value> {
+        snapshot(&self.measurement)
+    }
+}
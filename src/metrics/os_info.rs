@@ -0,0 +1,254 @@
+use crate::domain::{Collector, Metric};
+use crate::metrics::no_operation::NoOpCollector;
+use crate::metrics::util::{MetricOptions, gauge, into_labels, snapshot};
+use anyhow::Context;
+use prometheus::Registry;
+use prometheus::core::Desc;
+use prometheus::proto::MetricFamily;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use tokio::time::Instant;
+
+const NAME: &str = "os_info";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Config {
+    pub enabled: bool,
+    pub priority: Option<i32>,
+    pub max_series: Option<usize>,
+    /// The locations of the os-release file, the first readable one is used.
+    /// Point it to the host's file when running in a container.
+    pub os_release_paths: Vec<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            priority: None,
+            max_series: None,
+            os_release_paths: vec![
+                "/etc/os-release".to_owned(),
+                "/usr/lib/os-release".to_owned(),
+            ],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OsInfoStats {
+    #[serde(skip)]
+    pub timestamp: Instant,
+    pub kernel: String,
+    pub hostname: String,
+    pub machine: String,
+    /// Absent when there is no os-release file
+    pub os_name: Option<String>,
+    pub os_version: Option<String>,
+}
+
+pub trait DataSource {
+    fn os_info(&self) -> impl Future<Output = anyhow::Result<OsInfoStats>> + Send;
+}
+
+#[derive(Clone)]
+struct Metrics {
+    state: Arc<Mutex<Option<OsInfoStats>>>,
+    info: Desc,
+}
+
+impl Metrics {
+    pub fn new(
+        state: Arc<Mutex<Option<OsInfoStats>>>,
+        options: &MetricOptions,
+    ) -> anyhow::Result<Self> {
+        let labels = ["kernel", "hostname", "os_name", "os_version", "machine"];
+
+        Ok(Self {
+            state,
+            info: Desc::new(
+                options.name("system_os_info"),
+                "The kernel and the operating system release".into(),
+                labels.map(str::to_owned).to_vec(),
+                options.const_labels(),
+            )?,
+        })
+    }
+
+    pub fn register(&self, registry: &Registry) -> anyhow::Result<()> {
+        registry.register(Box::new(self.clone()))?;
+        Ok(())
+    }
+}
+
+impl prometheus::core::Collector for Metrics {
+    fn desc(&self) -> Vec<&Desc> {
+        vec![&self.info]
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        let guard = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(info) = guard.as_ref() else {
+            return vec![];
+        };
+
+        let l = into_labels(&[
+            ("kernel", &info.kernel),
+            ("hostname", &info.hostname),
+            ("os_name", info.os_name.as_deref().unwrap_or_default()),
+            ("os_version", info.os_version.as_deref().unwrap_or_default()),
+            ("machine", &info.machine),
+        ]);
+
+        vec![gauge(&self.info, l, 1.0)]
+    }
+}
+
+pub struct OsInfo<T> {
+    config: Config,
+    data_source: T,
+}
+
+impl<T> OsInfo<T>
+where
+    T: DataSource + Send + Sync + 'static,
+{
+    pub fn new(config: Config, data_source: T) -> Self {
+        Self {
+            config,
+            data_source,
+        }
+    }
+}
+
+impl<T> Metric for OsInfo<T>
+where
+    T: DataSource + Send + Sync + 'static,
+{
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    fn register(
+        self,
+        registry: &Registry,
+        options: &MetricOptions,
+    ) -> anyhow::Result<Box<dyn Collector>> {
+        if !self.config.enabled {
+            return Ok(Box::new(NoOpCollector::new(NAME)));
+        }
+
+        let collector = OsInfoCollector::new(self.data_source);
+        let metrics = Metrics::new(collector.measurements(), options)?;
+        metrics.register(registry)?;
+
+        Ok(Box::new(collector))
+    }
+}
+
+struct OsInfoCollector<T> {
+    measurement: Arc<Mutex<Option<OsInfoStats>>>,
+    data_source: T,
+}
+
+impl<T> OsInfoCollector<T>
+where
+    T: DataSource,
+{
+    fn new(data_source: T) -> Self {
+        Self {
+            measurement: Arc::new(Mutex::new(None)),
+            data_source,
+        }
+    }
+
+    fn measurements(&self) -> Arc<Mutex<Option<OsInfoStats>>> {
+        Arc::clone(&self.measurement)
+    }
+}
+
+#[async_trait::async_trait]
+impl<T> Collector for OsInfoCollector<T>
+where
+    T: DataSource + Send + Sync + 'static,
+{
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn collect(&self) -> anyhow::Result<()> {
+        // The release changes only with a reboot, so it is read once and cached
+        if self
+            .measurement
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .is_some()
+        {
+            return Ok(());
+        }
+
+        let info = self
+            .data_source
+            .os_info()
+            .await
+            .context("Failed to collect the OS information")?;
+
+        *self.measurement.lock().unwrap_or_else(|e| e.into_inner()) = Some(info);
+        Ok(())
+    }
+
+    fn snapshot(&self) -> Option<serde_json::Value> {
+        snapshot(&self.measurement)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[derive(Default)]
+    struct CountingDataSource(AtomicUsize);
+
+    impl DataSource for CountingDataSource {
+        async fn os_info(&self) -> anyhow::Result<OsInfoStats> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Ok(OsInfoStats {
+                timestamp: Instant::now(),
+                kernel: "6.8.0-45-generic".into(),
+                hostname: "nas".into(),
+                machine: "x86_64".into(),
+                os_name: Some("Ubuntu".into()),
+                os_version: None,
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_os_info_is_read_once() {
+        let collector = OsInfoCollector::new(CountingDataSource::default());
+        let registry = Registry::new();
+        Metrics::new(collector.measurements(), &MetricOptions::default())
+            .unwrap()
+            .register(&registry)
+            .unwrap();
+
+        collector.collect().await.unwrap();
+        collector.collect().await.unwrap();
+        assert_eq!(collector.data_source.0.load(Ordering::SeqCst), 1);
+
+        let families = registry.gather();
+        let metric = &families[0].get_metric()[0];
+        let labels = metric
+            .get_label()
+            .iter()
+            .map(|l| (l.name(), l.value()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(metric.get_gauge().value(), 1.0);
+        assert!(labels.contains(&("kernel", "6.8.0-45-generic")));
+        assert!(labels.contains(&("os_name", "Ubuntu")));
+        assert!(labels.contains(&("os_version", "")));
+    }
+}
@@ -0,0 +1,194 @@
+use crate::domain::{Collector, Metric};
+use crate::metrics::no_operation::NoOpCollector;
+use crate::metrics::util::{MetricOptions, gauge, into_labels, snapshot, update_measurement_if};
+use anyhow::Context;
+use prometheus::Registry;
+use prometheus::core::Desc;
+use prometheus::proto::MetricFamily;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use tokio::time::Instant;
+
+const NAME: &str = "platform";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Config {
+    pub enabled: bool,
+    pub priority: Option<i32>,
+    pub max_series: Option<usize>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            priority: None,
+            max_series: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PlatformStats {
+    #[serde(skip)]
+    pub timestamp: Instant,
+    /// The active ACPI platform profile, such as `balanced` or `low-power`
+    pub profile: Option<String>,
+    /// The CPU frequency scaling driver, such as `intel_pstate` or `acpi-cpufreq`
+    pub scaling_driver: Option<String>,
+}
+
+pub trait DataSource {
+    fn platform_stats(&self) -> impl Future<Output = anyhow::Result<PlatformStats>> + Send;
+}
+
+#[derive(Clone)]
+struct Metrics {
+    state: Arc<Mutex<Option<PlatformStats>>>,
+    profile: Desc,
+    scaling_driver: Desc,
+}
+
+impl Metrics {
+    pub fn new(
+        state: Arc<Mutex<Option<PlatformStats>>>,
+        options: &MetricOptions,
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
+            state,
+            profile: Desc::new(
+                options.name("system_platform_profile_info"),
+                "The active ACPI platform profile".into(),
+                vec!["profile".to_owned()],
+                options.const_labels(),
+            )?,
+            scaling_driver: Desc::new(
+                options.name("system_cpu_scaling_driver_info"),
+                "The CPU frequency scaling driver".into(),
+                vec!["driver".to_owned()],
+                options.const_labels(),
+            )?,
+        })
+    }
+
+    pub fn register(&self, registry: &Registry) -> anyhow::Result<()> {
+        registry.register(Box::new(self.clone()))?;
+        Ok(())
+    }
+}
+
+impl prometheus::core::Collector for Metrics {
+    fn desc(&self) -> Vec<&Desc> {
+        vec![&self.profile, &self.scaling_driver]
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        let guard = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(stats) = guard.as_ref() else {
+            return vec![];
+        };
+
+        let mut mf = Vec::with_capacity(2);
+        if let Some(profile) = stats.profile.as_deref() {
+            let l = into_labels(&[("profile", profile)]);
+            mf.push(gauge(&self.profile, l, 1.0));
+        }
+
+        if let Some(driver) = stats.scaling_driver.as_deref() {
+            let l = into_labels(&[("driver", driver)]);
+            mf.push(gauge(&self.scaling_driver, l, 1.0));
+        }
+
+        mf
+    }
+}
+
+pub struct Platform<T> {
+    config: Config,
+    data_source: T,
+}
+
+impl<T> Platform<T>
+where
+    T: DataSource + Send + Sync + 'static,
+{
+    pub fn new(config: Config, data_source: T) -> Self {
+        Self {
+            config,
+            data_source,
+        }
+    }
+}
+
+impl<T> Metric for Platform<T>
+where
+    T: DataSource + Send + Sync + 'static,
+{
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    fn register(
+        self,
+        registry: &Registry,
+        options: &MetricOptions,
+    ) -> anyhow::Result<Box<dyn Collector>> {
+        if !self.config.enabled {
+            return Ok(Box::new(NoOpCollector::new(NAME)));
+        }
+
+        let collector = PlatformCollector::new(self.data_source);
+        let metrics = Metrics::new(collector.measurements(), options)?;
+        metrics.register(registry)?;
+
+        Ok(Box::new(collector))
+    }
+}
+
+struct PlatformCollector<T> {
+    measurement: Arc<Mutex<Option<PlatformStats>>>,
+    data_source: T,
+}
+
+impl<T> PlatformCollector<T>
+where
+    T: DataSource,
+{
+    fn new(data_source: T) -> Self {
+        Self {
+            measurement: Arc::new(Mutex::new(None)),
+            data_source,
+        }
+    }
+
+    fn measurements(&self) -> Arc<Mutex<Option<PlatformStats>>> {
+        Arc::clone(&self.measurement)
+    }
+}
+
+#[async_trait::async_trait]
+impl<T> Collector for PlatformCollector<T>
+where
+    T: DataSource + Send + Sync + 'static,
+{
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn collect(&self) -> anyhow::Result<()> {
+        let stats = self
+            .data_source
+            .platform_stats()
+            .await
+            .context("Failed to collect platform statistics");
+
+        update_measurement_if(&self.measurement, stats, |old, new| {
+            old.timestamp < new.timestamp
+        })
+    }
+
+    fn snapshot(&self) -> Option<serde_json::Value> {
+        snapshot(&self.measurement)
+    }
+}
@@ -0,0 +1,230 @@
+use crate::domain::{Collector, Metric};
+use crate::metrics::no_operation::NoOpCollector;
+use crate::metrics::util::{
+    MetricOptions, gauge, into_labels, maybe_gauge, snapshot, update_measurement_if,
+};
+use anyhow::Context;
+use prometheus::Registry;
+use prometheus::core::Desc;
+use prometheus::proto::MetricFamily;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use tokio::time::Instant;
+
+const NAME: &str = "processes_top";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Config {
+    pub enabled: bool,
+    pub priority: Option<i32>,
+    pub max_series: Option<usize>,
+    /// Only the processes using the most CPU are reported, because the PIDs are unbounded
+    pub top_n: usize,
+    /// The minimum time between the two samples the usage is computed from.
+    /// The collection waits for it to elapse, unless `reuse_previous_sample` is set.
+    pub min_sample_interval_ms: u64,
+    /// Never wait while collecting. The usage is computed against the previous sample,
+    /// which is reused as-is while it is younger than `min_sample_interval_ms`.
+    pub reuse_previous_sample: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            // Reading every process is expensive on busy machines
+            enabled: false,
+            priority: None,
+            max_series: None,
+            top_n: 10,
+            min_sample_interval_ms: 250,
+            reuse_previous_sample: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ProcessStats {
+    pub pid: u32,
+    pub comm: String,
+    /// The share of the total CPU time of the machine, in the range `[0, 1]`
+    pub cpu: f64,
+    /// The resident set size, absent for kernel threads
+    pub memory_bytes: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TopProcessesStats {
+    #[serde(skip)]
+    pub timestamp: Instant,
+    pub processes: Vec<ProcessStats>,
+}
+
+pub trait DataSource {
+    /// Returns the `n` processes which used the most CPU since the previous call
+    fn top_processes(
+        &self,
+        n: usize,
+    ) -> impl Future<Output = anyhow::Result<TopProcessesStats>> + Send;
+}
+
+#[derive(Clone)]
+struct Metrics {
+    state: Arc<Mutex<Option<TopProcessesStats>>>,
+    zero_if_absent: HashSet<String>,
+    cpu: Desc,
+    memory: Desc,
+}
+
+impl Metrics {
+    pub fn new(
+        state: Arc<Mutex<Option<TopProcessesStats>>>,
+        options: &MetricOptions,
+    ) -> anyhow::Result<Self> {
+        let labels = vec!["comm".into(), "pid".into()];
+
+        Ok(Self {
+            state,
+            zero_if_absent: options.zero_if_absent.clone(),
+            cpu: Desc::new(
+                options.name("system_process_cpu_ratio"),
+                "Share of the total CPU time used by the process".into(),
+                labels.clone(),
+                options.const_labels(),
+            )?,
+            memory: Desc::new(
+                options.name("system_process_memory_bytes"),
+                "Resident memory of the process".into(),
+                labels,
+                options.const_labels(),
+            )?,
+        })
+    }
+
+    pub fn register(&self, registry: &Registry) -> anyhow::Result<()> {
+        registry.register(Box::new(self.clone()))?;
+        Ok(())
+    }
+}
+
+impl prometheus::core::Collector for Metrics {
+    fn desc(&self) -> Vec<&Desc> {
+        vec![&self.cpu, &self.memory]
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        let guard = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(stats) = guard.as_ref() else {
+            return vec![];
+        };
+
+        let mut mf = Vec::new();
+        for process in &stats.processes {
+            let pid = process.pid.to_string();
+            let l = into_labels(&[("comm", &process.comm), ("pid", &pid)]);
+            mf.push(gauge(&self.cpu, l.clone(), process.cpu));
+            maybe_gauge(
+                &self.zero_if_absent,
+                &mut mf,
+                &self.memory,
+                &l,
+                process.memory_bytes,
+            );
+        }
+
+        mf
+    }
+}
+
+pub struct ProcessesTop<T> {
+    config: Config,
+    data_source: T,
+}
+
+impl<T> ProcessesTop<T>
+where
+    T: DataSource + Send + Sync + 'static,
+{
+    pub fn new(config: Config, data_source: T) -> Self {
+        Self {
+            config,
+            data_source,
+        }
+    }
+}
+
+impl<T> Metric for ProcessesTop<T>
+where
+    T: DataSource + Send + Sync + 'static,
+{
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    fn register(
+        self,
+        registry: &Registry,
+        options: &MetricOptions,
+    ) -> anyhow::Result<Box<dyn Collector>> {
+        if !self.config.enabled {
+            return Ok(Box::new(NoOpCollector::new(NAME)));
+        }
+
+        let collector = ProcessesTopCollector::new(self.data_source, self.config.top_n);
+        let metrics = Metrics::new(collector.measurements(), options)?;
+        metrics.register(registry)?;
+
+        Ok(Box::new(collector))
+    }
+}
+
+struct ProcessesTopCollector<T> {
+    measurement: Arc<Mutex<Option<TopProcessesStats>>>,
+    data_source: T,
+    top_n: usize,
+}
+
+impl<T> ProcessesTopCollector<T>
+where
+    T: DataSource,
+{
+    fn new(data_source: T, top_n: usize) -> Self {
+        Self {
+            measurement: Arc::new(Mutex::new(None)),
+            data_source,
+            top_n,
+        }
+    }
+
+    fn measurements(&self) -> Arc<Mutex<Option<TopProcessesStats>>> {
+        Arc::clone(&self.measurement)
+    }
+}
+
+#[async_trait::async_trait]
+impl<T> Collector for ProcessesTopCollector<T>
+where
+    T: DataSource + Send + Sync + 'static,
+{
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn collect(&self) -> anyhow::Result<()> {
+        // The whole list is replaced, so the exited processes are dropped
+        let stats = self
+            .data_source
+            .top_processes(self.top_n)
+            .await
+            .context("Failed to collect process statistics");
+
+        update_measurement_if(&self.measurement, stats, |old, new| {
+            old.timestamp < new.timestamp
+        })
+    }
+
+    fn snapshot(&self) -> Option<serde_json::Value> {
+        snapshot(&self.measurement)
+    }
+}
@@ -0,0 +1,265 @@
+use crate::domain::{Collector, Metric};
+use crate::metrics::no_operation::NoOpCollector;
+use crate::metrics::util::{MetricOptions, counter, into_labels, snapshot, update_measurement_if};
+use anyhow::Context;
+use prometheus::Registry;
+use prometheus::core::Desc;
+use prometheus::proto::MetricFamily;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::time::Instant;
+
+const NAME: &str = "rapl";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Config {
+    pub enabled: bool,
+    pub priority: Option<i32>,
+    pub max_series: Option<usize>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            priority: None,
+            max_series: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ZoneStats {
+    /// The powercap zone identifier, such as `0` or `0:1`
+    pub index: String,
+    /// The name of the zone, such as `package-0`, `core` or `dram`
+    pub zone: String,
+    pub energy_uj: u64,
+    /// The value at which the energy counter wraps around
+    pub max_energy_range_uj: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RaplStats {
+    #[serde(skip)]
+    pub timestamp: Instant,
+    pub zones: Vec<ZoneStats>,
+}
+
+pub trait DataSource {
+    fn rapl_stats(&self) -> impl Future<Output = anyhow::Result<RaplStats>> + Send;
+}
+
+#[derive(Clone)]
+struct Metrics {
+    state: Arc<Mutex<Option<RaplStats>>>,
+    energy: Desc,
+}
+
+impl Metrics {
+    pub fn new(
+        state: Arc<Mutex<Option<RaplStats>>>,
+        options: &MetricOptions,
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
+            state,
+            energy: Desc::new(
+                options.name("system_rapl_energy_joules_total"),
+                "Energy consumed by the RAPL zone".into(),
+                vec!["index".to_owned(), "zone".to_owned()],
+                options.const_labels(),
+            )?,
+        })
+    }
+
+    pub fn register(&self, registry: &Registry) -> anyhow::Result<()> {
+        registry.register(Box::new(self.clone()))?;
+        Ok(())
+    }
+}
+
+impl prometheus::core::Collector for Metrics {
+    fn desc(&self) -> Vec<&Desc> {
+        vec![&self.energy]
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        let guard = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(stats) = guard.as_ref() else {
+            return vec![];
+        };
+
+        let mut mf = Vec::with_capacity(stats.zones.len());
+        for zone in &stats.zones {
+            let l = into_labels(&[("index", &zone.index), ("zone", &zone.zone)]);
+            mf.push(counter(&self.energy, l, zone.energy_uj as f64 / 1e6));
+        }
+
+        mf
+    }
+}
+
+pub struct Rapl<T> {
+    config: Config,
+    data_source: T,
+}
+
+impl<T> Rapl<T>
+where
+    T: DataSource + Send + Sync + 'static,
+{
+    pub fn new(config: Config, data_source: T) -> Self {
+        Self {
+            config,
+            data_source,
+        }
+    }
+}
+
+impl<T> Metric for Rapl<T>
+where
+    T: DataSource + Send + Sync + 'static,
+{
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    fn register(
+        self,
+        registry: &Registry,
+        options: &MetricOptions,
+    ) -> anyhow::Result<Box<dyn Collector>> {
+        if !self.config.enabled {
+            return Ok(Box::new(NoOpCollector::new(NAME)));
+        }
+
+        let collector = RaplCollector::new(self.data_source);
+        let metrics = Metrics::new(collector.measurements(), options)?;
+        metrics.register(registry)?;
+
+        Ok(Box::new(collector))
+    }
+}
+
+struct RaplCollector<T> {
+    measurement: Arc<Mutex<Option<RaplStats>>>,
+    // The last raw reading and the accumulated energy per zone index
+    energy: Mutex<HashMap<String, (u64, u64)>>,
+    data_source: T,
+}
+
+impl<T> RaplCollector<T>
+where
+    T: DataSource,
+{
+    fn new(data_source: T) -> Self {
+        Self {
+            measurement: Arc::new(Mutex::new(None)),
+            energy: Mutex::new(HashMap::new()),
+            data_source,
+        }
+    }
+
+    fn measurements(&self) -> Arc<Mutex<Option<RaplStats>>> {
+        Arc::clone(&self.measurement)
+    }
+
+    /// Replaces the raw readings with the energy accumulated across the counter wraparounds
+    fn accumulate(&self, stats: &mut RaplStats) {
+        let mut energy = self.energy.lock().unwrap_or_else(|e| e.into_inner());
+
+        let mut current = HashMap::with_capacity(stats.zones.len());
+        for zone in &mut stats.zones {
+            let previous = energy.get(&zone.index).copied();
+            let (raw, total) =
+                accumulate_energy(previous, zone.energy_uj, zone.max_energy_range_uj);
+
+            zone.energy_uj = total;
+            current.insert(zone.index.clone(), (raw, total));
+        }
+
+        *energy = current;
+    }
+}
+
+/// The RAPL energy counters are only 32 bits wide on some CPUs and wrap around at
+/// `max_energy_range_uj`, which happens every few minutes under load. A reading lower
+/// than the previous one is a wraparound, so the energy up to the wrap is added to the
+/// accumulated total. Returns the raw reading and the new total.
+fn accumulate_energy(previous: Option<(u64, u64)>, raw: u64, max_range: Option<u64>) -> (u64, u64) {
+    let Some((previous_raw, total)) = previous else {
+        return (raw, raw);
+    };
+
+    let delta = match raw.checked_sub(previous_raw) {
+        Some(delta) => delta,
+        // Without the range the energy consumed before the wraparound is unknown
+        None => max_range.map_or(raw, |max| max.saturating_sub(previous_raw) + raw),
+    };
+
+    (raw, total + delta)
+}
+
+#[async_trait::async_trait]
+impl<T> Collector for RaplCollector<T>
+where
+    T: DataSource + Send + Sync + 'static,
+{
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn collect(&self) -> anyhow::Result<()> {
+        let stats = self
+            .data_source
+            .rapl_stats()
+            .await
+            .context("Failed to collect RAPL statistics")
+            .map(|mut stats| {
+                self.accumulate(&mut stats);
+                stats
+            });
+
+        update_measurement_if(&self.measurement, stats, |old, new| {
+            old.timestamp < new.timestamp
+        })
+    }
+
+    fn snapshot(&self) -> Option<serde_json::Value> {
+        snapshot(&self.measurement)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accumulate_energy() {
+        let max = Some(262143328850);
+
+        assert_eq!(accumulate_energy(None, 1000, max), (1000, 1000));
+        assert_eq!(
+            accumulate_energy(Some((1000, 1000)), 1500, max),
+            (1500, 1500)
+        );
+        assert_eq!(
+            accumulate_energy(Some((1500, 5500)), 2500, max),
+            (2500, 6500)
+        );
+    }
+
+    #[test]
+    fn test_accumulate_energy_wraparound() {
+        let max = 262143328850;
+
+        let (raw, total) = accumulate_energy(Some((max - 100, max - 100)), 400, Some(max));
+        assert_eq!((raw, total), (400, max + 400));
+
+        // Unknown range, so only the energy since the wraparound is accounted for
+        let (raw, total) = accumulate_energy(Some((max - 100, max - 100)), 400, None);
+        assert_eq!((raw, total), (400, max - 100 + 400));
+    }
+}
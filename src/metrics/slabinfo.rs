@@ -0,0 +1,260 @@
+use crate::domain::{Collector, Metric, Probe};
+use crate::metrics::no_operation::NoOpCollector;
+use crate::metrics::util::{
+    MetricOptions, gauge, into_labels, probe, snapshot, update_measurement_if,
+};
+use anyhow::Context;
+use prometheus::Registry;
+use prometheus::core::Desc;
+use prometheus::proto::MetricFamily;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use tokio::time::Instant;
+
+const NAME: &str = "slabinfo";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Config {
+    pub enabled: bool,
+    pub priority: Option<i32>,
+    pub max_series: Option<usize>,
+    /// Only the largest caches are reported, because there are a few hundred of them
+    pub top_n: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            priority: None,
+            max_series: None,
+            top_n: 20,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Slab {
+    pub name: String,
+    pub active_objects: u64,
+    pub objects: u64,
+    pub object_size: u64,
+}
+
+impl Slab {
+    /// The memory allocated for the objects of the cache, whether in use or not
+    pub fn size(&self) -> u64 {
+        self.objects * self.object_size
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SlabStats {
+    #[serde(skip)]
+    pub timestamp: Instant,
+    pub slabs: Vec<Slab>,
+}
+
+pub trait DataSource: Probe {
+    fn slab_stats(&self) -> impl Future<Output = anyhow::Result<SlabStats>> + Send;
+}
+
+/// Keeps the `n` largest caches, ordered by their size
+fn top_slabs(mut slabs: Vec<Slab>, n: usize) -> Vec<Slab> {
+    slabs.sort_by(|a, b| b.size().cmp(&a.size()).then_with(|| a.name.cmp(&b.name)));
+    slabs.truncate(n);
+    slabs
+}
+
+#[derive(Clone)]
+struct Metrics {
+    state: Arc<Mutex<Option<SlabStats>>>,
+    active_objects: Desc,
+    size: Desc,
+}
+
+impl Metrics {
+    pub fn new(
+        state: Arc<Mutex<Option<SlabStats>>>,
+        options: &MetricOptions,
+    ) -> anyhow::Result<Self> {
+        let labels = vec!["slab".into()];
+
+        Ok(Self {
+            state,
+            active_objects: Desc::new(
+                options.name("system_slab_objects_active"),
+                "Number of objects in use in the slab cache".into(),
+                labels.clone(),
+                options.const_labels(),
+            )?,
+            size: Desc::new(
+                options.name("system_slab_size_bytes"),
+                "Memory allocated for the objects of the slab cache".into(),
+                labels,
+                options.const_labels(),
+            )?,
+        })
+    }
+
+    pub fn register(&self, registry: &Registry) -> anyhow::Result<()> {
+        registry.register(Box::new(self.clone()))?;
+        Ok(())
+    }
+}
+
+impl prometheus::core::Collector for Metrics {
+    fn desc(&self) -> Vec<&Desc> {
+        vec![&self.active_objects, &self.size]
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        let guard = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(stats) = guard.as_ref() else {
+            return vec![];
+        };
+
+        let mut mf = Vec::new();
+        for slab in &stats.slabs {
+            let l = into_labels(&[("slab", &slab.name)]);
+            mf.push(gauge(
+                &self.active_objects,
+                l.clone(),
+                slab.active_objects as f64,
+            ));
+            mf.push(gauge(&self.size, l, slab.size() as f64));
+        }
+
+        mf
+    }
+}
+
+pub struct SlabInfo<T> {
+    config: Config,
+    data_source: T,
+}
+
+impl<T> SlabInfo<T>
+where
+    T: DataSource + Send + Sync + 'static,
+{
+    pub fn new(config: Config, data_source: T) -> Self {
+        Self {
+            config,
+            data_source,
+        }
+    }
+}
+
+impl<T> Metric for SlabInfo<T>
+where
+    T: DataSource + Send + Sync + 'static,
+{
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    async fn supported(&self) -> bool {
+        probe(self.config.enabled, &self.data_source).await
+    }
+
+    fn register(
+        self,
+        registry: &Registry,
+        options: &MetricOptions,
+    ) -> anyhow::Result<Box<dyn Collector>> {
+        if !self.config.enabled {
+            return Ok(Box::new(NoOpCollector::new(NAME)));
+        }
+
+        let collector = SlabInfoCollector::new(self.data_source, self.config.top_n);
+        let metrics = Metrics::new(collector.measurements(), options)?;
+        metrics.register(registry)?;
+
+        Ok(Box::new(collector))
+    }
+}
+
+struct SlabInfoCollector<T> {
+    measurement: Arc<Mutex<Option<SlabStats>>>,
+    data_source: T,
+    top_n: usize,
+}
+
+impl<T> SlabInfoCollector<T>
+where
+    T: DataSource,
+{
+    fn new(data_source: T, top_n: usize) -> Self {
+        Self {
+            measurement: Arc::new(Mutex::new(None)),
+            data_source,
+            top_n,
+        }
+    }
+
+    fn measurements(&self) -> Arc<Mutex<Option<SlabStats>>> {
+        Arc::clone(&self.measurement)
+    }
+}
+
+#[async_trait::async_trait]
+impl<T> Collector for SlabInfoCollector<T>
+where
+    T: DataSource + Send + Sync + 'static,
+{
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn collect(&self) -> anyhow::Result<()> {
+        let stats = self
+            .data_source
+            .slab_stats()
+            .await
+            .map(|mut stats| {
+                stats.slabs = top_slabs(stats.slabs, self.top_n);
+                stats
+            })
+            .context("Failed to collect slab statistics");
+
+        update_measurement_if(&self.measurement, stats, |old, new| {
+            old.timestamp < new.timestamp
+        })
+    }
+
+    fn snapshot(&self) -> Option<serde_json::Value> {
+        snapshot(&self.measurement)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn slab(name: &str, objects: u64, object_size: u64) -> Slab {
+        Slab {
+            name: name.to_owned(),
+            active_objects: objects / 2,
+            objects,
+            object_size,
+        }
+    }
+
+    #[test]
+    fn test_top_slabs() {
+        let slabs = vec![
+            slab("dentry", 1000, 192),
+            slab("kmalloc-8k", 10, 8192),
+            slab("inode_cache", 500, 600),
+            slab("kmalloc-64", 100, 64),
+        ];
+
+        let names = top_slabs(slabs, 2)
+            .into_iter()
+            .map(|s| s.name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["inode_cache", "dentry"]);
+    }
+}
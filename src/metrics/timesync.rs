@@ -0,0 +1,200 @@
+use crate::domain::{Collector, Metric, Probe};
+use crate::metrics::no_operation::NoOpCollector;
+use crate::metrics::util::{MetricOptions, gauge, probe, snapshot, update_measurement_if};
+use anyhow::Context;
+use prometheus::Registry;
+use prometheus::core::Desc;
+use prometheus::proto::MetricFamily;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use tokio::time::Instant;
+
+const NAME: &str = "timesync";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Config {
+    pub enabled: bool,
+    pub priority: Option<i32>,
+    pub max_series: Option<usize>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            priority: None,
+            max_series: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TimesyncStats {
+    #[serde(skip)]
+    pub timestamp: Instant,
+    /// The correction which is being applied to the system clock. It is
+    /// positive when the system clock is behind the reference time.
+    pub offset_seconds: f64,
+    /// The rate at which the system clock would drift without the correction
+    pub frequency_ppm: f64,
+    pub stratum: u8,
+}
+
+pub trait DataSource: Probe {
+    fn timesync_stats(&self) -> impl Future<Output = anyhow::Result<TimesyncStats>> + Send;
+}
+
+#[derive(Clone)]
+struct Metrics {
+    state: Arc<Mutex<Option<TimesyncStats>>>,
+    offset: Desc,
+    frequency: Desc,
+    stratum: Desc,
+}
+
+impl Metrics {
+    pub fn new(
+        state: Arc<Mutex<Option<TimesyncStats>>>,
+        options: &MetricOptions,
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
+            state,
+            offset: Desc::new(
+                options.name("system_clock_offset_seconds"),
+                "Offset of the system clock from the reference time".into(),
+                vec![],
+                options.const_labels(),
+            )?,
+            frequency: Desc::new(
+                options.name("system_clock_frequency_ppm"),
+                "Frequency error of the system clock".into(),
+                vec![],
+                options.const_labels(),
+            )?,
+            stratum: Desc::new(
+                options.name("system_clock_stratum"),
+                "Distance from the reference clock in hops".into(),
+                vec![],
+                options.const_labels(),
+            )?,
+        })
+    }
+
+    pub fn register(&self, registry: &Registry) -> anyhow::Result<()> {
+        registry.register(Box::new(self.clone()))?;
+        Ok(())
+    }
+}
+
+impl prometheus::core::Collector for Metrics {
+    fn desc(&self) -> Vec<&Desc> {
+        vec![&self.offset, &self.frequency, &self.stratum]
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        let guard = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(stats) = guard.as_ref() else {
+            return vec![];
+        };
+
+        vec![
+            gauge(&self.offset, vec![], stats.offset_seconds),
+            gauge(&self.frequency, vec![], stats.frequency_ppm),
+            gauge(&self.stratum, vec![], f64::from(stats.stratum)),
+        ]
+    }
+}
+
+pub struct Timesync<T> {
+    config: Config,
+    data_source: T,
+}
+
+impl<T> Timesync<T>
+where
+    T: DataSource + Send + Sync + 'static,
+{
+    pub fn new(config: Config, data_source: T) -> Self {
+        Self {
+            config,
+            data_source,
+        }
+    }
+}
+
+impl<T> Metric for Timesync<T>
+where
+    T: DataSource + Send + Sync + 'static,
+{
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    async fn supported(&self) -> bool {
+        probe(self.config.enabled, &self.data_source).await
+    }
+
+    fn register(
+        self,
+        registry: &Registry,
+        options: &MetricOptions,
+    ) -> anyhow::Result<Box<dyn Collector>> {
+        if !self.config.enabled {
+            return Ok(Box::new(NoOpCollector::new(NAME)));
+        }
+
+        let collector = TimesyncCollector::new(self.data_source);
+        let metrics = Metrics::new(collector.measurements(), options)?;
+        metrics.register(registry)?;
+
+        Ok(Box::new(collector))
+    }
+}
+
+struct TimesyncCollector<T> {
+    measurement: Arc<Mutex<Option<TimesyncStats>>>,
+    data_source: T,
+}
+
+impl<T> TimesyncCollector<T>
+where
+    T: DataSource,
+{
+    fn new(data_source: T) -> Self {
+        Self {
+            measurement: Arc::new(Mutex::new(None)),
+            data_source,
+        }
+    }
+
+    fn measurements(&self) -> Arc<Mutex<Option<TimesyncStats>>> {
+        Arc::clone(&self.measurement)
+    }
+}
+
+#[async_trait::async_trait]
+impl<T> Collector for TimesyncCollector<T>
+where
+    T: DataSource + Send + Sync + 'static,
+{
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn collect(&self) -> anyhow::Result<()> {
+        let stats = self
+            .data_source
+            .timesync_stats()
+            .await
+            .context("Failed to collect clock synchronization statistics");
+
+        update_measurement_if(&self.measurement, stats, |old, new| {
+            old.timestamp < new.timestamp
+        })
+    }
+
+    fn snapshot(&self) -> Option<serde_json::Value> {
+        snapshot(&self.measurement)
+    }
+}
@@ -0,0 +1,310 @@
+//! Metrics of the Tokio runtime driving the exporter. The binary runs on the
+//! `current_thread` runtime, so there is a single worker, which is the thread
+//! serving the scrapes, and the global queue holds the tasks spawned from outside
+//! of it. The blocking pool is separate and serves the `spawn_blocking` calls,
+//! e.g. `statvfs`. The blocking pool and the I/O driver metrics are only available
+//! when built with `RUSTFLAGS="--cfg tokio_unstable"`, otherwise they are omitted.
+
+use crate::domain::{Collector, Metric};
+use crate::metrics::no_operation::NoOpCollector;
+use crate::metrics::util::{
+    MetricOptions, gauge, maybe_counter, maybe_gauge, snapshot, update_measurement_if,
+};
+use anyhow::Context;
+use prometheus::Registry;
+use prometheus::core::Desc;
+use prometheus::proto::MetricFamily;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use tokio::time::Instant;
+
+const NAME: &str = "tokio_runtime";
+
+/// Disabled by default, as it is only useful when debugging the exporter itself
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Config {
+    pub enabled: bool,
+    pub priority: Option<i32>,
+    pub max_series: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RuntimeStats {
+    #[serde(skip)]
+    pub timestamp: Instant,
+    pub workers: usize,
+    pub alive_tasks: usize,
+    /// The tasks waiting in the global (injection) queue
+    pub global_queue_depth: usize,
+    // Require the `tokio_unstable` cfg
+    pub blocking_threads: Option<usize>,
+    pub blocking_queue_depth: Option<usize>,
+    pub io_driver_ready_events: Option<u64>,
+}
+
+pub trait DataSource {
+    fn runtime_stats(&self) -> impl Future<Output = anyhow::Result<RuntimeStats>> + Send;
+}
+
+#[derive(Clone)]
+struct Metrics {
+    state: Arc<Mutex<Option<RuntimeStats>>>,
+    zero_if_absent: HashSet<String>,
+    workers: Desc,
+    alive_tasks: Desc,
+    global_queue_depth: Desc,
+    blocking_threads: Desc,
+    blocking_queue_depth: Desc,
+    io_driver_ready_events: Desc,
+}
+
+impl Metrics {
+    pub fn new(
+        state: Arc<Mutex<Option<RuntimeStats>>>,
+        options: &MetricOptions,
+    ) -> anyhow::Result<Self> {
+        // Like the rest of the exporter's own metrics, these are not namespaced
+        Ok(Self {
+            state,
+            zero_if_absent: options.zero_if_absent.clone(),
+            workers: Desc::new(
+                "hephaestus_tokio_workers".into(),
+                "Number of worker threads of the runtime".into(),
+                vec![],
+                options.const_labels(),
+            )?,
+            alive_tasks: Desc::new(
+                "hephaestus_tokio_alive_tasks".into(),
+                "Number of tasks which are spawned and not yet completed".into(),
+                vec![],
+                options.const_labels(),
+            )?,
+            global_queue_depth: Desc::new(
+                "hephaestus_tokio_global_queue_depth".into(),
+                "Number of tasks waiting in the global queue".into(),
+                vec![],
+                options.const_labels(),
+            )?,
+            blocking_threads: Desc::new(
+                "hephaestus_tokio_blocking_threads".into(),
+                "Number of threads of the blocking pool".into(),
+                vec![],
+                options.const_labels(),
+            )?,
+            blocking_queue_depth: Desc::new(
+                "hephaestus_tokio_blocking_queue_depth".into(),
+                "Number of tasks waiting for a thread of the blocking pool".into(),
+                vec![],
+                options.const_labels(),
+            )?,
+            io_driver_ready_events: Desc::new(
+                "hephaestus_tokio_io_driver_ready_events_total".into(),
+                "Number of ready events processed by the I/O driver".into(),
+                vec![],
+                options.const_labels(),
+            )?,
+        })
+    }
+
+    pub fn register(&self, registry: &Registry) -> anyhow::Result<()> {
+        registry.register(Box::new(self.clone()))?;
+        Ok(())
+    }
+}
+
+impl prometheus::core::Collector for Metrics {
+    fn desc(&self) -> Vec<&Desc> {
+        vec![
+            &self.workers,
+            &self.alive_tasks,
+            &self.global_queue_depth,
+            &self.blocking_threads,
+            &self.blocking_queue_depth,
+            &self.io_driver_ready_events,
+        ]
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        let guard = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(stats) = guard.as_ref() else {
+            return vec![];
+        };
+
+        let l = vec![];
+        let mut mf = vec![
+            gauge(&self.workers, l.clone(), stats.workers as f64),
+            gauge(&self.alive_tasks, l.clone(), stats.alive_tasks as f64),
+            gauge(
+                &self.global_queue_depth,
+                l.clone(),
+                stats.global_queue_depth as f64,
+            ),
+        ];
+
+        maybe_gauge(
+            &self.zero_if_absent,
+            &mut mf,
+            &self.blocking_threads,
+            &l,
+            stats.blocking_threads,
+        );
+        let depth = stats.blocking_queue_depth;
+        maybe_gauge(
+            &self.zero_if_absent,
+            &mut mf,
+            &self.blocking_queue_depth,
+            &l,
+            depth,
+        );
+        let ready = stats.io_driver_ready_events;
+        maybe_counter(
+            &self.zero_if_absent,
+            &mut mf,
+            &self.io_driver_ready_events,
+            &l,
+            ready,
+        );
+
+        mf
+    }
+}
+
+pub struct TokioRuntime<T> {
+    config: Config,
+    data_source: T,
+}
+
+impl<T> TokioRuntime<T>
+where
+    T: DataSource + Send + Sync + 'static,
+{
+    pub fn new(config: Config, data_source: T) -> Self {
+        Self {
+            config,
+            data_source,
+        }
+    }
+}
+
+impl<T> Metric for TokioRuntime<T>
+where
+    T: DataSource + Send + Sync + 'static,
+{
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    fn register(
+        self,
+        registry: &Registry,
+        options: &MetricOptions,
+    ) -> anyhow::Result<Box<dyn Collector>> {
+        if !self.config.enabled {
+            return Ok(Box::new(NoOpCollector::new(NAME)));
+        }
+
+        let collector = TokioRuntimeCollector::new(self.data_source);
+        let metrics = Metrics::new(collector.measurements(), options)?;
+        metrics.register(registry)?;
+
+        Ok(Box::new(collector))
+    }
+}
+
+struct TokioRuntimeCollector<T> {
+    measurement: Arc<Mutex<Option<RuntimeStats>>>,
+    data_source: T,
+}
+
+impl<T> TokioRuntimeCollector<T>
+where
+    T: DataSource,
+{
+    fn new(data_source: T) -> Self {
+        Self {
+            measurement: Arc::new(Mutex::new(None)),
+            data_source,
+        }
+    }
+
+    fn measurements(&self) -> Arc<Mutex<Option<RuntimeStats>>> {
+        Arc::clone(&self.measurement)
+    }
+}
+
+#[async_trait::async_trait]
+impl<T> Collector for TokioRuntimeCollector<T>
+where
+    T: DataSource + Send + Sync + 'static,
+{
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn collect(&self) -> anyhow::Result<()> {
+        let stats = self
+            .data_source
+            .runtime_stats()
+            .await
+            .context("Failed to collect Tokio runtime metrics");
+
+        update_measurement_if(&self.measurement, stats, |old, new| {
+            old.timestamp < new.timestamp
+        })
+    }
+
+    fn snapshot(&self) -> Option<serde_json::Value> {
+        snapshot(&self.measurement)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct StableRuntime;
+
+    impl DataSource for StableRuntime {
+        async fn runtime_stats(&self) -> anyhow::Result<RuntimeStats> {
+            Ok(RuntimeStats {
+                timestamp: Instant::now(),
+                workers: 1,
+                alive_tasks: 3,
+                global_queue_depth: 0,
+                blocking_threads: None,
+                blocking_queue_depth: None,
+                io_driver_ready_events: None,
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unstable_metrics_are_omitted() {
+        let config = Config {
+            enabled: true,
+            ..Default::default()
+        };
+
+        let registry = Registry::new();
+        let collector = TokioRuntime::new(config, StableRuntime)
+            .register(&registry, &MetricOptions::default())
+            .unwrap();
+        collector.collect().await.unwrap();
+
+        let names = registry
+            .gather()
+            .iter()
+            .map(|mf| mf.name().to_owned())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                "hephaestus_tokio_alive_tasks",
+                "hephaestus_tokio_global_queue_depth",
+                "hephaestus_tokio_workers",
+            ]
+        );
+    }
+}
@@ -1,41 +1,68 @@
-use crate::domain::{Collector, Metric};
+use crate::domain::{Collector, Metric, Probe};
 use crate::metrics::no_operation::NoOpCollector;
-use crate::metrics::util::update_measurement_if;
+use crate::metrics::util::{
+    MetricOptions, TemperatureUnit, gauge, into_labels, probe, snapshot, update_measurement_if,
+    value_if_absent, with_const_labels,
+};
+use anyhow::Context;
 use prometheus::Registry;
 use prometheus::core::Desc;
 use prometheus::proto::{LabelPair, MetricFamily, MetricType};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::HashSet;
 use std::sync::{Arc, Mutex};
 use tokio::time::Instant;
 
+const NAME: &str = "ups";
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     pub enabled: bool,
+    pub priority: Option<i32>,
+    pub max_series: Option<usize>,
+    /// Expose the verbatim `ups.status` as a label. Disabled by
+    /// default, because it creates a new series on each change
+    pub report_status: bool,
 }
 
 impl Default for Config {
     fn default() -> Self {
-        Self { enabled: true }
+        Self {
+            enabled: true,
+            priority: None,
+            max_series: None,
+            report_status: false,
+        }
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct UpsStats {
+    #[serde(skip)]
     pub timestamp: Instant,
     pub devices: Vec<UpsDeviceStats>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct UpsDeviceStats {
     pub device_name: String,
+    pub status: Option<String>,
+    pub flags: Option<UpsStatusFlags>,
 
     pub estimated_runtime: Option<f64>,
     pub battery_level: Option<f64>,
+    pub battery_packs: Option<f64>,
+    pub battery_packs_bad: Option<f64>,
+    pub battery_voltage: Option<f64>,
+    /// In Celsius, as reported by NUT
+    pub battery_temperature: Option<f64>,
     pub load: Option<f64>,
 
     pub input_voltage: Option<f64>,
     pub output_voltage: Option<f64>,
+    pub input_frequency: Option<f64>,
+    pub output_frequency: Option<f64>,
+    pub output_current: Option<f64>,
 
     pub nominal_apparent_power: Option<f64>,
     pub nominal_real_power: Option<f64>,
@@ -44,94 +71,228 @@ pub struct UpsDeviceStats {
     pub real_power: Option<f64>,
 }
 
-pub trait DataSource {
+/// The flags of interest from the space separated `ups.status`, e.g. `OB LB`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct UpsStatusFlags {
+    pub online: bool,
+    pub on_battery: bool,
+    pub low_battery: bool,
+    pub charging: bool,
+}
+
+impl UpsStatusFlags {
+    pub fn parse(status: &str) -> Self {
+        let mut flags = Self::default();
+        for flag in status.split_whitespace() {
+            match flag {
+                "OL" => flags.online = true,
+                "OB" => flags.on_battery = true,
+                "LB" => flags.low_battery = true,
+                "CHRG" => flags.charging = true,
+                _ => {}
+            }
+        }
+
+        flags
+    }
+}
+
+pub trait DataSource: Probe {
     fn ups_stats(&self) -> impl Future<Output = anyhow::Result<UpsStats>> + Send;
 }
 
 #[derive(Clone)]
 pub struct Metrics {
     state: Arc<Mutex<Option<UpsStats>>>,
+    zero_if_absent: HashSet<String>,
     runtime: Desc,
     battery_level: Desc,
+    battery_packs: Desc,
+    battery_packs_bad: Desc,
+    battery_voltage: Desc,
+    battery_temperature: Desc,
+    temperature_unit: TemperatureUnit,
     input_voltage: Desc,
     output_voltage: Desc,
+    input_frequency: Desc,
+    output_frequency: Desc,
+    output_current: Desc,
     nominal_apparent_power: Desc,
     nominal_real_power: Desc,
     apparent_power: Desc,
     real_power: Desc,
     load: Desc,
+    online: Desc,
+    on_battery: Desc,
+    low_battery: Desc,
+    charging: Desc,
+    status_info: Option<Desc>,
 }
 
 impl Metrics {
-    pub fn new(state: Arc<Mutex<Option<UpsStats>>>) -> anyhow::Result<Self> {
+    pub fn new(
+        state: Arc<Mutex<Option<UpsStats>>>,
+        report_status: bool,
+        options: &MetricOptions,
+    ) -> anyhow::Result<Self> {
         let labels = vec!["ups".to_string()];
         let runtime = Desc::new(
-            "system_ups_runtime_seconds".into(),
+            options.name("system_ups_runtime_seconds"),
             "Estimated battery runtime".into(),
             labels.clone(),
-            HashMap::new(),
+            options.const_labels(),
         )?;
 
         let battery_level = Desc::new(
-            "system_ups_battery_level_percent".into(),
+            options.name("system_ups_battery_level_percent"),
             "Battery charge level".into(),
             labels.clone(),
-            HashMap::new(),
+            options.const_labels(),
+        )?;
+        let battery_packs = Desc::new(
+            options.name("system_ups_battery_packs"),
+            "Number of battery packs".into(),
+            labels.clone(),
+            options.const_labels(),
+        )?;
+        let battery_packs_bad = Desc::new(
+            options.name("system_ups_battery_packs_bad"),
+            "Number of bad battery packs".into(),
+            labels.clone(),
+            options.const_labels(),
+        )?;
+        let battery_voltage = Desc::new(
+            options.name("system_ups_battery_voltage"),
+            "Battery voltage".into(),
+            labels.clone(),
+            options.const_labels(),
+        )?;
+        let battery_temperature = Desc::new(
+            options.temperature_name("system_ups_battery_temperature"),
+            "Battery temperature".into(),
+            labels.clone(),
+            options.const_labels(),
         )?;
         let input_voltage = Desc::new(
-            "system_ups_input_voltage".into(),
+            options.name("system_ups_input_voltage"),
             "Input line voltage".into(),
             labels.clone(),
-            HashMap::new(),
+            options.const_labels(),
         )?;
         let output_voltage = Desc::new(
-            "system_ups_output_voltage".into(),
+            options.name("system_ups_output_voltage"),
             "Output line voltage".into(),
             labels.clone(),
-            HashMap::new(),
+            options.const_labels(),
+        )?;
+        let input_frequency = Desc::new(
+            options.name("system_ups_input_frequency_hertz"),
+            "Input line frequency".into(),
+            labels.clone(),
+            options.const_labels(),
+        )?;
+        let output_frequency = Desc::new(
+            options.name("system_ups_output_frequency_hertz"),
+            "Output line frequency".into(),
+            labels.clone(),
+            options.const_labels(),
+        )?;
+        let output_current = Desc::new(
+            options.name("system_ups_output_current_amperes"),
+            "Output current".into(),
+            labels.clone(),
+            options.const_labels(),
         )?;
         let nominal_apparent_power = Desc::new(
-            "system_ups_nominal_apparent_power_va".into(),
+            options.name("system_ups_nominal_apparent_power_va"),
             "Nominal apparent power".into(),
             labels.clone(),
-            HashMap::new(),
+            options.const_labels(),
         )?;
         let nominal_real_power = Desc::new(
-            "system_ups_nominal_real_power_watts".into(),
+            options.name("system_ups_nominal_real_power_watts"),
             "Nominal real power".into(),
             labels.clone(),
-            HashMap::new(),
+            options.const_labels(),
         )?;
         let apparent_power = Desc::new(
-            "system_ups_apparent_power_va".into(),
+            options.name("system_ups_apparent_power_va"),
             "Apparent power draw".into(),
             labels.clone(),
-            HashMap::new(),
+            options.const_labels(),
         )?;
         let real_power = Desc::new(
-            "system_ups_real_power_watts".into(),
+            options.name("system_ups_real_power_watts"),
             "Real power draw".into(),
             labels.clone(),
-            HashMap::new(),
+            options.const_labels(),
         )?;
         let load = Desc::new(
-            "system_ups_load_percent".into(),
+            options.name("system_ups_load_percent"),
             "UPS load percentage".into(),
+            labels.clone(),
+            options.const_labels(),
+        )?;
+        let online = Desc::new(
+            options.name("system_ups_online"),
+            "Whether the UPS is powered by the utility line".into(),
+            labels.clone(),
+            options.const_labels(),
+        )?;
+        let on_battery = Desc::new(
+            options.name("system_ups_on_battery"),
+            "Whether the UPS is running on battery".into(),
+            labels.clone(),
+            options.const_labels(),
+        )?;
+        let low_battery = Desc::new(
+            options.name("system_ups_low_battery"),
+            "Whether the UPS battery is low".into(),
+            labels.clone(),
+            options.const_labels(),
+        )?;
+        let charging = Desc::new(
+            options.name("system_ups_charging"),
+            "Whether the UPS battery is charging".into(),
             labels,
-            HashMap::new(),
+            options.const_labels(),
         )?;
 
+        let status_info = match report_status {
+            false => None,
+            true => Some(Desc::new(
+                options.name("system_ups_status_info"),
+                "The raw UPS status as reported by NUT. Always 1".into(),
+                vec!["ups".to_owned(), "status".to_owned()],
+                options.const_labels(),
+            )?),
+        };
+
         Ok(Self {
             state,
+            zero_if_absent: options.zero_if_absent.clone(),
             runtime,
             battery_level,
+            battery_packs,
+            battery_packs_bad,
+            battery_voltage,
+            battery_temperature,
+            temperature_unit: options.temperature_unit,
             input_voltage,
             output_voltage,
+            input_frequency,
+            output_frequency,
+            output_current,
             nominal_apparent_power,
             nominal_real_power,
             apparent_power,
             real_power,
             load,
+            online,
+            on_battery,
+            low_battery,
+            charging,
+            status_info,
         })
     }
 
@@ -151,12 +312,13 @@ impl Metrics {
 
         let mut metrics = Vec::new();
         for ups in &stats.devices {
-            if let Some(val) = extract(ups) {
+            let val = extract(ups).or_else(|| value_if_absent(&self.zero_if_absent, desc));
+            if let Some(val) = val {
                 let mut m = prometheus::proto::Metric::default();
                 let mut lp = LabelPair::default();
                 lp.set_name("ups".into());
                 lp.set_value(ups.device_name.clone());
-                m.set_label(vec![lp]);
+                m.set_label(with_const_labels(desc, vec![lp]));
 
                 let mut g = prometheus::proto::Gauge::default();
                 g.set_value(val);
@@ -174,12 +336,26 @@ impl prometheus::core::Collector for Metrics {
         vec![
             &self.runtime,
             &self.battery_level,
+            &self.battery_packs,
+            &self.battery_packs_bad,
+            &self.battery_voltage,
+            &self.battery_temperature,
             &self.input_voltage,
             &self.output_voltage,
+            &self.input_frequency,
+            &self.output_frequency,
+            &self.output_current,
             &self.apparent_power,
             &self.real_power,
             &self.load,
+            &self.online,
+            &self.on_battery,
+            &self.low_battery,
+            &self.charging,
         ]
+        .into_iter()
+        .chain(self.status_info.as_ref())
+        .collect()
     }
 
     fn collect(&self) -> Vec<MetricFamily> {
@@ -192,6 +368,8 @@ impl prometheus::core::Collector for Metrics {
 
         mf.push(self.build_metric_family(&self.runtime, stats, |u| u.estimated_runtime));
         mf.push(self.build_metric_family(&self.battery_level, stats, |u| u.battery_level));
+        mf.push(self.build_metric_family(&self.battery_packs, stats, |u| u.battery_packs));
+        mf.push(self.build_metric_family(&self.battery_packs_bad, stats, |u| u.battery_packs_bad));
         mf.push(
             self.build_metric_family(&self.nominal_apparent_power, stats, |u| {
                 u.nominal_apparent_power
@@ -205,6 +383,33 @@ impl prometheus::core::Collector for Metrics {
         mf.push(self.build_metric_family(&self.load, stats, |u| u.load));
         mf.push(self.build_metric_family(&self.input_voltage, stats, |u| u.input_voltage));
         mf.push(self.build_metric_family(&self.output_voltage, stats, |u| u.output_voltage));
+        mf.push(self.build_metric_family(&self.battery_voltage, stats, |u| u.battery_voltage));
+        mf.push(
+            self.build_metric_family(&self.battery_temperature, stats, |u| {
+                u.battery_temperature
+                    .map(|t| self.temperature_unit.convert(t))
+            }),
+        );
+        mf.push(self.build_metric_family(&self.input_frequency, stats, |u| u.input_frequency));
+        mf.push(self.build_metric_family(&self.output_frequency, stats, |u| u.output_frequency));
+        mf.push(self.build_metric_family(&self.output_current, stats, |u| u.output_current));
+
+        let flag = |f: fn(&UpsStatusFlags) -> bool| {
+            move |u: &UpsDeviceStats| u.flags.as_ref().map(|flags| f64::from(u8::from(f(flags))))
+        };
+        mf.push(self.build_metric_family(&self.online, stats, flag(|f| f.online)));
+        mf.push(self.build_metric_family(&self.on_battery, stats, flag(|f| f.on_battery)));
+        mf.push(self.build_metric_family(&self.low_battery, stats, flag(|f| f.low_battery)));
+        mf.push(self.build_metric_family(&self.charging, stats, flag(|f| f.charging)));
+
+        if let Some(status_info) = &self.status_info {
+            for ups in &stats.devices {
+                if let Some(status) = &ups.status {
+                    let l = into_labels(&[("ups", &ups.device_name), ("status", status)]);
+                    mf.push(gauge(status_info, l, 1.0));
+                }
+            }
+        }
 
         mf
     }
@@ -231,15 +436,27 @@ impl<T> Metric for Ups<T>
 where
     T: DataSource + Send + Sync + 'static,
 {
-    fn register(self, registry: &Registry) -> anyhow::Result<Box<dyn Collector>> {
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    async fn supported(&self) -> bool {
+        probe(self.config.enabled, &self.data_source).await
+    }
+
+    fn register(
+        self,
+        registry: &Registry,
+        options: &MetricOptions,
+    ) -> anyhow::Result<Box<dyn Collector>> {
         if !self.config.enabled {
-            return Ok(Box::new(NoOpCollector::new()));
+            return Ok(Box::new(NoOpCollector::new(NAME)));
         }
 
         let collector = UpsCollector::new(self.data_source);
         let measurements = collector.measurements();
 
-        let metrics = Metrics::new(measurements)?;
+        let metrics = Metrics::new(measurements, self.config.report_status, options)?;
         metrics.register(registry)?;
 
         Ok(Box::new(collector))
@@ -272,19 +489,164 @@ impl<T> Collector for UpsCollector<T>
 where
     T: DataSource + Send + Sync + 'static,
 {
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
     #[tracing::instrument(level = "debug", skip_all)]
     async fn collect(&self) -> anyhow::Result<()> {
         let stats = self
             .data_source
             .ups_stats()
             .await
-            .inspect_err(|e| tracing::error!(error=?e, "Failed to collect UPS statistics"))
-            .ok();
+            .context("Failed to collect UPS statistics");
 
         update_measurement_if(&self.measurement, stats, |old, new| {
             old.timestamp < new.timestamp
-        });
+        })
+    }
 
-        Ok(())
+    fn snapshot(&self) -> Option<serde_json::Value> {
+        snapshot(&self.measurement)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct SingleUps;
+
+    impl Probe for SingleUps {}
+
+    impl DataSource for SingleUps {
+        async fn ups_stats(&self) -> anyhow::Result<UpsStats> {
+            Ok(UpsStats {
+                timestamp: Instant::now(),
+                devices: vec![UpsDeviceStats {
+                    device_name: "eaton".to_owned(),
+                    status: Some("OL CHRG".to_owned()),
+                    flags: Some(UpsStatusFlags::parse("OL CHRG")),
+                    estimated_runtime: Some(1800.0),
+                    battery_level: Some(0.9),
+                    battery_packs: None,
+                    battery_packs_bad: None,
+                    battery_voltage: None,
+                    battery_temperature: None,
+                    load: Some(0.2),
+                    input_voltage: None,
+                    output_voltage: None,
+                    input_frequency: None,
+                    output_frequency: None,
+                    output_current: None,
+                    nominal_apparent_power: None,
+                    nominal_real_power: None,
+                    apparent_power: None,
+                    real_power: None,
+                }],
+            })
+        }
+    }
+
+    async fn status_labels(config: Config) -> Vec<Vec<(String, String)>> {
+        let registry = Registry::new();
+        let collector = Ups::new(config, SingleUps)
+            .register(&registry, &MetricOptions::default())
+            .unwrap();
+        collector.collect().await.unwrap();
+
+        registry
+            .gather()
+            .iter()
+            .filter(|mf| mf.name() == "system_ups_status_info")
+            .flat_map(|mf| mf.get_metric())
+            .map(|m| {
+                assert_eq!(m.get_gauge().value(), 1.0);
+                m.get_label()
+                    .iter()
+                    .map(|l| (l.name().to_owned(), l.value().to_owned()))
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_status_info() {
+        let config = Config {
+            report_status: true,
+            ..Default::default()
+        };
+
+        let labels = status_labels(config).await;
+        assert_eq!(
+            labels,
+            [[
+                ("ups".to_owned(), "eaton".to_owned()),
+                ("status".to_owned(), "OL CHRG".to_owned())
+            ]]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_status_info_disabled() {
+        assert!(status_labels(Config::default()).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_snapshot() {
+        let registry = Registry::new();
+        let collector = Ups::new(Config::default(), SingleUps)
+            .register(&registry, &MetricOptions::default())
+            .unwrap();
+        assert_eq!(collector.snapshot(), None);
+
+        collector.collect().await.unwrap();
+
+        let snapshot = collector.snapshot().unwrap();
+        assert_eq!(snapshot["devices"][0]["device_name"], "eaton");
+        assert_eq!(snapshot["devices"][0]["load"], 0.2);
+    }
+
+    #[test]
+    fn test_parse_status_flags() {
+        let flags = UpsStatusFlags::parse("OB LB DISCHRG");
+        assert!(!flags.online);
+        assert!(flags.on_battery);
+        assert!(flags.low_battery);
+        assert!(!flags.charging);
+
+        let flags = UpsStatusFlags::parse(" OL  CHRG ");
+        assert_eq!(
+            flags,
+            UpsStatusFlags {
+                online: true,
+                charging: true,
+                ..Default::default()
+            }
+        );
+
+        assert_eq!(UpsStatusFlags::parse("OLD"), UpsStatusFlags::default());
+    }
+
+    #[tokio::test]
+    async fn test_status_flags_metrics() {
+        let registry = Registry::new();
+        let collector = Ups::new(Config::default(), SingleUps)
+            .register(&registry, &MetricOptions::default())
+            .unwrap();
+        collector.collect().await.unwrap();
+
+        let value = |name: &str| {
+            registry
+                .gather()
+                .iter()
+                .find(|mf| mf.name() == name)
+                .map(|mf| mf.get_metric()[0].get_gauge().value())
+        };
+
+        assert_eq!(value("system_ups_online"), Some(1.0));
+        assert_eq!(value("system_ups_on_battery"), Some(0.0));
+        assert_eq!(value("system_ups_low_battery"), Some(0.0));
+        assert_eq!(value("system_ups_charging"), Some(1.0));
     }
 }
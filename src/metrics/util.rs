@@ -1,8 +1,85 @@
+use crate::domain::Probe;
 use num_traits::ToPrimitive;
 use prometheus::core::Desc;
 use prometheus::proto::{LabelPair, MetricFamily, MetricType};
+use prometheus::{Gauge, Opts, Registry};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::sync::Mutex;
 
+/// The settings shared by all collector metrics, which are fixed when the metric
+/// descriptors are built
+#[derive(Debug, Clone, Default)]
+pub struct MetricOptions {
+    /// The prefix prepended to the names of all collector metrics
+    pub namespace: Option<String>,
+    /// The labels attached to every series, e.g. `host="fileserver"`
+    pub static_labels: HashMap<String, String>,
+    /// The unit of the exposed temperatures
+    pub temperature_unit: TemperatureUnit,
+    /// The names of the metrics which are reported as zero when their value is
+    /// not available, so that `rate()` and `increase()` work from the very first scrape
+    pub zero_if_absent: HashSet<String>,
+}
+
+impl MetricOptions {
+    /// Builds the fully qualified metric name, i.e. `<namespace>_<name>`
+    pub fn name(&self, name: &str) -> String {
+        match self.namespace.as_deref().filter(|ns| !ns.is_empty()) {
+            Some(namespace) => format!("{}_{}", namespace, name),
+            None => name.to_owned(),
+        }
+    }
+
+    /// Builds the fully qualified name of a temperature metric, suffixed with the unit
+    pub fn temperature_name(&self, name: &str) -> String {
+        self.name(&self.temperature_unit.metric_name(name))
+    }
+
+    /// The constant labels of the metric descriptors
+    pub fn const_labels(&self) -> HashMap<String, String> {
+        self.static_labels.clone()
+    }
+
+    /// Builds the options of the metrics which are not backed by a custom collector
+    pub fn opts(&self, name: &str, help: &str) -> Opts {
+        Opts::new(self.name(name), help).const_labels(self.const_labels())
+    }
+}
+
+/// The unit of the exposed temperatures. The data sources always report Celsius.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TemperatureUnit {
+    #[default]
+    Celsius,
+    Fahrenheit,
+}
+
+impl TemperatureUnit {
+    /// Suffixes the name of a temperature metric with the unit,
+    /// e.g. `system_smart_nvme_temperature_celsius`
+    pub fn metric_name(self, name: &str) -> String {
+        match self {
+            TemperatureUnit::Celsius => format!("{}_celsius", name),
+            TemperatureUnit::Fahrenheit => format!("{}_fahrenheit", name),
+        }
+    }
+
+    /// Converts a temperature in Celsius into this unit
+    pub fn convert(self, celsius: f64) -> f64 {
+        match self {
+            TemperatureUnit::Celsius => celsius,
+            TemperatureUnit::Fahrenheit => celsius * 9.0 / 5.0 + 32.0,
+        }
+    }
+}
+
+/// Returns the value to be reported in place of an absent one, if any
+pub fn value_if_absent(zero_if_absent: &HashSet<String>, desc: &Desc) -> Option<f64> {
+    zero_if_absent.contains(&desc.fq_name).then_some(0.0)
+}
+
 pub fn into_labels(kv: &[(&str, &str)]) -> Vec<LabelPair> {
     kv.iter()
         .copied()
@@ -16,6 +93,7 @@ pub fn into_labels(kv: &[(&str, &str)]) -> Vec<LabelPair> {
 }
 
 pub fn maybe_gauge<T>(
+    zero_if_absent: &HashSet<String>,
     families: &mut Vec<MetricFamily>,
     desc: &Desc,
     labels: &[LabelPair],
@@ -23,12 +101,18 @@ pub fn maybe_gauge<T>(
 ) where
     T: ToPrimitive,
 {
+    let val = match val {
+        Some(v) => Some(v.to_f64().unwrap_or(f64::NAN)),
+        None => value_if_absent(zero_if_absent, desc),
+    };
+
     if let Some(v) = val {
-        families.push(gauge(desc, labels.to_vec(), v.to_f64().unwrap_or(f64::NAN)));
+        families.push(gauge(desc, labels.to_vec(), v));
     }
 }
 
 pub fn maybe_counter<T>(
+    zero_if_absent: &HashSet<String>,
     families: &mut Vec<MetricFamily>,
     desc: &Desc,
     labels: &[LabelPair],
@@ -36,12 +120,13 @@ pub fn maybe_counter<T>(
 ) where
     T: ToPrimitive,
 {
+    let val = match val {
+        Some(v) => Some(v.to_f64().unwrap_or(f64::NAN)),
+        None => value_if_absent(zero_if_absent, desc),
+    };
+
     if let Some(v) = val {
-        families.push(counter(
-            desc,
-            labels.to_vec(),
-            v.to_f64().unwrap_or(f64::NAN),
-        ));
+        families.push(counter(desc, labels.to_vec(), v));
     }
 }
 
@@ -52,7 +137,7 @@ pub fn gauge(desc: &Desc, label_values: Vec<LabelPair>, value: f64) -> MetricFam
     mf.set_field_type(MetricType::GAUGE);
 
     let mut m = prometheus::proto::Metric::default();
-    m.set_label(label_values);
+    m.set_label(with_const_labels(desc, label_values));
 
     let mut g = prometheus::proto::Gauge::default();
     g.set_value(value);
@@ -69,7 +154,7 @@ pub fn counter(desc: &Desc, label_values: Vec<LabelPair>, value: f64) -> MetricF
     mf.set_field_type(MetricType::COUNTER);
 
     let mut m = prometheus::proto::Metric::default();
-    m.set_label(label_values);
+    m.set_label(with_const_labels(desc, label_values));
 
     let mut c = prometheus::proto::Counter::default();
     c.set_value(value);
@@ -79,19 +164,74 @@ pub fn counter(desc: &Desc, label_values: Vec<LabelPair>, value: f64) -> MetricF
     mf
 }
 
+/// Prepends the constant labels of the descriptor to the variable ones, as the
+/// hand-built metric families bypass the label handling of the `prometheus` crate
+pub fn with_const_labels(desc: &Desc, label_values: Vec<LabelPair>) -> Vec<LabelPair> {
+    if desc.const_label_pairs.is_empty() {
+        return label_values;
+    }
+
+    let mut labels = desc.const_label_pairs.clone();
+    labels.extend(label_values);
+    labels
+}
+
+/// Looks up the configured friendly name of a block device. The aliases may be keyed either
+/// by the kernel name (`nvme0n1`) or by the device path (`/dev/nvme0n1`).
+pub fn device_alias<'a>(aliases: &'a HashMap<String, String>, device: &str) -> Option<&'a str> {
+    aliases
+        .get(device)
+        .or_else(|| aliases.get(device.strip_prefix("/dev/")?))
+        .or_else(|| aliases.get(&format!("/dev/{}", device)))
+        .map(String::as_str)
+}
+
+/// Exposes a configured limit as a constant gauge, so that alerting rules and
+/// dashboards can refer to it instead of hardcoding the same value
+pub fn register_threshold(
+    registry: &Registry,
+    options: &MetricOptions,
+    name: &str,
+    help: &str,
+    value: Option<f64>,
+) -> anyhow::Result<()> {
+    let Some(value) = value else {
+        return Ok(());
+    };
+
+    let threshold = Gauge::with_opts(options.opts(name, help))?;
+    threshold.set(value);
+    registry.register(Box::new(threshold))?;
+
+    Ok(())
+}
+
+/// Probes the data source of a collector. The disabled collectors are replaced by a
+/// no-op collector anyway, so they are not probed.
+pub async fn probe(enabled: bool, data_source: &impl Probe) -> bool {
+    !enabled || data_source.supported().await
+}
+
+/// Replaces the whole measurement, rather than merging it with the previous one, so the
+/// entities absent from the latest read (unplugged disks, removed interfaces, stopped
+/// containers, etc) are no longer exported. A failed read is returned to the caller,
+/// so that it is accounted for in `hephaestus_collector_errors_total`.
 pub fn update_measurement_if<T>(
     target: &Mutex<Option<T>>,
-    value: Option<T>,
+    value: anyhow::Result<T>,
     predicate: impl Fn(&T, &T) -> bool,
-) {
+) -> anyhow::Result<()> {
     let mut guard = target.lock().unwrap_or_else(|e| e.into_inner());
 
     // If a metric collector has failed, we want to
     // stop exposing that metric instead of reporting
     // the previous, stale value
-    let Some(value) = value else {
-        *guard = None;
-        return;
+    let value = match value {
+        Ok(value) => value,
+        Err(e) => {
+            *guard = None;
+            return Err(e);
+        }
     };
 
     match guard.as_ref() {
@@ -102,4 +242,101 @@ pub fn update_measurement_if<T>(
             }
         }
     }
+
+    Ok(())
+}
+
+/// Serializes the last measurement of a collector
+pub fn snapshot<T: Serialize>(target: &Mutex<Option<T>>) -> Option<serde_json::Value> {
+    let guard = target.lock().unwrap_or_else(|e| e.into_inner());
+    guard
+        .as_ref()
+        .and_then(|value| serde_json::to_value(value).ok())
+}
+
+/// Metric vectors never forget a label set once it has been used. This keeps track of the
+/// label values set during the previous collection, so that the series of the entities which
+/// have vanished since then (offline cores, removed devices, etc) can be removed.
+#[derive(Debug, Default)]
+pub struct LabelTracker {
+    previous: Mutex<HashSet<String>>,
+}
+
+impl LabelTracker {
+    /// Records the label values of the current collection and returns
+    /// the ones that were present in the previous collection only
+    pub fn vanished(&self, current: impl IntoIterator<Item = String>) -> Vec<String> {
+        let current = current.into_iter().collect::<HashSet<_>>();
+        let mut previous = self.previous.lock().unwrap_or_else(|e| e.into_inner());
+
+        let vanished = previous.difference(&current).cloned().collect();
+        *previous = current;
+
+        vanished
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_device_alias() {
+        let aliases = HashMap::from([
+            ("nvme0n1".to_owned(), "system-ssd".to_owned()),
+            ("/dev/sda".to_owned(), "data-hdd".to_owned()),
+        ]);
+
+        assert_eq!(device_alias(&aliases, "nvme0n1"), Some("system-ssd"));
+        assert_eq!(device_alias(&aliases, "/dev/nvme0n1"), Some("system-ssd"));
+        assert_eq!(device_alias(&aliases, "sda"), Some("data-hdd"));
+        assert_eq!(device_alias(&aliases, "/dev/sda"), Some("data-hdd"));
+        assert_eq!(device_alias(&aliases, "sdb"), None);
+    }
+
+    #[test]
+    fn test_maybe_counter_zero_if_absent() {
+        let zero_if_absent = HashSet::from(["test_zero_if_absent_total".to_owned()]);
+
+        let listed = Desc::new(
+            "test_zero_if_absent_total".into(),
+            "help".into(),
+            vec![],
+            HashMap::new(),
+        )
+        .unwrap();
+        let unlisted = Desc::new(
+            "test_omitted_if_absent_total".into(),
+            "help".into(),
+            vec![],
+            HashMap::new(),
+        )
+        .unwrap();
+
+        let mut mf = vec![];
+        maybe_counter::<u64>(&zero_if_absent, &mut mf, &listed, &[], None);
+        maybe_counter::<u64>(&zero_if_absent, &mut mf, &unlisted, &[], None);
+
+        assert_eq!(mf.len(), 1);
+        assert_eq!(mf[0].name(), "test_zero_if_absent_total");
+        assert_eq!(mf[0].get_metric()[0].get_counter().value(), 0.0);
+
+        maybe_counter(&zero_if_absent, &mut mf, &listed, &[], Some(7));
+        assert_eq!(mf[1].get_metric()[0].get_counter().value(), 7.0);
+    }
+
+    #[test]
+    fn test_failed_update_clears_the_measurement() {
+        let measurement = Mutex::new(None);
+
+        update_measurement_if(&measurement, Ok(1), |old, new| old < new).unwrap();
+        assert_eq!(*measurement.lock().unwrap(), Some(1));
+
+        let result =
+            update_measurement_if(&measurement, Err(anyhow::anyhow!("failed")), |old, new| {
+                old < new
+            });
+        assert!(result.is_err());
+        assert_eq!(*measurement.lock().unwrap(), None);
+    }
 }
@@ -0,0 +1,317 @@
+use crate::domain::{Collector, Metric};
+use crate::metrics::no_operation::NoOpCollector;
+use crate::metrics::util::{
+    MetricOptions, counter, gauge, into_labels, snapshot, update_measurement_if,
+};
+use anyhow::Context;
+use prometheus::Registry;
+use prometheus::core::Desc;
+use prometheus::proto::MetricFamily;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use tokio::time::Instant;
+
+const NAME: &str = "vmstat";
+
+/// The `/proc/vmstat` keys which are always collected and exposed as dedicated metrics
+const KEY_PAGE_FAULTS: &str = "pgfault";
+const KEY_MAJOR_PAGE_FAULTS: &str = "pgmajfault";
+const KEY_SWAP_IN: &str = "pswpin";
+const KEY_SWAP_OUT: &str = "pswpout";
+const KEY_OOM_KILLS: &str = "oom_kill";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Config {
+    pub enabled: bool,
+    pub priority: Option<i32>,
+    pub max_series: Option<usize>,
+    /// Additional `/proc/vmstat` keys to expose via `system_vmstat_value{key}`
+    pub extra_keys: Vec<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            priority: None,
+            max_series: None,
+            extra_keys: vec![],
+        }
+    }
+}
+
+impl Config {
+    /// Returns all `/proc/vmstat` keys that have to be collected
+    pub fn keys(&self) -> Vec<String> {
+        let mut keys = [
+            KEY_PAGE_FAULTS,
+            KEY_MAJOR_PAGE_FAULTS,
+            KEY_SWAP_IN,
+            KEY_SWAP_OUT,
+            KEY_OOM_KILLS,
+        ]
+        .map(String::from)
+        .to_vec();
+
+        for key in &self.extra_keys {
+            if !keys.contains(key) {
+                keys.push(key.clone());
+            }
+        }
+
+        keys
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct VmStats {
+    #[serde(skip)]
+    pub timestamp: Instant,
+    pub values: HashMap<String, u64>,
+}
+
+pub trait DataSource {
+    fn vmstat(&self) -> impl Future<Output = anyhow::Result<VmStats>> + Send;
+}
+
+#[derive(Clone)]
+struct Metrics {
+    state: Arc<Mutex<Option<VmStats>>>,
+    extra_keys: Vec<String>,
+    page_faults: Desc,
+    major_page_faults: Desc,
+    swap_in: Desc,
+    swap_out: Desc,
+    oom_kills: Desc,
+    value: Desc,
+}
+
+impl Metrics {
+    pub fn new(
+        state: Arc<Mutex<Option<VmStats>>>,
+        extra_keys: Vec<String>,
+        options: &MetricOptions,
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
+            state,
+            extra_keys,
+            page_faults: Desc::new(
+                options.name("system_page_faults_total"),
+                "Number of page faults".into(),
+                vec![],
+                options.const_labels(),
+            )?,
+            major_page_faults: Desc::new(
+                options.name("system_major_page_faults_total"),
+                "Number of major page faults, which required disk IO".into(),
+                vec![],
+                options.const_labels(),
+            )?,
+            swap_in: Desc::new(
+                options.name("system_swap_in_pages_total"),
+                "Number of pages swapped in".into(),
+                vec![],
+                options.const_labels(),
+            )?,
+            swap_out: Desc::new(
+                options.name("system_swap_out_pages_total"),
+                "Number of pages swapped out".into(),
+                vec![],
+                options.const_labels(),
+            )?,
+            oom_kills: Desc::new(
+                options.name("system_oom_kills_total"),
+                "Number of processes killed by the OOM killer".into(),
+                vec![],
+                options.const_labels(),
+            )?,
+            value: Desc::new(
+                options.name("system_vmstat_value"),
+                "Raw value of the configured /proc/vmstat entry".into(),
+                vec!["key".to_owned()],
+                options.const_labels(),
+            )?,
+        })
+    }
+
+    pub fn register(&self, registry: &Registry) -> anyhow::Result<()> {
+        registry.register(Box::new(self.clone()))?;
+        Ok(())
+    }
+}
+
+impl prometheus::core::Collector for Metrics {
+    fn desc(&self) -> Vec<&Desc> {
+        vec![
+            &self.page_faults,
+            &self.major_page_faults,
+            &self.swap_in,
+            &self.swap_out,
+            &self.oom_kills,
+            &self.value,
+        ]
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        let guard = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(stats) = guard.as_ref() else {
+            return vec![];
+        };
+
+        let mut mf = Vec::with_capacity(5 + self.extra_keys.len());
+        for (desc, key) in [
+            (&self.page_faults, KEY_PAGE_FAULTS),
+            (&self.major_page_faults, KEY_MAJOR_PAGE_FAULTS),
+            (&self.swap_in, KEY_SWAP_IN),
+            (&self.swap_out, KEY_SWAP_OUT),
+            (&self.oom_kills, KEY_OOM_KILLS),
+        ] {
+            if let Some(value) = stats.values.get(key) {
+                mf.push(counter(desc, vec![], *value as f64));
+            }
+        }
+
+        // The extra keys may be either counters or gauges, so they are exposed as gauges
+        for key in &self.extra_keys {
+            if let Some(value) = stats.values.get(key) {
+                let l = into_labels(&[("key", key)]);
+                mf.push(gauge(&self.value, l, *value as f64));
+            }
+        }
+
+        mf
+    }
+}
+
+pub struct VmStat<T> {
+    config: Config,
+    data_source: T,
+}
+
+impl<T> VmStat<T>
+where
+    T: DataSource + Send + Sync + 'static,
+{
+    pub fn new(config: Config, data_source: T) -> Self {
+        Self {
+            config,
+            data_source,
+        }
+    }
+}
+
+impl<T> Metric for VmStat<T>
+where
+    T: DataSource + Send + Sync + 'static,
+{
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    fn register(
+        self,
+        registry: &Registry,
+        options: &MetricOptions,
+    ) -> anyhow::Result<Box<dyn Collector>> {
+        if !self.config.enabled {
+            return Ok(Box::new(NoOpCollector::new(NAME)));
+        }
+
+        // The duplicate keys would produce duplicate series, which fail the whole gather
+        let mut extra_keys = self.config.extra_keys;
+        let mut seen = HashSet::new();
+        extra_keys.retain(|key| seen.insert(key.clone()));
+
+        let collector = VmStatCollector::new(self.data_source);
+        let metrics = Metrics::new(collector.measurements(), extra_keys, options)?;
+        metrics.register(registry)?;
+
+        Ok(Box::new(collector))
+    }
+}
+
+struct VmStatCollector<T> {
+    measurement: Arc<Mutex<Option<VmStats>>>,
+    data_source: T,
+}
+
+impl<T> VmStatCollector<T>
+where
+    T: DataSource,
+{
+    fn new(data_source: T) -> Self {
+        Self {
+            measurement: Arc::new(Mutex::new(None)),
+            data_source,
+        }
+    }
+
+    fn measurements(&self) -> Arc<Mutex<Option<VmStats>>> {
+        Arc::clone(&self.measurement)
+    }
+}
+
+#[async_trait::async_trait]
+impl<T> Collector for VmStatCollector<T>
+where
+    T: DataSource + Send + Sync + 'static,
+{
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn collect(&self) -> anyhow::Result<()> {
+        let stats = self
+            .data_source
+            .vmstat()
+            .await
+            .context("Failed to collect vmstat statistics");
+
+        update_measurement_if(&self.measurement, stats, |old, new| {
+            old.timestamp < new.timestamp
+        })
+    }
+
+    fn snapshot(&self) -> Option<serde_json::Value> {
+        snapshot(&self.measurement)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Values;
+
+    impl DataSource for Values {
+        async fn vmstat(&self) -> anyhow::Result<VmStats> {
+            Ok(VmStats {
+                timestamp: Instant::now(),
+                values: HashMap::from([("nr_dirty".to_owned(), 12)]),
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_extra_keys() {
+        let config = Config {
+            extra_keys: vec!["nr_dirty".to_owned(), "nr_dirty".to_owned()],
+            ..Default::default()
+        };
+
+        let registry = Registry::new();
+        let collector = VmStat::new(config, Values)
+            .register(&registry, &MetricOptions::default())
+            .unwrap();
+        collector.collect().await.unwrap();
+
+        let families = registry.gather();
+        let value = families
+            .iter()
+            .find(|mf| mf.name() == "system_vmstat_value")
+            .unwrap();
+        assert_eq!(value.get_metric().len(), 1);
+    }
+}
@@ -0,0 +1,227 @@
+use crate::domain::{Collector, Metric, Probe};
+use crate::metrics::no_operation::NoOpCollector;
+use crate::metrics::util::{
+    MetricOptions, gauge, into_labels, maybe_gauge, probe, snapshot, update_measurement_if,
+};
+use anyhow::Context;
+use prometheus::Registry;
+use prometheus::core::Desc;
+use prometheus::proto::MetricFamily;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use tokio::time::Instant;
+
+const NAME: &str = "wifi";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Config {
+    pub enabled: bool,
+    pub priority: Option<i32>,
+    pub max_series: Option<usize>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            priority: None,
+            max_series: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WirelessInterface {
+    pub device: String,
+    /// Link quality in the range `[0, 1]`
+    pub link_quality: f64,
+    pub signal_dbm: Option<f64>,
+    pub noise_dbm: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WifiStats {
+    #[serde(skip)]
+    pub timestamp: Instant,
+    pub interfaces: Vec<WirelessInterface>,
+}
+
+pub trait DataSource: Probe {
+    fn wifi_stats(&self) -> impl Future<Output = anyhow::Result<WifiStats>> + Send;
+}
+
+#[derive(Clone)]
+struct Metrics {
+    state: Arc<Mutex<Option<WifiStats>>>,
+    zero_if_absent: HashSet<String>,
+    link_quality: Desc,
+    signal: Desc,
+    noise: Desc,
+}
+
+impl Metrics {
+    pub fn new(
+        state: Arc<Mutex<Option<WifiStats>>>,
+        options: &MetricOptions,
+    ) -> anyhow::Result<Self> {
+        let labels = vec!["device".into()];
+
+        Ok(Self {
+            state,
+            zero_if_absent: options.zero_if_absent.clone(),
+            link_quality: Desc::new(
+                options.name("system_wifi_link_quality_ratio"),
+                "Link quality of the wireless interface".into(),
+                labels.clone(),
+                options.const_labels(),
+            )?,
+            signal: Desc::new(
+                options.name("system_wifi_signal_dbm"),
+                "Received signal level of the wireless interface".into(),
+                labels.clone(),
+                options.const_labels(),
+            )?,
+            noise: Desc::new(
+                options.name("system_wifi_noise_dbm"),
+                "Background noise level of the wireless interface".into(),
+                labels,
+                options.const_labels(),
+            )?,
+        })
+    }
+
+    pub fn register(&self, registry: &Registry) -> anyhow::Result<()> {
+        registry.register(Box::new(self.clone()))?;
+        Ok(())
+    }
+}
+
+impl prometheus::core::Collector for Metrics {
+    fn desc(&self) -> Vec<&Desc> {
+        vec![&self.link_quality, &self.signal, &self.noise]
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        let guard = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(stats) = guard.as_ref() else {
+            return vec![];
+        };
+
+        let mut mf = Vec::new();
+        for interface in &stats.interfaces {
+            let l = into_labels(&[("device", &interface.device)]);
+            mf.push(gauge(&self.link_quality, l.clone(), interface.link_quality));
+            maybe_gauge(
+                &self.zero_if_absent,
+                &mut mf,
+                &self.signal,
+                &l,
+                interface.signal_dbm,
+            );
+            maybe_gauge(
+                &self.zero_if_absent,
+                &mut mf,
+                &self.noise,
+                &l,
+                interface.noise_dbm,
+            );
+        }
+
+        mf
+    }
+}
+
+pub struct Wifi<T> {
+    config: Config,
+    data_source: T,
+}
+
+impl<T> Wifi<T>
+where
+    T: DataSource + Send + Sync + 'static,
+{
+    pub fn new(config: Config, data_source: T) -> Self {
+        Self {
+            config,
+            data_source,
+        }
+    }
+}
+
+impl<T> Metric for Wifi<T>
+where
+    T: DataSource + Send + Sync + 'static,
+{
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    async fn supported(&self) -> bool {
+        probe(self.config.enabled, &self.data_source).await
+    }
+
+    fn register(
+        self,
+        registry: &Registry,
+        options: &MetricOptions,
+    ) -> anyhow::Result<Box<dyn Collector>> {
+        if !self.config.enabled {
+            return Ok(Box::new(NoOpCollector::new(NAME)));
+        }
+
+        let collector = WifiCollector::new(self.data_source);
+        let metrics = Metrics::new(collector.measurements(), options)?;
+        metrics.register(registry)?;
+
+        Ok(Box::new(collector))
+    }
+}
+
+struct WifiCollector<T> {
+    measurement: Arc<Mutex<Option<WifiStats>>>,
+    data_source: T,
+}
+
+impl<T> WifiCollector<T>
+where
+    T: DataSource,
+{
+    fn new(data_source: T) -> Self {
+        Self {
+            measurement: Arc::new(Mutex::new(None)),
+            data_source,
+        }
+    }
+
+    fn measurements(&self) -> Arc<Mutex<Option<WifiStats>>> {
+        Arc::clone(&self.measurement)
+    }
+}
+
+#[async_trait::async_trait]
+impl<T> Collector for WifiCollector<T>
+where
+    T: DataSource + Send + Sync + 'static,
+{
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn collect(&self) -> anyhow::Result<()> {
+        let stats = self
+            .data_source
+            .wifi_stats()
+            .await
+            .context("Failed to collect WiFi statistics");
+
+        update_measurement_if(&self.measurement, stats, |old, new| {
+            old.timestamp < new.timestamp
+        })
+    }
+
+    fn snapshot(&self) -> Option<serde_json::Value> {
+        snapshot(&self.measurement)
+    }
+}
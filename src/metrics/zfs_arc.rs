@@ -1,85 +1,198 @@
-use crate::domain::{Collector, Metric};
+use crate::domain::{Collector, Metric, Probe};
 use crate::metrics::no_operation::NoOpCollector;
-use crate::metrics::util::{maybe_counter, maybe_gauge, update_measurement_if};
+use crate::metrics::util::{
+    MetricOptions, maybe_counter, maybe_gauge, probe, snapshot, update_measurement_if,
+};
+use anyhow::Context;
 use prometheus::Registry;
 use prometheus::core::Desc;
 use prometheus::proto::MetricFamily;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::HashSet;
 use std::sync::{Arc, Mutex};
 use tokio::time::Instant;
 
+const NAME: &str = "zfs_arc";
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     pub enabled: bool,
+    pub priority: Option<i32>,
+    pub max_series: Option<usize>,
 }
 
 impl Default for Config {
     fn default() -> Self {
-        Self { enabled: true }
+        Self {
+            enabled: true,
+            priority: None,
+            max_series: None,
+        }
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct ArcStats {
+    #[serde(skip)]
     pub timestamp: Instant,
     pub hits: u64,
     pub misses: u64,
     pub size: u64,
     pub target_size: u64,
     pub max_size: u64,
+    pub mru_size: u64,
+    pub mfu_size: u64,
+    pub data_size: u64,
+    pub metadata_size: u64,
+    pub mru_ghost_hits: u64,
+    pub mfu_ghost_hits: u64,
+    pub l2_hits: Option<u64>,
+    pub l2_misses: Option<u64>,
+    pub l2_size: Option<u64>,
+    pub l2_asize: Option<u64>,
+    pub l2_read_bytes: Option<u64>,
+    pub l2_write_bytes: Option<u64>,
 }
 
-pub trait DataSource {
+pub trait DataSource: Probe {
     fn arc_stats(&self) -> impl Future<Output = anyhow::Result<ArcStats>> + Send;
 }
 
 #[derive(Clone)]
 struct Metrics {
     state: Arc<Mutex<Option<ArcStats>>>,
+    zero_if_absent: HashSet<String>,
     hits: Desc,
     misses: Desc,
     size: Desc,
     target_size: Desc,
     max_size: Desc,
+    mru_size: Desc,
+    mfu_size: Desc,
+    data_size: Desc,
+    metadata_size: Desc,
+    mru_ghost_hits: Desc,
+    mfu_ghost_hits: Desc,
+    l2_hits: Desc,
+    l2_misses: Desc,
+    l2_size: Desc,
+    l2_asize: Desc,
+    l2_read_bytes: Desc,
+    l2_write_bytes: Desc,
 }
 
 impl Metrics {
-    pub fn new(state: Arc<Mutex<Option<ArcStats>>>) -> anyhow::Result<Self> {
-        let labels = HashMap::new();
+    pub fn new(
+        state: Arc<Mutex<Option<ArcStats>>>,
+        options: &MetricOptions,
+    ) -> anyhow::Result<Self> {
+        let labels = options.const_labels();
 
         Ok(Self {
             state,
+            zero_if_absent: options.zero_if_absent.clone(),
             hits: Desc::new(
-                "zfs_arc_hits_total".into(),
+                options.name("zfs_arc_hits_total"),
                 "Total ARC hits".into(),
                 vec![],
                 labels.clone(),
             )?,
             misses: Desc::new(
-                "zfs_arc_misses_total".into(),
+                options.name("zfs_arc_misses_total"),
                 "Total ARC misses".into(),
                 vec![],
                 labels.clone(),
             )?,
             size: Desc::new(
-                "zfs_arc_size_bytes".into(),
+                options.name("zfs_arc_size_bytes"),
                 "Current size of ARC".into(),
                 vec![],
                 labels.clone(),
             )?,
             target_size: Desc::new(
-                "zfs_arc_target_size_bytes".into(),
+                options.name("zfs_arc_target_size_bytes"),
                 "Target size of ARC".into(),
                 vec![],
                 labels.clone(),
             )?,
             max_size: Desc::new(
-                "zfs_arc_max_size_bytes".into(),
+                options.name("zfs_arc_max_size_bytes"),
                 "Maximum size of ARC".into(),
                 vec![],
                 labels.clone(),
             )?,
+            mru_size: Desc::new(
+                options.name("zfs_arc_mru_size_bytes"),
+                "Size of the most recently used (MRU) part of ARC".into(),
+                vec![],
+                labels.clone(),
+            )?,
+            mfu_size: Desc::new(
+                options.name("zfs_arc_mfu_size_bytes"),
+                "Size of the most frequently used (MFU) part of ARC".into(),
+                vec![],
+                labels.clone(),
+            )?,
+            data_size: Desc::new(
+                options.name("zfs_arc_data_size_bytes"),
+                "Size of the data buffers in ARC".into(),
+                vec![],
+                labels.clone(),
+            )?,
+            metadata_size: Desc::new(
+                options.name("zfs_arc_metadata_size_bytes"),
+                "Size of the metadata buffers in ARC".into(),
+                vec![],
+                labels.clone(),
+            )?,
+            mru_ghost_hits: Desc::new(
+                options.name("zfs_arc_mru_ghost_hits_total"),
+                "Total hits on recently evicted MRU entries".into(),
+                vec![],
+                labels.clone(),
+            )?,
+            mfu_ghost_hits: Desc::new(
+                options.name("zfs_arc_mfu_ghost_hits_total"),
+                "Total hits on recently evicted MFU entries".into(),
+                vec![],
+                labels.clone(),
+            )?,
+            l2_hits: Desc::new(
+                options.name("zfs_arc_l2_hits_total"),
+                "Total L2ARC hits".into(),
+                vec![],
+                labels.clone(),
+            )?,
+            l2_misses: Desc::new(
+                options.name("zfs_arc_l2_misses_total"),
+                "Total L2ARC misses".into(),
+                vec![],
+                labels.clone(),
+            )?,
+            l2_size: Desc::new(
+                options.name("zfs_arc_l2_size_bytes"),
+                "Size of the data stored in L2ARC (before compression)".into(),
+                vec![],
+                labels.clone(),
+            )?,
+            l2_asize: Desc::new(
+                options.name("zfs_arc_l2_allocated_size_bytes"),
+                "Space allocated on the L2ARC devices (after compression)".into(),
+                vec![],
+                labels.clone(),
+            )?,
+            l2_read_bytes: Desc::new(
+                options.name("zfs_arc_l2_read_bytes_total"),
+                "Total bytes read from L2ARC".into(),
+                vec![],
+                labels.clone(),
+            )?,
+            l2_write_bytes: Desc::new(
+                options.name("zfs_arc_l2_written_bytes_total"),
+                "Total bytes written to L2ARC".into(),
+                vec![],
+                labels.clone(),
+            )?,
         })
     }
 
@@ -110,13 +223,25 @@ impl<T> Metric for ZfsArc<T>
 where
     T: DataSource + Send + Sync + 'static,
 {
-    fn register(self, registry: &Registry) -> anyhow::Result<Box<dyn Collector>> {
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    async fn supported(&self) -> bool {
+        probe(self.config.enabled, &self.data_source).await
+    }
+
+    fn register(
+        self,
+        registry: &Registry,
+        options: &MetricOptions,
+    ) -> anyhow::Result<Box<dyn Collector>> {
         if !self.config.enabled {
-            return Ok(Box::new(NoOpCollector::new()));
+            return Ok(Box::new(NoOpCollector::new(NAME)));
         }
 
         let collector = ZfsCollector::new(self.data_source);
-        let metrics = Metrics::new(collector.measurements())?;
+        let metrics = Metrics::new(collector.measurements(), options)?;
         metrics.register(registry)?;
 
         Ok(Box::new(collector))
@@ -149,20 +274,25 @@ impl<T> Collector for ZfsCollector<T>
 where
     T: DataSource + Send + Sync + 'static,
 {
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
     #[tracing::instrument(level = "debug", skip_all)]
     async fn collect(&self) -> anyhow::Result<()> {
         let stats = self
             .data_source
             .arc_stats()
             .await
-            .inspect_err(|e| tracing::error!(error=?e, "Failed to collect ZFS ARC statistics"))
-            .ok();
+            .context("Failed to collect ZFS ARC statistics");
 
         update_measurement_if(&self.measurement, stats, |old, new| {
             old.timestamp < new.timestamp
-        });
+        })
+    }
 
-        Ok(())
+    fn snapshot(&self) -> Option<serde_json::Value> {
+        snapshot(&self.measurement)
     }
 }
 
@@ -174,6 +304,18 @@ impl prometheus::core::Collector for Metrics {
             &self.size,
             &self.target_size,
             &self.max_size,
+            &self.mru_size,
+            &self.mfu_size,
+            &self.data_size,
+            &self.metadata_size,
+            &self.mru_ghost_hits,
+            &self.mfu_ghost_hits,
+            &self.l2_hits,
+            &self.l2_misses,
+            &self.l2_size,
+            &self.l2_asize,
+            &self.l2_read_bytes,
+            &self.l2_write_bytes,
         ]
     }
 
@@ -186,16 +328,127 @@ impl prometheus::core::Collector for Metrics {
         let mut mf = Vec::new();
         let l = vec![];
 
-        maybe_counter(&mut mf, &self.hits, &l, Some(stats.hits));
-        maybe_counter(&mut mf, &self.misses, &l, Some(stats.misses));
-        maybe_gauge(&mut mf, &self.size, &l, Some(stats.size as f64));
+        maybe_counter(
+            &self.zero_if_absent,
+            &mut mf,
+            &self.hits,
+            &l,
+            Some(stats.hits),
+        );
+        maybe_counter(
+            &self.zero_if_absent,
+            &mut mf,
+            &self.misses,
+            &l,
+            Some(stats.misses),
+        );
         maybe_gauge(
+            &self.zero_if_absent,
+            &mut mf,
+            &self.size,
+            &l,
+            Some(stats.size as f64),
+        );
+        maybe_gauge(
+            &self.zero_if_absent,
             &mut mf,
             &self.target_size,
             &l,
             Some(stats.target_size as f64),
         );
-        maybe_gauge(&mut mf, &self.max_size, &l, Some(stats.max_size as f64));
+        maybe_gauge(
+            &self.zero_if_absent,
+            &mut mf,
+            &self.max_size,
+            &l,
+            Some(stats.max_size as f64),
+        );
+        maybe_gauge(
+            &self.zero_if_absent,
+            &mut mf,
+            &self.mru_size,
+            &l,
+            Some(stats.mru_size),
+        );
+        maybe_gauge(
+            &self.zero_if_absent,
+            &mut mf,
+            &self.mfu_size,
+            &l,
+            Some(stats.mfu_size),
+        );
+        maybe_gauge(
+            &self.zero_if_absent,
+            &mut mf,
+            &self.data_size,
+            &l,
+            Some(stats.data_size),
+        );
+        maybe_gauge(
+            &self.zero_if_absent,
+            &mut mf,
+            &self.metadata_size,
+            &l,
+            Some(stats.metadata_size),
+        );
+        maybe_counter(
+            &self.zero_if_absent,
+            &mut mf,
+            &self.mru_ghost_hits,
+            &l,
+            Some(stats.mru_ghost_hits),
+        );
+        maybe_counter(
+            &self.zero_if_absent,
+            &mut mf,
+            &self.mfu_ghost_hits,
+            &l,
+            Some(stats.mfu_ghost_hits),
+        );
+
+        // The L2ARC series are reported only when the system has a cache device
+        maybe_counter(
+            &self.zero_if_absent,
+            &mut mf,
+            &self.l2_hits,
+            &l,
+            stats.l2_hits,
+        );
+        maybe_counter(
+            &self.zero_if_absent,
+            &mut mf,
+            &self.l2_misses,
+            &l,
+            stats.l2_misses,
+        );
+        maybe_gauge(
+            &self.zero_if_absent,
+            &mut mf,
+            &self.l2_size,
+            &l,
+            stats.l2_size,
+        );
+        maybe_gauge(
+            &self.zero_if_absent,
+            &mut mf,
+            &self.l2_asize,
+            &l,
+            stats.l2_asize,
+        );
+        maybe_counter(
+            &self.zero_if_absent,
+            &mut mf,
+            &self.l2_read_bytes,
+            &l,
+            stats.l2_read_bytes,
+        );
+        maybe_counter(
+            &self.zero_if_absent,
+            &mut mf,
+            &self.l2_write_bytes,
+            &l,
+            stats.l2_write_bytes,
+        );
 
         mf
     }
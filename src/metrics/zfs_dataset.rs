@@ -1,25 +1,34 @@
-use crate::domain::{Collector, Metric};
+use crate::domain::{Collector, Metric, Probe};
 use crate::metrics::no_operation::NoOpCollector;
-use crate::metrics::util::{maybe_counter, update_measurement_if};
+use crate::metrics::util::{MetricOptions, maybe_counter, probe, snapshot, update_measurement_if};
+use anyhow::Context;
 use prometheus::Registry;
 use prometheus::core::Desc;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::HashSet;
 use std::sync::{Arc, Mutex};
 use tokio::time;
 
+const NAME: &str = "zfs_dataset";
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     pub enabled: bool,
+    pub priority: Option<i32>,
+    pub max_series: Option<usize>,
 }
 
 impl Default for Config {
     fn default() -> Self {
-        Self { enabled: true }
+        Self {
+            enabled: true,
+            priority: None,
+            max_series: None,
+        }
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct DatasetIoStats {
     pub pool: String,
     pub dataset: String,
@@ -29,19 +38,21 @@ pub struct DatasetIoStats {
     pub nwritten: u64,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct ZfsIoStats {
+    #[serde(skip)]
     pub timestamp: time::Instant,
     pub datasets: Vec<DatasetIoStats>,
 }
 
-pub trait DataSource {
+pub trait DataSource: Probe {
     fn dataset_io(&self) -> impl Future<Output = anyhow::Result<ZfsIoStats>> + Send;
 }
 
 #[derive(Clone)]
 struct Metrics {
     state: Arc<Mutex<Option<ZfsIoStats>>>,
+    zero_if_absent: HashSet<String>,
     reads: Desc,
     writes: Desc,
     nread: Desc,
@@ -49,34 +60,38 @@ struct Metrics {
 }
 
 impl Metrics {
-    pub fn new(state: Arc<Mutex<Option<ZfsIoStats>>>) -> anyhow::Result<Self> {
+    pub fn new(
+        state: Arc<Mutex<Option<ZfsIoStats>>>,
+        options: &MetricOptions,
+    ) -> anyhow::Result<Self> {
         let labels = vec!["pool".to_owned(), "dataset".to_owned()];
 
         Ok(Self {
             state,
+            zero_if_absent: options.zero_if_absent.clone(),
             reads: Desc::new(
-                "zfs_dataset_reads_total".into(),
+                options.name("zfs_dataset_reads_total"),
                 "Total read operations".into(),
                 labels.clone(),
-                HashMap::new(),
+                options.const_labels(),
             )?,
             writes: Desc::new(
-                "zfs_dataset_writes_total".into(),
+                options.name("zfs_dataset_writes_total"),
                 "Total write operations".into(),
                 labels.clone(),
-                HashMap::new(),
+                options.const_labels(),
             )?,
             nread: Desc::new(
-                "zfs_dataset_read_bytes_total".into(),
+                options.name("zfs_dataset_read_bytes_total"),
                 "Total bytes read".into(),
                 labels.clone(),
-                HashMap::new(),
+                options.const_labels(),
             )?,
             nwritten: Desc::new(
-                "zfs_dataset_written_bytes_total".into(),
+                options.name("zfs_dataset_written_bytes_total"),
                 "Total bytes written".into(),
                 labels.clone(),
-                HashMap::new(),
+                options.const_labels(),
             )?,
         })
     }
@@ -103,10 +118,34 @@ impl prometheus::core::Collector for Metrics {
             let l =
                 crate::metrics::util::into_labels(&[("pool", &ds.pool), ("dataset", &ds.dataset)]);
 
-            maybe_counter(&mut mf, &self.reads, &l, Some(ds.reads));
-            maybe_counter(&mut mf, &self.writes, &l, Some(ds.writes));
-            maybe_counter(&mut mf, &self.nread, &l, Some(ds.nread));
-            maybe_counter(&mut mf, &self.nwritten, &l, Some(ds.nwritten));
+            maybe_counter(
+                &self.zero_if_absent,
+                &mut mf,
+                &self.reads,
+                &l,
+                Some(ds.reads),
+            );
+            maybe_counter(
+                &self.zero_if_absent,
+                &mut mf,
+                &self.writes,
+                &l,
+                Some(ds.writes),
+            );
+            maybe_counter(
+                &self.zero_if_absent,
+                &mut mf,
+                &self.nread,
+                &l,
+                Some(ds.nread),
+            );
+            maybe_counter(
+                &self.zero_if_absent,
+                &mut mf,
+                &self.nwritten,
+                &l,
+                Some(ds.nwritten),
+            );
         }
         mf
     }
@@ -133,13 +172,25 @@ impl<T> Metric for ZfsDatasetIo<T>
 where
     T: DataSource + Send + Sync + 'static,
 {
-    fn register(self, registry: &Registry) -> anyhow::Result<Box<dyn Collector>> {
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    async fn supported(&self) -> bool {
+        probe(self.config.enabled, &self.data_source).await
+    }
+
+    fn register(
+        self,
+        registry: &Registry,
+        options: &MetricOptions,
+    ) -> anyhow::Result<Box<dyn Collector>> {
         if !self.config.enabled {
-            return Ok(Box::new(NoOpCollector::new()));
+            return Ok(Box::new(NoOpCollector::new(NAME)));
         }
 
         let collector = ZfsDatasetIoCollector::new(self.data_source);
-        let metrics = Metrics::new(collector.measurements())?;
+        let metrics = Metrics::new(collector.measurements(), options)?;
         metrics.register(registry)?;
 
         Ok(Box::new(collector))
@@ -172,19 +223,24 @@ impl<T> Collector for ZfsDatasetIoCollector<T>
 where
     T: DataSource + Send + Sync + 'static,
 {
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
     #[tracing::instrument(level = "debug", skip_all)]
     async fn collect(&self) -> anyhow::Result<()> {
         let stats = self
             .data_source
             .dataset_io()
             .await
-            .inspect_err(|e| tracing::error!(error=?e, "Failed to collect ZFS dataset statistics"))
-            .ok();
+            .context("Failed to collect ZFS dataset statistics");
 
         update_measurement_if(&self.measurement, stats, |old, new| {
             old.timestamp < new.timestamp
-        });
+        })
+    }
 
-        Ok(())
+    fn snapshot(&self) -> Option<serde_json::Value> {
+        snapshot(&self.measurement)
     }
 }
@@ -0,0 +1,285 @@
+use crate::domain::{Collector, Metric, Probe};
+use crate::metrics::no_operation::NoOpCollector;
+use crate::metrics::util::{
+    MetricOptions, into_labels, maybe_gauge, probe, snapshot, update_measurement_if,
+};
+use anyhow::Context;
+use prometheus::Registry;
+use prometheus::core::Desc;
+use prometheus::proto::MetricFamily;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use tokio::time::Instant;
+
+const NAME: &str = "zpool";
+const HEALTH_ONLINE: &str = "ONLINE";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Config {
+    pub enabled: bool,
+    pub priority: Option<i32>,
+    pub max_series: Option<usize>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            priority: None,
+            max_series: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PoolStats {
+    pub pool: String,
+    pub health: String,
+    pub size: Option<u64>,
+    pub allocated: Option<u64>,
+    pub free: Option<u64>,
+    pub fragmentation: Option<f64>,
+    pub capacity: Option<f64>,
+    pub dedup: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ZpoolStats {
+    #[serde(skip)]
+    pub timestamp: Instant,
+    pub pools: Vec<PoolStats>,
+}
+
+pub trait DataSource: Probe {
+    fn pool_stats(&self) -> impl Future<Output = anyhow::Result<ZpoolStats>> + Send;
+}
+
+#[derive(Clone)]
+struct Metrics {
+    state: Arc<Mutex<Option<ZpoolStats>>>,
+    zero_if_absent: HashSet<String>,
+    health: Desc,
+    size: Desc,
+    allocated: Desc,
+    free: Desc,
+    fragmentation: Desc,
+    capacity: Desc,
+    dedup: Desc,
+}
+
+impl Metrics {
+    pub fn new(
+        state: Arc<Mutex<Option<ZpoolStats>>>,
+        options: &MetricOptions,
+    ) -> anyhow::Result<Self> {
+        let labels = vec!["pool".to_owned()];
+
+        Ok(Self {
+            state,
+            zero_if_absent: options.zero_if_absent.clone(),
+            health: Desc::new(
+                options.name("zfs_pool_health"),
+                "Pool health (1 = ONLINE, 0 = DEGRADED, FAULTED, etc)".into(),
+                labels.clone(),
+                options.const_labels(),
+            )?,
+            size: Desc::new(
+                options.name("zfs_pool_size_bytes"),
+                "Total size of the pool".into(),
+                labels.clone(),
+                options.const_labels(),
+            )?,
+            allocated: Desc::new(
+                options.name("zfs_pool_allocated_bytes"),
+                "Amount of storage allocated within the pool".into(),
+                labels.clone(),
+                options.const_labels(),
+            )?,
+            free: Desc::new(
+                options.name("zfs_pool_free_bytes"),
+                "Amount of unallocated storage in the pool".into(),
+                labels.clone(),
+                options.const_labels(),
+            )?,
+            fragmentation: Desc::new(
+                options.name("zfs_pool_fragmentation_ratio"),
+                "Fragmentation of the free space in the pool (0-1)".into(),
+                labels.clone(),
+                options.const_labels(),
+            )?,
+            capacity: Desc::new(
+                options.name("zfs_pool_capacity_ratio"),
+                "Ratio of the allocated to the total pool space (0-1)".into(),
+                labels.clone(),
+                options.const_labels(),
+            )?,
+            dedup: Desc::new(
+                options.name("zfs_pool_dedup_ratio"),
+                "Deduplication ratio of the pool".into(),
+                labels,
+                options.const_labels(),
+            )?,
+        })
+    }
+
+    pub fn register(&self, registry: &Registry) -> anyhow::Result<()> {
+        registry.register(Box::new(self.clone()))?;
+        Ok(())
+    }
+}
+
+impl prometheus::core::Collector for Metrics {
+    fn desc(&self) -> Vec<&Desc> {
+        vec![
+            &self.health,
+            &self.size,
+            &self.allocated,
+            &self.free,
+            &self.fragmentation,
+            &self.capacity,
+            &self.dedup,
+        ]
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        let guard = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(stats) = guard.as_ref() else {
+            return vec![];
+        };
+
+        let mut mf = Vec::with_capacity(stats.pools.len() * 7);
+        for pool in &stats.pools {
+            let l = into_labels(&[("pool", &pool.pool)]);
+            let health = if pool.health == HEALTH_ONLINE { 1 } else { 0 };
+
+            maybe_gauge(
+                &self.zero_if_absent,
+                &mut mf,
+                &self.health,
+                &l,
+                Some(health),
+            );
+            maybe_gauge(&self.zero_if_absent, &mut mf, &self.size, &l, pool.size);
+            maybe_gauge(
+                &self.zero_if_absent,
+                &mut mf,
+                &self.allocated,
+                &l,
+                pool.allocated,
+            );
+            maybe_gauge(&self.zero_if_absent, &mut mf, &self.free, &l, pool.free);
+            maybe_gauge(
+                &self.zero_if_absent,
+                &mut mf,
+                &self.fragmentation,
+                &l,
+                pool.fragmentation,
+            );
+            maybe_gauge(
+                &self.zero_if_absent,
+                &mut mf,
+                &self.capacity,
+                &l,
+                pool.capacity,
+            );
+            maybe_gauge(&self.zero_if_absent, &mut mf, &self.dedup, &l, pool.dedup);
+        }
+
+        mf
+    }
+}
+
+pub struct Zpool<T> {
+    config: Config,
+    data_source: T,
+}
+
+impl<T> Zpool<T>
+where
+    T: DataSource + Send + Sync + 'static,
+{
+    pub fn new(config: Config, data_source: T) -> Self {
+        Self {
+            config,
+            data_source,
+        }
+    }
+}
+
+impl<T> Metric for Zpool<T>
+where
+    T: DataSource + Send + Sync + 'static,
+{
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    async fn supported(&self) -> bool {
+        probe(self.config.enabled, &self.data_source).await
+    }
+
+    fn register(
+        self,
+        registry: &Registry,
+        options: &MetricOptions,
+    ) -> anyhow::Result<Box<dyn Collector>> {
+        if !self.config.enabled {
+            return Ok(Box::new(NoOpCollector::new(NAME)));
+        }
+
+        let collector = ZpoolCollector::new(self.data_source);
+        let metrics = Metrics::new(collector.measurements(), options)?;
+        metrics.register(registry)?;
+
+        Ok(Box::new(collector))
+    }
+}
+
+struct ZpoolCollector<T> {
+    measurement: Arc<Mutex<Option<ZpoolStats>>>,
+    data_source: T,
+}
+
+impl<T> ZpoolCollector<T>
+where
+    T: DataSource,
+{
+    fn new(data_source: T) -> Self {
+        Self {
+            measurement: Arc::new(Mutex::new(None)),
+            data_source,
+        }
+    }
+
+    fn measurements(&self) -> Arc<Mutex<Option<ZpoolStats>>> {
+        Arc::clone(&self.measurement)
+    }
+}
+
+#[async_trait::async_trait]
+impl<T> Collector for ZpoolCollector<T>
+where
+    T: DataSource + Send + Sync + 'static,
+{
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn collect(&self) -> anyhow::Result<()> {
+        let stats = self
+            .data_source
+            .pool_stats()
+            .await
+            .context("Failed to collect ZFS pool statistics");
+
+        update_measurement_if(&self.measurement, stats, |old, new| {
+            old.timestamp < new.timestamp
+        })
+    }
+
+    fn snapshot(&self) -> Option<serde_json::Value> {
+        snapshot(&self.measurement)
+    }
+}
@@ -0,0 +1,299 @@
+use crate::domain::{Collector, Metric, Probe};
+use crate::metrics::no_operation::NoOpCollector;
+use crate::metrics::util::{
+    MetricOptions, gauge, into_labels, maybe_gauge, probe, snapshot, update_measurement_if,
+};
+use anyhow::Context;
+use prometheus::Registry;
+use prometheus::core::Desc;
+use prometheus::proto::MetricFamily;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use tokio::time::Instant;
+
+const NAME: &str = "zram";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Config {
+    pub enabled: bool,
+    pub priority: Option<i32>,
+    pub max_series: Option<usize>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            priority: None,
+            max_series: None,
+        }
+    }
+}
+
+/// The leading columns of `/sys/block/zram<N>/mm_stat`, in bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct MmStat {
+    /// The uncompressed size of the stored data
+    pub original: u64,
+    /// The compressed size of the stored data
+    pub compressed: u64,
+    /// The memory used, including the allocator overhead and fragmentation
+    pub memory_used: u64,
+}
+
+impl MmStat {
+    /// How many times smaller the data is after compression
+    pub fn compression_ratio(&self) -> Option<f64> {
+        (self.compressed > 0).then(|| self.original as f64 / self.compressed as f64)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ZramDevice {
+    pub device: String,
+    pub disk_size: Option<u64>,
+    pub mm_stat: MmStat,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ZramStats {
+    #[serde(skip)]
+    pub timestamp: Instant,
+    pub devices: Vec<ZramDevice>,
+}
+
+pub trait DataSource: Probe {
+    fn zram_stats(&self) -> impl Future<Output = anyhow::Result<ZramStats>> + Send;
+}
+
+#[derive(Clone)]
+struct Metrics {
+    state: Arc<Mutex<Option<ZramStats>>>,
+    zero_if_absent: HashSet<String>,
+    original: Desc,
+    compressed: Desc,
+    memory_used: Desc,
+    disk_size: Desc,
+    compression_ratio: Desc,
+}
+
+impl Metrics {
+    pub fn new(
+        state: Arc<Mutex<Option<ZramStats>>>,
+        options: &MetricOptions,
+    ) -> anyhow::Result<Self> {
+        let labels = vec!["device".into()];
+
+        Ok(Self {
+            state,
+            zero_if_absent: options.zero_if_absent.clone(),
+            original: Desc::new(
+                options.name("system_zram_original_bytes"),
+                "Uncompressed size of the data stored in the zram device".into(),
+                labels.clone(),
+                options.const_labels(),
+            )?,
+            compressed: Desc::new(
+                options.name("system_zram_compressed_bytes"),
+                "Compressed size of the data stored in the zram device".into(),
+                labels.clone(),
+                options.const_labels(),
+            )?,
+            memory_used: Desc::new(
+                options.name("system_zram_memory_used_bytes"),
+                "Memory used by the zram device, including the overhead".into(),
+                labels.clone(),
+                options.const_labels(),
+            )?,
+            disk_size: Desc::new(
+                options.name("system_zram_disk_size_bytes"),
+                "Maximum uncompressed size of the zram device".into(),
+                labels.clone(),
+                options.const_labels(),
+            )?,
+            compression_ratio: Desc::new(
+                options.name("system_zram_compression_ratio"),
+                "Ratio of the uncompressed to the compressed data size".into(),
+                labels,
+                options.const_labels(),
+            )?,
+        })
+    }
+
+    pub fn register(&self, registry: &Registry) -> anyhow::Result<()> {
+        registry.register(Box::new(self.clone()))?;
+        Ok(())
+    }
+}
+
+impl prometheus::core::Collector for Metrics {
+    fn desc(&self) -> Vec<&Desc> {
+        vec![
+            &self.original,
+            &self.compressed,
+            &self.memory_used,
+            &self.disk_size,
+            &self.compression_ratio,
+        ]
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        let guard = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(stats) = guard.as_ref() else {
+            return vec![];
+        };
+
+        let mut mf = Vec::new();
+        for device in &stats.devices {
+            let l = into_labels(&[("device", &device.device)]);
+            let mm_stat = &device.mm_stat;
+
+            mf.push(gauge(&self.original, l.clone(), mm_stat.original as f64));
+            mf.push(gauge(
+                &self.compressed,
+                l.clone(),
+                mm_stat.compressed as f64,
+            ));
+            mf.push(gauge(
+                &self.memory_used,
+                l.clone(),
+                mm_stat.memory_used as f64,
+            ));
+            maybe_gauge(
+                &self.zero_if_absent,
+                &mut mf,
+                &self.disk_size,
+                &l,
+                device.disk_size,
+            );
+            let ratio = mm_stat.compression_ratio();
+            maybe_gauge(
+                &self.zero_if_absent,
+                &mut mf,
+                &self.compression_ratio,
+                &l,
+                ratio,
+            );
+        }
+
+        mf
+    }
+}
+
+pub struct Zram<T> {
+    config: Config,
+    data_source: T,
+}
+
+impl<T> Zram<T>
+where
+    T: DataSource + Send + Sync + 'static,
+{
+    pub fn new(config: Config, data_source: T) -> Self {
+        Self {
+            config,
+            data_source,
+        }
+    }
+}
+
+impl<T> Metric for Zram<T>
+where
+    T: DataSource + Send + Sync + 'static,
+{
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    async fn supported(&self) -> bool {
+        probe(self.config.enabled, &self.data_source).await
+    }
+
+    fn register(
+        self,
+        registry: &Registry,
+        options: &MetricOptions,
+    ) -> anyhow::Result<Box<dyn Collector>> {
+        if !self.config.enabled {
+            return Ok(Box::new(NoOpCollector::new(NAME)));
+        }
+
+        let collector = ZramCollector::new(self.data_source);
+        let metrics = Metrics::new(collector.measurements(), options)?;
+        metrics.register(registry)?;
+
+        Ok(Box::new(collector))
+    }
+}
+
+struct ZramCollector<T> {
+    measurement: Arc<Mutex<Option<ZramStats>>>,
+    data_source: T,
+}
+
+impl<T> ZramCollector<T>
+where
+    T: DataSource,
+{
+    fn new(data_source: T) -> Self {
+        Self {
+            measurement: Arc::new(Mutex::new(None)),
+            data_source,
+        }
+    }
+
+    fn measurements(&self) -> Arc<Mutex<Option<ZramStats>>> {
+        Arc::clone(&self.measurement)
+    }
+}
+
+#[async_trait::async_trait]
+impl<T> Collector for ZramCollector<T>
+where
+    T: DataSource + Send + Sync + 'static,
+{
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn collect(&self) -> anyhow::Result<()> {
+        let stats = self
+            .data_source
+            .zram_stats()
+            .await
+            .context("Failed to collect zram statistics");
+
+        update_measurement_if(&self.measurement, stats, |old, new| {
+            old.timestamp < new.timestamp
+        })
+    }
+
+    fn snapshot(&self) -> Option<serde_json::Value> {
+        snapshot(&self.measurement)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compression_ratio() {
+        let mm_stat = MmStat {
+            original: 4096 * 1000,
+            compressed: 1024 * 1000,
+            memory_used: 1100 * 1000,
+        };
+        assert_eq!(mm_stat.compression_ratio(), Some(4.0));
+
+        // An empty device has nothing to compare
+        let empty = MmStat {
+            original: 0,
+            compressed: 0,
+            memory_used: 0,
+        };
+        assert_eq!(empty.compression_ratio(), None);
+    }
+}
@@ -0,0 +1,153 @@
+use crate::server::handler::refresh_measurements;
+use crate::server::state::AppState;
+use prometheus::proto::{MetricFamily, MetricType};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Config {
+    pub enabled: bool,
+    /// Seconds between two consecutive checks
+    pub interval: u64,
+    /// Warn if fewer metric families are gathered
+    pub min_families: Option<usize>,
+    /// Warn if fewer samples are gathered
+    pub min_samples: Option<usize>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval: 300,
+            min_families: None,
+            min_samples: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GatherSummary {
+    pub families: usize,
+    pub samples: usize,
+}
+
+/// Counts the metric families and the samples, as they would appear in the exposition text
+pub fn summarize(families: &[MetricFamily]) -> GatherSummary {
+    let samples = families
+        .iter()
+        .flat_map(|mf| mf.get_metric().iter().map(|m| (mf.get_field_type(), m)))
+        .map(|(metric_type, m)| match metric_type {
+            MetricType::HISTOGRAM => {
+                let buckets = m.get_histogram().get_bucket();
+                let inf_seen = buckets.iter().any(|b| b.upper_bound() == f64::INFINITY);
+                // The buckets, plus the implicit +Inf bucket, the sum and the count
+                buckets.len() + usize::from(!inf_seen) + 2
+            }
+            MetricType::SUMMARY => m.get_summary().get_quantile().len() + 2,
+            _ => 1,
+        })
+        .sum();
+
+    GatherSummary {
+        families: families.len(),
+        samples,
+    }
+}
+
+/// Logs the number of gathered metric families and samples, and warns if they have dropped
+/// below the configured floor, which usually means that a collector stopped producing series
+pub fn check(summary: GatherSummary, config: &Config) -> bool {
+    tracing::info!(
+        families = summary.families,
+        samples = summary.samples,
+        "Gathered metrics"
+    );
+
+    let mut healthy = true;
+    if let Some(min) = config.min_families
+        && summary.families < min
+    {
+        tracing::warn!(families = summary.families, min, "Too few metric families");
+        healthy = false;
+    }
+
+    if let Some(min) = config.min_samples
+        && summary.samples < min
+    {
+        tracing::warn!(samples = summary.samples, min, "Too few metric samples");
+        healthy = false;
+    }
+
+    healthy
+}
+
+/// Runs the collectors once at startup, and then periodically checks the
+/// metrics gathered by the most recent scrape
+pub async fn run(state: AppState) {
+    let config = state.configuration.self_check.clone();
+    refresh_measurements(&state).await;
+
+    let mut interval = tokio::time::interval(Duration::from_secs(config.interval));
+    loop {
+        interval.tick().await;
+        check(summarize(&state.registry().gather()), &config);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prometheus::{Histogram, HistogramOpts, IntCounterVec, IntGauge, Opts, Registry};
+
+    fn fixture() -> Registry {
+        let registry = Registry::new();
+
+        let counter = IntCounterVec::new(Opts::new("requests_total", "help"), &["path"]).unwrap();
+        counter.with_label_values(&["/metrics"]).inc();
+        counter.with_label_values(&["/health"]).inc();
+        registry.register(Box::new(counter)).unwrap();
+
+        let gauge = IntGauge::new("temperature_celsius", "help").unwrap();
+        registry.register(Box::new(gauge)).unwrap();
+
+        let histogram =
+            Histogram::with_opts(HistogramOpts::new("latency_seconds", "help").buckets(vec![1.0]))
+                .unwrap();
+        registry.register(Box::new(histogram)).unwrap();
+
+        registry
+    }
+
+    #[test]
+    fn test_summarize() {
+        let summary = summarize(&fixture().gather());
+
+        // 2 counter series, 1 gauge, and the histogram with 2 buckets, a sum and a count
+        assert_eq!(
+            summary,
+            GatherSummary {
+                families: 3,
+                samples: 7
+            }
+        );
+    }
+
+    #[test]
+    fn test_check_floor() {
+        let summary = summarize(&fixture().gather());
+
+        let config = Config {
+            min_families: Some(3),
+            min_samples: Some(7),
+            ..Default::default()
+        };
+        assert!(check(summary, &config));
+
+        let config = Config {
+            min_families: Some(4),
+            ..Default::default()
+        };
+        assert!(!check(summary, &config));
+    }
+}
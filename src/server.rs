@@ -1,16 +1,20 @@
+use crate::config::Http;
+use crate::server::auth::Credentials;
 use crate::server::shutdown::shutdown_signal;
 use crate::server::state::AppState;
 use axum::Router;
 use axum::body::Body;
 use axum::http::{Request, StatusCode};
+use axum::middleware;
 use axum::routing::get;
 use std::error::Error;
-use std::net::ToSocketAddrs;
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
 use std::time::Duration;
 use tokio::net::TcpListener;
 use tower::Layer;
 use tower_http::LatencyUnit;
 use tower_http::catch_panic::CatchPanicLayer;
+use tower_http::compression::CompressionLayer;
 use tower_http::normalize_path::NormalizePathLayer;
 use tower_http::request_id::{
     MakeRequestUuid, PropagateRequestIdLayer, RequestId, SetRequestIdLayer,
@@ -19,16 +23,26 @@ use tower_http::timeout::TimeoutLayer;
 use tower_http::trace::{DefaultOnResponse, TraceLayer};
 use tracing::Level;
 
+pub mod auth;
+pub mod background;
 pub mod handler;
+pub mod influx;
+pub mod openmetrics;
+pub mod reload;
 pub mod shutdown;
 pub mod state;
+pub mod telemetry;
 
 pub async fn start_server(state: AppState) -> Result<(), Box<dyn Error + Send + Sync>> {
     let config = state.configuration.clone();
-    let router = create_router(state);
+    if let Some(interval) = config.collector.collector_interval_secs {
+        background::spawn_collection_loops(&state, Duration::from_secs(interval));
+    }
+
+    let router = create_router(state)?;
 
     let mut handles = Vec::new();
-    for addr in (config.http.address.as_str(), config.http.port).to_socket_addrs()? {
+    for addr in bind_addresses(&config.http)? {
         let listener = TcpListener::bind(addr)
             .await
             .map_err(|e| format!("Could not bind to {}: {}", addr, e))?;
@@ -45,14 +59,6 @@ pub async fn start_server(state: AppState) -> Result<(), Box<dyn Error + Send +
         handles.push(handle);
     }
 
-    if handles.is_empty() {
-        return Err(format!(
-            "The bind address [{}:{}] did not resolve to any IP addresses",
-            config.http.address, config.http.port
-        )
-        .into());
-    }
-
     for handle in handles {
         match handle.await {
             Ok(Ok(())) => (),
@@ -64,9 +70,72 @@ pub async fn start_server(state: AppState) -> Result<(), Box<dyn Error + Send +
     Ok(())
 }
 
-fn create_router(state: AppState) -> Router {
-    let router = Router::new()
-        .route("/metrics", get(handler::metrics))
+/// Resolves the configured `http.address`, which may be either an IPv4 or an IPv6 address,
+/// optionally in brackets, or a host name, into the socket addresses the server has to listen
+/// on. On Linux, a socket bound to `::` also accepts IPv4 connections, unless the
+/// `net.ipv6.bindv6only` sysctl is set.
+fn bind_addresses(http: &Http) -> Result<Vec<SocketAddr>, Box<dyn Error + Send + Sync>> {
+    let address = http.address.trim_start_matches('[').trim_end_matches(']');
+    match address.parse::<IpAddr>() {
+        Ok(ip) => return Ok(vec![SocketAddr::new(ip, http.port)]),
+        // Host names cannot contain colons, so this is a malformed IPv6 address
+        Err(e) if address.contains(':') => {
+            return Err(format!("Invalid bind address [{}]: {}", http.address, e).into());
+        }
+        Err(_) => {}
+    }
+
+    let addresses = (http.address.as_str(), http.port)
+        .to_socket_addrs()
+        .map_err(|e| format!("Invalid bind address [{}]: {}", http.address, e))?
+        .collect::<Vec<_>>();
+
+    if addresses.is_empty() {
+        return Err(format!(
+            "The bind address [{}:{}] did not resolve to any IP addresses",
+            http.address, http.port
+        )
+        .into());
+    }
+
+    Ok(addresses)
+}
+
+fn create_router(state: AppState) -> anyhow::Result<Router> {
+    let http = &state.configuration.http;
+    let metrics_path = http.metrics_path.trim_end_matches('/');
+
+    let mut router = Router::new()
+        .route(metrics_path, get(handler::metrics))
+        .route(
+            &format!("{}/influx", metrics_path),
+            get(handler::influx_metrics),
+        )
+        .route(
+            &format!("{}/{{collector}}", metrics_path),
+            get(handler::collector_metrics),
+        );
+
+    if http.enable_debug {
+        router = router.route("/debug/stats", get(handler::debug_stats));
+    }
+
+    if let Some(credentials) = Credentials::new(http.auth.as_ref())? {
+        router = router.route_layer(middleware::from_fn_with_state(
+            credentials,
+            auth::require_auth,
+        ));
+    }
+
+    // Added after the authentication layer, so that the health checks do not need credentials
+    if http.enable_health {
+        let health_path = http.health_path.trim_end_matches('/');
+        router = router.route(health_path, get(handler::health));
+    }
+
+    let router = router
+        // Compresses the encoded exposition text, honoring the client's Accept-Encoding
+        .layer(CompressionLayer::new())
         .layer(CatchPanicLayer::new())
         .layer(TimeoutLayer::with_status_code(
             StatusCode::INTERNAL_SERVER_ERROR,
@@ -98,5 +167,454 @@ fn create_router(state: AppState) -> Router {
         .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid))
         .with_state(state);
 
-    Router::new().fallback_service(NormalizePathLayer::trim_trailing_slash().layer(router))
+    Ok(Router::new().fallback_service(NormalizePathLayer::trim_trailing_slash().layer(router)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bootstrap::register_collector;
+    use crate::config::{Auth, Configuration};
+    use crate::domain::{Collector, RegisteredCollector};
+    use crate::metrics::MetricOptions;
+    use crate::metrics::cpu_frequency::{self, CoreFreqStats, CpuFreqStats};
+    use crate::metrics::memory_usage::{self, RamStats, SwapStats, ZswapStats};
+    use prometheus::Registry;
+    use std::net::Ipv6Addr;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tower::ServiceExt;
+
+    struct FakeMemory;
+
+    impl memory_usage::DataSource for FakeMemory {
+        async fn swap(&self) -> anyhow::Result<SwapStats> {
+            Ok(SwapStats {
+                total: 2048,
+                used: 1024,
+                free: 1024,
+            })
+        }
+
+        async fn ram(&self) -> anyhow::Result<RamStats> {
+            Ok(RamStats {
+                total: 4096,
+                ..Default::default()
+            })
+        }
+
+        async fn zswap(&self) -> anyhow::Result<Option<ZswapStats>> {
+            Ok(None)
+        }
+    }
+
+    struct FakeCpuFrequency;
+
+    impl cpu_frequency::DataSource for FakeCpuFrequency {
+        async fn cpu_freq(&self) -> anyhow::Result<CpuFreqStats> {
+            Ok(CpuFreqStats {
+                cores: vec![CoreFreqStats {
+                    core: 0,
+                    current: 3_600_000_000,
+                    min: None,
+                    max: None,
+                    governor: None,
+                }],
+            })
+        }
+    }
+
+    async fn test_router() -> Router {
+        test_router_with(Configuration::default()).await
+    }
+
+    async fn test_router_with(configuration: Configuration) -> Router {
+        let registry = Registry::new();
+
+        let memory = memory_usage::MemoryUsage::new(
+            configuration.collector.memory_usage.clone(),
+            FakeMemory,
+        );
+        let cpu_freq = cpu_frequency::CpuFrequency::new(
+            configuration.collector.cpu_frequency.clone(),
+            FakeCpuFrequency,
+        );
+
+        let collectors = vec![
+            register_collector(memory, 0, None, &MetricOptions::default(), &registry)
+                .await
+                .unwrap(),
+            register_collector(cpu_freq, 0, None, &MetricOptions::default(), &registry)
+                .await
+                .unwrap(),
+        ];
+
+        create_router(AppState::new(configuration, registry, collectors)).unwrap()
+    }
+
+    async fn get(router: Router, uri: &str) -> (StatusCode, String) {
+        get_with_auth(router, uri, None).await
+    }
+
+    async fn get_with_auth(
+        router: Router,
+        uri: &str,
+        authorization: Option<&str>,
+    ) -> (StatusCode, String) {
+        let mut request = Request::builder().uri(uri);
+        if let Some(authorization) = authorization {
+            request = request.header("Authorization", authorization);
+        }
+
+        let request = request.body(Body::empty()).unwrap();
+        let response = router.oneshot(request).await.unwrap();
+
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_all_metrics() {
+        let (status, body) = get(test_router().await, "/metrics").await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("system_memory_total_bytes 4096"));
+        assert!(body.contains("system_cpu_core_frequency_hertz"));
+    }
+
+    #[tokio::test]
+    async fn test_single_collector_metrics() {
+        let (status, body) = get(test_router().await, "/metrics/memory").await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("system_memory_total_bytes 4096"));
+        assert!(!body.contains("system_cpu_core_frequency_hertz"));
+    }
+
+    #[tokio::test]
+    async fn test_influx_metrics() {
+        let (status, body) = get(test_router().await, "/metrics/influx").await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("system_memory_total_bytes value=4096 "));
+    }
+
+    #[tokio::test]
+    async fn test_debug_stats() {
+        let (status, _) = get(test_router().await, "/debug/stats").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let mut configuration = Configuration::default();
+        configuration.http.enable_debug = true;
+
+        let (status, body) = get(test_router_with(configuration).await, "/debug/stats").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, r#"{"cpu_frequency":null,"memory":null}"#);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_collector_metrics() {
+        let (status, _) = get(test_router().await, "/metrics/unknown").await;
+
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_custom_paths() {
+        let mut configuration = Configuration::default();
+        configuration.http.metrics_path = "/prometheus".to_owned();
+        configuration.http.enable_health = false;
+        let router = test_router_with(configuration).await;
+
+        let (status, body) = get(router.clone(), "/prometheus").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("system_memory_total_bytes 4096"));
+
+        let (status, body) = get(router.clone(), "/prometheus/memory").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("system_memory_total_bytes 4096"));
+
+        let (status, _) = get(router.clone(), "/metrics").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, _) = get(router, "/health").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_gzip_compression() {
+        let request = Request::builder()
+            .uri("/metrics")
+            .header("Accept-Encoding", "gzip")
+            .body(Body::empty())
+            .unwrap();
+        let response = test_router().await.oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["Content-Encoding"], "gzip");
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        // The gzip magic number
+        assert_eq!(&body[..2], &[0x1f, 0x8b]);
+    }
+
+    #[tokio::test]
+    async fn test_no_compression_by_default() {
+        let request = Request::builder()
+            .uri("/metrics")
+            .body(Body::empty())
+            .unwrap();
+        let response = test_router().await.oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().get("Content-Encoding").is_none());
+    }
+
+    async fn get_accepting(router: Router, accept: &str) -> (StatusCode, String, String) {
+        let request = Request::builder()
+            .uri("/metrics")
+            .header("Accept", accept)
+            .body(Body::empty())
+            .unwrap();
+        let response = router.oneshot(request).await.unwrap();
+
+        let status = response.status();
+        let content_type = response.headers()["Content-Type"]
+            .to_str()
+            .unwrap()
+            .to_owned();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+
+        (
+            status,
+            content_type,
+            String::from_utf8(body.to_vec()).unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_legacy_text_format() {
+        let (status, content_type, body) = get_accepting(test_router().await, "text/plain").await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(content_type, "text/plain; version=0.0.4");
+        assert!(body.contains("system_memory_total_bytes 4096"));
+        assert!(!body.contains("# EOF"));
+    }
+
+    #[tokio::test]
+    async fn test_openmetrics_format() {
+        let accept = "application/openmetrics-text;version=1.0.0,text/plain;version=0.0.4;q=0.5";
+        let (status, content_type, body) = get_accepting(test_router().await, accept).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert!(content_type.starts_with("application/openmetrics-text"));
+        assert!(body.contains("system_memory_total_bytes 4096"));
+        assert!(body.ends_with("# EOF\n"));
+    }
+
+    #[tokio::test]
+    async fn test_health() {
+        let (status, body) = get(test_router().await, "/health").await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, "OK");
+    }
+
+    async fn auth_router(auth: Auth) -> Router {
+        let mut configuration = Configuration::default();
+        configuration.http.auth = Some(auth);
+        test_router_with(configuration).await
+    }
+
+    #[tokio::test]
+    async fn test_bearer_auth() {
+        let router = auth_router(Auth {
+            bearer_token: Some("s3cr3t".to_owned()),
+            ..Default::default()
+        })
+        .await;
+
+        let (status, _) = get(router.clone(), "/metrics").await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let (status, _) = get_with_auth(router.clone(), "/metrics", Some("Bearer wrong")).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let (status, _) = get_with_auth(router.clone(), "/metrics/memory", None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let (status, _) = get(router.clone(), "/health").await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, body) = get_with_auth(router, "/metrics", Some("Bearer s3cr3t")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("system_memory_total_bytes 4096"));
+    }
+
+    #[tokio::test]
+    async fn test_basic_auth() {
+        let router = auth_router(Auth {
+            basic_username: Some("prometheus".to_owned()),
+            basic_password: Some("hunter2".to_owned()),
+            ..Default::default()
+        })
+        .await;
+
+        // base64("prometheus:wrong")
+        let (status, _) = get_with_auth(
+            router.clone(),
+            "/metrics",
+            Some("Basic cHJvbWV0aGV1czp3cm9uZw=="),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        // base64("prometheus:hunter2")
+        let (status, _) =
+            get_with_auth(router, "/metrics", Some("Basic cHJvbWV0aGV1czpodW50ZXIy")).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[test]
+    fn test_invalid_auth_is_rejected() {
+        for auth in [
+            Auth {
+                basic_username: Some("prometheus".to_owned()),
+                ..Default::default()
+            },
+            Auth {
+                basic_password: Some("hunter2".to_owned()),
+                ..Default::default()
+            },
+            Auth {
+                bearer_token: Some(String::new()),
+                ..Default::default()
+            },
+            Auth::default(),
+        ] {
+            assert!(Credentials::new(Some(&auth)).is_err(), "{:?}", auth);
+        }
+
+        assert!(Credentials::new(None).unwrap().is_none());
+    }
+
+    struct CountingCollector(Arc<AtomicUsize>);
+
+    #[async_trait::async_trait]
+    impl Collector for CountingCollector {
+        fn name(&self) -> &'static str {
+            "counting"
+        }
+
+        async fn collect(&self) -> anyhow::Result<()> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    async fn count_collections(
+        scrape_cache_secs: u64,
+        collector_interval_secs: Option<u64>,
+    ) -> usize {
+        let mut configuration = Configuration::default();
+        configuration.http.scrape_cache_secs = scrape_cache_secs;
+        configuration.collector.collector_interval_secs = collector_interval_secs;
+
+        let runs = Arc::new(AtomicUsize::new(0));
+        let router = create_router(AppState::new(
+            configuration,
+            Registry::new(),
+            vec![RegisteredCollector {
+                collector: Box::new(CountingCollector(Arc::clone(&runs))),
+                registry: Registry::new(),
+                priority: 0,
+            }],
+        ))
+        .unwrap();
+
+        for _ in 0..3 {
+            let (status, _) = get(router.clone(), "/metrics").await;
+            assert_eq!(status, StatusCode::OK);
+        }
+
+        runs.load(Ordering::SeqCst)
+    }
+
+    #[tokio::test]
+    async fn test_scrape_cache() {
+        assert_eq!(count_collections(3600, None).await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_scrape_cache_disabled() {
+        assert_eq!(count_collections(0, None).await, 3);
+    }
+
+    #[tokio::test]
+    async fn test_background_collection_skips_scrape_collection() {
+        assert_eq!(count_collections(0, Some(15)).await, 0);
+    }
+
+    fn http(address: &str) -> Http {
+        Http {
+            address: address.to_owned(),
+            port: 9123,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_bind_addresses_loopback() {
+        let addresses = bind_addresses(&http("127.0.0.1")).unwrap();
+
+        assert_eq!(addresses, vec![SocketAddr::from(([127, 0, 0, 1], 9123))]);
+        assert!(addresses.iter().all(|a| a.ip().is_loopback()));
+    }
+
+    #[test]
+    fn test_bind_addresses_unspecified() {
+        let addresses = bind_addresses(&http("0.0.0.0")).unwrap();
+
+        assert_eq!(addresses, vec![SocketAddr::from(([0, 0, 0, 0], 9123))]);
+    }
+
+    #[test]
+    fn test_bind_addresses_ipv6() {
+        for address in ["::1", "[::1]"] {
+            let addresses = bind_addresses(&http(address)).unwrap();
+
+            assert_eq!(addresses.len(), 1);
+            let SocketAddr::V6(addr) = addresses[0] else {
+                panic!("Expected an IPv6 address, got {}", addresses[0]);
+            };
+            assert_eq!(*addr.ip(), Ipv6Addr::LOCALHOST);
+            assert_eq!(addr.port(), 9123);
+        }
+    }
+
+    #[test]
+    fn test_bind_addresses_ipv6_unspecified() {
+        let addresses = bind_addresses(&http("::")).unwrap();
+
+        assert_eq!(
+            addresses,
+            vec![SocketAddr::from((Ipv6Addr::UNSPECIFIED, 9123))]
+        );
+    }
+
+    #[test]
+    fn test_bind_addresses_invalid() {
+        let error = bind_addresses(&http("[::1::2]")).unwrap_err();
+
+        assert!(error.to_string().contains("[::1::2]"));
+    }
 }
@@ -0,0 +1,104 @@
+use crate::config::Auth;
+use axum::extract::{Request, State};
+use axum::http::{StatusCode, header};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use std::sync::Arc;
+
+/// The expected scheme and credentials of the `Authorization` header
+#[derive(Clone)]
+pub struct Credentials {
+    scheme: &'static str,
+    secret: Arc<[u8]>,
+}
+
+impl Credentials {
+    /// Returns `None` if authentication is not configured. A partial or an empty
+    /// configuration is an error, rather than silently serving the metrics without it.
+    pub fn new(auth: Option<&Auth>) -> anyhow::Result<Option<Self>> {
+        let Some(auth) = auth else {
+            return Ok(None);
+        };
+
+        match (
+            &auth.bearer_token,
+            &auth.basic_username,
+            &auth.basic_password,
+        ) {
+            (Some(_), Some(_), _) | (Some(_), _, Some(_)) => Err(anyhow::anyhow!(
+                "http.auth: bearer_token and basic credentials are mutually exclusive"
+            )),
+            (Some(token), None, None) if token.is_empty() => {
+                Err(anyhow::anyhow!("http.auth: bearer_token must not be empty"))
+            }
+            (Some(token), None, None) => Ok(Some(Self {
+                scheme: "Bearer",
+                secret: token.as_bytes().into(),
+            })),
+            (None, Some(username), Some(password)) => {
+                if username.is_empty() || password.is_empty() {
+                    return Err(anyhow::anyhow!(
+                        "http.auth: basic_username and basic_password must not be empty"
+                    ));
+                }
+
+                Ok(Some(Self {
+                    scheme: "Basic",
+                    secret: STANDARD
+                        .encode(format!("{}:{}", username, password))
+                        .as_bytes()
+                        .into(),
+                }))
+            }
+            (None, Some(_), None) | (None, None, Some(_)) => Err(anyhow::anyhow!(
+                "http.auth: both basic_username and basic_password are required"
+            )),
+            (None, None, None) => Err(anyhow::anyhow!(
+                "http.auth: either bearer_token or basic credentials are required"
+            )),
+        }
+    }
+
+    fn matches(&self, authorization: &[u8]) -> bool {
+        let Some(idx) = authorization.iter().position(|b| *b == b' ') else {
+            return false;
+        };
+
+        let (scheme, secret) = (&authorization[..idx], &authorization[idx + 1..]);
+        scheme.eq_ignore_ascii_case(self.scheme.as_bytes())
+            && constant_time_eq(secret.trim_ascii(), &self.secret)
+    }
+}
+
+pub async fn require_auth(
+    State(credentials): State<Credentials>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let authorized = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .is_some_and(|value| credentials.matches(value.as_bytes()));
+
+    if !authorized {
+        return (
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, credentials.scheme)],
+        )
+            .into_response();
+    }
+
+    next.run(request).await
+}
+
+/// Compares the values in a time which depends only on their lengths, so that
+/// the response time does not reveal how much of the secret has been guessed
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
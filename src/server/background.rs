@@ -0,0 +1,83 @@
+use crate::server::handler::{collector_timeout, timed_collect};
+use crate::server::state::AppState;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::MissedTickBehavior;
+
+/// Runs each collector in a task of its own, every `interval`. Slow collectors
+/// skip the missed ticks, instead of running back to back to catch up. The loops
+/// of the previous collectors, if any, are stopped.
+pub fn spawn_collection_loops(state: &AppState, interval: Duration) {
+    let collectors = state.collectors();
+    let timeout = collector_timeout(state);
+    let handles = (0..collectors.len())
+        .map(|idx| {
+            let collectors = Arc::clone(&collectors);
+
+            tokio::spawn(async move {
+                let collector = collectors[idx].collector.as_ref();
+                let mut ticker = tokio::time::interval(interval);
+                ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+                loop {
+                    ticker.tick().await;
+                    if let Err(error) = timed_collect(collector, timeout).await {
+                        tracing::error!(
+                            ?error,
+                            collector = collector.name(),
+                            "Metrics collector failed"
+                        );
+                    }
+                }
+            })
+        })
+        .collect();
+
+    let mut guard = state.background.lock().unwrap_or_else(|e| e.into_inner());
+    for previous in std::mem::replace(&mut *guard, handles) {
+        previous.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Configuration;
+    use crate::domain::{Collector, RegisteredCollector};
+    use prometheus::Registry;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct CountingCollector(Arc<AtomicUsize>);
+
+    #[async_trait::async_trait]
+    impl Collector for CountingCollector {
+        fn name(&self) -> &'static str {
+            "background"
+        }
+
+        async fn collect(&self) -> anyhow::Result<()> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_collection_loops() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let state = AppState::new(
+            Configuration::default(),
+            Registry::new(),
+            vec![RegisteredCollector {
+                collector: Box::new(CountingCollector(Arc::clone(&runs))),
+                registry: Registry::new(),
+                priority: 0,
+            }],
+        );
+
+        spawn_collection_loops(&state, Duration::from_secs(10));
+
+        // The first tick completes immediately
+        tokio::time::sleep(Duration::from_secs(25)).await;
+        assert_eq!(runs.load(Ordering::SeqCst), 3);
+    }
+}
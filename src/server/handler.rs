@@ -1,40 +1,384 @@
+use crate::domain::{Collector, RegisteredCollector};
+use crate::server::influx::{self, INFLUX_FORMAT};
+use crate::server::openmetrics::{self, OPENMETRICS_FORMAT};
 use crate::server::state::AppState;
-use axum::extract::State;
+use crate::server::telemetry::{
+    COLLECTOR_DURATION, COLLECTOR_ERRORS, COLLECTOR_TIMEOUTS, SCRAPE_DURATION,
+};
+use axum::extract::{Path, State};
+use axum::http::header::{ACCEPT, CONTENT_TYPE};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
 use futures::StreamExt;
 use futures::stream::FuturesUnordered;
-use std::time::Duration;
+use prometheus::proto::MetricFamily;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tokio::time::Instant;
 
 #[tracing::instrument(level = "debug", skip_all)]
-pub async fn metrics(State(state): State<AppState>) -> String {
-    if let Ok(mut last_collection) = state.last_collection.try_lock()
-        && last_collection.elapsed() > Duration::from_secs(1)
+pub async fn metrics(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    refresh_if_stale(&state).await;
+    encode_response(&state.registry().gather(), &headers)
+}
+
+/// Renders all metrics in the InfluxDB line protocol
+#[tracing::instrument(level = "debug", skip_all)]
+pub async fn influx_metrics(State(state): State<AppState>) -> Response {
+    refresh_if_stale(&state).await;
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+
+    let body = influx::encode(&state.registry().gather(), timestamp);
+    ([(CONTENT_TYPE, INFLUX_FORMAT)], body).into_response()
+}
+
+/// Shared by the scrapes and the StatsD pushes, so that only one collection runs at a time
+pub(crate) async fn refresh_if_stale(state: &AppState) {
+    // The background collection keeps the measurements up to date on its own
+    let background = state
+        .configuration
+        .collector
+        .collector_interval_secs
+        .is_some();
+
+    // While a collection is in progress, concurrent scrapes are served the previous measurements
+    let cache_ttl = Duration::from_secs(state.configuration.http.scrape_cache_secs);
+    if !background
+        && let Ok(mut last_collection) = state.last_collection.try_lock()
+        && last_collection.is_none_or(|last| last.elapsed() >= cache_ttl)
     {
-        *last_collection = Instant::now();
-        refresh_measurements(&state).await;
+        let start = Instant::now();
+        refresh_measurements(state).await;
+        SCRAPE_DURATION.observe(start.elapsed().as_secs_f64());
+
+        *last_collection = Some(Instant::now());
     }
+}
 
-    encode_response(&state)
+pub async fn health() -> &'static str {
+    "OK"
 }
 
-#[tracing::instrument(level = "trace", skip_all)]
-async fn refresh_measurements(state: &AppState) {
-    let mut futures = FuturesUnordered::new();
-    for collector in state.collectors.iter() {
-        futures.push(collector.collect());
+#[tracing::instrument(level = "debug", skip_all, fields(collector = %name))]
+pub async fn collector_metrics(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, StatusCode> {
+    let collectors = state.collectors();
+    let Some(registered) = collectors.iter().find(|c| c.collector.name() == name) else {
+        return Err(StatusCode::NOT_FOUND);
+    };
+
+    let timeout = collector_timeout(&state);
+    if let Err(error) = timed_collect(registered.collector.as_ref(), timeout).await {
+        tracing::error!(?error, "Metrics collector failed");
     }
 
-    while let Some(result) = futures.next().await {
-        if let Err(error) = result {
-            tracing::error!(?error, "Metrics collector failed");
+    Ok(encode_response(&registered.registry.gather(), &headers))
+}
+
+/// Returns the last measurement of each collector, or `null` if there is none
+#[tracing::instrument(level = "debug", skip_all)]
+pub async fn debug_stats(State(state): State<AppState>) -> Response {
+    let stats = state
+        .collectors()
+        .iter()
+        .map(|c| {
+            let snapshot = c.collector.snapshot().unwrap_or_default();
+            (c.collector.name().to_owned(), snapshot)
+        })
+        .collect::<serde_json::Map<_, _>>();
+
+    let body = serde_json::Value::Object(stats).to_string();
+    ([(CONTENT_TYPE, "application/json")], body).into_response()
+}
+
+#[tracing::instrument(level = "trace", skip_all)]
+pub(crate) async fn refresh_measurements(state: &AppState) {
+    let timeout = state
+        .configuration
+        .http
+        .collection_timeout
+        .map(Duration::from_millis);
+
+    collect_by_priority(&state.collectors(), timeout, collector_timeout(state)).await;
+}
+
+pub(super) fn collector_timeout(state: &AppState) -> Option<Duration> {
+    state
+        .configuration
+        .collector
+        .collector_timeout_ms
+        .map(Duration::from_millis)
+}
+
+/// Runs the collectors in groups of equal priority, starting with the highest one. The
+/// collectors within a group are executed concurrently. When the timeout expires, the
+/// collectors which have not completed yet are abandoned and the remaining groups are skipped.
+/// Without a timeout nothing is skipped, so all collectors are executed concurrently.
+/// Each collector is additionally bounded by the `collector_timeout_ms`.
+async fn collect_by_priority(
+    collectors: &[RegisteredCollector],
+    timeout: Option<Duration>,
+    collector_timeout: Option<Duration>,
+) {
+    let deadline = timeout.map(|t| Instant::now() + t);
+    let groups = match deadline {
+        None => vec![collectors],
+        Some(_) => collectors
+            .chunk_by(|a, b| a.priority == b.priority)
+            .collect(),
+    };
+
+    for group in groups {
+        let mut futures = FuturesUnordered::new();
+        for registered in group {
+            futures.push(timed_collect(
+                registered.collector.as_ref(),
+                collector_timeout,
+            ));
+        }
+
+        let collection = async {
+            while let Some(result) = futures.next().await {
+                if let Err(error) = result {
+                    tracing::error!(?error, "Metrics collector failed");
+                }
+            }
+        };
+
+        match deadline {
+            None => collection.await,
+            Some(deadline) => {
+                if tokio::time::timeout_at(deadline, collection).await.is_err() {
+                    tracing::warn!(
+                        priority = group[0].priority,
+                        "Collection timed out, skipping the remaining collectors"
+                    );
+                    return;
+                }
+            }
         }
     }
 }
 
+/// Runs the collector, recording its duration and whether it has failed. A collector which
+/// does not complete within the timeout is abandoned and keeps its previous measurements.
+pub(super) async fn timed_collect(
+    collector: &dyn Collector,
+    timeout: Option<Duration>,
+) -> anyhow::Result<()> {
+    let start = Instant::now();
+    let result = match timeout {
+        None => Ok(collector.collect().await),
+        Some(timeout) => tokio::time::timeout(timeout, collector.collect()).await,
+    };
+
+    let name = collector.name();
+    COLLECTOR_DURATION
+        .with_label_values(&[name])
+        .observe(start.elapsed().as_secs_f64());
+
+    let Ok(result) = result else {
+        tracing::warn!(collector = name, ?timeout, "Metrics collector timed out");
+        COLLECTOR_TIMEOUTS.with_label_values(&[name]).inc();
+        return Ok(());
+    };
+
+    if result.is_err() {
+        COLLECTOR_ERRORS.with_label_values(&[name]).inc();
+    }
+
+    result
+}
+
+/// Uses the OpenMetrics format if the client accepts it, or the legacy text format otherwise
 #[tracing::instrument(level = "trace", skip_all)]
-fn encode_response(state: &AppState) -> String {
-    let metric_families = state.registry.gather();
+fn encode_response(metric_families: &[MetricFamily], headers: &HeaderMap) -> Response {
+    let openmetrics = headers
+        .get_all(ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .any(|value| value.contains("application/openmetrics-text"));
+
+    if openmetrics {
+        let body = openmetrics::encode(metric_families);
+        return ([(CONTENT_TYPE, OPENMETRICS_FORMAT)], body).into_response();
+    }
+
     let encoder = prometheus::TextEncoder::new();
+    match encoder.encode_to_string(metric_families) {
+        Ok(body) => ([(CONTENT_TYPE, prometheus::TEXT_FORMAT)], body).into_response(),
+        Err(error) => {
+            tracing::error!(?error, "Failed to encode the metrics");
+            let body = "Failed to encode the metrics";
+            (StatusCode::INTERNAL_SERVER_ERROR, body).into_response()
+        }
+    }
+}
 
-    encoder.encode_to_string(&metric_families).unwrap()
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::Collector;
+    use prometheus::Registry;
+    use std::sync::{Arc, Mutex};
+
+    struct SlowCollector {
+        name: &'static str,
+        delay: Duration,
+        started: Arc<Mutex<Vec<&'static str>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl Collector for SlowCollector {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        async fn collect(&self) -> anyhow::Result<()> {
+            self.started.lock().unwrap().push(self.name);
+            tokio::time::sleep(self.delay).await;
+            Ok(())
+        }
+    }
+
+    fn collector(
+        name: &'static str,
+        priority: i32,
+        delay_ms: u64,
+        started: &Arc<Mutex<Vec<&'static str>>>,
+    ) -> RegisteredCollector {
+        RegisteredCollector {
+            collector: Box::new(SlowCollector {
+                name,
+                delay: Duration::from_millis(delay_ms),
+                started: Arc::clone(started),
+            }),
+            registry: Registry::new(),
+            priority,
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_collect_by_priority() {
+        let started = Arc::new(Mutex::new(vec![]));
+        let collectors = vec![
+            collector("cpu", 100, 10, &started),
+            collector("memory", 100, 10, &started),
+            collector("ups", 50, 10, &started),
+            collector("smart", 0, 10, &started),
+        ];
+
+        let start = Instant::now();
+        collect_by_priority(&collectors, Some(Duration::from_secs(1)), None).await;
+
+        assert_eq!(start.elapsed(), Duration::from_millis(30));
+        let started = started.lock().unwrap();
+        assert_eq!(started.len(), 4);
+        assert_eq!(started[2..], ["ups", "smart"]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_collect_concurrently_without_timeout() {
+        let started = Arc::new(Mutex::new(vec![]));
+        let collectors = vec![
+            collector("cpu", 100, 10, &started),
+            collector("ups", 50, 10, &started),
+            collector("smart", 0, 10, &started),
+        ];
+
+        let start = Instant::now();
+        collect_by_priority(&collectors, None, None).await;
+
+        assert_eq!(start.elapsed(), Duration::from_millis(10));
+        assert_eq!(started.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_collect_by_priority_skips_low_priority_on_timeout() {
+        let started = Arc::new(Mutex::new(vec![]));
+        let collectors = vec![
+            collector("cpu", 100, 10, &started),
+            collector("ups", 50, 1000, &started),
+            collector("smart", 0, 10, &started),
+        ];
+
+        collect_by_priority(&collectors, Some(Duration::from_millis(100)), None).await;
+
+        assert_eq!(*started.lock().unwrap(), ["cpu", "ups"]);
+    }
+
+    struct FailingCollector;
+
+    #[async_trait::async_trait]
+    impl Collector for FailingCollector {
+        fn name(&self) -> &'static str {
+            "failing"
+        }
+
+        async fn collect(&self) -> anyhow::Result<()> {
+            Err(anyhow::anyhow!("boom"))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_encode_failure() {
+        let mut family = MetricFamily::default();
+        family.set_name("empty".to_owned());
+
+        let response = encode_response(&[family], &HeaderMap::new());
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(body, "Failed to encode the metrics");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_collector_telemetry() {
+        let started = Arc::new(Mutex::new(vec![]));
+        let collectors = vec![
+            collector("telemetry", 100, 10, &started),
+            RegisteredCollector {
+                collector: Box::new(FailingCollector),
+                registry: Registry::new(),
+                priority: 100,
+            },
+        ];
+
+        collect_by_priority(&collectors, None, None).await;
+
+        let duration = COLLECTOR_DURATION.with_label_values(&["telemetry"]);
+        assert_eq!(duration.get_sample_count(), 1);
+        assert_eq!(duration.get_sample_sum(), 0.01);
+
+        assert_eq!(COLLECTOR_ERRORS.with_label_values(&["telemetry"]).get(), 0);
+        assert_eq!(COLLECTOR_ERRORS.with_label_values(&["failing"]).get(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_collector_timeout() {
+        let started = Arc::new(Mutex::new(vec![]));
+        let collectors = vec![
+            collector("hung", 100, 60_000, &started),
+            collector("fast", 100, 10, &started),
+            collector("next", 0, 10, &started),
+        ];
+
+        let start = Instant::now();
+        collect_by_priority(&collectors, None, Some(Duration::from_millis(100))).await;
+
+        // The hung collector is abandoned, without holding back the other collectors
+        assert_eq!(start.elapsed(), Duration::from_millis(100));
+        assert_eq!(started.lock().unwrap().len(), 3);
+        assert_eq!(COLLECTOR_TIMEOUTS.with_label_values(&["hung"]).get(), 1);
+        assert_eq!(COLLECTOR_TIMEOUTS.with_label_values(&["fast"]).get(), 0);
+        assert_eq!(COLLECTOR_ERRORS.with_label_values(&["hung"]).get(), 0);
+    }
 }
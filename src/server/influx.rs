@@ -0,0 +1,147 @@
+use prometheus::proto::{Metric, MetricFamily, MetricType};
+use std::fmt::Write;
+
+pub const INFLUX_FORMAT: &str = "text/plain; charset=utf-8";
+
+/// Encodes the metric families in the InfluxDB line protocol. The metric name becomes
+/// the measurement, the labels become tags and the sample is stored in the `value` field.
+/// Histograms and summaries are written as a single line with the `sum` and `count`
+/// fields, plus one field per bucket upper bound or quantile, as Telegraf does.
+pub fn encode(families: &[MetricFamily], timestamp_ns: u128) -> String {
+    let mut out = String::new();
+
+    for mf in families {
+        let name = mf.name();
+
+        for m in mf.get_metric() {
+            let fields = match mf.get_field_type() {
+                MetricType::COUNTER => vec![("value".to_owned(), m.get_counter().value())],
+                MetricType::GAUGE => vec![("value".to_owned(), m.get_gauge().value())],
+                MetricType::UNTYPED => vec![("value".to_owned(), m.untyped.value())],
+                MetricType::HISTOGRAM => {
+                    let h = m.get_histogram();
+                    let mut fields = vec![
+                        ("sum".to_owned(), h.get_sample_sum()),
+                        ("count".to_owned(), h.get_sample_count() as f64),
+                    ];
+                    for b in h.get_bucket() {
+                        let count = b.cumulative_count() as f64;
+                        fields.push((format_bound(b.upper_bound()), count));
+                    }
+                    fields
+                }
+                MetricType::SUMMARY => {
+                    let s = m.get_summary();
+                    let mut fields = vec![
+                        ("sum".to_owned(), s.sample_sum()),
+                        ("count".to_owned(), s.sample_count() as f64),
+                    ];
+                    for q in s.get_quantile() {
+                        fields.push((format_bound(q.quantile()), q.value()));
+                    }
+                    fields
+                }
+            };
+
+            line(&mut out, name, m, &fields, timestamp_ns);
+        }
+    }
+
+    out
+}
+
+fn line(out: &mut String, name: &str, metric: &Metric, fields: &[(String, f64)], ts: u128) {
+    // The line protocol has no representation for NaN and infinity
+    let fields = fields
+        .iter()
+        .filter(|(_, value)| value.is_finite())
+        .map(|(key, value)| format!("{}={}", escape(key), value))
+        .collect::<Vec<_>>();
+
+    // A line without fields is rejected by InfluxDB
+    if fields.is_empty() {
+        return;
+    }
+
+    out.push_str(&escape_measurement(name));
+    for lp in metric.get_label() {
+        // Empty tag values are not allowed
+        if !lp.value().is_empty() {
+            let _ = write!(out, ",{}={}", escape(lp.name()), escape(lp.value()));
+        }
+    }
+
+    let _ = writeln!(out, " {} {}", fields.join(","), ts);
+}
+
+fn format_bound(value: f64) -> String {
+    match value {
+        f64::INFINITY => "+Inf".to_owned(),
+        v => v.to_string(),
+    }
+}
+
+/// Escapes the tag keys, the tag values and the field keys
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            ',' | '=' | ' ' | '\\' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push_str("\\n"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+fn escape_measurement(value: &str) -> String {
+    value.replace(',', "\\,").replace(' ', "\\ ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prometheus::{GaugeVec, Histogram, HistogramOpts, IntCounterVec, Opts, Registry};
+
+    #[test]
+    fn test_encode() {
+        let registry = Registry::new();
+
+        let requests = IntCounterVec::new(Opts::new("requests_total", "help"), &["path"]).unwrap();
+        requests.with_label_values(&["/metrics"]).inc_by(3);
+        registry.register(Box::new(requests)).unwrap();
+
+        let temperature = GaugeVec::new(Opts::new("temp", "help"), &["device", "model"]).unwrap();
+        temperature
+            .with_label_values(&["sda", "WD Red, 4TB"])
+            .set(42.5);
+        temperature.with_label_values(&["sdb", ""]).set(f64::NAN);
+        temperature.with_label_values(&["sdc", ""]).set(40.0);
+        registry.register(Box::new(temperature)).unwrap();
+
+        let latency = Histogram::with_opts(
+            HistogramOpts::new("latency_seconds", "help").buckets(vec![0.5, 1.0]),
+        )
+        .unwrap();
+        latency.observe(0.75);
+        registry.register(Box::new(latency)).unwrap();
+
+        let expected = r#"latency_seconds sum=0.75,count=1,0.5=0,1=1 1000
+requests_total,path=/metrics value=3 1000
+temp,device=sda,model=WD\ Red\,\ 4TB value=42.5 1000
+temp,device=sdc value=40 1000
+"#;
+
+        assert_eq!(encode(&registry.gather(), 1000), expected);
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape(r"a=b c,d\e"), r"a\=b\ c\,d\\e");
+        assert_eq!(escape_measurement("a b,c=d"), r"a\ b\,c=d");
+    }
+}
@@ -0,0 +1,186 @@
+use prometheus::proto::{LabelPair, Metric, MetricFamily, MetricType};
+use std::fmt::Write;
+
+pub const OPENMETRICS_FORMAT: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+const COUNTER_SUFFIX: &str = "_total";
+
+/// Encodes the metric families in the OpenMetrics text format, which the `prometheus`
+/// crate does not support. It differs from the legacy text format mainly in the counter
+/// naming, the `unknown` type for untyped metrics and the mandatory `# EOF` terminator.
+pub fn encode(families: &[MetricFamily]) -> String {
+    let mut out = String::new();
+
+    for mf in families {
+        let name = mf.name();
+        let metric_type = mf.get_field_type();
+
+        // The counter metadata refers to the metric family name without the `_total` suffix.
+        // Counters lacking the suffix are exposed as `unknown`, to keep the series names intact.
+        let (family, type_name) = match metric_type {
+            MetricType::COUNTER => match name.strip_suffix(COUNTER_SUFFIX) {
+                Some(family) => (family, "counter"),
+                None => (name, "unknown"),
+            },
+            MetricType::GAUGE => (name, "gauge"),
+            MetricType::SUMMARY => (name, "summary"),
+            MetricType::HISTOGRAM => (name, "histogram"),
+            MetricType::UNTYPED => (name, "unknown"),
+        };
+
+        let _ = writeln!(out, "# TYPE {} {}", family, type_name);
+        if !mf.help().is_empty() {
+            let _ = writeln!(out, "# HELP {} {}", family, escape(mf.help()));
+        }
+
+        for m in mf.get_metric() {
+            match metric_type {
+                MetricType::COUNTER => sample(&mut out, name, m, None, m.get_counter().value()),
+                MetricType::GAUGE => sample(&mut out, name, m, None, m.get_gauge().value()),
+                MetricType::UNTYPED => sample(&mut out, name, m, None, m.untyped.value()),
+                MetricType::HISTOGRAM => {
+                    let h = m.get_histogram();
+                    let mut inf_seen = false;
+                    for b in h.get_bucket() {
+                        let upper_bound = b.upper_bound();
+                        inf_seen |= upper_bound == f64::INFINITY;
+
+                        let le = ("le", format_value(upper_bound));
+                        let bucket = format!("{}_bucket", name);
+                        sample(&mut out, &bucket, m, Some(le), b.cumulative_count() as f64);
+                    }
+
+                    let count = h.get_sample_count() as f64;
+                    if !inf_seen {
+                        let le = ("le", format_value(f64::INFINITY));
+                        sample(&mut out, &format!("{}_bucket", name), m, Some(le), count);
+                    }
+
+                    sample(
+                        &mut out,
+                        &format!("{}_sum", name),
+                        m,
+                        None,
+                        h.get_sample_sum(),
+                    );
+                    sample(&mut out, &format!("{}_count", name), m, None, count);
+                }
+                MetricType::SUMMARY => {
+                    let s = m.get_summary();
+                    for q in s.get_quantile() {
+                        let quantile = ("quantile", format_value(q.quantile()));
+                        sample(&mut out, name, m, Some(quantile), q.value());
+                    }
+
+                    let count = s.sample_count() as f64;
+                    sample(&mut out, &format!("{}_sum", name), m, None, s.sample_sum());
+                    sample(&mut out, &format!("{}_count", name), m, None, count);
+                }
+            }
+        }
+    }
+
+    out.push_str("# EOF\n");
+    out
+}
+
+fn sample(
+    out: &mut String,
+    name: &str,
+    metric: &Metric,
+    additional_label: Option<(&str, String)>,
+    value: f64,
+) {
+    out.push_str(name);
+    write_labels(out, metric.get_label(), additional_label);
+    let _ = writeln!(out, " {}", format_value(value));
+}
+
+fn write_labels(out: &mut String, labels: &[LabelPair], additional: Option<(&str, String)>) {
+    if labels.is_empty() && additional.is_none() {
+        return;
+    }
+
+    let mut separator = '{';
+    for lp in labels {
+        let _ = write!(out, "{}{}=\"{}\"", separator, lp.name(), escape(lp.value()));
+        separator = ',';
+    }
+
+    if let Some((name, value)) = additional {
+        let _ = write!(out, "{}{}=\"{}\"", separator, name, escape(&value));
+    }
+
+    out.push('}');
+}
+
+fn format_value(value: f64) -> String {
+    match value {
+        f64::INFINITY => "+Inf".to_owned(),
+        f64::NEG_INFINITY => "-Inf".to_owned(),
+        v if v.is_nan() => "NaN".to_owned(),
+        v => v.to_string(),
+    }
+}
+
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '"' => escaped.push_str("\\\""),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prometheus::{Histogram, HistogramOpts, IntCounterVec, IntGauge, Opts, Registry};
+
+    #[test]
+    fn test_encode() {
+        let registry = Registry::new();
+
+        let requests = IntCounterVec::new(
+            Opts::new("requests_total", "Number of \"requests\""),
+            &["path"],
+        )
+        .unwrap();
+        requests.with_label_values(&["/metrics"]).inc_by(3);
+        registry.register(Box::new(requests)).unwrap();
+
+        let temperature = IntGauge::new("temperature_celsius", "Temperature").unwrap();
+        temperature.set(42);
+        registry.register(Box::new(temperature)).unwrap();
+
+        let latency = Histogram::with_opts(
+            HistogramOpts::new("latency_seconds", "Latency").buckets(vec![0.5, 1.0]),
+        )
+        .unwrap();
+        latency.observe(0.75);
+        registry.register(Box::new(latency)).unwrap();
+
+        let expected = r#"# TYPE latency_seconds histogram
+# HELP latency_seconds Latency
+latency_seconds_bucket{le="0.5"} 0
+latency_seconds_bucket{le="1"} 1
+latency_seconds_bucket{le="+Inf"} 1
+latency_seconds_sum 0.75
+latency_seconds_count 1
+# TYPE requests counter
+# HELP requests Number of \"requests\"
+requests_total{path="/metrics"} 3
+# TYPE temperature_celsius gauge
+# HELP temperature_celsius Temperature
+temperature_celsius 42
+# EOF
+"#;
+
+        assert_eq!(encode(&registry.gather()), expected);
+    }
+}
@@ -0,0 +1,192 @@
+use crate::bootstrap::init_collectors;
+use crate::config::Configuration;
+use crate::server::background::spawn_collection_loops;
+use crate::server::state::AppState;
+use prometheus::Registry;
+use serde::Serialize;
+use std::time::Duration;
+
+/// Re-reads the configuration on SIGHUP and applies the log level and the collector
+/// settings. The listener keeps running, so the scrapes are not interrupted.
+#[cfg(unix)]
+pub async fn reload_on_sighup(
+    config_path: String,
+    state: AppState,
+    logs: crate::logging::LogReloader,
+) -> anyhow::Result<()> {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    let mut signal = signal(SignalKind::hangup())?;
+    while signal.recv().await.is_some() {
+        let result = async {
+            let config = Configuration::load(&config_path)?;
+            config.validate()?;
+            logs.reload(&config.log)?;
+            reload(&state, &config).await
+        }
+        .await;
+
+        match result {
+            Ok(()) => tracing::info!("SIGHUP received, reloaded the configuration"),
+            Err(e) => tracing::error!(error=?e, "Failed to reload the configuration"),
+        }
+    }
+
+    Ok(())
+}
+
+/// Replaces the collectors with ones built from the new configuration. The settings
+/// which cannot be changed at runtime keep their startup values until a restart.
+/// The collectors are probed again, so the ones which were unsupported at startup,
+/// e.g. because the docker daemon was not running yet, are picked up by a reload.
+pub async fn reload(state: &AppState, config: &Configuration) -> anyhow::Result<()> {
+    for section in restart_required(&state.configuration, config) {
+        tracing::warn!(
+            section,
+            "The setting cannot be changed at runtime, a restart is needed to apply it"
+        );
+    }
+
+    let registry = Registry::new();
+    let collectors = init_collectors(config, &registry).await?;
+    state.replace_collectors(registry, collectors);
+
+    // The new collectors have not run yet
+    *state.last_collection.lock().await = None;
+
+    if let Some(interval) = state.configuration.collector.collector_interval_secs {
+        spawn_collection_loops(state, Duration::from_secs(interval));
+    }
+
+    Ok(())
+}
+
+/// Lists the changed settings which are applied only at startup
+fn restart_required(current: &Configuration, new: &Configuration) -> Vec<&'static str> {
+    let log_outputs = |c: &Configuration| {
+        (
+            c.log.enable_stdout,
+            c.log.enable_log_file,
+            c.log.log_file_directory.clone(),
+            c.log.max_log_files,
+        )
+    };
+
+    let mut sections = vec![];
+    if log_outputs(current) != log_outputs(new) {
+        sections.push("log");
+    }
+
+    if !same(&current.http, &new.http) {
+        sections.push("http");
+    }
+
+    if current.collector.collector_interval_secs != new.collector.collector_interval_secs {
+        sections.push("collector.collector_interval_secs");
+    }
+
+    if current.collector.collector_timeout_ms != new.collector.collector_timeout_ms {
+        sections.push("collector.collector_timeout_ms");
+    }
+
+    if !same(&current.self_check, &new.self_check) {
+        sections.push("self_check");
+    }
+
+    if !same(&current.statsd, &new.statsd) {
+        sections.push("statsd");
+    }
+
+    sections
+}
+
+fn same(a: &impl Serialize, b: &impl Serialize) -> bool {
+    match (serde_json::to_value(a), serde_json::to_value(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn users_priority(state: &AppState) -> Option<i32> {
+        state
+            .collectors()
+            .iter()
+            .find(|c| c.collector.name() == "users")
+            .map(|c| c.priority)
+    }
+
+    #[tokio::test]
+    async fn test_reload_collectors() {
+        let mut config = Configuration::default();
+        config.collector.users.priority = Some(1);
+
+        let registry = Registry::new();
+        let collectors = init_collectors(&config, &registry).await.unwrap();
+        let state = AppState::new(config.clone(), registry, collectors);
+        assert_eq!(users_priority(&state), Some(1));
+
+        config.collector.users.enabled = true;
+        config.collector.users.priority = Some(2);
+        reload(&state, &config).await.unwrap();
+        assert_eq!(users_priority(&state), Some(2));
+    }
+
+    #[tokio::test]
+    async fn test_reload_probes_unsupported_collectors() {
+        let proc_root =
+            std::env::temp_dir().join(format!("hephaestus-reload-proc-{}", std::process::id()));
+        std::fs::create_dir_all(&proc_root).unwrap();
+
+        let mut config = Configuration::default();
+        config.datasource.proc_root = proc_root.to_string_lossy().into_owned();
+        config.collector.zfs_arc.enabled = true;
+
+        let has_arc_metrics = |state: &AppState| {
+            let families = state.registry().gather();
+            families.iter().any(|mf| mf.name() == "zfs_arc_hits_total")
+        };
+
+        let registry = Registry::new();
+        let collectors = init_collectors(&config, &registry).await.unwrap();
+        let state = AppState::new(config.clone(), registry, collectors);
+        for registered in state.collectors().iter() {
+            let _ = registered.collector.collect().await;
+        }
+        assert!(!has_arc_metrics(&state));
+
+        // The ZFS module is loaded after the exporter has started
+        let kstat = proc_root.join("spl/kstat/zfs");
+        std::fs::create_dir_all(&kstat).unwrap();
+        std::fs::write(
+            kstat.join("arcstats"),
+            "13 1 0x01 2 2\nname type data\nhits 4 100\nmisses 4 10\nsize 4 4096\n",
+        )
+        .unwrap();
+
+        reload(&state, &config).await.unwrap();
+        for registered in state.collectors().iter() {
+            let _ = registered.collector.collect().await;
+        }
+        assert!(has_arc_metrics(&state));
+
+        std::fs::remove_dir_all(&proc_root).unwrap();
+    }
+
+    #[test]
+    fn test_restart_required() {
+        let current = Configuration::default();
+
+        let mut new = current.clone();
+        new.log.level = "DEBUG".to_owned();
+        new.collector.users.enabled = true;
+        assert!(restart_required(&current, &new).is_empty());
+
+        new.http.port += 1;
+        new.log.enable_stdout = !new.log.enable_stdout;
+        assert_eq!(restart_required(&current, &new), vec!["log", "http"]);
+    }
+}
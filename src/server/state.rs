@@ -1,9 +1,10 @@
 use crate::config::Configuration;
-use crate::domain::Collector;
+use crate::domain::RegisteredCollector;
 use prometheus::Registry;
 use std::ops::Deref;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
 use tokio::time::Instant;
 
 #[derive(Clone)]
@@ -11,6 +12,26 @@ pub struct AppState {
     pub inner: Arc<Inner>,
 }
 
+impl AppState {
+    pub fn new(
+        configuration: Configuration,
+        registry: Registry,
+        collectors: Vec<RegisteredCollector>,
+    ) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                configuration,
+                collection: RwLock::new(Collection {
+                    registry,
+                    collectors: Arc::new(collectors),
+                }),
+                background: std::sync::Mutex::new(vec![]),
+                last_collection: Mutex::new(None),
+            }),
+        }
+    }
+}
+
 impl Deref for AppState {
     type Target = Inner;
 
@@ -20,8 +41,37 @@ impl Deref for AppState {
 }
 
 pub struct Inner {
+    /// The configuration the exporter was started with. A reload only replaces the collectors.
     pub configuration: Configuration,
-    pub registry: Registry,
-    pub collectors: Vec<Box<dyn Collector>>,
-    pub last_collection: Mutex<Instant>,
+    collection: RwLock<Collection>,
+    /// The tasks of the background collection, if enabled
+    pub(crate) background: std::sync::Mutex<Vec<JoinHandle<()>>>,
+    /// The time of the last collection, or `None` if the collectors have not run yet
+    pub last_collection: Mutex<Option<Instant>>,
+}
+
+/// The collectors are replaced together with the registry exposing their metrics
+struct Collection {
+    registry: Registry,
+    collectors: Arc<Vec<RegisteredCollector>>,
+}
+
+impl Inner {
+    pub fn registry(&self) -> Registry {
+        let guard = self.collection.read().unwrap_or_else(|e| e.into_inner());
+        guard.registry.clone()
+    }
+
+    pub fn collectors(&self) -> Arc<Vec<RegisteredCollector>> {
+        let guard = self.collection.read().unwrap_or_else(|e| e.into_inner());
+        Arc::clone(&guard.collectors)
+    }
+
+    pub fn replace_collectors(&self, registry: Registry, collectors: Vec<RegisteredCollector>) {
+        let mut guard = self.collection.write().unwrap_or_else(|e| e.into_inner());
+        *guard = Collection {
+            registry,
+            collectors: Arc::new(collectors),
+        };
+    }
 }
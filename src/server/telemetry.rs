@@ -0,0 +1,82 @@
+use prometheus::{
+    Histogram, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry,
+};
+use std::sync::LazyLock;
+
+/// Always 1, so that a running exporter can be told apart from a failed scrape
+pub static UP: LazyLock<IntGauge> = LazyLock::new(|| {
+    IntGauge::new("hephaestus_up", "Whether the exporter is running")
+        .expect("the up gauge is valid")
+});
+
+pub static BUILD_INFO: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    IntGaugeVec::new(
+        Opts::new(
+            "hephaestus_build_info",
+            "The version of the exporter and the toolchain it was built with",
+        ),
+        &["version", "rustc", "git_sha"],
+    )
+    .expect("the build info gauge is valid")
+});
+
+pub static SCRAPE_DURATION: LazyLock<Histogram> = LazyLock::new(|| {
+    Histogram::with_opts(HistogramOpts::new(
+        "hephaestus_scrape_duration_seconds",
+        "Time spent running all collectors for a scrape",
+    ))
+    .expect("the scrape duration histogram is valid")
+});
+
+pub static COLLECTOR_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    HistogramVec::new(
+        HistogramOpts::new(
+            "hephaestus_collector_duration_seconds",
+            "Time spent running the collector",
+        ),
+        &["collector"],
+    )
+    .expect("the collector duration histogram is valid")
+});
+
+pub static COLLECTOR_ERRORS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    IntCounterVec::new(
+        Opts::new(
+            "hephaestus_collector_errors_total",
+            "Number of failed collector runs",
+        ),
+        &["collector"],
+    )
+    .expect("the collector errors counter is valid")
+});
+
+pub static COLLECTOR_TIMEOUTS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    IntCounterVec::new(
+        Opts::new(
+            "hephaestus_collector_timeouts_total",
+            "Number of collector runs abandoned after exceeding the collector timeout",
+        ),
+        &["collector"],
+    )
+    .expect("the collector timeouts counter is valid")
+});
+
+/// Registers the metrics describing the exporter itself
+pub fn register(registry: &Registry) -> anyhow::Result<()> {
+    UP.set(1);
+    BUILD_INFO
+        .with_label_values(&[
+            env!("CARGO_PKG_VERSION"),
+            env!("HEPHAESTUS_RUSTC_VERSION"),
+            env!("HEPHAESTUS_GIT_SHA"),
+        ])
+        .set(1);
+
+    registry.register(Box::new(UP.clone()))?;
+    registry.register(Box::new(BUILD_INFO.clone()))?;
+    registry.register(Box::new(SCRAPE_DURATION.clone()))?;
+    registry.register(Box::new(COLLECTOR_DURATION.clone()))?;
+    registry.register(Box::new(COLLECTOR_ERRORS.clone()))?;
+    registry.register(Box::new(COLLECTOR_TIMEOUTS.clone()))?;
+    Ok(())
+}
@@ -0,0 +1,218 @@
+use crate::server::handler::refresh_if_stale;
+use crate::server::state::AppState;
+use prometheus::proto::{Metric, MetricFamily, MetricType};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::net::UdpSocket;
+
+/// Keeps the datagrams below the typical MTU, so that they are not fragmented
+const MAX_DATAGRAM_SIZE: usize = 1432;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Config {
+    pub enabled: bool,
+    /// The StatsD server, e.g. `127.0.0.1:8125`
+    pub address: String,
+    /// Seconds between two consecutive pushes
+    pub interval_secs: u64,
+    /// Prepended to the metric names, separated by a dot
+    pub prefix: Option<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            address: "127.0.0.1:8125".to_owned(),
+            interval_secs: 10,
+            prefix: None,
+        }
+    }
+}
+
+/// Translates the gathered metrics into StatsD lines. All samples are sent as absolute
+/// gauges, including the counters, and the labels are sent as DogStatsD tags, e.g.
+/// `system_disk_read_bytes_total:1024|g|#device:sda`. The histograms and the summaries
+/// are reduced to their `_sum` and `_count` samples.
+pub fn to_statsd_lines(families: &[MetricFamily], prefix: Option<&str>) -> Vec<String> {
+    let mut lines = vec![];
+
+    for mf in families {
+        let name = match prefix {
+            Some(prefix) => format!("{}.{}", prefix, mf.name()),
+            None => mf.name().to_owned(),
+        };
+
+        for m in mf.get_metric() {
+            let tags = tags(m);
+            let mut push = |name: &str, value: f64| gauge_lines(&mut lines, name, value, &tags);
+
+            match mf.get_field_type() {
+                MetricType::COUNTER => push(&name, m.get_counter().value()),
+                MetricType::GAUGE => push(&name, m.get_gauge().value()),
+                MetricType::UNTYPED => push(&name, m.untyped.value()),
+                MetricType::HISTOGRAM => {
+                    let histogram = m.get_histogram();
+                    push(&format!("{}_sum", name), histogram.get_sample_sum());
+                    push(
+                        &format!("{}_count", name),
+                        histogram.get_sample_count() as f64,
+                    );
+                }
+                MetricType::SUMMARY => {
+                    let summary = m.get_summary();
+                    push(&format!("{}_sum", name), summary.sample_sum());
+                    push(&format!("{}_count", name), summary.sample_count() as f64);
+                }
+            }
+        }
+    }
+
+    lines
+}
+
+fn tags(m: &Metric) -> String {
+    let tags = m
+        .get_label()
+        .iter()
+        .map(|l| format!("{}:{}", l.name(), sanitize(l.value())))
+        .collect::<Vec<_>>();
+
+    match tags.is_empty() {
+        true => String::new(),
+        false => format!("|#{}", tags.join(",")),
+    }
+}
+
+/// The tag values must not contain the StatsD separators
+fn sanitize(value: &str) -> String {
+    value.replace([',', '|', '#', ':', '\n'], "_")
+}
+
+fn gauge_lines(lines: &mut Vec<String>, name: &str, value: f64, tags: &str) {
+    if !value.is_finite() {
+        return;
+    }
+
+    // A signed gauge value is applied as a delta, so a negative value
+    // can only be set by resetting the gauge to zero first
+    if value < 0.0 {
+        lines.push(format!("{}:0|g{}", name, tags));
+    }
+
+    lines.push(format!("{}:{}|g{}", name, value, tags));
+}
+
+/// Packs the lines into as few datagrams as possible
+fn datagrams(lines: &[String]) -> Vec<String> {
+    let mut datagrams = vec![];
+    let mut current = String::new();
+
+    for line in lines {
+        if !current.is_empty() && current.len() + 1 + line.len() > MAX_DATAGRAM_SIZE {
+            datagrams.push(std::mem::take(&mut current));
+        }
+
+        if !current.is_empty() {
+            current.push('\n');
+        }
+        current.push_str(line);
+    }
+
+    if !current.is_empty() {
+        datagrams.push(current);
+    }
+
+    datagrams
+}
+
+/// Periodically pushes the metrics to the StatsD server. When the collectors do not run
+/// in the background, they are executed before each push.
+pub async fn run(state: AppState) -> anyhow::Result<()> {
+    let config = state.configuration.statsd.clone();
+
+    let socket = UdpSocket::bind(match config.address.contains('[') {
+        true => "[::]:0",
+        false => "0.0.0.0:0",
+    })
+    .await?;
+    socket.connect(&config.address).await?;
+
+    let mut interval = tokio::time::interval(Duration::from_secs(config.interval_secs));
+    loop {
+        interval.tick().await;
+
+        // Shares the lock with the scrapes, so that they do not collect at the same time
+        refresh_if_stale(&state).await;
+
+        let lines = to_statsd_lines(&state.registry().gather(), config.prefix.as_deref());
+        for datagram in datagrams(&lines) {
+            if let Err(error) = socket.send(datagram.as_bytes()).await {
+                tracing::warn!(?error, "Failed to send the metrics to the StatsD server");
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prometheus::{Histogram, HistogramOpts, IntCounterVec, IntGauge, Opts, Registry};
+
+    fn fixture() -> Registry {
+        let registry = Registry::new();
+
+        let counter = IntCounterVec::new(Opts::new("requests_total", "help"), &["path"]).unwrap();
+        counter.with_label_values(&["/metrics"]).inc_by(3);
+        registry.register(Box::new(counter)).unwrap();
+
+        let gauge = IntGauge::new("temperature", "help").unwrap();
+        gauge.set(-5);
+        registry.register(Box::new(gauge)).unwrap();
+
+        let histogram =
+            Histogram::with_opts(HistogramOpts::new("duration_seconds", "help")).unwrap();
+        histogram.observe(0.5);
+        histogram.observe(1.5);
+        registry.register(Box::new(histogram)).unwrap();
+
+        registry
+    }
+
+    #[test]
+    fn test_to_statsd_lines() {
+        let lines = to_statsd_lines(&fixture().gather(), Some("home"));
+        assert_eq!(
+            lines,
+            vec![
+                "home.duration_seconds_sum:2|g",
+                "home.duration_seconds_count:2|g",
+                "home.requests_total:3|g|#path:/metrics",
+                "home.temperature:0|g",
+                "home.temperature:-5|g",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_to_statsd_lines_without_prefix() {
+        let lines = to_statsd_lines(&fixture().gather(), None);
+        assert_eq!(lines[2], "requests_total:3|g|#path:/metrics");
+    }
+
+    #[test]
+    fn test_sanitize_tag_values() {
+        assert_eq!(sanitize("a,b|c#d:e"), "a_b_c_d_e");
+    }
+
+    #[test]
+    fn test_datagrams() {
+        let lines = vec!["a".repeat(1000), "b".repeat(400), "c".repeat(100)];
+
+        let datagrams = datagrams(&lines);
+        assert_eq!(datagrams.len(), 2);
+        assert_eq!(datagrams[0].len(), 1401);
+        assert_eq!(datagrams[1], "c".repeat(100));
+    }
+}
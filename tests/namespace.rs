@@ -0,0 +1,54 @@
+//! Checks that the configured namespace prefixes the exposed metric names
+
+use hephaestus::bootstrap::register_collector;
+use hephaestus::metrics::MetricOptions;
+use hephaestus::metrics::users::{Config, DataSource, UserStats, Users};
+use prometheus::{Encoder, Registry, TextEncoder};
+use tokio::time::Instant;
+
+struct FixedUsers;
+
+impl DataSource for FixedUsers {
+    async fn user_stats(&self) -> anyhow::Result<UserStats> {
+        Ok(UserStats {
+            timestamp: Instant::now(),
+            users: 2,
+            sessions: 3,
+        })
+    }
+}
+
+#[tokio::test]
+async fn test_namespace_in_exposition() {
+    let config = Config {
+        enabled: true,
+        ..Default::default()
+    };
+    let options = MetricOptions {
+        namespace: Some("node".to_owned()),
+        ..Default::default()
+    };
+    let registry = Registry::new();
+    let registered =
+        register_collector(Users::new(config, FixedUsers), 0, None, &options, &registry)
+            .await
+            .unwrap();
+    registered.collector.collect().await.unwrap();
+
+    let mut buffer = vec![];
+    TextEncoder::new()
+        .encode(&registry.gather(), &mut buffer)
+        .unwrap();
+    let exposition = String::from_utf8(buffer).unwrap();
+
+    assert!(
+        exposition.contains("node_system_logged_in_users 2"),
+        "{}",
+        exposition
+    );
+    assert!(
+        exposition.contains("node_system_sessions 3"),
+        "{}",
+        exposition
+    );
+}
@@ -0,0 +1,53 @@
+//! Checks that the configured static labels are attached to the exposed series
+
+use hephaestus::bootstrap::register_collector;
+use hephaestus::metrics::MetricOptions;
+use hephaestus::metrics::users::{Config, DataSource, UserStats, Users};
+use prometheus::{Encoder, Registry, TextEncoder};
+use std::collections::HashMap;
+use tokio::time::Instant;
+
+struct FixedUsers;
+
+impl DataSource for FixedUsers {
+    async fn user_stats(&self) -> anyhow::Result<UserStats> {
+        Ok(UserStats {
+            timestamp: Instant::now(),
+            users: 2,
+            sessions: 3,
+        })
+    }
+}
+
+#[tokio::test]
+async fn test_static_labels_in_exposition() {
+    let config = Config {
+        enabled: true,
+        ..Default::default()
+    };
+    let options = MetricOptions {
+        static_labels: HashMap::from([
+            ("host".to_owned(), "fileserver".to_owned()),
+            ("env".to_owned(), "home".to_owned()),
+        ]),
+        ..Default::default()
+    };
+    let registry = Registry::new();
+    let registered =
+        register_collector(Users::new(config, FixedUsers), 0, None, &options, &registry)
+            .await
+            .unwrap();
+    registered.collector.collect().await.unwrap();
+
+    let mut buffer = vec![];
+    TextEncoder::new()
+        .encode(&registry.gather(), &mut buffer)
+        .unwrap();
+    let exposition = String::from_utf8(buffer).unwrap();
+
+    assert!(
+        exposition.contains(r#"system_logged_in_users{env="home",host="fileserver"} 2"#),
+        "{}",
+        exposition
+    );
+}
@@ -0,0 +1,78 @@
+//! Checks that the configured temperature unit applies to both the names
+//! and the values of the temperature metrics
+
+use hephaestus::bootstrap::register_collector;
+use hephaestus::domain::Probe;
+use hephaestus::metrics::MetricOptions;
+use hephaestus::metrics::TemperatureUnit;
+use hephaestus::metrics::disk_smart::{
+    Config, DataSource, Device, NvmeDevice, Smart, SmartReports,
+};
+use prometheus::{Encoder, Registry, TextEncoder};
+use tokio::time::Instant;
+
+struct WarmDisk;
+
+impl Probe for WarmDisk {}
+
+impl DataSource for WarmDisk {
+    async fn scan_devices(&self) -> anyhow::Result<Vec<String>> {
+        Ok(vec!["/dev/nvme0".to_owned()])
+    }
+
+    async fn disk_temps(&self, _devices: &[String]) -> anyhow::Result<SmartReports> {
+        let mut nvme = NvmeDevice::new(Device {
+            device: "/dev/nvme0".to_owned(),
+            id: None,
+            model: "Samsung SSD 980 PRO 2TB".to_owned(),
+            serial_number: "S6B0NL0T123456".to_owned(),
+            health_passed: Some(true),
+            selftest_passed: None,
+            selftest_remaining_percent: None,
+        });
+        nvme.temperature = Some(40.0);
+
+        Ok(SmartReports {
+            timestamp: Instant::now(),
+            sata: vec![],
+            nvme: vec![nvme],
+        })
+    }
+}
+
+#[tokio::test]
+async fn test_fahrenheit_in_exposition() {
+    let config = Config {
+        enabled: true,
+        temperature_warning_threshold: Some(50.0),
+        ..Default::default()
+    };
+    let options = MetricOptions {
+        temperature_unit: TemperatureUnit::Fahrenheit,
+        ..Default::default()
+    };
+    let registry = Registry::new();
+    let registered = register_collector(Smart::new(config, WarmDisk), 0, None, &options, &registry)
+        .await
+        .unwrap();
+    registered.collector.collect().await.unwrap();
+
+    let mut buffer = vec![];
+    TextEncoder::new()
+        .encode(&registry.gather(), &mut buffer)
+        .unwrap();
+    let exposition = String::from_utf8(buffer).unwrap();
+
+    assert!(
+        exposition.contains("system_smart_nvme_temperature_fahrenheit{"),
+        "{}",
+        exposition
+    );
+    assert!(exposition.contains("} 104\n"), "{}", exposition);
+    assert!(
+        exposition.contains("system_smart_temperature_warning_threshold_fahrenheit 122"),
+        "{}",
+        exposition
+    );
+    assert!(!exposition.contains("celsius"), "{}", exposition);
+}